@@ -0,0 +1,143 @@
+// `bf!`, which translates a Brainfuck program to Rust while the calling crate is built.
+//
+//     let out: Vec<u8> = bf_macros::bf!("++++++++[>++++++++<-]>+.", input = b"");
+//
+// `input` is any expression whose value is `AsRef<[u8]>` and defaults to nothing. Any other
+// `key = value` pair is a setting as in the config file, e.g. `cell_size = 16`. The program
+// itself has to be a string literal, since it is compiled before anything runs.
+use proc_macro::{Delimiter, Literal, TokenStream, TokenTree};
+
+use bf::{Program, Settings};
+
+#[proc_macro]
+pub fn bf(tokens: TokenStream) -> TokenStream {
+    match expand(tokens) {
+        Ok(code) => code.parse().unwrap(),
+        Err(err) => format!("::std::compile_error!({err:?})").parse().unwrap(),
+    }
+}
+
+fn expand(tokens: TokenStream) -> Result<String, String> {
+    let mut args = split_args(tokens).into_iter();
+    let source = match args.next().as_deref() {
+        Some([TokenTree::Literal(literal)]) => string_literal(literal)?,
+        _ => return Err("bf! expects the program as a string literal".to_string()),
+    };
+
+    let mut settings = Settings::default();
+    let mut input = "b\"\"".to_string();
+    for arg in args {
+        let (key, value) = match &arg[..] {
+            [TokenTree::Ident(key), TokenTree::Punct(eq), value @ ..]
+                if eq.as_char() == '=' && !value.is_empty() =>
+            {
+                (key.to_string(), value)
+            }
+            _ => return Err("bf! options are written as `key = value`".to_string()),
+        };
+        let value: TokenStream = value.iter().cloned().collect();
+        match key.as_str() {
+            "input" => input = value.to_string(),
+            _ => {
+                let value = match value.clone().into_iter().next() {
+                    Some(TokenTree::Literal(literal)) if value.to_string().starts_with('"') => {
+                        string_literal(&literal)?
+                    }
+                    _ => value.to_string(),
+                };
+                settings.set(&key, &value)?;
+            }
+        }
+    }
+
+    let program = Program::with_extensions(&source, settings.opt_level, settings.extensions)
+        .map_err(|err| format!("bf!: {err}"))?;
+    let body = bf::transpile::to_rust_body(&program, &settings);
+    // Reading a slice and writing a vector can't fail, so neither can the run
+    Ok(format!(
+        "{{
+            fn run(
+                input: &mut dyn ::std::io::Read,
+                output: &mut dyn ::std::io::Write,
+            ) -> ::std::io::Result<()> {{
+                #[allow(unused_imports)]
+                use ::std::io::{{Read, Write}};
+                {body}
+            }}
+            let mut input: &[u8] = ::std::convert::AsRef::<[u8]>::as_ref(&{input});
+            let mut output = ::std::vec::Vec::<u8>::new();
+            run(&mut input, &mut output).unwrap();
+            output
+        }}"
+    ))
+}
+
+// The comma separated arguments, commas inside brackets don't count
+fn split_args(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut args = vec![vec![]];
+    for token in tokens {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => args.push(vec![]),
+            // Expressions passed through from another macro arrive wrapped in a group
+            TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+                args.last_mut().unwrap().extend(group.stream())
+            }
+            token => args.last_mut().unwrap().push(token),
+        }
+    }
+    // Allow a trailing comma
+    if args.len() > 1 && args.last().unwrap().is_empty() {
+        args.pop();
+    }
+    args
+}
+
+// The contents of a plain or raw string literal
+fn string_literal(literal: &Literal) -> Result<String, String> {
+    let text = literal.to_string();
+    if let Some(raw) = text.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return Ok(raw[hashes + 1..raw.len() - hashes - 1].to_string());
+    }
+    let Some(inner) = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    else {
+        return Err(format!("expected a string literal, got {text}"));
+    };
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('0') => out.push('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => out.push(c),
+            // A line continuation skips the newline and the indentation after it
+            Some('\n') => {
+                let rest = chars.as_str().trim_start();
+                chars = rest.chars();
+            }
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16).map_err(|_| "invalid \\x escape")?;
+                out.push(byte as char);
+            }
+            Some('u') => {
+                let hex: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let c = u32::from_str_radix(hex.trim_start_matches('{'), 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or("invalid \\u escape")?;
+                out.push(c);
+            }
+            _ => return Err(format!("unsupported escape in {text}")),
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,23 @@
+use bf_macros::bf;
+
+#[test]
+fn runs_at_compile_time() {
+    let out = bf!("++++++++[>++++++++<-]>+.", input = b"");
+    assert_eq!(out, b"A");
+}
+
+#[test]
+fn reads_its_input() {
+    let data = String::from("Hi!");
+    assert_eq!(bf!(",[.,]", input = data), b"Hi!");
+    assert_eq!(bf!(r"+[,.]", input = b"a\0b"), b"a\0");
+}
+
+#[test]
+fn takes_settings() {
+    // Doubling until the cell wraps around to zero takes longer with wider cells
+    assert_eq!(bf!("++[>+<++]>."), [127]);
+    assert_eq!(bf!("++[>+<++]>.", cell_size = 16), [255]);
+    assert_eq!(bf!("+,.", eof = "unchanged"), [1]);
+    assert_eq!(bf!("+,.", eof = "minus1", opt_level = 0), [255]);
+}
@@ -0,0 +1,2 @@
+[ Prints "Hello World!" followed by a newline. ]
+++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.
@@ -0,0 +1,284 @@
+// Static facts about a program, gathered without running it.
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::error::BfError;
+use crate::program::{self, Program, Span};
+use crate::propagate::{self, Fact, Facts};
+use crate::token::BfToken;
+
+// The eight commands, in the order counts are reported
+pub const COMMANDS: [char; 8] = ['+', '-', '>', '<', '[', ']', '.', ','];
+
+// A matched pair of brackets, by source offset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub open: usize,
+    pub close: usize,
+    pub depth: usize, // 1 for loops at the top level
+}
+
+// The structure of a program that passed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramInfo {
+    pub instructions: usize,
+    pub loops: Vec<Loop>, // In order of their opening bracket
+    pub max_depth: usize,
+}
+
+// Validates the brackets of `source` without running it.
+pub fn check(source: &str) -> Result<ProgramInfo, BfError> {
+    let program = Program::compile(source, 0)?;
+    let mut loops = vec![];
+    let mut depth = 0;
+    let mut max_depth = 0;
+    for (idx, token) in program.tokens.iter().enumerate() {
+        match token {
+            BfToken::JUM => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+                loops.push(Loop {
+                    open: program.spans[idx].start,
+                    close: program.spans[program.jumps[idx]].start,
+                    depth,
+                });
+            }
+            BfToken::BAC => depth -= 1,
+            _ => (),
+        }
+    }
+    Ok(ProgramInfo {
+        instructions: program.len(),
+        loops,
+        max_depth,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    pub counts: [usize; 8], // Occurrences of each of `COMMANDS`
+    pub characters: usize,
+    pub loops: usize,
+    pub max_depth: usize,
+    pub tape_span: Option<usize>, // Cells visited, only known when every loop is balanced
+    pub facts: Facts,             // By command, see `propagate`
+    pub dead_loops: usize,        // Loops whose cell is always zero when they are reached
+    pub comments: Vec<Span>,      // Comment loops, counted as comments rather than commands
+}
+
+impl Inspection {
+    pub fn instructions(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    // Fraction of the source that isn't a command
+    pub fn comment_ratio(&self) -> f64 {
+        match self.characters {
+            0 => 0.0,
+            n => (n - self.instructions()) as f64 / n as f64,
+        }
+    }
+}
+
+pub fn inspect(source: &str) -> Result<Inspection, BfError> {
+    let program = Program::compile(source, 0)?;
+    let info = check(source)?;
+    let facts = propagate::analyze(&program.tokens);
+    let comments = program::comment_loops(source, Default::default())?;
+
+    let mut counts = [0; 8];
+    let mut characters = 0;
+    let mut ahead = comments.iter().peekable();
+    for (offset, c) in source.char_indices() {
+        characters += 1;
+        while ahead.next_if(|span| span.end <= offset).is_some() {}
+        let comment = ahead.peek().is_some_and(|span| span.start <= offset);
+        if let Some(idx) = COMMANDS.iter().position(|&cmd| cmd == c && !comment) {
+            counts[idx] += 1;
+        }
+    }
+
+    Ok(Inspection {
+        counts,
+        characters,
+        loops: info.loops.len(),
+        max_depth: info.max_depth,
+        tape_span: tape_span(&program),
+        dead_loops: program
+            .tokens
+            .iter()
+            .zip(&facts.cells)
+            .filter(|&(&token, &fact)| token == BfToken::JUM && fact == Fact::Const(0))
+            .count(),
+        facts,
+        comments,
+    })
+}
+
+// When every loop body moves the pointer back to where it started, each instruction always
+// runs at the same offset, so the cells the program can reach are known ahead of time.
+pub fn tape_span(program: &Program) -> Option<usize> {
+    let mut offset = 0isize;
+    let (mut min, mut max) = (0, 0);
+    let mut starts = vec![];
+    for token in &program.tokens {
+        match token {
+            BfToken::MOV(n) => {
+                offset += n;
+                min = min.min(offset);
+                max = max.max(offset);
+            }
+            BfToken::JUM => starts.push(offset),
+            BfToken::BAC if starts.pop() != Some(offset) => return None,
+            // Where a scan stops depends on the tape
+            BfToken::SCN(_) => return None,
+            _ => (),
+        }
+    }
+    Some((max - min) as usize + 1)
+}
+
+// What is known about the tape while walking the program in order.
+struct Known {
+    cells: HashMap<isize, Option<i64>>, // `None` for a cell whose value is unknown
+    rest_zero: bool,                    // Cells not in `cells` are still zero
+    pointer: isize,
+}
+
+impl Known {
+    // Only values too small to wrap around to zero are trusted, whatever the cell size
+    fn value(&self) -> Option<i64> {
+        let default = self.rest_zero.then_some(0);
+        let value = self.cells.get(&self.pointer).copied().unwrap_or(default)?;
+        Some(value).filter(|v| v.abs() < 256)
+    }
+
+    fn set(&mut self, value: Option<i64>) {
+        self.cells.insert(self.pointer, value);
+    }
+
+    fn forget(&mut self) {
+        self.cells.clear();
+        self.rest_zero = false;
+    }
+}
+
+// Suspicious constructs that are legal but probably mistakes: loops that can never run,
+// empty loops that can never end and loops that never change the cell they test.
+pub fn warnings(source: &str) -> Result<Vec<Diagnostic>, BfError> {
+    let program = Program::compile(source, 0)?;
+    let tokens = &program.tokens;
+    let spans = &program.spans;
+    let span = |from: usize, to: usize| Span {
+        start: spans[from].start,
+        end: spans[to].end,
+    };
+
+    let mut warnings = vec![];
+    let mut known = Known {
+        cells: HashMap::new(),
+        rest_zero: true,
+        pointer: 0,
+    };
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match tokens[idx] {
+            BfToken::CEL(n) => {
+                let value = known.value().map(|v| v + n as i64);
+                known.set(value);
+            }
+            BfToken::MOV(n) => known.pointer += n,
+            BfToken::ACC | BfToken::NUM | BfToken::RND | BfToken::EXT(_) => known.set(None),
+            // Only the parent is followed, the child sees a different tape
+            BfToken::FRK => known.set(Some(0)),
+            BfToken::JUM => {
+                let close = program.jumps[idx];
+                match known.value() {
+                    // Skipping a loop on a fresh cell is how comments and generated code work,
+                    // only one right after a loop that just ended is suspicious
+                    Some(0) if idx > 0 && tokens[idx - 1] == BfToken::BAC => warnings.push(
+                        Diagnostic::warning("loop never runs")
+                            .with_label(span(idx, close), "this loop is always skipped")
+                            .with_label(
+                                span(idx - 1, idx - 1),
+                                "the loop before it leaves the cell at zero",
+                            ),
+                    ),
+                    Some(0) => (),
+                    Some(_) if close == idx + 1 => {
+                        let mut warning = Diagnostic::warning("empty loop never ends")
+                            .with_label(span(idx, close), "the cell is never zero here");
+                        if close + 1 < tokens.len() {
+                            let rest = span(close + 1, tokens.len() - 1);
+                            warning = warning.with_label(rest, "so this is never reached");
+                        }
+                        warnings.push(warning);
+                    }
+                    _ => {
+                        if let Some(warning) = stuck_loop(&program, idx, close) {
+                            warnings.push(warning);
+                        }
+                    }
+                }
+                // Skipped loops leave everything as it was, anything else is a guess
+                if known.value() == Some(0) {
+                    idx = close;
+                } else {
+                    known.forget();
+                }
+            }
+            BfToken::BAC => {
+                known.forget();
+                known.set(Some(0));
+            }
+            _ => (),
+        }
+        idx += 1;
+    }
+    Ok(warnings)
+}
+
+// The comment loops compiling leaves out, see `program::comment_loops`
+pub fn comments(source: &str) -> Result<Vec<Diagnostic>, BfError> {
+    Ok(program::comment_loops(source, Default::default())?
+        .into_iter()
+        .map(|span| {
+            Diagnostic::note("comment loop left out")
+                .with_label(span, "this loop can never run, so its text is a comment")
+        })
+        .collect())
+}
+
+// An innermost loop that returns to the same cell without ever changing it
+fn stuck_loop(program: &Program, open: usize, close: usize) -> Option<Diagnostic> {
+    let mut offset = 0;
+    for token in &program.tokens[open + 1..close] {
+        match token {
+            BfToken::MOV(n) => offset += n,
+            BfToken::CEL(_)
+            | BfToken::ACC
+            | BfToken::NUM
+            | BfToken::RND
+            | BfToken::FRK
+            | BfToken::EXT(_)
+                if offset == 0 =>
+            {
+                return None
+            }
+            BfToken::JUM | BfToken::BAC => return None,
+            _ => (),
+        }
+    }
+    if offset != 0 {
+        return None;
+    }
+    let span = Span {
+        start: program.spans[open].start,
+        end: program.spans[close].end,
+    };
+    Some(
+        Diagnostic::warning("loop never changes the cell it tests")
+            .with_label(span, "once entered, this loop never ends")
+            .with_help("add a `-` or `+` for the loop's cell, or move the pointer on"),
+    )
+}
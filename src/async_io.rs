@@ -0,0 +1,188 @@
+// Non-blocking I/O for hosting programs inside async applications.
+//
+// The traits mirror the shape of `futures::io::{AsyncRead, AsyncWrite}` so adapting a socket or
+// pipe from any runtime is a couple of lines forwarding `poll_read`/`poll_write`/`poll_flush`.
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::cancel::CancelToken;
+use crate::error::BfError;
+use crate::machine::{Machine, Step};
+use crate::program::Program;
+
+// How many instructions run between yields back to the executor
+const YIELD_EVERY: u64 = 1 << 16;
+
+pub trait AsyncRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+pub trait AsyncWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>>;
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+impl<T: AsyncRead + Unpin + ?Sized> AsyncRead for &mut T {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin + ?Sized> AsyncWrite for &mut T {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+}
+
+// In-memory input is always ready
+impl AsyncRead for &[u8] {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(io::Read::read(&mut *self, buf))
+    }
+}
+
+impl AsyncWrite for Vec<u8> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+// Same as `Interpreter`, except `,` and `.` await instead of blocking the thread.
+pub struct AsyncInterpreter<R, W> {
+    machine: Machine,
+    input: R,
+    output: W,
+    cancel: Option<CancelToken>,
+}
+
+impl<R: AsyncRead + Unpin, W: AsyncWrite + Unpin> AsyncInterpreter<R, W> {
+    pub fn new(program: Program, input: R, output: W) -> Self {
+        Self::from_machine(Machine::new(program), input, output)
+    }
+
+    pub fn from_machine(machine: Machine, input: R, output: W) -> Self {
+        Self {
+            machine,
+            input,
+            output,
+            cancel: None,
+        }
+    }
+
+    // See `Interpreter::set_cancel`, a task waiting on I/O sees it once that's done
+    pub fn set_cancel(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
+
+    pub async fn run(&mut self) -> Result<(), BfError> {
+        loop {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(BfError::Interrupted);
+            }
+            match self.machine.step()? {
+                Step::Continue => (),
+                Step::Output(byte) => self.write_byte(byte).await?,
+                Step::Number(value) => {
+                    for byte in value.to_string().bytes() {
+                        self.write_byte(byte).await?
+                    }
+                }
+                Step::Input => {
+                    self.flush().await?;
+                    let byte = self.read_byte().await?;
+                    self.machine.input(byte)
+                }
+                Step::Fork => {
+                    let reason = "forking with `Y` isn't supported by the async interpreter";
+                    return Err(BfError::Unsupported(reason.to_string()));
+                }
+                Step::Halted => break,
+            }
+            // Long stretches of pure computation shouldn't starve other tasks
+            if self.machine.steps().is_multiple_of(YIELD_EVERY) {
+                YieldNow(false).await;
+            }
+        }
+        self.flush().await?;
+        Ok(())
+    }
+
+    async fn read_byte(&mut self) -> Result<Option<u8>, BfError> {
+        let mut buf = [0u8];
+        let read = poll_fn(|cx| Pin::new(&mut self.input).poll_read(cx, &mut buf)).await?;
+        Ok((read > 0).then_some(buf[0]))
+    }
+
+    async fn write_byte(&mut self, byte: u8) -> Result<(), BfError> {
+        let written = poll_fn(|cx| Pin::new(&mut self.output).poll_write(cx, &[byte])).await?;
+        if written == 0 {
+            return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BfError> {
+        poll_fn(|cx| Pin::new(&mut self.output).poll_flush(cx)).await?;
+        Ok(())
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn into_parts(self) -> (Machine, R, W) {
+        (self.machine, self.input, self.output)
+    }
+}
+
+// Returns `Pending` once so the executor gets a chance to run something else
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,253 @@
+// Interchangeable ways of executing a compiled program, so they can be compared uniformly.
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::error::BfError;
+use crate::interpreter::Interpreter;
+use crate::program::Program;
+use crate::settings::Settings;
+use crate::tape::{Tape, MAX_GATHERED};
+use crate::threaded::Threaded;
+use crate::tiered;
+use crate::token::BfToken;
+use crate::transpile::to_rust;
+
+pub struct Io<'a> {
+    pub input: &'a mut dyn Read,
+    pub output: &'a mut dyn Write,
+}
+
+// What a backend can tell about a finished run, anything it can't observe is `None`
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    pub prepare: Duration, // Time spent before the program started, e.g. compiling it
+    pub elapsed: Duration,
+    pub steps: Option<u64>,
+    pub tape: Option<(Vec<u32>, usize)>, // The visited cells and the pointer into them
+}
+
+pub trait ExecBackend {
+    fn name(&self) -> &'static str;
+
+    fn execute(
+        &self,
+        program: &Program,
+        io: Io<'_>,
+        settings: &Settings,
+    ) -> Result<RunReport, BfError>;
+}
+
+// The step-by-step `Machine`, the only backend that supports every setting.
+pub struct InterpreterBackend;
+
+impl ExecBackend for InterpreterBackend {
+    fn name(&self) -> &'static str {
+        "interp"
+    }
+
+    fn execute(
+        &self,
+        program: &Program,
+        io: Io<'_>,
+        settings: &Settings,
+    ) -> Result<RunReport, BfError> {
+        let start = Instant::now();
+        let mut interpreter =
+            Interpreter::with_settings(program.clone(), settings, io.input, io.output);
+        interpreter.run()?;
+        let machine = interpreter.machine();
+        // A sparse tape too long to gather goes unreported, like the backends that can't
+        let gathered = machine.memory().cell_count() <= MAX_GATHERED;
+        Ok(RunReport {
+            prepare: Duration::ZERO,
+            elapsed: start.elapsed(),
+            steps: Some(machine.steps()),
+            tape: gathered.then(|| (machine.tape().into_owned(), machine.pointer())),
+        })
+    }
+}
+
+// Runs the program as threaded code, a closure per instruction, see `threaded`.
+pub struct ThreadedBackend;
+
+impl ExecBackend for ThreadedBackend {
+    fn name(&self) -> &'static str {
+        "threaded"
+    }
+
+    fn execute(
+        &self,
+        program: &Program,
+        io: Io<'_>,
+        settings: &Settings,
+    ) -> Result<RunReport, BfError> {
+        let start = Instant::now();
+        let threaded = Threaded::compile(program)?;
+        let prepare = start.elapsed();
+        let start = Instant::now();
+        let finished = threaded.run(settings, io.input, io.output)?;
+        Ok(RunReport {
+            prepare,
+            elapsed: start.elapsed(),
+            steps: Some(finished.steps),
+            tape: Some((finished.tape, finished.pointer)),
+        })
+    }
+}
+
+// Interprets the program, switching its hot loops to threaded code as it goes, see `tiered`.
+pub struct TieredBackend;
+
+impl ExecBackend for TieredBackend {
+    fn name(&self) -> &'static str {
+        "tiered"
+    }
+
+    fn execute(
+        &self,
+        program: &Program,
+        io: Io<'_>,
+        settings: &Settings,
+    ) -> Result<RunReport, BfError> {
+        let start = Instant::now();
+        let finished = tiered::run(program, settings, io.input, io.output, tiered::HOT)?.finished;
+        Ok(RunReport {
+            prepare: Duration::ZERO,
+            elapsed: start.elapsed(),
+            steps: Some(finished.steps),
+            tape: Some((finished.tape, finished.pointer)),
+        })
+    }
+}
+
+// Translates the program to Rust, compiles it with `rustc -O` and runs the binary.
+// Input is read up front, so it doesn't suit interactive programs, and steps aren't counted.
+pub struct RustBackend;
+
+impl ExecBackend for RustBackend {
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+
+    fn execute(
+        &self,
+        program: &Program,
+        io: Io<'_>,
+        settings: &Settings,
+    ) -> Result<RunReport, BfError> {
+        if settings.max_steps.is_some() {
+            return Err(BfError::Unsupported(
+                "the rust backend can't enforce a step limit".to_string(),
+            ));
+        }
+        if let Tape::Fixed(_) = settings.tape {
+            return Err(BfError::Unsupported(
+                "the rust backend can't keep to a fixed tape".to_string(),
+            ));
+        }
+        if program.tokens.contains(&BfToken::FRK) {
+            return Err(BfError::Unsupported(
+                "the rust backend can't run forking programs".to_string(),
+            ));
+        }
+
+        let start = Instant::now();
+        let dir = std::env::temp_dir().join(format!("bf-rust-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let source = dir.join("program.rs");
+        let binary = dir.join("program");
+        std::fs::write(&source, to_rust(program, settings))?;
+        let status = Command::new("rustc")
+            .args(["-O", "-A", "warnings", "--edition", "2021", "-o"])
+            .arg(&binary)
+            .arg(&source)
+            .status()?;
+        if !status.success() {
+            return Err(BfError::Unsupported(format!("rustc failed with {status}")));
+        }
+        let prepare = start.elapsed();
+
+        // Waiting for the end of input would stall programs that never read any
+        let mut input = vec![];
+        if program.tokens.contains(&BfToken::ACC) || program.tokens.contains(&BfToken::NUM) {
+            io.input.read_to_end(&mut input)?;
+        }
+        let start = Instant::now();
+        let mut child = Command::new(&binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        // Feed stdin from another thread so a chatty program can't deadlock on a full pipe
+        let mut stdin = child.stdin.take().unwrap();
+        let feeder = std::thread::spawn(move || stdin.write_all(&input));
+        let mut stdout = child.stdout.take().unwrap();
+        std::io::copy(&mut stdout, io.output)?;
+        let status = child.wait()?;
+        let elapsed = start.elapsed();
+        // The program may exit without reading all of its input
+        let _ = feeder.join();
+        let _ = std::fs::remove_dir_all(&dir);
+        if !status.success() {
+            return Err(BfError::Unsupported(format!(
+                "program exited with {status}"
+            )));
+        }
+        io.output.flush()?;
+
+        Ok(RunReport {
+            prepare,
+            elapsed,
+            steps: None,
+            tape: None,
+        })
+    }
+}
+
+// Compiles the program to a WebAssembly module and runs it in-process, see `wasm_vm`. A check
+// of the emitter rather than a fast way to run, the module is interpreted in turn.
+#[cfg(feature = "wasm")]
+pub struct WasmBackend;
+
+#[cfg(feature = "wasm")]
+impl ExecBackend for WasmBackend {
+    fn name(&self) -> &'static str {
+        "wasm"
+    }
+
+    fn execute(
+        &self,
+        program: &Program,
+        io: Io<'_>,
+        settings: &Settings,
+    ) -> Result<RunReport, BfError> {
+        let start = Instant::now();
+        let module = crate::wasm_vm::compile(program, settings)?;
+        let prepare = start.elapsed();
+        let start = Instant::now();
+        crate::wasm_vm::execute(&module, settings, io.input, io.output)?;
+        Ok(RunReport {
+            prepare,
+            elapsed: start.elapsed(),
+            steps: None,
+            tape: None,
+        })
+    }
+}
+
+pub fn backends() -> Vec<Box<dyn ExecBackend>> {
+    vec![
+        Box::new(InterpreterBackend),
+        Box::new(ThreadedBackend),
+        Box::new(TieredBackend),
+        Box::new(RustBackend),
+        #[cfg(feature = "wasm")]
+        Box::new(WasmBackend),
+    ]
+}
+
+pub fn backend(name: &str) -> Option<Box<dyn ExecBackend>> {
+    backends()
+        .into_iter()
+        .find(|backend| backend.name() == name)
+}
@@ -0,0 +1,343 @@
+// The one place to configure a run from code, e.g.
+//
+//     InterpreterBuilder::new()
+//         .cell::<u16>()
+//         .eof(Eof::Minus1)
+//         .tape(Tape::Fixed(30_000))
+//         .max_steps(1_000_000_000)
+//         .source(code)
+//         .build()?
+//         .run()?;
+//
+// Without `input` and `output` the interpreter reads stdin and writes stdout. Settings are
+// used to compile source and to set up a fresh machine, a machine passed in already has its
+// own, so only the flush policy applies to it.
+use std::io::{stdin, stdout, Read, Stdin, Stdout, Write};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::cancel::CancelToken;
+use crate::error::BfError;
+use crate::extension::Extensions;
+use crate::golden::Golden;
+use crate::input::Word;
+use crate::interpreter::Interpreter;
+use crate::live::LiveTape;
+use crate::machine::Machine;
+use crate::opcode::Opcodes;
+use crate::passes::Pass;
+use crate::profile::Profiler;
+use crate::program::Program;
+use crate::settings::{CellWidth, Eof, Flush, Settings};
+use crate::tape::Tape;
+use crate::trace::Tracer;
+
+enum Source {
+    Code(String),
+    Program(Program),
+    Machine(Box<Machine>), // Boxed, it dwarfs the others
+}
+
+pub struct InterpreterBuilder<R = Stdin, W = Stdout> {
+    settings: Settings,
+    source: Option<Source>,
+    passes: Option<Vec<Box<dyn Pass>>>, // Instead of the ones for the optimization level
+    input: R,
+    output: W,
+    cancel: Option<CancelToken>,
+    trace: Option<Tracer>,
+    preload: Option<Vec<u8>>,
+    detect_hangs: bool,
+    coverage: bool,
+    profile: bool,
+    live: Option<LiveTape>,
+    history: usize,
+    speed: Option<u64>,
+    word: Word,
+    opcodes: Opcodes,
+    golden: Option<Golden>,
+}
+
+impl InterpreterBuilder {
+    pub fn new() -> Self {
+        Self {
+            settings: Settings::default(),
+            source: None,
+            passes: None,
+            input: stdin(),
+            output: stdout(),
+            cancel: None,
+            trace: None,
+            preload: None,
+            detect_hangs: false,
+            coverage: false,
+            profile: false,
+            live: None,
+            history: 0,
+            speed: None,
+            word: Word::default(),
+            opcodes: Opcodes::default(),
+            golden: None,
+        }
+    }
+}
+
+impl Default for InterpreterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Read, W: Write> InterpreterBuilder<R, W> {
+    pub fn cell<T: CellWidth>(mut self) -> Self {
+        self.settings.cell_size = T::SIZE;
+        self
+    }
+
+    pub fn eof(mut self, eof: Eof) -> Self {
+        self.settings.eof = eof;
+        self
+    }
+
+    pub fn tape(mut self, tape: Tape) -> Self {
+        self.settings.tape = tape;
+        self
+    }
+
+    // Zero turns the limit off, like in config files
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.settings.max_steps = Some(max_steps).filter(|&n| n > 0);
+        self
+    }
+
+    pub fn opt_level(mut self, opt_level: u8) -> Self {
+        self.settings.opt_level = opt_level;
+        self
+    }
+
+    pub fn passes(mut self, passes: Vec<Box<dyn Pass>>) -> Self {
+        self.passes = Some(passes);
+        self
+    }
+
+    pub fn flush(mut self, flush: Flush) -> Self {
+        self.settings.flush = flush;
+        self
+    }
+
+    pub fn extensions(mut self, extensions: Extensions) -> Self {
+        self.settings.extensions = extensions;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.settings.seed = Some(seed);
+        self
+    }
+
+    // Replaces everything set so far, for settings loaded from a config file or a spec
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn source(mut self, code: impl Into<String>) -> Self {
+        self.source = Some(Source::Code(code.into()));
+        self
+    }
+
+    pub fn program(mut self, program: Program) -> Self {
+        self.source = Some(Source::Program(program));
+        self
+    }
+
+    // Carries on with a machine that may already have run, e.g. one restored from a checkpoint
+    pub fn machine(mut self, machine: Machine) -> Self {
+        self.source = Some(Source::Machine(Box::new(machine)));
+        self
+    }
+
+    pub fn input<T: Read>(self, input: T) -> InterpreterBuilder<T, W> {
+        InterpreterBuilder {
+            settings: self.settings,
+            source: self.source,
+            passes: self.passes,
+            input,
+            output: self.output,
+            cancel: self.cancel,
+            trace: self.trace,
+            preload: self.preload,
+            detect_hangs: self.detect_hangs,
+            coverage: self.coverage,
+            profile: self.profile,
+            live: self.live,
+            history: self.history,
+            speed: self.speed,
+            word: self.word,
+            opcodes: self.opcodes,
+            golden: self.golden,
+        }
+    }
+
+    pub fn output<T: Write>(self, output: T) -> InterpreterBuilder<R, T> {
+        InterpreterBuilder {
+            settings: self.settings,
+            source: self.source,
+            passes: self.passes,
+            input: self.input,
+            output,
+            cancel: self.cancel,
+            trace: self.trace,
+            preload: self.preload,
+            detect_hangs: self.detect_hangs,
+            coverage: self.coverage,
+            profile: self.profile,
+            live: self.live,
+            history: self.history,
+            speed: self.speed,
+            word: self.word,
+            opcodes: self.opcodes,
+            golden: self.golden,
+        }
+    }
+
+    // See `Interpreter::set_cancel`
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub fn interrupt(self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel(flag.into())
+    }
+
+    pub fn trace(mut self, tracer: Tracer) -> Self {
+        self.trace = Some(tracer);
+        self
+    }
+
+    // Bytes written into the cells from the pointer rightwards before the run. Source is then
+    // compiled without the passes that assume a blank tape, see `passes::BLANK`, and keeps a
+    // loop opening it even if that reads as a comment.
+    pub fn preload(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.preload = Some(data.into());
+        self
+    }
+
+    pub fn detect_hangs(mut self) -> Self {
+        self.detect_hangs = true;
+        self
+    }
+
+    pub fn coverage(mut self) -> Self {
+        self.coverage = true;
+        self
+    }
+
+    // See `Interpreter::set_profiler`
+    pub fn profile(mut self) -> Self {
+        self.profile = true;
+        self
+    }
+
+    // See `Machine::keep_history`
+    pub fn history(mut self, len: usize) -> Self {
+        self.history = len;
+        self
+    }
+
+    // See `Interpreter::set_live_tape`
+    pub fn live_tape(mut self, live: LiveTape) -> Self {
+        self.live = Some(live);
+        self
+    }
+
+    // See `Interpreter::set_speed`
+    pub fn speed(mut self, rate: u64) -> Self {
+        self.speed = Some(rate);
+        self
+    }
+
+    // See `Interpreter::set_golden`
+    pub fn golden(mut self, golden: Golden) -> Self {
+        self.golden = Some(golden);
+        self
+    }
+
+    // See `Machine::set_input_word`
+    pub fn input_word(mut self, word: Word) -> Self {
+        self.word = word;
+        self
+    }
+
+    // Host opcodes, which source is compiled with on top of the extensions
+    pub fn opcodes(mut self, opcodes: Opcodes) -> Self {
+        self.opcodes = opcodes;
+        self
+    }
+
+    // Compiles the source if that's what was given, failing as `Program::with_max_depth` does
+    pub fn build(self) -> Result<Interpreter<R, W>, BfError> {
+        let settings = self.settings;
+        let mut machine = match self.source {
+            Some(Source::Code(code)) => {
+                let mut passes = self
+                    .passes
+                    .unwrap_or_else(|| crate::passes::for_level(settings.opt_level));
+                if self.preload.is_some() {
+                    passes = crate::passes::without_blank(passes);
+                }
+                let (program, _) = Program::with_tape_start(
+                    &code,
+                    self.opcodes.extend(settings.extensions),
+                    &passes,
+                    settings.max_depth,
+                    self.preload.is_none(),
+                )?;
+                Machine::with_settings(program, &settings)
+            }
+            Some(Source::Program(program)) => Machine::with_settings(program, &settings),
+            Some(Source::Machine(machine)) => *machine,
+            None => {
+                return Err(BfError::Unsupported(
+                    "an interpreter needs source, a program or a machine to run".to_string(),
+                ))
+            }
+        };
+        if let Some(data) = &self.preload {
+            machine.preload(data);
+        }
+        if self.coverage {
+            machine.track_coverage();
+        }
+        machine.keep_history(self.history);
+        machine.set_input_word(self.word);
+        if !self.opcodes.is_empty() {
+            machine.set_opcodes(self.opcodes);
+        }
+        // Last, so it starts from the preloaded tape
+        if self.detect_hangs {
+            machine.detect_hangs();
+        }
+        let mut interpreter = Interpreter::from_machine(machine, self.input, self.output);
+        interpreter.set_flush(settings.flush);
+        if let Some(token) = self.cancel {
+            interpreter.set_cancel(token);
+        }
+        if let Some(tracer) = self.trace {
+            interpreter.set_trace(tracer);
+        }
+        if self.profile {
+            let profiler = Profiler::new(interpreter.machine().program());
+            interpreter.set_profiler(profiler);
+        }
+        if let Some(live) = self.live {
+            interpreter.set_live_tape(live);
+        }
+        interpreter.set_speed(self.speed);
+        if let Some(golden) = self.golden {
+            interpreter.set_golden(golden);
+        }
+        Ok(interpreter)
+    }
+}
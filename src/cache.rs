@@ -0,0 +1,123 @@
+// Compiled programs saved as `.bfc` artifacts, so running the same source again can skip
+// tokenizing and optimizing it. An artifact is found by `key`, a hash of the source and of
+// everything compiling it depends on, and holds the key too, so one that was renamed or
+// written by another version is recompiled rather than trusted.
+use crate::extension::Extensions;
+use crate::passes::Pass;
+use crate::program::{find_jumps, Program, Span};
+use crate::token::BfToken;
+
+const MAGIC: &[u8; 8] = b"BFCACHE1";
+// A tag byte, then the operand and the span as three u64s
+const RECORD: usize = 25;
+
+// FNV-1a, which unlike `DefaultHasher` gives the same key in every build
+pub fn key(
+    code: &str,
+    extensions: Extensions,
+    passes: &[Box<dyn Pass>],
+    max_depth: Option<usize>,
+) -> u64 {
+    let names: Vec<&str> = passes.iter().map(|pass| pass.name()).collect();
+    let settings = format!(
+        "{} {extensions} {:x} {} {}",
+        env!("CARGO_PKG_VERSION"),
+        extensions.opcodes,
+        names.join(","),
+        max_depth.unwrap_or(0)
+    );
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in settings.bytes().chain([0]).chain(code.bytes()) {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn to_bytes(program: &Program, key: u64) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend(key.to_le_bytes());
+    write_records(program, &mut out);
+    out
+}
+
+// The program in an artifact written with the same key, `None` for anything else
+pub fn from_bytes(bytes: &[u8], key: u64) -> Option<Program> {
+    let rest = bytes.strip_prefix(MAGIC)?;
+    let (header, records) = rest.split_at_checked(8)?;
+    if field(header, 0) != key {
+        return None;
+    }
+    read_records(records)
+}
+
+fn field(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+// The number of instructions, then a record for each, shared with `link::Unit`
+pub(crate) fn write_records(program: &Program, out: &mut Vec<u8>) {
+    out.extend((program.len() as u64).to_le_bytes());
+    for (&token, span) in program.tokens.iter().zip(&program.spans) {
+        let (tag, operand) = match token {
+            BfToken::CEL(n) => (0, n),
+            BfToken::MOV(n) => (1, n),
+            BfToken::SET(n) => (2, n),
+            BfToken::SCN(n) => (3, n),
+            BfToken::JUM => (4, 0),
+            BfToken::BAC => (5, 0),
+            BfToken::ACC => (6, 0),
+            BfToken::OUT => (7, 0),
+            BfToken::RND => (8, 0),
+            BfToken::FRK => (9, 0),
+            BfToken::DEC => (10, 0),
+            BfToken::NUM => (11, 0),
+            BfToken::NAN => (12, 0),
+            BfToken::EXT(c) => (13, c as isize),
+        };
+        out.push(tag);
+        for n in [operand as u64, span.start as u64, span.end as u64] {
+            out.extend(n.to_le_bytes());
+        }
+    }
+}
+
+// The program `write_records` wrote, `None` if `bytes` hold anything else
+pub(crate) fn read_records(bytes: &[u8]) -> Option<Program> {
+    let (header, records) = bytes.split_at_checked(8)?;
+    let len = usize::try_from(field(header, 0)).ok()?;
+    if len.checked_mul(RECORD)? != records.len() {
+        return None;
+    }
+    let mut tokens = Vec::with_capacity(len);
+    let mut spans = Vec::with_capacity(len);
+    for record in records.chunks_exact(RECORD) {
+        let operand = field(record, 1) as isize;
+        tokens.push(match record[0] {
+            0 => BfToken::CEL(operand),
+            1 => BfToken::MOV(operand),
+            2 => BfToken::SET(operand),
+            3 => BfToken::SCN(operand),
+            4 => BfToken::JUM,
+            5 => BfToken::BAC,
+            6 => BfToken::ACC,
+            7 => BfToken::OUT,
+            8 => BfToken::RND,
+            9 => BfToken::FRK,
+            10 => BfToken::DEC,
+            11 => BfToken::NUM,
+            12 => BfToken::NAN,
+            13 => BfToken::EXT(u8::try_from(operand).ok().filter(u8::is_ascii)?),
+            _ => return None,
+        });
+        spans.push(Span {
+            start: field(record, 9) as usize,
+            end: field(record, 17) as usize,
+        });
+    }
+    let jumps = find_jumps(&tokens, &spans, None).ok()?;
+    Some(Program {
+        tokens,
+        jumps,
+        spans,
+    })
+}
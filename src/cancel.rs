@@ -0,0 +1,36 @@
+// A handle for stopping a run from another thread, for hosts like GUIs and servers that need
+// to abort a program without killing the process.
+//
+// Clones share one flag. Once it is set, a run given the token stops before its next
+// instruction with `BfError::Interrupted`, leaving the machine where it was, so after `reset`
+// the same run can be resumed.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+// Shares a flag set elsewhere, like a Ctrl-C handler's
+impl From<Arc<AtomicBool>> for CancelToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+}
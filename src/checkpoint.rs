@@ -0,0 +1,244 @@
+// Saves a paused machine to bytes and back, so a long run can be stopped and picked up later.
+use crate::error::BfError;
+use crate::extension::Number;
+use crate::machine::Machine;
+use crate::program::Program;
+use crate::rng::Rng;
+use crate::settings::Settings;
+use crate::tape::{Memory, MAX_GATHERED};
+
+const MAGIC: &[u8; 8] = b"BFCKPT03";
+// Before a partly read word was saved
+const MAGIC_V2: &[u8; 8] = b"BFCKPT02";
+// and before the generator and a partly read number were
+const MAGIC_V1: &[u8; 8] = b"BFCKPT01";
+
+// Everything needed to rebuild a machine, the program is kept as source and recompiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub name: String,
+    pub source: String,
+    pub ir: bool, // The source is textual IR rather than Brainfuck
+    pub settings: Settings,
+    pub ip: usize,
+    pub steps: u64,
+    pub tape: Vec<u32>,
+    pub pointer: usize,
+    pub rng: u64,         // State of the generator behind `?`
+    pub number: Number,   // What a `;` had read of its number so far
+    pub partial: Vec<u8>, // The bytes a `,` had read of its word, see `--input-width`
+}
+
+impl Checkpoint {
+    // Fails when the tape is too long to save, which a sparse one can be
+    pub fn capture(
+        machine: &Machine,
+        name: &str,
+        source: &str,
+        ir: bool,
+        settings: &Settings,
+    ) -> Result<Self, BfError> {
+        let cells = machine.usage().cells;
+        if cells > MAX_GATHERED {
+            return Err(BfError::Checkpoint(format!(
+                "the tape spans {cells} cells, too many to save"
+            )));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            source: source.to_string(),
+            ir,
+            settings: *settings,
+            ip: machine.ip(),
+            steps: machine.steps(),
+            tape: machine.tape().into_owned(),
+            pointer: machine.pointer(),
+            rng: machine.resumable().0.state(),
+            number: machine.resumable().1,
+            partial: machine.resumable().2.to_vec(),
+        })
+    }
+
+    pub fn program(&self) -> Result<Program, BfError> {
+        match self.ir {
+            true => crate::ir::assemble(&self.source),
+            false => Program::with_settings(&self.source, &self.settings),
+        }
+    }
+
+    // A machine running `program`, normally `self.program()`, exactly where the captured one stopped
+    pub fn restore(&self, program: Program) -> Result<Machine, BfError> {
+        if self.ip > program.len() || self.pointer >= self.tape.len().max(1) {
+            return Err(BfError::Checkpoint(
+                "position is outside the program or tape".to_string(),
+            ));
+        }
+        let mut machine = Machine::with_settings(program, &self.settings);
+        let tape = Memory::from_cells(self.settings.tape, self.tape.clone(), self.pointer);
+        machine.restore(
+            self.ip,
+            self.steps,
+            tape,
+            Rng::from_state(self.rng),
+            self.number,
+            self.partial.clone(),
+        );
+        Ok(machine)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        let settings = format!(
+            "{} {} {} {} {} {} {}",
+            self.settings.cell_size,
+            self.settings.eof,
+            self.settings.tape,
+            self.settings.max_steps.unwrap_or(0),
+            self.settings.opt_level,
+            self.settings.extensions,
+            self.settings.max_depth.unwrap_or(0)
+        );
+        for text in [&self.name, &self.source, &settings] {
+            out.extend((text.len() as u64).to_le_bytes());
+            out.extend(text.as_bytes());
+        }
+        out.push(self.ir as u8);
+        for n in [self.ip as u64, self.steps, self.pointer as u64] {
+            out.extend(n.to_le_bytes());
+        }
+        out.extend((self.tape.len() as u64).to_le_bytes());
+        for cell in &self.tape {
+            out.extend(cell.to_le_bytes());
+        }
+        out.extend(self.rng.to_le_bytes());
+        out.extend(self.number.value.to_le_bytes());
+        out.extend([self.number.negative as u8, self.number.started as u8]);
+        out.extend((self.partial.len() as u64).to_le_bytes());
+        out.extend(&self.partial);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BfError> {
+        let invalid = |what: &str| BfError::Checkpoint(what.to_string());
+        let mut reader = Reader(bytes);
+        let version = match reader.take(MAGIC.len())? {
+            magic if magic == MAGIC => 3,
+            magic if magic == MAGIC_V2 => 2,
+            magic if magic == MAGIC_V1 => 1,
+            _ => return Err(invalid("not a checkpoint file")),
+        };
+        let name = reader.text()?;
+        let source = reader.text()?;
+        let settings = reader.text()?;
+        let ir = reader.take(1)?[0] != 0;
+        let ip = reader.u64()? as usize;
+        let steps = reader.u64()?;
+        let pointer = reader.u64()? as usize;
+        let len = reader.u64()? as usize;
+        let tape = reader
+            .take(
+                len.checked_mul(4)
+                    .ok_or_else(|| invalid("tape too large"))?,
+            )?
+            .chunks_exact(4)
+            .map(|cell| u32::from_le_bytes(cell.try_into().unwrap()))
+            .collect();
+        let (rng, number) = match version {
+            // Starting the generator over, as resuming one of these always did
+            1 => (Rng::from_time().state(), Number::default()),
+            _ => {
+                let rng = reader.u64()?;
+                let value = reader.u64()?;
+                let flags = reader.take(2)?;
+                let number = Number {
+                    value,
+                    negative: flags[0] != 0,
+                    started: flags[1] != 0,
+                };
+                (rng, number)
+            }
+        };
+        let partial = match version {
+            3 => {
+                let len = reader.u64()? as usize;
+                reader.take(len)?.to_vec()
+            }
+            _ => vec![],
+        };
+
+        let mut fields: Vec<&str> = settings.split(' ').collect();
+        // Checkpoints from before extensions existed have none enabled
+        if fields.len() == 5 {
+            fields.push("none");
+        }
+        let default_depth = Settings::default().max_depth.unwrap_or(0).to_string();
+        // and ones from before nesting could be limited have the default limit
+        if fields.len() == 6 {
+            fields.push(&default_depth);
+        }
+        let [cell_size, eof, tape_kind, max_steps, opt_level, extensions, max_depth] = fields[..]
+        else {
+            return Err(invalid("malformed settings"));
+        };
+        let settings = Settings {
+            cell_size: cell_size.parse().map_err(|err: String| invalid(&err))?,
+            eof: eof.parse().map_err(|err: String| invalid(&err))?,
+            tape: tape_kind.parse().map_err(|err: String| invalid(&err))?,
+            max_steps: match max_steps.parse() {
+                Ok(0) => None,
+                Ok(n) => Some(n),
+                Err(_) => return Err(invalid("malformed step limit")),
+            },
+            opt_level: opt_level
+                .parse()
+                .map_err(|_| invalid("malformed optimization level"))?,
+            extensions: extensions.parse().map_err(|err: String| invalid(&err))?,
+            max_depth: match max_depth.parse() {
+                Ok(0) => None,
+                Ok(n) => Some(n),
+                Err(_) => return Err(invalid("malformed nesting limit")),
+            },
+            // How output is flushed isn't part of the program's state, and the generator is
+            // saved as it was rather than by its seed
+            ..Settings::default()
+        };
+
+        Ok(Self {
+            name,
+            source,
+            ir,
+            settings,
+            ip,
+            steps,
+            tape,
+            pointer,
+            rng,
+            number,
+            partial,
+        })
+    }
+}
+
+// Pulls little-endian fields off the front of a checkpoint.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BfError> {
+        if self.0.len() < n {
+            return Err(BfError::Checkpoint("file is truncated".to_string()));
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u64(&mut self) -> Result<u64, BfError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn text(&mut self) -> Result<String, BfError> {
+        let len = self.u64()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| BfError::Checkpoint("text is not UTF-8".to_string()))
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use bf::backend::{backend, backends, ExecBackend, Io};
+
+use super::{compile, config, report, unknown, Args, CliResult, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut names = None;
+    let mut runs = 3;
+    let mut input = String::new();
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--backend" => names = Some(args.value(&arg)?),
+            "--runs" => runs = args.parsed::<usize>(&arg)?.max(1),
+            "--input-string" => input = args.value(&arg)?,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("bench needs a program".to_string()))?;
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    let program = compile(&mut code, &settings).map_err(|err| report(&err, &file, &code, None))?;
+
+    let selected: Vec<Box<dyn ExecBackend>> = match names {
+        None => backends(),
+        Some(names) => names
+            .split(',')
+            .map(|name| {
+                backend(name).ok_or_else(|| UsageError(format!("Unknown backend `{name}`")))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    println!(
+        "{:<8} {:>12} {:>12} {:>12}  output",
+        "backend", "prepare", "best", "mean"
+    );
+    let mut expected: Option<Vec<u8>> = None;
+    for backend in selected {
+        let mut prepare = Duration::MAX;
+        let mut best = Duration::MAX;
+        let mut total = Duration::ZERO;
+        let mut output = vec![];
+        for _ in 0..runs {
+            output.clear();
+            let io = Io {
+                input: &mut input.as_bytes(),
+                output: &mut output,
+            };
+            let run = backend
+                .execute(&program, io, &settings)
+                .map_err(|err| format!("{}: {err}", backend.name()))?;
+            prepare = prepare.min(run.prepare);
+            best = best.min(run.elapsed);
+            total += run.elapsed;
+        }
+        // Every backend should agree with the first one
+        let verdict = match &expected {
+            None => "reference",
+            Some(expected) if *expected == output => "matches",
+            Some(_) => "DIFFERS",
+        };
+        expected.get_or_insert(output);
+        println!(
+            "{:<8} {:>12} {:>12} {:>12}  {verdict}",
+            backend.name(),
+            format!("{prepare:.2?}"),
+            format!("{best:.2?}"),
+            format!("{:.2?}", total / runs as u32)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// Compiled programs kept between runs in the user's cache directory, see `bf::cache`. The
+// cache is only ever a shortcut: a directory that can't be read or written means compiling
+// as if it weren't there.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use bf::passes::{Pass, PassStats};
+use bf::{BfError, Program, Settings};
+
+// Sources shorter than this compile faster than their artifact is read
+const MIN_SIZE: usize = 1 << 12;
+
+fn dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("BF_RUST_CACHE_DIR") {
+        return Some(dir.into());
+    }
+    let base = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("bf-rust"))
+}
+
+// Like `Program::with_max_depth`, with no pass statistics when the program came from the
+// cache
+pub fn compile(
+    code: &str,
+    settings: &Settings,
+    passes: &[Box<dyn Pass>],
+) -> Result<(Program, Option<Vec<PassStats>>), BfError> {
+    let compile = || Program::with_max_depth(code, settings.extensions, passes, settings.max_depth);
+    let Some(dir) = dir().filter(|_| code.len() >= MIN_SIZE) else {
+        return compile().map(|(program, stats)| (program, Some(stats)));
+    };
+    let key = bf::cache::key(code, settings.extensions, passes, settings.max_depth);
+    let path = dir.join(format!("{key:016x}.bfc"));
+    if let Some(program) = fs::read(&path)
+        .ok()
+        .and_then(|bytes| bf::cache::from_bytes(&bytes, key))
+    {
+        return Ok((program, None));
+    }
+    let (program, stats) = compile()?;
+    // Written aside and renamed, so a run reading it at the same time never sees half of it
+    let partial = dir.join(format!("{key:016x}.{}.tmp", std::process::id()));
+    let _ = fs::create_dir_all(&dir)
+        .and_then(|()| fs::write(&partial, bf::cache::to_bytes(&program, key)))
+        .and_then(|()| fs::rename(&partial, &path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&partial);
+        });
+    Ok((program, Some(stats)))
+}
@@ -0,0 +1,91 @@
+use bf::diagnostic::line_col;
+
+use super::{color, expand, report, unknown, Args, CliResult, Reported, UsageError};
+
+pub fn main(args: Args) -> CliResult {
+    let mut files = vec![];
+    let mut show_loops = false;
+    for arg in args {
+        match arg.as_str() {
+            "--loops" => show_loops = true,
+            _ if arg.starts_with('-') => return Err(unknown(&arg)),
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        return Err(UsageError("check needs at least one program".to_string()).into());
+    }
+
+    // Keep going after a bad file so every problem is reported in one pass
+    let mut failed = 0;
+    for file in &files {
+        let mut code = match std::fs::read_to_string(file) {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("error: {file}: {err}");
+                failed += 1;
+                continue;
+            }
+        };
+        let info = match expand(&mut code).and_then(|()| bf::check(&code)) {
+            Ok(info) => info,
+            Err(err) => {
+                eprint!("{}", report(&err, file, &code, None));
+                failed += 1;
+                continue;
+            }
+        };
+
+        // Valid programs can still have mistakes worth a look, they don't fail the check
+        let warnings = bf::warnings(&code)?;
+        let comments = bf::analysis::comments(&code)?;
+        for diagnostic in warnings.iter().chain(&comments) {
+            eprint!("{}", diagnostic.render(file, &code, color()));
+        }
+        let mut noted = String::new();
+        for (n, noun) in [
+            (warnings.len(), "warning"),
+            (comments.len(), "comment loop"),
+        ] {
+            if n > 0 {
+                noted += &format!(", {}", plural(n, noun));
+            }
+        }
+        println!(
+            "{file}: ok, {}, {} (max nesting depth {}){noted}",
+            plural(info.instructions, "instruction"),
+            plural(info.loops.len(), "loop"),
+            info.max_depth
+        );
+        if show_loops {
+            for lp in &info.loops {
+                let (open_line, open_col) = line_col(&code, lp.open);
+                let (close_line, close_col) = line_col(&code, lp.close);
+                println!(
+                    "{}[ {}:{} .. {}:{} ]",
+                    "  ".repeat(lp.depth),
+                    open_line + 1,
+                    open_col + 1,
+                    close_line + 1,
+                    close_col + 1
+                );
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        n => Err(Box::new(Reported(format!(
+            "{n} of {} programs failed\n",
+            files.len()
+        )))),
+    }
+}
+
+// `1 loop` but `2 loops`
+fn plural(n: usize, noun: &str) -> String {
+    match n {
+        1 => format!("1 {noun}"),
+        n => format!("{n} {noun}s"),
+    }
+}
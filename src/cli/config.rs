@@ -0,0 +1,72 @@
+// Defaults come from the config file, then `BF_RUST_*` variables, then command line flags.
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use bf::Settings;
+
+use super::{Args, UsageError};
+
+// Each setting with its environment variable and command line flags, `spec` first so the
+// variables for single settings override it
+const SETTINGS: &[(&str, &str, &[&str])] = &[
+    ("spec", "BF_RUST_SPEC", &["--spec"]),
+    ("cell_size", "BF_RUST_CELL_SIZE", &["--cell-size"]),
+    ("eof", "BF_RUST_EOF", &["--eof"]),
+    ("tape", "BF_RUST_TAPE", &["--tape"]),
+    ("max_steps", "BF_RUST_MAX_STEPS", &["--max-steps"]),
+    ("max_depth", "BF_RUST_MAX_DEPTH", &["--max-depth"]),
+    ("opt_level", "BF_RUST_OPT_LEVEL", &["-O", "--opt-level"]),
+    ("flush", "BF_RUST_FLUSH", &["--flush"]),
+    ("extensions", "BF_RUST_EXTENSIONS", &["--extensions"]),
+    ("seed", "BF_RUST_SEED", &["--seed"]),
+];
+
+pub fn load() -> Result<Settings, Box<dyn Error>> {
+    let mut settings = Settings::default();
+    if let Some(path) = config_path() {
+        match fs::read_to_string(&path) {
+            Ok(text) => settings
+                .apply_config(&text)
+                .map_err(|err| format!("{}: {err}", path.display()))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => (),
+            Err(err) => return Err(format!("{}: {err}", path.display()).into()),
+        }
+    }
+    for (key, var, _) in SETTINGS {
+        if let Ok(value) = env::var(var) {
+            settings
+                .set(key, &value)
+                .map_err(|err| format!("{var}: {err}"))?;
+        }
+    }
+    Ok(settings)
+}
+
+// Handles the flags shared by every command that runs programs, false if `arg` isn't one
+pub fn flag(settings: &mut Settings, arg: &str, args: &mut Args) -> Result<bool, UsageError> {
+    // `-O3` as well as `-O 3`
+    if let Some(level) = arg.strip_prefix("-O").filter(|level| !level.is_empty()) {
+        settings.set("opt_level", level).map_err(UsageError)?;
+        return Ok(true);
+    }
+    let Some((key, _, _)) = SETTINGS.iter().find(|(_, _, flags)| flags.contains(&arg)) else {
+        return Ok(false);
+    };
+    let value = args.value(arg)?;
+    settings.set(key, &value).map_err(UsageError)?;
+    Ok(true)
+}
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("BF_RUST_CONFIG") {
+        return Some(path.into());
+    }
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("bf-rust").join("config.toml"))
+}
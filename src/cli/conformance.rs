@@ -0,0 +1,17 @@
+use super::{config, unknown, Args, CliResult};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            _ => return Err(unknown(&arg)),
+        }
+    }
+    let findings = bf::conformance::run(&settings);
+    let width = findings.iter().map(|f| f.probe.len()).max().unwrap_or(0);
+    for finding in findings {
+        println!("{:<width$}  {}", finding.probe, finding.behavior);
+    }
+    Ok(())
+}
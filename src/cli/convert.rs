@@ -0,0 +1,50 @@
+use bf::dialect::{Dialect, Substitution};
+use bf::Extensions;
+
+use super::{expand, report, unknown, Args, CliResult, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut from = Dialect::Brainfuck;
+    let mut to = None;
+    // Kept as written unless asked, apart from comments
+    let mut opt_level = 0;
+    let mut extensions = Extensions::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = dialect(&args.value(&arg)?)?,
+            "--to" => to = Some(dialect(&args.value(&arg)?)?),
+            "-O" | "--opt-level" => opt_level = args.parsed(&arg)?,
+            "--extensions" => extensions = args.value(&arg)?.parse().map_err(UsageError)?,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("convert needs a program".to_string()))?;
+    let to = to.ok_or_else(|| UsageError("convert needs --to DIALECT".to_string()))?;
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    // Snippets are Brainfuck, other dialects have no `@use`
+    if matches!(from, Dialect::Brainfuck) {
+        expand(&mut code).map_err(|err| report(&err, &file, &code, None))?;
+    }
+    let program = from
+        .compile(&code, opt_level, extensions)
+        .map_err(|err| report(&err, &file, &code, None))?;
+    let converted = to.write(&program)?;
+    println!("{converted}");
+    Ok(())
+}
+
+// `bf`, `ook` or `map:FILE`
+fn dialect(name: &str) -> Result<Dialect, Box<dyn std::error::Error>> {
+    if let Some(dialect) = Dialect::named(name) {
+        return Ok(dialect);
+    }
+    let Some(path) = name.strip_prefix("map:") else {
+        let message = format!("Unknown dialect {name:?}, expected bf, ook or map:FILE");
+        return Err(UsageError(message).into());
+    };
+    let text = std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+    let map = Substitution::parse(&text).map_err(|err| format!("{path}: {err}"))?;
+    Ok(Dialect::Map(map))
+}
@@ -0,0 +1,65 @@
+use std::path::Path;
+use std::time::Instant;
+
+use super::{config, unknown, Args, CliResult};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut dir = None;
+    let mut bless = false;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--bless" => bless = true,
+            _ if arg.starts_with('-') || dir.is_some() => return Err(unknown(&arg)),
+            _ => dir = Some(arg),
+        }
+    }
+    let dir = dir.unwrap_or_else(|| "corpus".to_string());
+    let cases =
+        bf::corpus::load(Path::new(&dir), &settings).map_err(|err| format!("{dir}: {err}"))?;
+
+    let mut failed = 0;
+    for case in &cases {
+        let start = Instant::now();
+        let result = case.run();
+        let time = start.elapsed();
+        let verdict = match (result, &case.expected) {
+            (Err(err), _) => format!("FAIL  {}: {err}", case.name),
+            // Record the output as the new expectation
+            (Ok(output), expected) if bless && expected.as_ref() != Some(&output) => {
+                let path = case.output_path();
+                std::fs::write(&path, output)
+                    .map_err(|err| format!("{}: {err}", path.display()))?;
+                format!("saved {} ({time:.2?})", case.name)
+            }
+            (Ok(output), Some(expected)) if output == *expected => {
+                format!("ok    {} ({time:.2?})", case.name)
+            }
+            (Ok(output), Some(expected)) => {
+                let at = output
+                    .iter()
+                    .zip(expected)
+                    .position(|(a, b)| a != b)
+                    .unwrap_or(output.len().min(expected.len()));
+                format!(
+                    "FAIL  {}: output differs from {}.out at byte {at}",
+                    case.name, case.name
+                )
+            }
+            (Ok(_), None) => format!(
+                "FAIL  {}: no {}.out, record it with --bless",
+                case.name, case.name
+            ),
+        };
+        if verdict.starts_with("FAIL") {
+            failed += 1;
+        }
+        println!("{verdict}");
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{failed} of {} programs failed", cases.len()).into()),
+    }
+}
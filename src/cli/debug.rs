@@ -0,0 +1,159 @@
+// An interactive debugger, reading commands from stdin and showing where the program stopped.
+use std::io::{stderr, stdin, stdout, BufRead, Write};
+use std::sync::atomic::Ordering;
+
+use bf::{BfError, Debugger, Machine, RunState};
+
+use super::run::tape_window;
+use super::{compile, config, report, signal, unknown, Args, CliResult, UsageError};
+
+const HELP: &str = "\
+step [N], s [N]   Run N instructions (default 1)
+next-loop, nl     Run until the current loop exits
+finish, f         Run to the `]` of the current loop
+until N, u N      Run until instruction N is next, numbered as in --emit-ir
+continue, c       Run to the end
+tape [N], t [N]   Show N cells either side of the pointer (default 8)
+where, w          Show the next instruction
+help, h           List these commands
+quit, q           Leave the debugger
+An empty line repeats the previous command, Ctrl-C stops a run.";
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut input = None;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--input-string" => input = Some(args.value(&arg)?),
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("debug needs a program".to_string()))?;
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    let program = compile(&mut code, &settings).map_err(|err| report(&err, &file, &code, None))?;
+
+    let mut machine = Machine::with_settings(program, &settings);
+    // Without --input-string, `,` asks for a line when it needs one
+    if let Some(input) = &input {
+        machine.feed(input.as_bytes());
+        machine.close_input();
+    }
+    let mut debugger = Debugger::new(machine);
+    let interrupt = signal::interrupt_flag();
+    debugger.set_interrupt(interrupt.clone());
+
+    let mut lines = stdin().lock().lines();
+    let mut last = String::new();
+    show(debugger.machine(), &code);
+    loop {
+        eprint!("(bf) ");
+        stderr().flush()?;
+        let Some(line) = lines.next() else {
+            return Ok(());
+        };
+        let line = line?;
+        if !line.trim().is_empty() {
+            last = line;
+        }
+        let mut words = last.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let arg = words.next();
+        let number = |default| match arg {
+            None => Ok(default),
+            Some(word) => word
+                .parse()
+                .map_err(|_| format!("expected a number, got `{word}`")),
+        };
+        // Ctrl-C only stops the run it happened in
+        interrupt.store(false, Ordering::Relaxed);
+        signal::interrupt_flag();
+
+        let state = loop {
+            let state = match command {
+                "step" | "s" => match number(1) {
+                    Ok(count) => Some(debugger.step(count)),
+                    Err(err) => break Err(err),
+                },
+                "next-loop" | "nl" => debugger.next_loop(),
+                "finish" | "f" => debugger.finish(),
+                "until" | "u" => match arg.map(str::parse) {
+                    Some(Ok(target)) => Some(debugger.until(target)),
+                    _ => break Err("until needs an instruction number".to_string()),
+                },
+                "continue" | "c" => Some(debugger.resume()),
+                "tape" | "t" => match number(8) {
+                    Ok(radius) => {
+                        eprint!("{}", tape_window(debugger.machine(), radius as usize));
+                        break Ok(None);
+                    }
+                    Err(err) => break Err(err),
+                },
+                "where" | "w" => {
+                    show(debugger.machine(), &code);
+                    break Ok(None);
+                }
+                "help" | "h" => {
+                    eprintln!("{HELP}");
+                    break Ok(None);
+                }
+                "quit" | "q" => return Ok(()),
+                _ => break Err(format!("unknown command `{command}`, try `help`")),
+            };
+            let Some(state) = state else {
+                break Err("not inside a loop".to_string());
+            };
+            let machine = debugger.machine_mut();
+            stdout().write_all(&machine.take_output())?;
+            stdout().flush()?;
+            if !matches!(state, RunState::NeedsInput) {
+                break Ok(Some(state));
+            }
+            // Feed the program a line, then carry on with the same command
+            eprint!("input> ");
+            stderr().flush()?;
+            match lines.next() {
+                Some(line) => machine.feed(format!("{}\n", line?).as_bytes()),
+                None => machine.close_input(),
+            }
+        };
+        match state {
+            Ok(None) => (),
+            Ok(Some(RunState::Finished)) => eprintln!(
+                "program finished after {} steps",
+                debugger.machine().steps()
+            ),
+            Ok(Some(RunState::Error(BfError::Interrupted))) => {
+                eprintln!("interrupted");
+                show(debugger.machine(), &code);
+            }
+            Ok(Some(RunState::Error(err))) => eprintln!("error: {err}"),
+            Ok(Some(_)) => show(debugger.machine(), &code),
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+}
+
+// The next instruction with the source it came from, and the state around it
+fn show(machine: &Machine, code: &str) {
+    let program = machine.program();
+    let ip = machine.ip();
+    let Some(span) = program.spans.get(ip) else {
+        eprintln!("at the end, after {} steps", machine.steps());
+        return;
+    };
+    let source: String = code[span.start..span.end].chars().take(16).collect();
+    eprintln!(
+        "{ip:>5}  {:<10} `{source}` at {}, depth {}, cell {} = {}, {} steps",
+        bf::ir::mnemonic(program, ip),
+        span.start,
+        program.depth(ip),
+        machine.pointer(),
+        machine.cell(),
+        machine.steps()
+    );
+}
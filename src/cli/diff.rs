@@ -0,0 +1,85 @@
+use bf::diff::{lockstep, Verdict};
+use bf::{Machine, Tape};
+
+use super::{compile, config, report, unknown, Args, CliResult, Reported, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut files = vec![];
+    let mut input = vec![];
+    let mut tapes = false;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--input" => {
+                let path = args.value(&arg)?;
+                input = std::fs::read(&path).map_err(|err| format!("{path}: {err}"))?;
+            }
+            "--input-string" => input = args.value(&arg)?.into_bytes(),
+            "--tapes" => tapes = true,
+            _ if arg.starts_with('-') || files.len() == 2 => return Err(unknown(&arg)),
+            _ => files.push(arg),
+        }
+    }
+    if files.len() != 2 {
+        return Err(UsageError("diff needs two programs".to_string()).into());
+    }
+    // The same program twice would never end when it doesn't
+    if settings.max_steps.is_none() {
+        settings.max_steps = Some(10_000_000);
+    }
+    // Looking up cells every step is cheapest on a contiguous tape
+    settings.tape = Tape::Dynamic;
+
+    let mut machines = vec![];
+    for file in &files {
+        let mut code = std::fs::read_to_string(file).map_err(|err| format!("{file}: {err}"))?;
+        let program =
+            compile(&mut code, &settings).map_err(|err| report(&err, file, &code, None))?;
+        let mut machine = Machine::with_settings(program, &settings);
+        machine.feed(&input);
+        machine.close_input();
+        machines.push(machine);
+    }
+    let right = machines.pop().unwrap();
+    let left = machines.pop().unwrap();
+    let result = lockstep(left, right, tapes);
+
+    let (a, b) = (&files[0], &files[1]);
+    let [ip_a, ip_b] = result.ips;
+    let at = format!(
+        "after step {} ({a} at instruction {ip_a}, {b} at {ip_b})",
+        result.step
+    );
+    let byte = |byte: Option<u8>| match byte {
+        Some(byte) => format!("'{}' ({byte})", [byte].escape_ascii()),
+        None => "nothing".to_string(),
+    };
+    let message = match result.verdict {
+        Verdict::Same => {
+            println!(
+                "same output ({} bytes), {} steps",
+                result.outputs[0].len(),
+                result.step
+            );
+            return Ok(());
+        }
+        Verdict::Output {
+            at: idx,
+            left,
+            right,
+        } => format!(
+            "outputs differ at byte {idx} {at}: {a} wrote {}, {b} wrote {}",
+            byte(left),
+            byte(right)
+        ),
+        Verdict::Tape { cell, left, right } => {
+            format!("tapes differ at cell {cell} {at}: {a} has {left}, {b} has {right}")
+        }
+        Verdict::Failed { left, error } => {
+            let file = if left { a } else { b };
+            format!("{file} failed {at}: {error}")
+        }
+    };
+    Err(Box::new(Reported(format!("{message}\n"))))
+}
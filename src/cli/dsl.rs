@@ -0,0 +1,30 @@
+use std::io::{stdin, stdout};
+
+use bf::{Interpreter, Program};
+
+use super::{config, report, unknown, Args, CliResult, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut run = false;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--run" => run = true,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("dsl needs a program".to_string()))?;
+    let source = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    let code = bf::dsl::compile(&source).map_err(|err| report(&err, &file, &source, None))?;
+
+    if !run {
+        println!("{code}");
+        return Ok(());
+    }
+    let program = Program::with_settings(&code, &settings)?;
+    Interpreter::with_settings(program, &settings, stdin().lock(), stdout().lock()).run()?;
+    Ok(())
+}
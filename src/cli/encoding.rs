@@ -0,0 +1,134 @@
+// How output bytes are turned into text for the terminal. Raw passes them through untouched,
+// UTF-8 checks them, replacing or refusing invalid sequences, and Latin-1 turns each byte into
+// the character with that code, written out as UTF-8.
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Encoding {
+    Raw,
+    Utf8,
+    Latin1,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Self::Raw),
+            "utf8" | "utf-8" => Ok(Self::Utf8),
+            "latin1" | "latin-1" => Ok(Self::Latin1),
+            _ => Err(format!(
+                "Invalid output encoding {s:?}, expected raw, utf8 or latin1"
+            )),
+        }
+    }
+}
+
+// What UTF-8 output does with bytes that aren't UTF-8
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Invalid {
+    Replace, // Write U+FFFD in their place
+    Error,   // Fail the write, stopping the program
+}
+
+impl FromStr for Invalid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(Self::Replace),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "Invalid policy for invalid UTF-8 {s:?}, expected replace or error"
+            )),
+        }
+    }
+}
+
+pub struct Encoder<W: Write> {
+    inner: W,
+    encoding: Encoding,
+    invalid: Invalid,
+    pending: Vec<u8>, // The start of a UTF-8 sequence the next write may complete
+    written: u64,     // Bytes taken in before `pending`, for pointing at the bad one
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(inner: W, encoding: Encoding, invalid: Invalid) -> Self {
+        Self {
+            inner,
+            encoding,
+            invalid,
+            pending: vec![],
+            written: 0,
+        }
+    }
+
+    // Deals with a sequence the output stopped in the middle of
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            self.bad()?;
+        }
+        self.inner.flush()
+    }
+
+    fn bad(&mut self) -> io::Result<()> {
+        match self.invalid {
+            Invalid::Replace => self.inner.write_all("\u{FFFD}".as_bytes()),
+            Invalid::Error => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("output is not UTF-8 from byte {}", self.written),
+            )),
+        }
+    }
+
+    fn utf8(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.pending.extend(buf);
+        loop {
+            let (valid, skip) = match std::str::from_utf8(&self.pending) {
+                Ok(_) => (self.pending.len(), None),
+                Err(err) => (err.valid_up_to(), err.error_len()),
+            };
+            self.inner.write_all(&self.pending[..valid])?;
+            self.pending.drain(..valid);
+            self.written += valid as u64;
+            // Without an error length the rest is an unfinished sequence, kept for later
+            let Some(skip) = skip else {
+                return Ok(());
+            };
+            self.bad()?;
+            self.pending.drain(..skip);
+            self.written += skip as u64;
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.encoding {
+            Encoding::Raw => self.inner.write_all(buf)?,
+            Encoding::Utf8 => self.utf8(buf)?,
+            Encoding::Latin1 => {
+                let text: String = buf.iter().map(|&byte| byte as char).collect();
+                self.inner.write_all(text.as_bytes())?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Encoder<W> {
+    // A run that failed can't report a last bad sequence, but can still show it replaced
+    fn drop(&mut self) {
+        if !self.pending.is_empty() && self.invalid == Invalid::Replace {
+            let _ = self.finish();
+        }
+    }
+}
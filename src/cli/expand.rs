@@ -0,0 +1,27 @@
+use bf::stdlib::SNIPPETS;
+
+use super::{report, unknown, Args, CliResult, UsageError};
+
+pub fn main(args: Args) -> CliResult {
+    let mut file = None;
+    let mut list = false;
+    for arg in args {
+        match arg.as_str() {
+            "--list" => list = true,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    if list {
+        for snippet in SNIPPETS {
+            let call = format!("{}({})", snippet.name, snippet.params);
+            println!("  {call:<18}{}", snippet.doc);
+        }
+        return Ok(());
+    }
+    let file = file.ok_or_else(|| UsageError("expand needs a program".to_string()))?;
+    let code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    let expanded = bf::stdlib::expand(&code).map_err(|err| report(&err, &file, &code, None))?;
+    print!("{expanded}");
+    Ok(())
+}
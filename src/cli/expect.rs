@@ -0,0 +1,39 @@
+use bf::expect::Script;
+use bf::Machine;
+
+use super::{compile, config, report, unknown, Args, CliResult, Reported, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut files = vec![];
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            _ if arg.starts_with('-') || files.len() == 2 => return Err(unknown(&arg)),
+            _ => files.push(arg),
+        }
+    }
+    let [file, script_file] = &files[..] else {
+        return Err(UsageError("expect needs a program and a script".to_string()).into());
+    };
+    // An expect that never matches shouldn't wait forever
+    if settings.max_steps.is_none() {
+        settings.max_steps = Some(10_000_000);
+    }
+
+    let mut code = std::fs::read_to_string(file).map_err(|err| format!("{file}: {err}"))?;
+    let text =
+        std::fs::read_to_string(script_file).map_err(|err| format!("{script_file}: {err}"))?;
+    let script = Script::parse(&text).map_err(|err| report(&err, script_file, &text, None))?;
+    let program = compile(&mut code, &settings).map_err(|err| report(&err, file, &code, None))?;
+    let mut machine = Machine::with_settings(program, &settings);
+    match script.run(&mut machine) {
+        Ok(()) => {
+            println!("ok    {script_file}: {} steps", machine.steps());
+            Ok(())
+        }
+        Err(failure) => Err(Box::new(Reported(format!(
+            "FAIL  {script_file}: {failure}\n"
+        )))),
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+use std::time::Duration;
+
+use bf::fuzz::{fuzz, Grammar, Inputs, Kind, Options};
+
+use super::{compile, config, report, unknown, Args, CliResult, Reported, UsageError};
+
+// Input bytes shown for each finding, the rest are in the saved file
+const SHOWN: usize = 48;
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut grammar = None;
+    let mut save = None;
+    let mut options = Options::default();
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--runs" => options.runs = args.parsed(&arg)?,
+            "--max-len" => options.max_len = args.parsed(&arg)?,
+            "--timeout-ms" => options.limits.timeout = Duration::from_millis(args.parsed(&arg)?),
+            "--slow" => options.slow = args.parsed(&arg)?,
+            "--grammar" => grammar = Some(args.value(&arg)?),
+            "--save" => save = Some(args.value(&arg)?),
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let Some(file) = file else {
+        return Err(UsageError("fuzz needs a program".to_string()).into());
+    };
+    // Without --max-steps, or with 0 for none, the fuzzer keeps its own limit
+    if let Some(steps) = settings.max_steps {
+        options.limits.max_steps = steps;
+    }
+    // The seed picks the inputs, so the same one finds the same inputs again
+    options.seed = settings.seed.unwrap_or(0);
+    let inputs = match grammar {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path).map_err(|err| format!("{path}: {err}"))?;
+            Inputs::Grammar(Grammar::parse(&text).map_err(|err| format!("{path}: {err}"))?)
+        }
+        None => Inputs::Random,
+    };
+
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    let program = compile(&mut code, &settings).map_err(|err| report(&err, &file, &code, None))?;
+    let report = fuzz(&program, &settings, &inputs, &options);
+    println!(
+        "{} runs, {} finished taking a median of {} steps, {} inputs kept",
+        report.runs,
+        report.finished,
+        report.median,
+        report.findings.len()
+    );
+    if report.findings.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(dir) = &save {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{dir}: {err}"))?;
+    }
+    let mut message = String::new();
+    for finding in &report.findings {
+        let shown = &finding.input[..finding.input.len().min(SHOWN)];
+        let more = if finding.input.len() > SHOWN {
+            "..."
+        } else {
+            ""
+        };
+        message += &format!(
+            "{:<12}  run {}, {} steps, input \"{}\"{more}\n",
+            finding.kind.label(),
+            finding.run,
+            finding.steps,
+            shown.escape_ascii()
+        );
+        if let Kind::Error(err) = &finding.kind {
+            message += &format!("              {err}\n");
+        }
+        // Named like the judge's inputs, replayed by giving one as stdin
+        if let Some(dir) = &save {
+            let path = Path::new(dir).join(format!("run-{}.in", finding.run));
+            std::fs::write(&path, &finding.input)
+                .map_err(|err| format!("{}: {err}", path.display()))?;
+        }
+    }
+    Err(Box::new(Reported(message)))
+}
@@ -0,0 +1,28 @@
+use super::{expand, report, unknown, Args, CliResult, UsageError};
+
+pub fn main(args: Args) -> CliResult {
+    let mut file = None;
+    for arg in args {
+        match arg.as_str() {
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("golf needs a program".to_string()))?;
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    expand(&mut code).map_err(|err| report(&err, &file, &code, None))?;
+    let golfed = bf::golf::golf(&code).map_err(|err| report(&err, &file, &code, None))?;
+
+    println!("{}", golfed.code);
+    let saved = golfed.original - golfed.code.len().min(golfed.original);
+    eprintln!(
+        "{} -> {} characters ({} from comments, {} from {} constant rewrites), {:.1}% smaller",
+        golfed.original,
+        golfed.code.len(),
+        golfed.original - golfed.minified,
+        golfed.minified as isize - golfed.code.len() as isize,
+        golfed.rewrites,
+        saved as f64 * 100.0 / golfed.original.max(1) as f64
+    );
+    Ok(())
+}
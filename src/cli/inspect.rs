@@ -0,0 +1,87 @@
+use bf::analysis::COMMANDS;
+use bf::propagate::Fact;
+
+use super::{expand, report, unknown, Args, CliResult, UsageError};
+
+pub fn main(args: Args) -> CliResult {
+    let mut file = None;
+    let mut facts = false;
+    for arg in args {
+        match arg.as_str() {
+            "--facts" => facts = true,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("inspect needs a program".to_string()))?;
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    expand(&mut code).map_err(|err| report(&err, &file, &code, None))?;
+    let info = bf::inspect(&code).map_err(|err| report(&err, &file, &code, None))?;
+
+    println!(
+        "Instructions: {} of {} characters ({:.1}% comments)",
+        info.instructions(),
+        info.characters,
+        info.comment_ratio() * 100.0
+    );
+    let counts: Vec<String> = COMMANDS
+        .iter()
+        .zip(info.counts)
+        .map(|(cmd, count)| format!("{cmd} {count}"))
+        .collect();
+    println!("  {}", counts.join("  "));
+    println!(
+        "Loops: {} (max nesting depth {})",
+        info.loops, info.max_depth
+    );
+    match info.tape_span {
+        Some(span) => println!("Tape span: {span} cells"),
+        None => println!("Tape span: unknown, some loops move the pointer"),
+    }
+    let known = &info.facts;
+    println!(
+        "Known values: {} of {} commands always run on the same cell value",
+        known.known(),
+        info.instructions()
+    );
+    println!("Dead loops: {}", info.dead_loops);
+    let commented: usize = info.comments.iter().map(|span| span.end - span.start).sum();
+    println!(
+        "Comment loops: {}, {commented} bytes left out",
+        info.comments.len()
+    );
+    let prefix = &known.prefix;
+    println!(
+        "Constant prefix: {} commands, writing {} bytes {:?}",
+        prefix.len,
+        prefix.output.len(),
+        String::from_utf8_lossy(&prefix.output)
+    );
+    if facts {
+        // Commands and facts line up one to one at optimization level 0, once comment loops
+        // are left out
+        let (mut line, mut column) = (1, 0);
+        let mut commands = vec![];
+        let mut ahead = info.comments.iter().peekable();
+        for (offset, c) in code.char_indices() {
+            column += 1;
+            while ahead.next_if(|span| span.end <= offset).is_some() {}
+            let comment = ahead.peek().is_some_and(|span| span.start <= offset);
+            if COMMANDS.contains(&c) && !comment {
+                commands.push((c, line, column));
+            }
+            if c == '\n' {
+                (line, column) = (line + 1, 0);
+            }
+        }
+        for ((c, line, column), fact) in commands.into_iter().zip(&known.cells) {
+            let fact = match fact {
+                Fact::Unreached => "never runs".to_string(),
+                Fact::Const(value) => format!("cell is {value}"),
+                Fact::Varies => "cell varies".to_string(),
+            };
+            println!("  {line}:{column}  {c}  {fact}");
+        }
+    }
+    Ok(())
+}
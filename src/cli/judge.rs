@@ -0,0 +1,135 @@
+use std::path::Path;
+use std::time::Duration;
+
+use bf::judge::{judge, load, rank, Limits, Submission, Verdict};
+use bf::quine::Normalize;
+
+use super::json;
+use super::{config, unknown, Args, CliResult, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut paths = vec![];
+    let mut limits = Limits::default();
+    let mut normalize = Normalize::Exact;
+    let mut as_json = false;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--timeout-ms" => limits.timeout = Duration::from_millis(args.parsed(&arg)?),
+            "--max-output" => limits.max_output = args.parsed(&arg)?,
+            "--normalize" => normalize = args.value(&arg)?.parse().map_err(UsageError)?,
+            "--json" => as_json = true,
+            _ if arg.starts_with('-') => return Err(unknown(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() < 2 {
+        return Err(UsageError("judge needs a test directory and programs".to_string()).into());
+    }
+    // Without --max-steps, or with 0 for none, the judge keeps its own limit
+    if let Some(steps) = settings.max_steps {
+        limits.max_steps = steps;
+    }
+    let dir = paths.remove(0);
+    let tests = load(Path::new(&dir)).map_err(|err| format!("{dir}: {err}"))?;
+    if tests.is_empty() {
+        return Err(format!("{dir}: no test cases, each needs a NAME.out").into());
+    }
+
+    let mut submissions = vec![];
+    for path in &paths {
+        let source = std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+        submissions.push(judge(path, &source, &settings, &tests, limits, normalize));
+    }
+    rank(&mut submissions);
+    match as_json {
+        true => println!("{}", to_json(&submissions)),
+        false => print!("{}", scoreboard(&submissions, tests.len())),
+    }
+    Ok(())
+}
+
+fn scoreboard(submissions: &[Submission], cases: usize) -> String {
+    let width = submissions
+        .iter()
+        .map(|submission| submission.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("program".len());
+    let mut out = format!(
+        "rank  {:<width$}  passed  {:>10}  {:>12}\n",
+        "program", "time", "instructions"
+    );
+    for (idx, submission) in submissions.iter().enumerate() {
+        let passed = format!("{}/{cases}", submission.passed());
+        out += &format!(
+            "{:>4}  {:<width$}  {passed:<6}  {:>10.2?}  {:>12}\n",
+            idx + 1,
+            submission.name,
+            submission.time(),
+            submission.steps()
+        );
+    }
+    // Then why each failing case failed
+    for submission in submissions {
+        for case in &submission.cases {
+            let detail = match &case.verdict {
+                Verdict::Accepted => continue,
+                Verdict::WrongAnswer(at) => format!("differs at byte {at}"),
+                Verdict::Error(message) => message.clone(),
+                _ => format!("after {} steps", case.steps),
+            };
+            out += &format!(
+                "FAIL  {} {}: {} ({detail})\n",
+                submission.name,
+                case.test,
+                case.verdict.label()
+            );
+        }
+    }
+    out
+}
+
+// `[{"program":..,"rank":1,"passed":2,"time_ms":..,"steps":..,"cases":[{"test":..,
+// "verdict":"accepted","time_ms":..,"steps":..},..]},..]`, verdicts being the labels with
+// `_` for spaces, an `at` for wrong answers and a `message` for errors
+fn to_json(submissions: &[Submission]) -> String {
+    let entries: Vec<String> = submissions
+        .iter()
+        .enumerate()
+        .map(|(idx, submission)| {
+            let cases: Vec<String> = submission
+                .cases
+                .iter()
+                .map(|case| {
+                    let mut entry = format!(
+                        "{{\"test\":{},\"verdict\":{},\"time_ms\":{:.3},\"steps\":{}",
+                        json::quote(&case.test),
+                        json::quote(&case.verdict.label().replace(' ', "_")),
+                        case.time.as_secs_f64() * 1000.0,
+                        case.steps
+                    );
+                    match &case.verdict {
+                        Verdict::WrongAnswer(at) => entry += &format!(",\"at\":{at}"),
+                        Verdict::Error(message) => {
+                            entry += &format!(",\"message\":{}", json::quote(message))
+                        }
+                        _ => (),
+                    }
+                    entry + "}"
+                })
+                .collect();
+            format!(
+                "{{\"program\":{},\"rank\":{},\"passed\":{},\"time_ms\":{:.3},\"steps\":{},\"cases\":[{}]}}",
+                json::quote(&submission.name),
+                idx + 1,
+                submission.passed(),
+                submission.time().as_secs_f64() * 1000.0,
+                submission.steps(),
+                cases.join(",")
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(","))
+}
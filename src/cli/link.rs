@@ -0,0 +1,34 @@
+use bf::link::{Handoff, Unit};
+
+use super::{unknown, Args, CliResult, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut files = vec![];
+    let mut out = None;
+    let mut handoff = Handoff::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => out = Some(args.value(&arg)?),
+            "--handoff" => handoff = args.value(&arg)?.parse().map_err(UsageError)?,
+            _ if arg.starts_with('-') => return Err(unknown(&arg)),
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        return Err(UsageError("link needs compiled units, made with --emit-bfc".into()).into());
+    }
+    let out = out.ok_or_else(|| UsageError("link needs -o FILE".to_string()))?;
+    let units = files
+        .iter()
+        .map(|file| {
+            let bytes = std::fs::read(file).map_err(|err| format!("{file}: {err}"))?;
+            Unit::from_bytes(&bytes).map_err(|err| format!("{file}: {err}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let linked = bf::link::link(&units, handoff).map_err(|(idx, err)| match err {
+        bf::BfError::Unsupported(reason) => format!("{}: {reason}", files[idx]),
+        err => format!("{}: {err}", files[idx]),
+    })?;
+    std::fs::write(&out, linked.to_bytes()).map_err(|err| format!("{out}: {err}"))?;
+    Ok(())
+}
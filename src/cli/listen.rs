@@ -0,0 +1,39 @@
+// Serves a program over TCP: every accepted connection gets a fresh run, with `,` reading
+// from the connection and `.` writing to it.
+use std::io::BufWriter;
+use std::net::{TcpListener, TcpStream};
+
+use bf::{BfError, Interpreter, Program, Settings};
+
+use super::CliResult;
+
+pub fn serve(addr: &str, program: &Program, settings: &Settings, name: &str) -> CliResult {
+    let listener = TcpListener::bind(addr).map_err(|err| format!("{addr}: {err}"))?;
+    eprintln!("{name}: listening on {}", listener.local_addr()?);
+    // One connection at a time, so a program never has to share its output
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("accept failed: {err}");
+                continue;
+            }
+        };
+        let peer = stream
+            .peer_addr()
+            .map_or_else(|_| "unknown peer".to_string(), |addr| addr.to_string());
+        match session(stream, program, settings) {
+            Ok(steps) => eprintln!("{peer}: finished after {steps} steps"),
+            Err(err) => eprintln!("{peer}: {err}"),
+        }
+    }
+    Ok(())
+}
+
+fn session(stream: TcpStream, program: &Program, settings: &Settings) -> Result<u64, BfError> {
+    let input = stream.try_clone()?;
+    let mut interpreter =
+        Interpreter::with_settings(program.clone(), settings, input, BufWriter::new(stream));
+    interpreter.run()?;
+    Ok(interpreter.machine().steps())
+}
@@ -0,0 +1,468 @@
+mod alloc;
+mod bench;
+mod cache;
+mod check;
+mod config;
+mod conformance;
+mod convert;
+mod corpus;
+mod debug;
+mod diff;
+mod dsl;
+mod encoding;
+mod expand;
+mod expect;
+mod fuzz;
+mod golf;
+mod inspect;
+mod json;
+mod judge;
+mod link;
+mod listen;
+mod obfuscate;
+mod pipe;
+mod quine_check;
+mod run;
+mod serve;
+mod signal;
+mod sink;
+mod terminal;
+mod tui;
+mod watch;
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::io::IsTerminal;
+
+use bf::{BfError, Diagnostic, Program, Settings, Span};
+
+const COMMANDS: &[&str] = &[
+    "run",
+    "pipe",
+    "inspect",
+    "check",
+    "obfuscate",
+    "golf",
+    "dsl",
+    "bench",
+    "watch",
+    "corpus",
+    "conformance",
+    "serve",
+    "debug",
+    "expect",
+    "diff",
+    "expand",
+    "quine-check",
+    "tui",
+    "judge",
+    "convert",
+    "fuzz",
+    "link",
+];
+
+pub type CliResult = Result<(), Box<dyn Error>>;
+
+const USAGE: &str = "\
+Usage: bf-rust [run] [FILE] [OPTIONS]
+       bf-rust [run] -e CODE [OPTIONS]
+       bf-rust pipe FILE... [--parallel]
+       bf-rust inspect FILE [--facts]
+       bf-rust check FILE... [--loops]
+       bf-rust obfuscate FILE [--seed N] [--density PERCENT]
+       bf-rust golf FILE
+       bf-rust dsl FILE [--run]
+       bf-rust bench FILE [--backend NAME,...] [--runs N]
+       bf-rust watch FILE [--input-string STRING]
+       bf-rust corpus [DIR] [--bless]
+       bf-rust conformance [SETTINGS]
+       bf-rust debug FILE [--input-string STRING] [SETTINGS]
+       bf-rust expect FILE SCRIPT [SETTINGS]
+       bf-rust diff FILE FILE [--input FILE | --input-string STRING] [--tapes] [SETTINGS]
+       bf-rust expand FILE | --list
+       bf-rust quine-check FILE [--normalize exact|trailing|whitespace|commands] [SETTINGS]
+       bf-rust tui FILE --replay TRACE [--speed N] [SETTINGS]
+       bf-rust judge TESTS FILE... [--timeout-ms N] [--max-output N] [--normalize MODE] [--json]
+       bf-rust convert FILE --to DIALECT [--from DIALECT] [-O N] [--extensions LIST]
+       bf-rust fuzz FILE [--runs N] [--max-len N] [--grammar FILE] [--timeout-ms N] [--slow N]
+                    [--save DIR] [SETTINGS]
+       bf-rust link FILE... -o OUT [--handoff continue|origin]
+       bf-rust serve [--port N] [--host ADDR] [--timeout-ms N] [--max-output N] [SETTINGS]
+
+Commands:
+  run     Run a program (the default, FILE defaults to code.txt)
+          Ctrl-C stops it and reports where it was and the tape around the pointer
+  pipe    Run programs in sequence, feeding each one's output to the next
+  inspect Report instruction counts, loop nesting, tape span and what constant
+          propagation knows without running, --facts lists each command's cell value
+  check   Validate brackets without running, warn about suspicious loops and note the
+          comment loops (prose in a loop opening the program or right after another loop,
+          which can never run) that are left out of the program, --loops lists every loop
+  obfuscate
+          Rewrite a program with no-op noise and junk comments, keeping its behavior
+  golf    Shorten a program, replacing long constant runs with multiplication loops
+          (assumes 8-bit wrapping cells)
+  dsl     Compile the mini language (variables, while, if, print, read) to Brainfuck,
+          or run it straight away with --run
+  bench   Time the program on each backend and check that their outputs agree
+  watch   Re-run the program every time FILE is saved, clearing the screen in between
+  corpus  Run every NAME.b in DIR (default corpus/) with NAME.in as input and NAME.settings,
+          checking the output against NAME.out, --bless records the outputs instead
+  conformance
+          Run the classic portability tests (EOF, tape length, cell size, nesting, bounds,
+          unmatched brackets) and describe how the settings behave on each
+  debug   Step through a program interactively: by instruction, to the end of a loop or
+          iteration, or up to an instruction, `help` at its prompt lists the commands
+  expect  Drive a program with SCRIPT, steps like `expect \"Name?\"` that run it until the
+          text is written, `send \"Bob\\n\"` that give it input and `eof`, one per line or
+          separated by `;`, failing if the program stops before an expected text shows up
+  diff    Run two programs in lockstep on the same input and report the first output
+          byte they disagree on, or with --tapes the first cell, and the step it happened
+  expand  Print a program with its `@use NAME(ARGS)` lines replaced by the snippets they
+          name, as every command running it does first; --list shows the snippets
+  quine-check
+          Run a program without input and check that it prints its own source, ignoring
+          trailing whitespace, all whitespace or everything but commands with --normalize,
+          and show the bytes that differ when it nearly does
+  tui     Step back and forth through a run recorded with --trace-format binary, showing
+          the source, tape and output at each step, with the settings it was recorded with
+  judge   Run every program against the cases in TESTS, NAME.out with an optional NAME.in,
+          within --max-steps (default 10000000), --timeout-ms (default 5000) and --max-output
+          bytes (default 1048576) each, comparing outputs like quine-check's --normalize,
+          and print a scoreboard ranked by cases passed, then fewest instructions
+  convert Translate a program between dialects: bf (the default --from), ook for Ook!, or
+          map:FILE, a file of `COMMAND = WORD` lines spelling each command; comments are
+          dropped, and -O N (default 0) writes the program as that level optimizes it
+  fuzz    Run the program --runs times (default 1000) on inputs of up to --max-len bytes
+          (default 64), random or generated from a grammar of `NAME = \"text\" other | ...`
+          rules, and list the inputs that made it fail, hit --max-steps (default 1000000)
+          or --timeout-ms (default 5000), or take over --slow times (default 10) the median
+          steps; --seed N (default 0) picks the inputs and --save DIR writes them to run-N.in
+  link    Join units compiled with --emit-bfc into one, run like any program, without
+          optimizing them again; each starts on the cell the last one ended on, or with
+          --handoff origin the cell it started on (its moves have to be known ahead)
+  serve   Answer HTTP requests to POST /run (port 8080 on 127.0.0.1 by default) that send
+          JSON with a program, input and settings, running it within --max-steps (default
+          10000000), --timeout-ms (default 5000) and --max-output bytes (default 1048576),
+          and replying with the output, steps taken and how the run ended
+
+Options:
+  -e CODE                 Run CODE given on the command line instead of a file
+  --input-string STRING   Feed STRING to the program instead of stdin
+  --bang-input            Treat everything after the first `!` outside a loop as input,
+                          for files stored in the PROGRAM!INPUT convention
+  --input-newline lf|crlf|cr
+                          Give every line ending of the input, LF, CRLF or CR, to the
+                          program as the one it expects (LF, leaving input as it is, by
+                          default)
+  --input-binary FILE     Feed FILE's bytes to the program exactly as they are, with no
+                          text processing
+  --input-width 8|16|32   How many bits of --input-binary each `,` reads into a cell (8
+                          by default), the interpreter only
+  --input-endian little|big
+                          The byte order of those wider values (little by default)
+  --input-repeat          Start the input over from its beginning whenever it runs out,
+                          reading EOF only if it was empty
+  --raw                   Pass keypresses to `,` as they are typed, without echo or
+                          waiting for Enter, for games and other interactive programs
+  -o, --output FILE       Write the program's output to FILE instead of stdout
+  --mmap                  Write -o through a memory mapping of the file, for programs
+                          that produce gigabytes
+  --output-encoding raw|utf8|latin1
+                          Write output bytes as they are (the default), as UTF-8 text
+                          or as Latin-1 characters, one per byte
+  --output-newline lf|crlf|cr
+                          Write the line endings of a program that ends its lines with
+                          CRLF or CR as LF (LF, leaving output as it is, by default)
+  --invalid-utf8 replace|error
+                          With --output-encoding utf8, show bytes that aren't UTF-8
+                          as U+FFFD (the default) or stop the program
+  -v, --verbose           Print compilation and execution stats to stderr, with what the
+                          run used: the tape's length, memory and reallocations, bytes
+                          read and written, and allocations over the whole process
+  --stats-json FILE       Write the stats of a run with the interpreter to FILE as JSON
+  --spec classic|modern|nesdev
+                          Set the cell size, EOF mode and tape like a family of other
+                          interpreters: classic is 8-bit cells with EOF unchanged,
+                          modern 32-bit cells, EOF -1 and a sparse tape, nesdev 8-bit
+                          cells with EOF 0; later flags override single settings
+  --cell-size 8|16|32     Bits per tape cell
+  --eof zero|minus1|unchanged
+                          What `,` stores once the input runs out
+  --tape dynamic|sparse|fixed:N
+                          Contiguous tape, 4 KiB pages allocated as they are visited, or
+                          N cells from the starting one that moving off is an error
+  --max-steps N           Stop with an error after N instructions (0 for no limit)
+  --max-depth N           Refuse programs with loops nested more than N deep (default
+                          1048576, 0 for no limit)
+  -O, --opt-level N       0 runs every character as-is, 1 folds runs of `+-<>`,
+                          2 (the default) also turns clear loops into a single SET,
+                          3 also turns loops that only move, like `[>]`, into a scan
+                          and unrolls loops run a known number of times, and
+                          propagates constants, storing known values, dropping loops
+                          that never run and running the start of the program ahead;
+                          -O0 to -O3 work too
+  --passes LIST           Run exactly these optimization passes in this order instead,
+                          comma separated from fold, clear-loops, scan, unroll and
+                          propagate, or none;
+                          -v reports the instructions before and after each
+  --no-cache              Compile afresh, without reading or writing the cache of
+                          compiled programs
+  --flush always|line|block
+                          When output is written out: after every byte, every newline
+                          (the default) or only as buffers fill and before input
+  --extensions LIST       Enable commands beyond the standard eight, comma separated:
+                          random (`?` stores a pseudo-random byte in the current cell)
+                          fork (`Y` splits into two threads that take turns between
+                          I/O; the parent's cell becomes 0, the child moves right and
+                          stores 1 there)
+                          numbers (`:` writes the current cell in decimal, `;` skips
+                          whitespace and reads a number with an optional sign, taking
+                          the byte after it too; not supported by wasm)
+  --seed N                Seed for `?`, so runs are reproducible (default: the clock)
+  --interactive           Same as --flush always, so programs drawing with ANSI escape
+                          codes render as they go
+  --detect-hangs          Stop with an error when a loop comes back around with the tape,
+                          pointer and input unchanged, as it can never end
+  --coverage FILE         Write how often each line ran to FILE, as lcov for .info and
+                          .lcov files or otherwise as annotated source
+  --tape-init FILE        Start with the bytes of FILE in the cells from the pointer on,
+                          compiling without what assumes a blank tape: unroll, propagate
+                          and leaving out a comment loop that opens the program
+  --tape-init-hex HEX     Start with the bytes written as HEX, like 48656c6c6f
+  --listen ADDR           Accept TCP connections on ADDR (like 127.0.0.1:4000) one at a
+                          time, running the program afresh with `,` and `.` on each
+  --dump-tape FILE        Write the final tape to FILE, after a header with the pointer
+                          position and cell width, for programs whose result is in memory
+  --exit-cell N|current   When the program ends, exit with the low 8 bits of cell N,
+                          counted from the leftmost cell it visited (the one it started
+                          on unless it went left of it), or of the cell under the
+                          pointer, the interpreter only
+  --exit-last-output      Exit with the last byte the program wrote instead (0 if none)
+  --checkpoint FILE       On Ctrl-C, save the running program's state to FILE and exit
+  --resume FILE           Continue from a checkpoint, its program and settings replace
+                          FILE and any settings flags
+  --emit-ir               Print the optimized instructions instead of running
+  --ir                    Read the program as instructions printed by --emit-ir
+  --emit-bfc FILE         Write the optimized program to FILE as a compiled unit for
+                          `link`, FILE runs in place of the source
+  --emit-wat              Print the program compiled to a WebAssembly text module
+  --emit-wasm FILE        Write the program compiled to a binary WebAssembly module
+  --wasi                  Make --emit-wat and --emit-wasm build a WASI command, reading
+                          stdin and writing stdout, to run with wasmtime or wasmer
+  --emit-rust             Print the program translated to a Rust program
+  --trace FILE            Record every instruction run, with the pointer and any value
+                          written, to FILE
+  --trace-format json|binary
+                          JSON Lines (the default) or compact LEB128 records with the
+                          tape every 100000 steps, which `tui --replay` can show
+  --report-html FILE      Write a standalone web page replaying the run, with the source,
+                          a timeline to scrub through, the tape and the output so far
+  --record-golden FILE    Write a hash chain of the machine's states to FILE, one taken
+                          after every --golden-every N reads and writes (default 1) and
+                          one at the end, for checking later runs against
+  --check-golden FILE     Fail unless the run goes through the same states as the one
+                          recorded in FILE, naming the first that differs; -O3 computes
+                          values ahead of time, so it only matches recordings at -O3
+  --emit-dot              Print the control flow between loops as a Graphviz graph
+  --dot-profile FILE      Run the program and write the same graph to FILE, shaded by how
+                          often each part ran
+  --history N             Keep the last N instructions run (default 16, 0 for none) and
+                          list them with their place in the source when the run fails
+  --speed N               Run at most N instructions a second, evenly paced, to watch a
+                          program at work (default 0, as fast as it can); for tui,
+                          play the trace forward at N steps a second, space pausing
+  --profile               Time every instruction, I/O included, and report the slowest
+                          instructions and loops with how often they ran
+  --backend interp|threaded|tiered|rust|wasm
+                          Run with the interpreter (the default), as threaded code with
+                          a closure per instruction, which is faster without the
+                          interpreter's debugging features, interpreted with hot loops
+                          switched to threaded code (closures, not machine code) once
+                          they warm up, or translate to Rust, build it with rustc and
+                          run the binary; wasm, in builds with the wasm feature, checks
+                          the --emit-wasm module by running it on a small built-in
+                          WebAssembly interpreter, slower than the default
+  --runs N                Runs per backend for bench (default 3)
+  --parallel              Run pipeline stages concurrently
+  -h, --help              Print this message
+
+Defaults for the settings above are read from ~/.config/bf-rust/config.toml
+(or $BF_RUST_CONFIG) as `cell_size = 16`, `eof = \"minus1\"`, ... and then from
+BF_RUST_SPEC, BF_RUST_CELL_SIZE, BF_RUST_EOF, BF_RUST_TAPE, BF_RUST_MAX_STEPS,
+BF_RUST_OPT_LEVEL, BF_RUST_FLUSH, BF_RUST_EXTENSIONS and BF_RUST_SEED.
+
+`run` keeps programs of 4 KiB or more compiled in ~/.cache/bf-rust (or
+$XDG_CACHE_HOME/bf-rust, or $BF_RUST_CACHE_DIR) as `.bfc` files named after a hash
+of the source and the settings compiling depends on, and loads them from there when run
+again.";
+
+// A mistake in the command line itself, reported along with the usage text
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{USAGE}", self.0)
+    }
+}
+
+impl Error for UsageError {}
+
+// An error already rendered with its source snippet, printed as-is
+#[derive(Debug)]
+pub struct Reported(pub String);
+
+impl fmt::Display for Reported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for Reported {}
+
+// A program that finished fine but sets the exit code, see `run --exit-cell`
+#[derive(Debug)]
+pub struct Exit(pub i32);
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit code {}", self.0)
+    }
+}
+
+impl Error for Exit {}
+
+pub fn color() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+// Compiles a program's source as `run` does, `@use` lines expanded first. Spans index the
+// expanded source, which takes the place of `code` so errors can be shown against it.
+pub fn compile(code: &mut String, settings: &Settings) -> Result<Program, BfError> {
+    expand(code)?;
+    Program::with_settings(code, settings)
+}
+
+// Replaces the `@use` lines of `code` with their snippets, for commands that read source
+// without compiling it through `compile`
+pub fn expand(code: &mut String) -> Result<(), BfError> {
+    if let Cow::Owned(expanded) = bf::stdlib::expand(code)? {
+        *code = expanded;
+    }
+    Ok(())
+}
+
+// Renders `err` against the source of the program `name` it came from
+pub fn report(err: &BfError, name: &str, source: &str, at: Option<Span>) -> Box<dyn Error> {
+    let diagnostic = Diagnostic::from_error(err, source, at);
+    Box::new(Reported(diagnostic.render(name, source, color())))
+}
+
+// Walks the command line, splitting `--flag=value` into a flag and its value.
+pub struct Args {
+    args: std::vec::IntoIter<String>,
+    pending: Option<String>,
+}
+
+impl Args {
+    pub fn new(args: Vec<String>) -> Self {
+        Self {
+            args: args.into_iter(),
+            pending: None,
+        }
+    }
+
+    // The value following `flag`, either `--flag value` or `--flag=value`
+    pub fn value(&mut self, flag: &str) -> Result<String, UsageError> {
+        self.pending
+            .take()
+            .or_else(|| self.args.next())
+            .ok_or_else(|| UsageError(format!("Missing value for {flag}")))
+    }
+
+    pub fn parsed<T: std::str::FromStr>(&mut self, flag: &str) -> Result<T, UsageError> {
+        let value = self.value(flag)?;
+        value
+            .parse()
+            .map_err(|_| UsageError(format!("Invalid value for {flag}: {value}")))
+    }
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if let Some(value) = self.pending.take() {
+            return Some(value);
+        }
+        let arg = self.args.next()?;
+        match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                self.pending = Some(value.to_string());
+                Some(flag.to_string())
+            }
+            _ => Some(arg),
+        }
+    }
+}
+
+pub fn unknown(arg: &str) -> Box<dyn Error> {
+    Box::new(UsageError(format!("Unknown argument: {arg}")))
+}
+
+pub fn main() -> i32 {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return 0;
+    }
+
+    let command = match args.first().map(String::as_str) {
+        Some(command) if COMMANDS.contains(&command) => args.remove(0),
+        _ => "run".to_string(),
+    };
+    let args = Args::new(args);
+    let result = match command.as_str() {
+        "pipe" => pipe::main(args),
+        "inspect" => inspect::main(args),
+        "check" => check::main(args),
+        "obfuscate" => obfuscate::main(args),
+        "golf" => golf::main(args),
+        "dsl" => dsl::main(args),
+        "bench" => bench::main(args),
+        "watch" => watch::main(args),
+        "corpus" => corpus::main(args),
+        "conformance" => conformance::main(args),
+        "serve" => serve::main(args),
+        "debug" => debug::main(args),
+        "expect" => expect::main(args),
+        "diff" => diff::main(args),
+        "expand" => expand::main(args),
+        "quine-check" => quine_check::main(args),
+        "tui" => tui::main(args),
+        "judge" => judge::main(args),
+        "convert" => convert::main(args),
+        "fuzz" => fuzz::main(args),
+        "link" => link::main(args),
+        _ => run::main(args),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(err) if err.is::<Exit>() => err.downcast_ref::<Exit>().unwrap().0,
+        Err(err) if err.is::<Reported>() => {
+            eprint!("{err}");
+            1
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            if err.is::<UsageError>() {
+                2
+            } else {
+                1
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use bf::rng::Rng;
+use bf::Obfuscator;
+
+use super::{expand, report, unknown, Args, CliResult, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut rng = None;
+    let mut density = 30;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => rng = Some(Rng::new(args.parsed(&arg)?)),
+            "--density" => density = args.parsed(&arg)?,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("obfuscate needs a program".to_string()))?;
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    expand(&mut code).map_err(|err| report(&err, &file, &code, None))?;
+
+    let rng = rng.unwrap_or_else(Rng::from_time);
+    let out = Obfuscator::new(rng, density)
+        .obfuscate(&code)
+        .map_err(|err| report(&err, &file, &code, None))?;
+    println!("{out}");
+    Ok(())
+}
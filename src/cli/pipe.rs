@@ -0,0 +1,38 @@
+use std::io::{stdin, stdout};
+
+use bf::Pipeline;
+
+use super::{compile, config, report, unknown, Args, CliResult, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut files = vec![];
+    let mut parallel = false;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--parallel" => parallel = true,
+            _ if arg.starts_with('-') => return Err(unknown(&arg)),
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        return Err(UsageError("pipe needs at least one program".to_string()).into());
+    }
+
+    let mut stages = vec![];
+    for file in &files {
+        let mut code = std::fs::read_to_string(file).map_err(|err| format!("{file}: {err}"))?;
+        let program =
+            compile(&mut code, &settings).map_err(|err| report(&err, file, &code, None))?;
+        stages.push(program);
+    }
+
+    let pipeline = Pipeline::with_settings(stages, settings);
+    if parallel {
+        pipeline.run_concurrent(stdin(), stdout())?;
+    } else {
+        pipeline.run(stdin().lock(), stdout().lock())?;
+    }
+    Ok(())
+}
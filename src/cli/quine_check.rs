@@ -0,0 +1,68 @@
+use bf::quine::{compare, Normalize};
+use bf::{Machine, RunState};
+
+use super::{compile, config, report, unknown, Args, CliResult, Reported, UsageError};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut normalize = Normalize::Exact;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--normalize" => normalize = args.value(&arg)?.parse().map_err(UsageError)?,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let Some(file) = file else {
+        return Err(UsageError("quine-check needs a program".to_string()).into());
+    };
+    // Most attempts at a quine that go wrong print forever
+    if settings.max_steps.is_none() {
+        settings.max_steps = Some(10_000_000);
+    }
+
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    let program = compile(&mut code, &settings).map_err(|err| report(&err, &file, &code, None))?;
+    let mut machine = Machine::with_settings(program, &settings);
+    machine.close_input();
+    let mut output = vec![];
+    loop {
+        let state = machine.run_for(1 << 20);
+        output.extend(machine.take_output());
+        match state {
+            RunState::Paused => (),
+            RunState::Finished | RunState::NeedsInput => break,
+            RunState::Error(err) => {
+                let at = machine.program().spans.get(machine.ip()).copied();
+                return Err(report(&err, &file, &code, at));
+            }
+        }
+    }
+
+    let Some(mismatch) = compare(code.as_bytes(), &output, normalize) else {
+        println!(
+            "ok    {file} prints its own source ({} bytes)",
+            output.len()
+        );
+        return Ok(());
+    };
+    let near = if mismatch.is_near() { "nearly " } else { "" };
+    let mut message = format!(
+        "FAIL  {file} {near}prints its own source: {} bytes of source, {} of output, \
+         differing from byte {}\n",
+        code.len(),
+        output.len(),
+        mismatch.at
+    );
+    // The stretch itself is only readable when it's short
+    if mismatch.is_near() {
+        message += &format!(
+            "  source has \"{}\"\n  output has \"{}\"\n",
+            mismatch.source.escape_ascii(),
+            mismatch.output.escape_ascii()
+        );
+    }
+    Err(Box::new(Reported(message)))
+}
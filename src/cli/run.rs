@@ -0,0 +1,803 @@
+use std::io::{stdin, stdout, BufReader, BufWriter, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use bf::backend::Io;
+use bf::coverage::Coverage;
+use bf::golden::{Divergence, Golden};
+use bf::input::{Repeat, Word};
+use bf::link::Unit;
+use bf::newline::{InputNewlines, Newline, OutputNewlines};
+use bf::passes::PassStats;
+use bf::trace::{TraceFormat, Tracer};
+use bf::wasm::Target;
+use bf::{BfError, Checkpoint, Diagnostic, Flush, InterpreterBuilder, Machine, Program, Settings};
+
+use super::alloc;
+use super::encoding::{Encoder, Encoding, Invalid};
+use super::terminal::RawMode;
+use super::{
+    color, config, listen, report, signal, unknown, Args, CliResult, Exit, Reported, UsageError,
+};
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut inline = None;
+    let mut input_string = None;
+    let mut bang_input = false;
+    let mut input_binary = None;
+    let mut input_width = None;
+    let mut input_endian = None;
+    let mut input_repeat = false;
+    let mut verbose = false;
+    let mut stats_json = None;
+    let mut raw = false;
+    let mut emit_ir = false;
+    let mut emit_bfc = None;
+    let mut ir = false;
+    let mut emit_wat = false;
+    let mut emit_wasm = None;
+    let mut target = Target::Env;
+    let mut emit_rust = false;
+    let mut emit_dot = false;
+    let mut dot_profile = None;
+    let mut trace = None;
+    let mut report_html = None;
+    let mut output_file = None;
+    let mut mmap = false;
+    let mut trace_format = TraceFormat::Json;
+    let mut backend = None;
+    let mut checkpoint = None;
+    let mut coverage = None;
+    let mut detect_hangs = false;
+    let mut profile = false;
+    // Shown when the run fails
+    let mut history = 16;
+    let mut dump_tape = None;
+    let mut exit = None;
+    let mut record_golden = None;
+    let mut check_golden = None;
+    let mut golden_every = 1;
+    let mut tape_init = None;
+    let mut listen = None;
+    let mut resume = None;
+    let mut passes = None;
+    let mut no_cache = false;
+    let mut speed = 0;
+    let mut encoding = Encoding::Raw;
+    let mut invalid = None;
+    let mut input_newline = Newline::Lf;
+    let mut output_newline = Newline::Lf;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "-e" => inline = Some(args.value(&arg)?),
+            "--input-string" => input_string = Some(args.value(&arg)?),
+            "--bang-input" => bang_input = true,
+            "--input-binary" => input_binary = Some(args.value(&arg)?),
+            "--input-width" => input_width = Some(args.value(&arg)?),
+            "--input-endian" => input_endian = Some(args.value(&arg)?.parse().map_err(UsageError)?),
+            "--input-repeat" => input_repeat = true,
+            "-o" | "--output" => output_file = Some(args.value(&arg)?),
+            "--mmap" => mmap = true,
+            "-v" | "--verbose" => verbose = true,
+            "--stats-json" => stats_json = Some(args.value(&arg)?),
+            "--raw" => raw = true,
+            "--interactive" => settings.flush = Flush::Always,
+            "--emit-ir" => emit_ir = true,
+            "--emit-bfc" => emit_bfc = Some(args.value(&arg)?),
+            "--ir" => ir = true,
+            "--emit-wat" => emit_wat = true,
+            "--emit-wasm" => emit_wasm = Some(args.value(&arg)?),
+            "--wasi" => target = Target::Wasi,
+            "--emit-rust" => emit_rust = true,
+            "--emit-dot" => emit_dot = true,
+            "--dot-profile" => dot_profile = Some(args.value(&arg)?),
+            "--trace" => trace = Some(args.value(&arg)?),
+            "--report-html" => report_html = Some(args.value(&arg)?),
+            "--trace-format" => {
+                trace_format = args.value(&arg)?.parse().map_err(UsageError)?;
+            }
+            "--backend" => backend = Some(args.value(&arg)?),
+            "--checkpoint" => checkpoint = Some(args.value(&arg)?),
+            "--coverage" => coverage = Some(args.value(&arg)?),
+            "--detect-hangs" => detect_hangs = true,
+            "--profile" => profile = true,
+            "--history" => history = args.parsed(&arg)?,
+            "--dump-tape" => dump_tape = Some(args.value(&arg)?),
+            "--exit-cell" | "--exit-last-output" if exit.is_some() => {
+                return Err(UsageError(
+                    "--exit-cell and --exit-last-output can't be used together".into(),
+                )
+                .into());
+            }
+            "--exit-cell" => {
+                exit =
+                    Some(match args.value(&arg)?.as_str() {
+                        "current" => ExitCode::Current,
+                        value => ExitCode::Cell(value.parse().map_err(|_| {
+                            UsageError(format!("Invalid value for {arg}: {value}"))
+                        })?),
+                    })
+            }
+            "--exit-last-output" => exit = Some(ExitCode::LastOutput),
+            "--record-golden" => record_golden = Some(args.value(&arg)?),
+            "--check-golden" => check_golden = Some(args.value(&arg)?),
+            "--golden-every" => golden_every = args.parsed(&arg)?,
+            "--listen" => listen = Some(args.value(&arg)?),
+            "--tape-init" => {
+                let path = args.value(&arg)?;
+                tape_init = Some(std::fs::read(&path).map_err(|err| format!("{path}: {err}"))?);
+            }
+            "--tape-init-hex" => tape_init = Some(parse_hex(&args.value(&arg)?)?),
+            "--resume" => resume = Some(args.value(&arg)?),
+            "--no-cache" => no_cache = true,
+            "--speed" => speed = args.parsed(&arg)?,
+            "--passes" => passes = Some(bf::passes::parse(&args.value(&arg)?).map_err(UsageError)?),
+            "--output-encoding" => encoding = args.value(&arg)?.parse().map_err(UsageError)?,
+            "--input-newline" => input_newline = args.value(&arg)?.parse().map_err(UsageError)?,
+            "--output-newline" => output_newline = args.value(&arg)?.parse().map_err(UsageError)?,
+            "--invalid-utf8" => invalid = Some(args.value(&arg)?.parse().map_err(UsageError)?),
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let resumed = match resume {
+        Some(path) => {
+            let bytes = std::fs::read(&path).map_err(|err| format!("{path}: {err}"))?;
+            Some(Checkpoint::from_bytes(&bytes).map_err(|err| format!("{path}: {err}"))?)
+        }
+        None => None,
+    };
+    let (name, code) = match (inline, file) {
+        _ if resumed.is_some() => {
+            let resumed = resumed.as_ref().unwrap();
+            settings = Settings {
+                flush: settings.flush,
+                ..resumed.settings
+            };
+            ir = resumed.ir;
+            (resumed.name.clone(), resumed.source.clone())
+        }
+        (Some(_), Some(file)) => return Err(unknown(&file)),
+        (Some(code), None) => ("<inline>".to_string(), code),
+        (None, file) => {
+            let file = file.unwrap_or_else(|| "code.txt".to_string());
+            let bytes = std::fs::read(&file).map_err(|err| format!("{file}: {err}"))?;
+            // A compiled unit runs as the IR it disassembles to
+            if bf::link::is_unit(&bytes) {
+                let unit = Unit::from_bytes(&bytes).map_err(|err| format!("{file}: {err}"))?;
+                ir = true;
+                (file, bf::ir::disassemble(&unit.program))
+            } else {
+                let code = String::from_utf8(bytes).map_err(|err| format!("{file}: {err}"))?;
+                (file, code)
+            }
+        }
+    };
+    if invalid.is_some() && encoding != Encoding::Utf8 {
+        return Err(UsageError("--invalid-utf8 needs --output-encoding utf8".into()).into());
+    }
+    let invalid = invalid.unwrap_or(Invalid::Replace);
+    if mmap && output_file.is_none() {
+        return Err(UsageError("--mmap needs -o FILE".into()).into());
+    }
+    if output_file.is_some() && listen.is_some() {
+        return Err(UsageError("--listen sends output to its connections, not -o".into()).into());
+    }
+    let sink = || -> Result<Box<dyn Write>, String> {
+        let sink: Box<dyn Write> = match &output_file {
+            Some(path) => {
+                super::sink::create(path, mmap).map_err(|err| format!("{path}: {err}"))?
+            }
+            None => Box::new(BufWriter::new(stdout().lock())),
+        };
+        Ok(Box::new(OutputNewlines::new(sink, output_newline)))
+    };
+    let backend = match backend.as_deref() {
+        None | Some("interp") => None,
+        Some(name) => Some(
+            bf::backend::backend(name)
+                .ok_or_else(|| UsageError(format!("Unknown backend `{name}`")))?,
+        ),
+    };
+    let mut code = match ir {
+        true => code,
+        false => bf::stdlib::expand(&code)
+            .map_err(|err| report(&err, &name, &code, None))?
+            .into_owned(),
+    };
+    // A file without a `!` still reads its input from stdin
+    if bang_input {
+        if resumed.is_some() || ir {
+            return Err(UsageError("--bang-input needs Brainfuck source".into()).into());
+        }
+        if let (program, Some(rest)) = bf::program::split_bang(&code) {
+            if input_string.is_some() {
+                return Err(UsageError(
+                    "--input-string can't be used with a file that has its input after `!`".into(),
+                )
+                .into());
+            }
+            input_string = Some(rest.to_string());
+            code = program.to_string();
+        }
+    }
+    if input_binary.is_some() && (input_string.is_some() || input_newline != Newline::Lf) {
+        return Err(UsageError(
+            "--input-binary can't be used with --input-string, --bang-input or --input-newline"
+                .into(),
+        )
+        .into());
+    }
+    if (input_width.is_some() || input_endian.is_some()) && input_binary.is_none() {
+        return Err(
+            UsageError("--input-width and --input-endian need --input-binary".into()).into(),
+        );
+    }
+    let word = Word::with_bits(
+        input_width.as_deref().unwrap_or("8"),
+        input_endian.unwrap_or_default(),
+    )
+    .map_err(UsageError)?;
+    if word.bits() > settings.cell_size.mask().count_ones() {
+        return Err(UsageError("--input-width can't be wider than the cells".into()).into());
+    }
+    let from_stdin = input_string.is_none() && input_binary.is_none();
+    let input: Box<dyn Read> = match (input_string, &input_binary) {
+        (Some(string), _) => Box::new(std::io::Cursor::new(string.into_bytes())),
+        // Read as it is, with nothing done to line endings
+        (None, Some(path)) => Box::new(BufReader::new(
+            std::fs::File::open(path).map_err(|err| format!("{path}: {err}"))?,
+        )),
+        (None, None) => Box::new(stdin().lock()),
+    };
+    let input: Box<dyn Read> = match input_repeat {
+        true => Box::new(Repeat::new(input)),
+        false => input,
+    };
+    let mut input = InputNewlines::new(input, input_newline);
+
+    // A checkpoint only records the optimization level
+    if passes.is_some() && (ir || checkpoint.is_some() || resumed.is_some()) {
+        return Err(UsageError("--passes can't be used with --ir or checkpoints".into()).into());
+    }
+    if tape_init.is_some()
+        && passes
+            .iter()
+            .flatten()
+            .any(|pass| bf::passes::BLANK.contains(&pass.name()))
+    {
+        return Err(UsageError(
+            "--passes unroll and propagate assume a blank tape, which --tape-init fills".into(),
+        )
+        .into());
+    }
+    let mut passes = passes.unwrap_or_else(|| bf::passes::for_level(settings.opt_level));
+    // Values worked out from a blank tape would be wrong on the preloaded one
+    if tape_init.is_some() {
+        passes = bf::passes::without_blank(passes);
+    }
+    // Compiled afresh, the cache's artifacts don't say what a unit needs to be linked
+    if let Some(out) = emit_bfc {
+        if ir || resumed.is_some() {
+            return Err(UsageError("--emit-bfc needs Brainfuck source".into()).into());
+        }
+        let unit = Unit::compile(&code, settings.extensions, &passes, settings.max_depth)
+            .map_err(|err| report(&err, &name, &code, None))?;
+        std::fs::write(&out, unit.to_bytes()).map_err(|err| format!("{out}: {err}"))?;
+        return Ok(());
+    }
+    let start = SystemTime::now();
+    let (program, pass_stats) = match ir {
+        true => bf::ir::assemble(&code).map(|program| (program, Some(vec![]))),
+        // The cache doesn't tell programs for a preloaded tape from the others
+        false if no_cache || tape_init.is_some() => Program::with_tape_start(
+            &code,
+            settings.extensions,
+            &passes,
+            settings.max_depth,
+            tape_init.is_none(),
+        )
+        .map(|(program, stats)| (program, Some(stats))),
+        false => super::cache::compile(&code, &settings, &passes),
+    }
+    .map_err(|err| report(&err, &name, &code, None))?;
+    let compile_time = SystemTime::now().duration_since(start)?;
+    if emit_ir {
+        print!("{}", bf::ir::disassemble(&program));
+        return Ok(());
+    }
+    if target == Target::Wasi && !emit_wat && emit_wasm.is_none() {
+        return Err(UsageError("--wasi needs --emit-wat or --emit-wasm".into()).into());
+    }
+    if emit_wat {
+        print!("{}", bf::wasm::emit_wat_for(&program, &settings, target));
+        return Ok(());
+    }
+    if let Some(out) = emit_wasm {
+        let module = bf::wasm::emit_wasm_for(&program, &settings, target);
+        std::fs::write(&out, module).map_err(|err| format!("{out}: {err}"))?;
+        return Ok(());
+    }
+    if emit_rust {
+        print!("{}", bf::transpile::to_rust(&program, &settings));
+        return Ok(());
+    }
+    if emit_dot {
+        print!("{}", bf::dot::to_dot(&program, None));
+        return Ok(());
+    }
+
+    if profile && (backend.is_some() || listen.is_some()) {
+        return Err(UsageError("--profile needs the interpreter".into()).into());
+    }
+    if speed > 0 && (backend.is_some() || listen.is_some()) {
+        return Err(UsageError("--speed needs the interpreter".into()).into());
+    }
+    if exit.is_some() && listen.is_some() {
+        return Err(UsageError("--listen runs many programs, with no one exit code".into()).into());
+    }
+    if matches!(exit, Some(ExitCode::Cell(_) | ExitCode::Current)) && backend.is_some() {
+        return Err(UsageError("--exit-cell needs the interpreter".into()).into());
+    }
+    if record_golden.is_some() && check_golden.is_some() {
+        return Err(
+            UsageError("--record-golden and --check-golden can't be used together".into()).into(),
+        );
+    }
+    if (record_golden.is_some() || check_golden.is_some())
+        && (backend.is_some() || listen.is_some() || resumed.is_some())
+    {
+        return Err(
+            UsageError("golden runs start from the beginning with the interpreter".into()).into(),
+        );
+    }
+    let expected = match &check_golden {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+            Some(Golden::from_text(&text).map_err(|err| format!("{path}: {err}"))?)
+        }
+        None => None,
+    };
+    if word.bytes > 1 && (backend.is_some() || listen.is_some()) {
+        return Err(UsageError("--input-width needs the interpreter".into()).into());
+    }
+
+    if let Some(addr) = listen {
+        if backend.is_some() || resumed.is_some() || tape_init.is_some() {
+            return Err(UsageError(
+                "--listen runs the program from the start with the interpreter".into(),
+            )
+            .into());
+        }
+        return listen::serve(&addr, &program, &settings, &name);
+    }
+
+    // Held until the run is over, dropping it puts the terminal back
+    let _raw = match raw && from_stdin {
+        true => RawMode::enable()?,
+        false => None,
+    };
+
+    if dump_tape.is_some() && backend.is_some() {
+        return Err(UsageError("--dump-tape needs the interpreter".into()).into());
+    }
+    // A checkpoint recompiles for a blank tape, which the preloaded one isn't
+    if tape_init.is_some() && (backend.is_some() || checkpoint.is_some() || resumed.is_some()) {
+        return Err(
+            UsageError("--tape-init can't be used with a backend or a checkpoint".into()).into(),
+        );
+    }
+    if stats_json.is_some() && backend.is_some() {
+        return Err(UsageError("--stats-json needs the interpreter".into()).into());
+    }
+    if coverage.is_some() && (ir || backend.is_some()) {
+        return Err(
+            UsageError("--coverage needs Brainfuck source and the interpreter".into()).into(),
+        );
+    }
+    if detect_hangs && backend.is_some() {
+        return Err(UsageError("--detect-hangs needs the interpreter".into()).into());
+    }
+    if checkpoint.is_some() && backend.is_some() {
+        return Err(UsageError("--checkpoint needs the interpreter".into()).into());
+    }
+    if trace.is_some() && backend.is_some() {
+        return Err(UsageError("--trace needs the interpreter".into()).into());
+    }
+    if dot_profile.is_some() && backend.is_some() {
+        return Err(UsageError("--dot-profile needs the interpreter".into()).into());
+    }
+    // The report replays the trace from a blank tape
+    if report_html.is_some()
+        && (trace.is_some() || resumed.is_some() || tape_init.is_some() || backend.is_some())
+    {
+        return Err(UsageError(
+            "--report-html runs from the start with the interpreter and without --trace".into(),
+        )
+        .into());
+    }
+    if let Some(backend) = backend {
+        let mut output = LastByte::new(Encoder::new(sink()?, encoding, invalid));
+        let io = Io {
+            input: &mut input,
+            output: &mut output,
+        };
+        let run = backend
+            .execute(&program, io, &settings)
+            .map_err(|err| report(&err, &name, &code, None))?;
+        output.inner.finish()?;
+        if verbose {
+            eprintln!("Compilation time: {compile_time:?}");
+            print_passes(pass_stats.as_deref());
+            eprintln!("Backend preparation: {:?}", run.prepare);
+            eprintln!("Time taken: {:?}", run.elapsed);
+            print_allocator();
+        }
+        return exit_with(exit.map(|_| output.last.unwrap_or(0)));
+    }
+
+    let start = SystemTime::now();
+    // Buffered here so the flush policy alone decides when output appears
+    let mut builder = InterpreterBuilder::new()
+        .settings(settings)
+        .input(input)
+        .output(LastByte::new(Encoder::new(sink()?, encoding, invalid)))
+        .interrupt(signal::interrupt_flag());
+    builder = match &resumed {
+        Some(resumed) => builder.machine(
+            resumed
+                .restore(program)
+                .map_err(|err| report(&err, &name, &code, None))?,
+        ),
+        None => builder.program(program),
+    };
+    if let Some(data) = tape_init {
+        builder = builder.preload(data);
+    }
+    if coverage.is_some() || dot_profile.is_some() {
+        builder = builder.coverage();
+    }
+    if detect_hangs {
+        builder = builder.detect_hangs();
+    }
+    if profile {
+        builder = builder.profile();
+    }
+    builder = builder.history(history).input_word(word);
+    // A check takes its states as often as the recording did
+    match &expected {
+        Some(expected) => builder = builder.golden(Golden::new(expected.every())),
+        None if record_golden.is_some() => builder = builder.golden(Golden::new(golden_every)),
+        None => (),
+    }
+    // 0 for full speed, like the other limits
+    if speed > 0 {
+        builder = builder.speed(speed);
+    }
+    if let Some(path) = &trace {
+        let file = std::fs::File::create(path).map_err(|err| format!("{path}: {err}"))?;
+        builder = builder.trace(Tracer::new(Box::new(BufWriter::new(file)), trace_format)?);
+    }
+    let recorded = SharedBuffer::default();
+    if report_html.is_some() {
+        let out = Box::new(recorded.clone());
+        builder = builder.trace(Tracer::new(out, TraceFormat::Binary)?);
+    }
+    let mut interpreter = builder.build()?;
+    let result = interpreter.run();
+    // Also written when the run fails, a step limit is a good way to cover a program that hangs
+    if let Some(path) = &coverage {
+        write_coverage(interpreter.machine(), &name, &code, path)?;
+    }
+    if let (Some(tracer), Some(path)) = (interpreter.take_trace(), &trace) {
+        let events = tracer.events();
+        tracer.finish().map_err(|err| format!("{path}: {err}"))?;
+        eprintln!("Trace: {events} events written to {path}");
+    }
+    if let Some(path) = &report_html {
+        let bytes = recorded.0.lock().unwrap();
+        let events = bf::trace::read_binary(&bytes)?;
+        let page = bf::html::run_report(&name, &code, interpreter.machine().program(), &events);
+        std::fs::write(path, page).map_err(|err| format!("{path}: {err}"))?;
+        eprintln!("Report: {} steps written to {path}", events.len());
+    }
+    if let Some(profiler) = interpreter.take_profiler() {
+        let program = interpreter.machine().program();
+        eprint!("{}", profiler.report(program).render(program, &code, 15));
+    }
+    if let Some(path) = &dot_profile {
+        let machine = interpreter.machine();
+        let dot = bf::dot::to_dot(machine.program(), machine.hits());
+        std::fs::write(path, dot).map_err(|err| format!("{path}: {err}"))?;
+    }
+    if let Some(path) = &dump_tape {
+        let machine = interpreter.machine();
+        write_tape(machine, &settings, path)?;
+    }
+    if let Err(err) = result {
+        let machine = interpreter.machine();
+        if let (BfError::Interrupted, Some(path)) = (&err, &checkpoint) {
+            let saved = Checkpoint::capture(machine, &name, &code, ir, &settings)
+                .map_err(|err| format!("{path}: {err}"))?;
+            std::fs::write(path, saved.to_bytes()).map_err(|err| format!("{path}: {err}"))?;
+            eprintln!(
+                "\nInterrupted after {} steps, resume with --resume {path}",
+                machine.steps()
+            );
+            return Ok(());
+        }
+        let at = machine.program().spans.get(machine.ip()).copied();
+        if let BfError::Interrupted = err {
+            // Say where it was stuck, for telling an infinite loop from a slow one
+            let diagnostic = Diagnostic::from_error(&err, &code, at).with_help(format!(
+                "instruction {} of {}, after {} steps",
+                machine.ip(),
+                machine.program().len(),
+                machine.steps()
+            ));
+            let rendered = diagnostic.render(&name, &code, color());
+            let history = history_report(machine, &code);
+            return Err(Reported(format!("{rendered}{}{history}", tape_window(machine, 8))).into());
+        }
+        let rendered = Diagnostic::from_error(&err, &code, at).render(&name, &code, color());
+        // Failing to write output has nothing to do with what ran before
+        let history = match err {
+            BfError::Io(_) => String::new(),
+            _ => history_report(machine, &code),
+        };
+        return Err(Reported(format!("{rendered}{history}")).into());
+    }
+    let golden = interpreter.take_golden();
+    let (machine, _, mut output) = interpreter.into_parts();
+    output
+        .inner
+        .finish()
+        .map_err(|err| report(&err.into(), &name, &code, None))?;
+    if let (Some(path), Some(golden)) = (&record_golden, &golden) {
+        std::fs::write(path, golden.to_text()).map_err(|err| format!("{path}: {err}"))?;
+    }
+    if let (Some(path), Some(expected), Some(golden)) = (&check_golden, &expected, &golden) {
+        if let Some(divergence) = golden.compare(expected) {
+            return Err(format!("{path}: {}", describe(divergence)).into());
+        }
+    }
+    let time = SystemTime::now().duration_since(start)?;
+
+    if verbose {
+        eprintln!("Compilation time: {compile_time:?}");
+        print_passes(pass_stats.as_deref());
+        eprintln!(
+            "Tape: {} cells, pointer at {}",
+            machine.usage().cells,
+            machine.pointer()
+        );
+        eprintln!(
+            "Time taken: {time:?}\nCommands Processed: {}",
+            machine.steps()
+        );
+        let usage = machine.usage();
+        eprintln!(
+            "Tape memory: {} bytes, {} reallocations",
+            usage.allocated, usage.reallocations
+        );
+        eprintln!("I/O: {} bytes read, {} written", usage.read, usage.written);
+        print_allocator();
+    }
+    if let Some(path) = stats_json {
+        let stats = stats_to_json(&machine, compile_time, time);
+        std::fs::write(&path, stats + "\n").map_err(|err| format!("{path}: {err}"))?;
+    }
+    exit_with(exit.map(|exit| match exit {
+        ExitCode::Cell(at) => machine.memory().cell(at) as u8,
+        ExitCode::Current => machine.cell() as u8,
+        ExitCode::LastOutput => output.last.unwrap_or(0),
+    }))
+}
+
+fn describe(divergence: Divergence) -> String {
+    let state = divergence.state + 1;
+    match (divergence.found, divergence.expected) {
+        (Some(found), Some(expected)) => format!(
+            "state {state} differs from the golden run, after {} reads and writes \
+             (step {} now, {} when recorded)",
+            found.io, found.steps, expected.steps
+        ),
+        (None, Some(expected)) => format!(
+            "the run ended before golden state {state}, after {} reads and writes",
+            expected.io
+        ),
+        (found, _) => format!(
+            "the run went on past the golden run's last state, to state {state} after {} \
+             reads and writes",
+            found.map_or(0, |found| found.io)
+        ),
+    }
+}
+
+// Where `--exit-cell` and `--exit-last-output` take the exit code from
+#[derive(Copy, Clone)]
+enum ExitCode {
+    Cell(usize), // From the leftmost cell visited
+    Current,
+    LastOutput,
+}
+
+fn exit_with(code: Option<u8>) -> CliResult {
+    match code {
+        Some(code @ 1..) => Err(Exit(code as i32).into()),
+        _ => Ok(()),
+    }
+}
+
+// Passes output on, remembering the last byte for `--exit-last-output`
+struct LastByte<W> {
+    inner: W,
+    last: Option<u8>,
+}
+
+impl<W> LastByte<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, last: None }
+    }
+}
+
+impl<W: Write> Write for LastByte<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if written > 0 {
+            self.last = Some(buf[written - 1]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn print_allocator() {
+    let stats = alloc::stats();
+    eprintln!(
+        "Allocator: {} allocations of {} bytes, at most {} bytes at once",
+        stats.allocations, stats.allocated, stats.peak
+    );
+}
+
+fn stats_to_json(machine: &Machine, compile_time: Duration, time: Duration) -> String {
+    let usage = machine.usage();
+    let heap = alloc::stats();
+    format!(
+        "{{\"compile_ms\":{:.3},\"time_ms\":{:.3},\"steps\":{},\"tape_cells\":{},\
+         \"pointer\":{},\"tape_bytes\":{},\"tape_reallocations\":{},\"bytes_read\":{},\
+         \"bytes_written\":{},\"allocations\":{},\"allocated_bytes\":{},\"peak_heap_bytes\":{}}}",
+        compile_time.as_secs_f64() * 1000.0,
+        time.as_secs_f64() * 1000.0,
+        machine.steps(),
+        usage.cells,
+        machine.pointer(),
+        usage.allocated,
+        usage.reallocations,
+        usage.read,
+        usage.written,
+        heap.allocations,
+        heap.allocated,
+        heap.peak
+    )
+}
+
+// Trace output kept in memory, shared with the tracer that writes it
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn print_passes(stats: Option<&[PassStats]>) {
+    let Some(stats) = stats else {
+        eprintln!("  loaded from the cache");
+        return;
+    };
+    for pass in stats {
+        eprintln!(
+            "  {}: {} -> {} instructions in {:?}",
+            pass.name, pass.before, pass.after, pass.elapsed
+        );
+    }
+}
+
+// lcov for `.info` and `.lcov` files, annotated source for anything else
+fn write_coverage(machine: &Machine, name: &str, code: &str, path: &str) -> CliResult {
+    let coverage = Coverage::new(machine.program(), machine.hits().unwrap(), code);
+    let report = match path.ends_with(".info") || path.ends_with(".lcov") {
+        true => coverage.lcov(name, code),
+        false => coverage.annotate(code),
+    };
+    std::fs::write(path, report).map_err(|err| format!("{path}: {err}"))?;
+    eprintln!(
+        "Coverage: {:.1}% ({} of {} commands) written to {path}",
+        coverage.percent(),
+        coverage.covered(),
+        coverage.commands()
+    );
+    Ok(())
+}
+
+// Pairs of hex digits, whitespace between them is ignored
+fn parse_hex(text: &str) -> Result<Vec<u8>, UsageError> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let invalid = || {
+        UsageError(format!(
+            "Invalid hex {text:?}, expected pairs of hex digits"
+        ))
+    };
+    if !digits.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).map_err(|_| invalid())
+        })
+        .collect()
+}
+
+fn write_tape(machine: &Machine, settings: &Settings, path: &str) -> CliResult {
+    let cells = machine.usage().cells;
+    if cells > bf::tape::MAX_GATHERED {
+        return Err(format!("{path}: the tape spans {cells} cells, too many to dump").into());
+    }
+    let dump = bf::tape::dump(&machine.tape(), machine.pointer(), settings.cell_size);
+    std::fs::write(path, dump).map_err(|err| format!("{path}: {err}"))?;
+    Ok(())
+}
+
+// The cells within `radius` of the pointer, with the current one in brackets
+pub fn tape_window(machine: &Machine, radius: usize) -> String {
+    let tape = machine.memory();
+    let pointer = machine.pointer();
+    let start = pointer.saturating_sub(radius);
+    let end = (pointer + radius + 1).min(tape.cell_count());
+    let cells: Vec<String> = (start..end)
+        .map(|idx| match idx == pointer {
+            true => format!("[{}]", tape.cell(idx)),
+            false => tape.cell(idx).to_string(),
+        })
+        .collect();
+    format!(
+        "tape from cell {start} (pointer at {pointer}): {}\n",
+        cells.join(" ")
+    )
+}
+
+// The instructions that ran last, oldest first, with where they are in the source
+fn history_report(machine: &Machine, code: &str) -> String {
+    let Some(history) = machine.history().filter(|history| !history.is_empty()) else {
+        return String::new();
+    };
+    let program = machine.program();
+    let mut out = format!("last {} instructions, oldest first:\n", history.len());
+    for ip in history {
+        let span = program.spans[ip];
+        let (line, col) = bf::diagnostic::line_col(code, span.start);
+        let source: String = code[span.start..span.end].chars().take(16).collect();
+        out += &format!(
+            "{ip:>7}  {:<10} `{source}` at {}:{}\n",
+            bf::ir::mnemonic(program, ip),
+            line + 1,
+            col + 1
+        );
+    }
+    out
+}
@@ -0,0 +1,318 @@
+// An HTTP API for running programs, so a playground website can use this crate as its backend.
+//
+// `POST /run` takes a JSON object with the `program` source, an optional `input` string and
+// any of the config file's settings (`cell_size`, `eof`, `tape`, `opt_level`, `extensions`,
+// `seed`), plus the limits `max_steps`, `timeout_ms` and `max_output`, which can only lower the
+// server's own, and a fixed tape is refused past 4Mi cells. The time limit covers compiling
+// as well as running. The answer is a JSON object like
+//
+//     {"status":"finished","output":"Hi\n","steps":31,"elapsed_ms":0,"tape_cells":2}
+//
+// where `status` is `finished`, `step_limit`, `timeout`, `output_limit` or `error`, the last
+// with an `error` message, which is also how a program that doesn't compile is reported. The
+// output is decoded as UTF-8 with invalid bytes replaced, and stops where a limit was hit.
+// Requests that can't be understood get a 400 and `{"error":"..."}`.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bf::{BfError, CancelToken, Interpreter, Settings, Tape};
+
+use super::json::{self, Json};
+use super::{compile, config, unknown, Args, CliResult};
+
+// Requests larger than this are turned away before being read
+const MAX_BODY: usize = 1 << 20;
+const MAX_HEADERS: usize = 100;
+// Cells a fixed tape from a request may have, the others only grow as far as steps take them
+const MAX_FIXED_TAPE: usize = 1 << 22;
+
+// What a single run may use, requests can ask for less
+#[derive(Debug, Copy, Clone)]
+struct Limits {
+    max_steps: u64,
+    timeout: Duration,
+    max_output: usize,
+}
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut settings = config::load()?;
+    let mut host = "127.0.0.1".to_string();
+    let mut port: u16 = 8080;
+    let mut limits = Limits {
+        max_steps: 10_000_000,
+        timeout: Duration::from_secs(5),
+        max_output: 1 << 20,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = args.parsed(&arg)?,
+            "--host" => host = args.value(&arg)?,
+            "--timeout-ms" => limits.timeout = Duration::from_millis(args.parsed(&arg)?),
+            "--max-output" => limits.max_output = args.parsed(&arg)?,
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            _ => return Err(unknown(&arg)),
+        }
+    }
+    // An unlimited default is fine on the command line but not for strangers' programs
+    if let Some(steps) = settings.max_steps {
+        limits.max_steps = steps;
+    }
+
+    let addr = format!("{host}:{port}");
+    let listener = TcpListener::bind(&addr).map_err(|err| format!("{addr}: {err}"))?;
+    eprintln!("serving on http://{}", listener.local_addr()?);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(err) = handle(stream, settings, limits) {
+                        eprintln!("request failed: {err}");
+                    }
+                });
+            }
+            Err(err) => eprintln!("accept failed: {err}"),
+        }
+    }
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+// An HTTP status line and the message to send with it
+type Failure = (&'static str, String);
+
+fn handle(mut stream: TcpStream, settings: Settings, limits: Limits) -> io::Result<()> {
+    // A client that stops sending shouldn't hold a thread forever
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let (status, body) = match read_request(&stream) {
+        Ok(request) => respond(&request, settings, limits),
+        Err((status, message)) => (status, error(&message)),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, Failure> {
+    let bad = |message: &str| ("400 Bad Request", message.to_string());
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|_| bad("Unreadable request line"))?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(bad("Malformed request line"));
+    };
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap().to_string();
+
+    let mut length = 0;
+    for idx in 0.. {
+        if idx == MAX_HEADERS {
+            return Err(bad("Too many headers"));
+        }
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .map_err(|_| bad("Unreadable header"))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(bad("Malformed header"));
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value
+                .trim()
+                .parse()
+                .map_err(|_| bad("Malformed Content-Length"))?;
+        }
+    }
+    if length > MAX_BODY {
+        return Err((
+            "413 Payload Too Large",
+            format!("Requests are limited to {MAX_BODY} bytes"),
+        ));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad("Body shorter than its Content-Length"))?;
+    Ok(Request { method, path, body })
+}
+
+fn respond(request: &Request, settings: Settings, limits: Limits) -> (&'static str, String) {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/run") => match run(&request.body, settings, limits) {
+            Ok(body) => ("200 OK", body),
+            Err(message) => ("400 Bad Request", error(&message)),
+        },
+        // Browsers ask before sending JSON across origins
+        ("OPTIONS", _) => ("204 No Content", String::new()),
+        (_, "/run") => ("405 Method Not Allowed", error("Use POST for /run")),
+        (_, path) => ("404 Not Found", error(&format!("No such endpoint {path}"))),
+    }
+}
+
+fn error(message: &str) -> String {
+    format!("{{\"error\":{}}}", json::quote(message))
+}
+
+// Runs the program a request describes, Err only for requests that make no sense
+fn run(body: &[u8], mut settings: Settings, mut limits: Limits) -> Result<String, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "Body is not UTF-8".to_string())?;
+    let Json::Object(fields) = json::parse(text)? else {
+        return Err("Expected a JSON object".to_string());
+    };
+    let mut source = None;
+    let mut input = vec![];
+    for (key, value) in fields {
+        let whole = |value: f64| match value >= 0.0 && value.fract() == 0.0 {
+            true => Ok(value as u64),
+            false => Err(format!("{key} must be a whole number")),
+        };
+        match (key.as_str(), value) {
+            ("program", Json::String(text)) => source = Some(text),
+            ("input", Json::String(text)) => input = text.into_bytes(),
+            ("program" | "input", _) => return Err(format!("{key} must be a string")),
+            // Zero means no limit, which here is the server's
+            ("max_steps", Json::Number(n)) => match whole(n)? {
+                0 => (),
+                n => limits.max_steps = limits.max_steps.min(n),
+            },
+            ("timeout_ms", Json::Number(n)) => {
+                limits.timeout = limits.timeout.min(Duration::from_millis(whole(n)?))
+            }
+            ("max_output", Json::Number(n)) => {
+                limits.max_output = limits.max_output.min(whole(n)? as usize)
+            }
+            ("max_steps" | "timeout_ms" | "max_output", _) => {
+                return Err(format!("{key} must be a number"))
+            }
+            // How output is flushed means nothing when it's all sent at once
+            ("flush", _) => return Err("Unknown setting \"flush\"".to_string()),
+            (key, Json::String(text)) => settings.set(key, &text)?,
+            (key, Json::Number(n)) => settings.set(key, &whole(n)?.to_string())?,
+            (key, _) => return Err(format!("{key} must be a string or a number")),
+        }
+    }
+    let mut source = source.ok_or_else(|| "Missing program".to_string())?;
+    settings.max_steps = Some(limits.max_steps);
+    if let Tape::Fixed(cells) = settings.tape {
+        if cells > MAX_FIXED_TAPE {
+            return Err(format!(
+                "A fixed tape of {cells} cells is more than the {MAX_FIXED_TAPE} allowed"
+            ));
+        }
+    }
+    // Levels past 3 run the same passes
+    settings.opt_level = settings.opt_level.min(3);
+
+    // The deadline covers compiling too
+    let start = Instant::now();
+    let (compiled, program) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = compiled.send(compile(&mut source, &settings));
+    });
+    // One still compiling at the deadline is left to finish on its own
+    let program = match program.recv_timeout(limits.timeout) {
+        Ok(Ok(program)) => program,
+        Ok(Err(err)) => return Ok(outcome(Err(Some(err)), &[], 0, 0, start)),
+        Err(_) => return Ok(outcome(Err(Some(BfError::Interrupted)), &[], 0, 0, start)),
+    };
+    let mut output = Capped {
+        bytes: vec![],
+        limit: limits.max_output,
+        full: false,
+    };
+    let mut interpreter = Interpreter::with_settings(program, &settings, &input[..], &mut output);
+    let cancel = CancelToken::new();
+    interpreter.set_cancel(cancel.clone());
+    // Stopped early by dropping `done` when the run finishes in time
+    let (done, finished) = mpsc::channel::<()>();
+    let left = limits.timeout.saturating_sub(start.elapsed());
+    let timer = thread::spawn(move || {
+        if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(left) {
+            cancel.cancel();
+        }
+    });
+    let result = interpreter.run();
+    drop(done);
+    let _ = timer.join();
+    let machine = interpreter.machine();
+    let (steps, cells) = (machine.steps(), machine.usage().cells);
+    let result = match result {
+        Err(BfError::Io(_)) if output.full => Err(None),
+        result => result.map_err(Some),
+    };
+    Ok(outcome(result, &output.bytes, steps, cells, start))
+}
+
+// The response to a run, `Err(None)` when the output limit stopped it
+fn outcome(
+    result: Result<(), Option<BfError>>,
+    output: &[u8],
+    steps: u64,
+    cells: usize,
+    start: Instant,
+) -> String {
+    let (status, message) = match result {
+        Ok(()) => ("finished", None),
+        Err(None) => ("output_limit", None),
+        Err(Some(BfError::StepLimit(_))) => ("step_limit", None),
+        Err(Some(BfError::Interrupted)) => ("timeout", None),
+        Err(Some(err)) => ("error", Some(err.to_string())),
+    };
+    let mut body = format!(
+        "{{\"status\":\"{status}\",\"output\":{},\"steps\":{steps},\"elapsed_ms\":{},\"tape_cells\":{cells}",
+        json::quote(&String::from_utf8_lossy(output)),
+        start.elapsed().as_millis()
+    );
+    if let Some(message) = message {
+        body.push_str(&format!(",\"error\":{}", json::quote(&message)));
+    }
+    body.push('}');
+    body
+}
+
+// Collects output up to a limit, failing the write that goes past it
+struct Capped {
+    bytes: Vec<u8>,
+    limit: usize,
+    full: bool,
+}
+
+impl Write for Capped {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let room = self.limit - self.bytes.len();
+        if buf.len() > room {
+            self.bytes.extend(&buf[..room]);
+            self.full = true;
+            return Err(io::Error::other("output limit reached"));
+        }
+        self.bytes.extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
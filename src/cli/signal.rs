@@ -0,0 +1,34 @@
+// Turns Ctrl-C into a flag the interpreter polls, instead of killing the process mid-run.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+static INTERRUPT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+// The flag set by the next Ctrl-C, a second one kills the process as usual
+pub fn interrupt_flag() -> Arc<AtomicBool> {
+    let flag = INTERRUPT.get_or_init(Default::default).clone();
+    install();
+    flag
+}
+
+#[cfg(unix)]
+fn install() {
+    const SIGINT: i32 = 2;
+    // `None` is SIG_DFL, the default action
+    extern "C" {
+        fn signal(signum: i32, handler: Option<extern "C" fn(i32)>) -> usize;
+    }
+
+    extern "C" fn on_interrupt(_: i32) {
+        if let Some(flag) = INTERRUPT.get() {
+            flag.store(true, Ordering::Relaxed);
+        }
+        // A program stuck waiting on input never checks the flag, let the next Ctrl-C through
+        unsafe { signal(SIGINT, None) };
+    }
+
+    unsafe { signal(SIGINT, Some(on_interrupt)) };
+}
+
+#[cfg(not(unix))]
+fn install() {}
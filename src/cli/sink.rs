@@ -0,0 +1,146 @@
+// Output files for `-o`, for programs that write far more than is worth keeping in memory.
+//
+// The default is a file behind a large buffer. With `--mmap` the file is instead grown a window
+// at a time and written through a shared memory mapping of that window, so output costs no
+// system call per buffer; the file is cut back to what was written once done.
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+
+const BUFFER: usize = 1 << 20;
+
+pub fn create(path: &str, mmap: bool) -> io::Result<Box<dyn Write>> {
+    if mmap {
+        // Writable shared mappings need the file open for reading too
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        return Ok(Box::new(mapped::Mapped::new(file)?));
+    }
+    Ok(Box::new(BufWriter::with_capacity(
+        BUFFER,
+        File::create(path)?,
+    )))
+}
+
+#[cfg(unix)]
+mod mapped {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io::{self, Write};
+    use std::os::fd::AsRawFd;
+
+    // A multiple of every page size in use
+    const WINDOW: usize = 64 << 20;
+    const PROT_READ: i32 = 1;
+    const PROT_WRITE: i32 = 2;
+    const MAP_SHARED: i32 = 1;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: i32,
+            flags: i32,
+            fd: i32,
+            off: i64,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> i32;
+    }
+
+    pub struct Mapped {
+        file: File,
+        window: *mut u8, // Null until the first write
+        start: u64,      // Where the window starts in the file
+        used: usize,     // Bytes written into the window
+    }
+
+    impl Mapped {
+        pub fn new(file: File) -> io::Result<Self> {
+            Ok(Self {
+                file,
+                window: std::ptr::null_mut(),
+                start: 0,
+                used: 0,
+            })
+        }
+
+        // Unmaps the full window and maps the next one, growing the file to cover it
+        fn advance(&mut self) -> io::Result<()> {
+            if !self.window.is_null() {
+                self.unmap();
+                self.start += WINDOW as u64;
+                self.used = 0;
+            }
+            self.file.set_len(self.start + WINDOW as u64)?;
+            let window = unsafe {
+                mmap(
+                    std::ptr::null_mut(),
+                    WINDOW,
+                    PROT_READ | PROT_WRITE,
+                    MAP_SHARED,
+                    self.file.as_raw_fd(),
+                    self.start as i64,
+                )
+            };
+            // MAP_FAILED is all ones
+            if window as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            self.window = window as *mut u8;
+            Ok(())
+        }
+
+        fn unmap(&mut self) {
+            unsafe { munmap(self.window as *mut c_void, WINDOW) };
+            self.window = std::ptr::null_mut();
+        }
+    }
+
+    impl Write for Mapped {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.window.is_null() || self.used == WINDOW {
+                self.advance()?;
+            }
+            let count = buf.len().min(WINDOW - self.used);
+            // The window is WINDOW bytes of the file, mapped writable and only used from here
+            unsafe {
+                std::ptr::copy_nonoverlapping(buf.as_ptr(), self.window.add(self.used), count);
+            }
+            self.used += count;
+            Ok(count)
+        }
+
+        // Written bytes are already in the page cache, the kernel writes them back
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Drop for Mapped {
+        // Nothing can report a failure here, at worst the file keeps zeroes at the end
+        fn drop(&mut self) {
+            if !self.window.is_null() {
+                self.unmap();
+            }
+            let _ = self.file.set_len(self.start + self.used as u64);
+        }
+    }
+}
+
+// Without mmap the option just means a bigger buffer
+#[cfg(not(unix))]
+mod mapped {
+    use std::fs::File;
+    use std::io::{self, BufWriter};
+
+    pub struct Mapped;
+
+    impl Mapped {
+        pub fn new(file: File) -> io::Result<BufWriter<File>> {
+            Ok(BufWriter::with_capacity(64 << 20, file))
+        }
+    }
+}
@@ -0,0 +1,58 @@
+// Switches the terminal so `,` gets each keypress straight away, restoring it when dropped.
+use std::io::{stdin, IsTerminal};
+use std::process::{Command, Stdio};
+
+pub struct RawMode {
+    saved: String, // `stty -g` output from before the switch
+}
+
+impl RawMode {
+    // Does nothing, returning `None`, when stdin isn't a terminal
+    pub fn enable() -> Result<Option<Self>, String> {
+        if !stdin().is_terminal() {
+            return Ok(None);
+        }
+        let saved = stty(&["-g"])?;
+        // Not fully raw: Ctrl-C still interrupts and output newlines still return the cursor
+        stty(&["-icanon", "-echo", "min", "1", "time", "0"])?;
+        Ok(Some(Self {
+            saved: saved.trim().to_string(),
+        }))
+    }
+
+    // Like `enable` but Ctrl-C arrives as a key too, for full-screen views that quit on it
+    pub fn keys() -> Result<Option<Self>, String> {
+        let raw = Self::enable()?;
+        if raw.is_some() {
+            stty(&["-isig"])?;
+        }
+        Ok(raw)
+    }
+}
+
+// Rows and columns of the terminal on stdin
+pub fn size() -> Option<(usize, usize)> {
+    let size = stty(&["size"]).ok()?;
+    let (rows, cols) = size.trim().split_once(' ')?;
+    Some((rows.parse().ok()?, cols.parse().ok()?))
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+// stty works on the terminal it's given as stdin
+fn stty(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("raw mode needs stty: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("stty {} failed", args.join(" ")));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,331 @@
+// A full-screen view of a recorded run. `--replay` scrubs back and forth through a binary
+// trace, showing the source, the tape and the output at any step without running anything:
+// the tape comes from the keyframe before the step and the writes since. `--speed` plays it
+// forward on its own, at that many steps a second.
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{stdin, stdout, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use bf::diagnostic::line_col;
+use bf::pace::Pace;
+use bf::trace::{read_recording, Recording};
+use bf::{BfToken, Program};
+
+use super::terminal::{self, RawMode};
+use super::{compile, config, report, unknown, Args, CliResult, UsageError};
+
+const KEYS: &str = "←/→ step  ↑/↓ 100  PgUp/PgDn 10000  g/G start/end  p/n output  q quit";
+const PLAY: &str = "  space play/pause";
+// Playing never redraws more often than this
+const FRAME: Duration = Duration::from_millis(16);
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut replay = None;
+    let mut speed = 0;
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--replay" => replay = Some(args.value(&arg)?),
+            "--speed" => speed = args.parsed(&arg)?,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("tui needs a program".to_string()))?;
+    let replay = replay.ok_or_else(|| UsageError("tui needs --replay TRACE".to_string()))?;
+    let mut code = std::fs::read_to_string(&file).map_err(|err| format!("{file}: {err}"))?;
+    let program = compile(&mut code, &settings).map_err(|err| report(&err, &file, &code, None))?;
+    let bytes = std::fs::read(&replay).map_err(|err| format!("{replay}: {err}"))?;
+    let recording = read_recording(&bytes).map_err(|err| format!("{replay}: {err}"))?;
+    // Instruction numbers only line up with the settings the trace was recorded with
+    if recording
+        .events
+        .iter()
+        .any(|event| event.ip >= program.len())
+    {
+        return Err(format!("{replay} wasn't recorded from {file} with these settings").into());
+    }
+
+    let Some(_raw) = RawMode::keys()? else {
+        return Err("tui needs a terminal".into());
+    };
+    let output = Output::new(&program, &recording);
+    let mut view = View {
+        file,
+        code,
+        program,
+        recording,
+        output,
+        step: 0,
+        pace: (speed > 0).then(|| Pace::new(speed)),
+        playing: speed > 0,
+    };
+    let mut out = stdout().lock();
+    // The alternate screen leaves the shell's scrollback as it was
+    write!(out, "\x1b[?1049h\x1b[?25l")?;
+    let result = view.run(&mut out);
+    write!(out, "\x1b[?25h\x1b[?1049l")?;
+    out.flush()?;
+    result
+}
+
+// Every byte the run wrote, with how many events in it was written
+struct Output {
+    bytes: Vec<u8>,
+    steps: Vec<usize>,
+}
+
+impl Output {
+    fn new(program: &Program, recording: &Recording) -> Self {
+        let mut tape: HashMap<isize, u32> = HashMap::new();
+        let (mut bytes, mut steps) = (vec![], vec![]);
+        for (idx, event) in recording.events.iter().enumerate() {
+            if let Some(value) = event.write {
+                tape.insert(event.pointer, value);
+            }
+            if program.tokens[event.ip] == BfToken::OUT {
+                bytes.push(tape.get(&event.pointer).copied().unwrap_or(0) as u8);
+                steps.push(idx + 1);
+            }
+        }
+        Self { bytes, steps }
+    }
+
+    // Bytes written in the first `step` events
+    fn written(&self, step: usize) -> usize {
+        self.steps.partition_point(|&at| at <= step)
+    }
+}
+
+struct View {
+    file: String,
+    code: String,
+    program: Program,
+    recording: Recording,
+    output: Output,
+    step: usize, // Events replayed so far
+    pace: Option<Pace>,
+    playing: bool,
+}
+
+impl View {
+    fn run(&mut self, out: &mut impl Write) -> CliResult {
+        let keys = keys();
+        loop {
+            out.write_all(self.draw().as_bytes())?;
+            out.flush()?;
+            let received = match (&mut self.pace, self.playing) {
+                (Some(pace), true) => {
+                    keys.recv_timeout(pace.ahead().unwrap_or_default().max(FRAME))
+                }
+                _ => keys.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let read = match received {
+                Ok(read) => read,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.play();
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            };
+            // Keys typed or pasted quickly can arrive together
+            let mut rest = &read[..];
+            while !rest.is_empty() {
+                let len = match rest {
+                    [0x1b, b'[', tail @ ..] => tail
+                        .iter()
+                        .position(|byte| (0x40..0x7f).contains(byte))
+                        .map_or(rest.len(), |end| end + 3),
+                    _ => 1,
+                };
+                let (key, after) = rest.split_at(len);
+                rest = after;
+                if !self.press(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // Takes every step that has come due since the last frame
+    fn play(&mut self) {
+        let total = self.recording.events.len();
+        let Some(pace) = &mut self.pace else {
+            return;
+        };
+        while self.step < total && pace.ahead().is_none() {
+            pace.step();
+            self.step += 1;
+        }
+        self.playing &= self.step < total;
+    }
+
+    // Moves to where `key` says, false when it means quit
+    fn press(&mut self, key: &[u8]) -> bool {
+        let total = self.recording.events.len();
+        let step = self.step;
+        self.step = match key {
+            b"q" | b"\x03" | b"\x1b" => return false,
+            b" " if self.pace.is_some() => {
+                self.playing = !self.playing;
+                // From the end, playing starts over
+                if self.playing && step == total {
+                    self.step = 0;
+                }
+                if let Some(pace) = &mut self.pace {
+                    pace.restart();
+                }
+                return true;
+            }
+            b"\x1b[C" | b"l" => step + 1,
+            b"\x1b[D" | b"h" => step.saturating_sub(1),
+            b"\x1b[B" | b"j" => step + 100,
+            b"\x1b[A" | b"k" => step.saturating_sub(100),
+            b"\x1b[6~" => step + 10_000,
+            b"\x1b[5~" => step.saturating_sub(10_000),
+            b"g" | b"\x1b[H" => 0,
+            b"G" | b"\x1b[F" => total,
+            // To the step that writes the next byte of output, or the one before
+            b"n" => {
+                let next = self.output.written(step);
+                self.output.steps.get(next).copied().unwrap_or(total)
+            }
+            b"p" => match self.output.written(step.saturating_sub(1)) {
+                0 => 0,
+                before => self.output.steps[before - 1],
+            },
+            _ => step,
+        }
+        .min(total);
+        true
+    }
+
+    fn draw(&self) -> String {
+        let (rows, cols) = terminal::size().unwrap_or((24, 80));
+        let events = &self.recording.events;
+        let next = events.get(self.step);
+        let pointer = self.recording.pointer_at(self.step);
+        let mut lines = vec![];
+        let state = match (&self.pace, self.playing) {
+            (None, _) => "",
+            (Some(_), true) => "  playing",
+            (Some(_), false) => "  paused",
+        };
+        lines.push(format!(
+            "{}  step {} of {}{state}",
+            self.file,
+            self.step,
+            events.len()
+        ));
+        lines.push(match next {
+            Some(event) => {
+                let span = self.program.spans[event.ip];
+                let (line, col) = line_col(&self.code, span.start);
+                format!(
+                    "next {} {} at {}:{}, depth {}, pointer at cell {pointer}",
+                    event.ip,
+                    bf::ir::mnemonic(&self.program, event.ip),
+                    line + 1,
+                    col + 1,
+                    self.program.depth(event.ip)
+                )
+            }
+            None => format!("end of the trace, pointer at cell {pointer}"),
+        });
+        lines.push(String::new());
+
+        // The source around the next instruction, which is highlighted
+        let span = next.map(|event| self.program.spans[event.ip]);
+        let height = rows.saturating_sub(10).max(1);
+        let current = span.map_or(0, |span| line_col(&self.code, span.start).0);
+        let first = current.saturating_sub(height / 2);
+        let mut offset = 0;
+        for (idx, line) in self.code.split('\n').enumerate() {
+            let start = offset;
+            offset += line.len() + 1;
+            if idx < first {
+                continue;
+            }
+            if idx >= first + height {
+                break;
+            }
+            let mut text = String::new();
+            let mut lit = false;
+            for (at, c) in line.char_indices().take(cols) {
+                let inside =
+                    span.is_some_and(|span| (span.start..span.end).contains(&(start + at)));
+                if inside != lit {
+                    text.push_str(if inside { "\x1b[7m" } else { "\x1b[0m" });
+                    lit = inside;
+                }
+                text.push(if c.is_control() { ' ' } else { c });
+            }
+            if lit {
+                text.push_str("\x1b[0m");
+            }
+            lines.push(text);
+        }
+        while lines.len() < height + 3 {
+            lines.push(String::new());
+        }
+
+        // The tape around the pointer, as far as the screen is wide
+        let tape = self.recording.tape_at(self.step);
+        let radius = (cols / 6).saturating_sub(1) as isize / 2;
+        lines.push(String::new());
+        lines.push(format!("tape from cell {}", pointer - radius));
+        let mut cells = String::new();
+        for at in pointer - radius..=pointer + radius {
+            let cell = tape.get(&at).copied().unwrap_or(0);
+            match at == pointer {
+                true => write!(cells, "\x1b[7m{cell:>5}\x1b[0m ").unwrap(),
+                false => write!(cells, "{cell:>5} ").unwrap(),
+            }
+        }
+        lines.push(cells);
+
+        // The last line of output so far
+        let written = &self.output.bytes[..self.output.written(self.step)];
+        let last = written
+            .split(|&byte| byte == b'\n')
+            .next_back()
+            .unwrap_or(&[]);
+        let shown = last.escape_ascii().to_string();
+        let shown = &shown[shown.len().saturating_sub(cols)..];
+        lines.push(String::new());
+        lines.push(format!("output, {} bytes", written.len()));
+        lines.push(shown.to_string());
+
+        let mut screen = String::from("\x1b[H");
+        for line in lines.iter().take(rows.saturating_sub(1)) {
+            screen.push_str(line);
+            screen.push_str("\x1b[K\r\n");
+        }
+        screen.push_str("\x1b[J");
+        let keys = match self.pace {
+            Some(_) => format!("{KEYS}{PLAY}"),
+            None => KEYS.to_string(),
+        };
+        screen.extend(keys.chars().take(cols));
+        screen
+    }
+}
+
+// Keys as they're read, from a thread of their own so playing doesn't wait on them. The
+// channel closes at the end of input.
+fn keys() -> Receiver<Vec<u8>> {
+    let (send, keys) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buffer = [0; 64];
+        while let Ok(read @ 1..) = stdin().read(&mut buffer) {
+            if send.send(buffer[..read].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    keys
+}
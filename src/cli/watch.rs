@@ -0,0 +1,99 @@
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+use bf::{Machine, RunState, Settings};
+
+use super::{compile, config, report, unknown, Args, CliResult, UsageError};
+
+// Steps between checks for an edit, so even a program stuck in a loop restarts promptly
+const CHUNK: u64 = 1 << 20;
+const POLL: Duration = Duration::from_millis(200);
+
+pub fn main(mut args: Args) -> CliResult {
+    let mut file = None;
+    let mut input = String::new();
+    let mut settings = config::load()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            _ if config::flag(&mut settings, &arg, &mut args)? => (),
+            "--input-string" => input = args.value(&arg)?,
+            _ if arg.starts_with('-') || file.is_some() => return Err(unknown(&arg)),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or_else(|| UsageError("watch needs a program".to_string()))?;
+
+    loop {
+        let seen = modified(&file);
+        print!("\x1b[2J\x1b[H");
+        stdout().flush()?;
+        let changed = match std::fs::read_to_string(&file) {
+            Ok(code) => run(&file, code, input.as_bytes(), &settings, &seen)?,
+            Err(err) => {
+                eprintln!("error: {file}: {err}");
+                false
+            }
+        };
+        if !changed {
+            eprintln!("\n-- waiting for {file} to change (Ctrl-C to quit)");
+            while modified(&file) == seen {
+                std::thread::sleep(POLL);
+            }
+        }
+    }
+}
+
+fn modified(file: &str) -> Option<SystemTime> {
+    Path::new(file)
+        .metadata()
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+// Runs one version of the program, returning early with `true` if the file changes meanwhile
+fn run(
+    file: &str,
+    mut code: String,
+    input: &[u8],
+    settings: &Settings,
+    seen: &Option<SystemTime>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let program = match compile(&mut code, settings) {
+        Ok(program) => program,
+        Err(err) => {
+            eprint!("{}", report(&err, file, &code, None));
+            return Ok(false);
+        }
+    };
+
+    let start = Instant::now();
+    let mut machine = Machine::with_settings(program, settings);
+    machine.feed(input);
+    machine.close_input();
+    let mut out = stdout().lock();
+    loop {
+        let state = machine.run_for(CHUNK);
+        out.write_all(&machine.take_output())?;
+        out.flush()?;
+        match state {
+            RunState::Paused if modified(file) != *seen => return Ok(true),
+            RunState::Paused => (),
+            RunState::Finished => break,
+            // Watched runs get no more input than was given, so a read waiting for
+            // some gets EOF instead
+            RunState::NeedsInput => machine.close_input(),
+            RunState::Error(err) => {
+                let at = machine.program().spans.get(machine.ip()).copied();
+                eprint!("{}", report(&err, file, &code, at));
+                return Ok(false);
+            }
+        }
+    }
+    eprintln!(
+        "\n-- finished in {:?} after {} steps",
+        start.elapsed(),
+        machine.steps()
+    );
+    Ok(false)
+}
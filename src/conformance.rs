@@ -0,0 +1,159 @@
+// Probes for the behaviors Brainfuck implementations disagree on, mostly Daniel Cristofani's
+// well-known test programs, each run under the given settings and described in plain words.
+use crate::error::BfError;
+use crate::interpreter::Interpreter;
+use crate::program::Program;
+use crate::settings::Settings;
+
+// Enough for every probe, so one that hangs under odd settings still gets reported
+const MAX_STEPS: u64 = 100_000_000;
+
+// Loops nested inside each other by the nesting probe
+const DEPTH: usize = 10_000;
+
+// What one probe found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub probe: &'static str,
+    pub behavior: String,
+}
+
+// A program, what to feed it, and how to read what it did
+struct Probe {
+    name: &'static str,
+    source: String,
+    input: &'static [u8],
+    judge: fn(Result<Vec<u8>, BfError>) -> String,
+}
+
+fn probes() -> Vec<Probe> {
+    vec![
+        Probe {
+            name: "eof",
+            // Reads a newline and then hits the end of input, printing two letters twice
+            source: ">,>+++++++++,>+++++++++++[<++++++<++++++<+>>>-]<<.>.<<-.>.>.<<.".into(),
+            input: b"\n",
+            judge: |result| match result.as_deref() {
+                Ok(b"LK\nLK\n") => "end of input leaves the cell unchanged".into(),
+                Ok(b"LB\nLB\n") => "end of input stores 0".into(),
+                Ok(b"LA\nLA\n") => "end of input stores -1".into(),
+                Ok(out) if out.contains(&b'O') => "newline doesn't read as 10".into(),
+                other => unexpected(other),
+            },
+        },
+        Probe {
+            name: "tape",
+            // Walks out to cell 30000 and prints a `#` from there
+            source: "++++[>++++++<-]>[>+++++>+++++++<<-]>>++++<[[>[[>>+<<-]<]>>>-]>-[>+>+<<-]>]\
+                     +++++[>+++++++<<++>-]>.<<."
+                .into(),
+            input: b"",
+            judge: |result| match result.as_deref() {
+                Ok(b"#\n") => "reaches cell 30000".into(),
+                other => unexpected(other),
+            },
+        },
+        Probe {
+            name: "obscure",
+            // A leading `[]`, comment characters that are commands elsewhere and a loop that
+            // has to be skipped, printing an H
+            source: "[]++++++++++[>>+>+>++++++[<<+<+++>>>-]<<<<-]\
+                     \"A*$\";?@![#>>+<<]>[>>]<<<<[>++<[-]]>.>."
+                .into(),
+            input: b"",
+            judge: |result| match result.as_deref() {
+                Ok(b"H\n") => "comments and skipped loops are handled".into(),
+                other => unexpected(other),
+            },
+        },
+        Probe {
+            name: "cell size",
+            // Adds up to 256 and then 65536, printing how wide cells are by which wraps to 0
+            source: "++++++++[>++++++++<-]>[<++++>-]+<[>-<[>++++<-]>[<++++++++>-]<[>++++++++<-]\
+                     +>[>++++++++++[>+++++<-]>+.-.[-]<<[-]<->]<[>>+++++++[>+++++++<-]>.+++++.\
+                     [-]<<<-]]>[>++++++++[>+++++++<-]>.[-]<<-]"
+                .into(),
+            input: b"",
+            judge: |result| match result.as_deref() {
+                Ok(b"8") => "8 bit cells that wrap around".into(),
+                Ok(b"16") => "16 bit cells".into(),
+                Ok(b"32") => "32 bit cells".into(),
+                other => unexpected(other),
+            },
+        },
+        Probe {
+            name: "nesting",
+            // Loops nested deeper than a recursive interpreter's stack would allow
+            source: format!(
+                "+{}-{}++++++++[>++++<-]>+.",
+                "[".repeat(DEPTH),
+                "]".repeat(DEPTH)
+            ),
+            input: b"",
+            judge: |result| match result.as_deref() {
+                Ok(b"!") => format!("{DEPTH} nested loops run"),
+                other => unexpected(other),
+            },
+        },
+        Probe {
+            name: "left edge",
+            // Moves left of the starting cell, which the usual 30000 cell array doesn't allow
+            source: "<++++++++[>++++<-]>+.".into(),
+            input: b"",
+            judge: |result| match result.as_deref() {
+                Ok(b"!") => "the tape extends left of the starting cell".into(),
+                other => unexpected(other),
+            },
+        },
+        Probe {
+            name: "unmatched [",
+            source: "+++++[>+++++++>++<<-]>.>.[".into(),
+            input: b"",
+            judge: brackets,
+        },
+        Probe {
+            name: "unmatched ]",
+            source: "+++++[>+++++++>++<<-]>.>.][".into(),
+            input: b"",
+            judge: brackets,
+        },
+    ]
+}
+
+fn brackets(result: Result<Vec<u8>, BfError>) -> String {
+    match result {
+        Err(err @ (BfError::UnopenedBracket(_) | BfError::UnclosedBracket(_))) => {
+            format!("rejected before running: {err}")
+        }
+        other => unexpected(other.as_deref()),
+    }
+}
+
+fn unexpected(result: Result<&[u8], &BfError>) -> String {
+    match result {
+        Ok(out) => format!("unexpected output {:?}", String::from_utf8_lossy(out)),
+        Err(err) => format!("failed: {err}"),
+    }
+}
+
+// Runs every probe under `settings`, in a fixed order
+pub fn run(settings: &Settings) -> Vec<Finding> {
+    let settings = Settings {
+        max_steps: settings.max_steps.or(Some(MAX_STEPS)),
+        ..*settings
+    };
+    probes()
+        .into_iter()
+        .map(|probe| {
+            let result = Program::with_settings(&probe.source, &settings).and_then(|program| {
+                let mut output = vec![];
+                Interpreter::with_settings(program, &settings, probe.input, &mut output).run()?;
+                Ok(output)
+            });
+            Finding {
+                probe: probe.name,
+                behavior: (probe.judge)(result),
+            }
+        })
+        .collect()
+}
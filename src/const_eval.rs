@@ -0,0 +1,92 @@
+// An interpreter simple enough to run in a `const` context, for embedding the output of small
+// programs as constants:
+//
+//     const HI: [u8; 3] = bf::bf_eval("++++++++[>+++++++++<-]>.+.+++++++++++++++++++++++++.");
+//
+// It is the standard dialect with 8-bit cells and a 30000 cell tape. Programs can't read input,
+// and anything that goes wrong, including output that isn't exactly `N` bytes, stops the build.
+
+const TAPE: usize = 30000;
+
+pub const fn bf_eval<const N: usize>(src: &str) -> [u8; N] {
+    let code = src.as_bytes();
+    check_brackets(code);
+    let mut tape = [0u8; TAPE];
+    let mut pointer = 0;
+    let mut output = [0u8; N];
+    let mut written = 0;
+    let mut pc = 0;
+    while pc < code.len() {
+        match code[pc] {
+            b'+' => tape[pointer] = tape[pointer].wrapping_add(1),
+            b'-' => tape[pointer] = tape[pointer].wrapping_sub(1),
+            b'>' => {
+                pointer += 1;
+                if pointer == TAPE {
+                    panic!("bf_eval: moved off the right end of the tape");
+                }
+            }
+            b'<' => {
+                if pointer == 0 {
+                    panic!("bf_eval: moved off the left end of the tape");
+                }
+                pointer -= 1;
+            }
+            b'.' => {
+                if written == N {
+                    panic!("bf_eval: the program writes more than N bytes");
+                }
+                output[written] = tape[pointer];
+                written += 1;
+            }
+            b',' => panic!("bf_eval: programs can't read input"),
+            b'[' if tape[pointer] == 0 => pc = partner(code, pc),
+            b']' if tape[pointer] != 0 => pc = partner(code, pc),
+            _ => (),
+        }
+        pc += 1;
+    }
+    if written != N {
+        panic!("bf_eval: the program writes fewer than N bytes");
+    }
+    output
+}
+
+// Brackets are matched one jump at a time, so make sure they all have a partner first
+const fn check_brackets(code: &[u8]) {
+    let mut depth = 0;
+    let mut pc = 0;
+    while pc < code.len() {
+        match code[pc] {
+            b'[' => depth += 1,
+            b']' if depth == 0 => panic!("bf_eval: unopened bracket"),
+            b']' => depth -= 1,
+            _ => (),
+        }
+        pc += 1;
+    }
+    if depth != 0 {
+        panic!("bf_eval: unclosed bracket");
+    }
+}
+
+// The matching bracket, found by counting nesting in the direction the bracket points
+const fn partner(code: &[u8], at: usize) -> usize {
+    let forward = code[at] == b'[';
+    let mut depth = 0;
+    let mut pc = at;
+    loop {
+        match code[pc] {
+            b'[' => depth += 1,
+            b']' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            return pc;
+        }
+        match forward {
+            true => pc += 1,
+            false => pc -= 1,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// A directory of programs with their expected output, used as a regression suite.
+//
+// Each `NAME.b` may come with `NAME.in` to feed it, `NAME.settings` holding `key = value` lines
+// like the config file, and `NAME.out`, the exact output it must produce.
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::error::BfError;
+use crate::interpreter::Interpreter;
+use crate::program::Program;
+use crate::settings::Settings;
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub path: PathBuf, // The `.b` file
+    pub source: String,
+    pub input: Vec<u8>,
+    pub expected: Option<Vec<u8>>, // `None` until an output has been recorded
+    pub settings: Settings,
+}
+
+impl Case {
+    // Runs the program with its input and settings, returning everything it wrote
+    pub fn run(&self) -> Result<Vec<u8>, BfError> {
+        let program = Program::with_settings(&self.source, &self.settings)?;
+        let mut output = vec![];
+        Interpreter::with_settings(program, &self.settings, &self.input[..], &mut output).run()?;
+        Ok(output)
+    }
+
+    // Where the expected output lives, for recording a new one
+    pub fn output_path(&self) -> PathBuf {
+        self.path.with_extension("out")
+    }
+}
+
+// Every case in `dir`, sorted by name, each starting from `base` settings
+pub fn load(dir: &Path, base: &Settings) -> io::Result<Vec<Case>> {
+    let mut cases = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "b") {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let mut settings = *base;
+        if let Some(text) = optional(&path.with_extension("settings"))? {
+            for line in String::from_utf8_lossy(&text).lines() {
+                let line = line.split('#').next().unwrap().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let invalid = |err: String| {
+                    io::Error::new(ErrorKind::InvalidData, format!("{name}.settings: {err}"))
+                };
+                let (key, value) = line
+                    .split_once('=')
+                    .ok_or_else(|| invalid(format!("expected `key = value`, got {line:?}")))?;
+                settings
+                    .set(key.trim(), value.trim().trim_matches('"'))
+                    .map_err(invalid)?;
+            }
+        }
+        cases.push(Case {
+            source: fs::read_to_string(&path)?,
+            input: optional(&path.with_extension("in"))?.unwrap_or_default(),
+            expected: optional(&path.with_extension("out"))?,
+            settings,
+            name,
+            path,
+        });
+    }
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(cases)
+}
+
+fn optional(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,110 @@
+// Which source commands ran and how often, from the per-instruction hit counts of a machine.
+use std::fmt::Write;
+
+use crate::analysis::COMMANDS;
+use crate::extension::Extensions;
+use crate::program::{self, Program};
+use crate::token::BfToken;
+
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    counts: Vec<Option<u64>>, // Per source byte, `None` for anything that isn't a command
+}
+
+impl Coverage {
+    // Spreads each instruction's count over the commands it was compiled from
+    pub fn new(program: &Program, hits: &[u64], source: &str) -> Self {
+        let mut counts = vec![None; source.len()];
+        let random = program.tokens.contains(&BfToken::RND);
+        for (offset, c) in source.char_indices() {
+            if COMMANDS.contains(&c) || (random && c == '?') {
+                counts[offset] = Some(0);
+            }
+        }
+        // Comment loops aren't part of the program
+        let extensions = Extensions {
+            random,
+            ..Extensions::default()
+        };
+        for span in program::comment_loops(source, extensions).unwrap_or_default() {
+            counts[span.start..span.end].fill(None);
+        }
+        for (span, &hits) in program.spans.iter().zip(hits) {
+            for count in counts[span.start..span.end.min(source.len())]
+                .iter_mut()
+                .flatten()
+            {
+                *count = (*count).max(hits);
+            }
+        }
+        Self { counts }
+    }
+
+    pub fn commands(&self) -> usize {
+        self.counts.iter().flatten().count()
+    }
+
+    pub fn covered(&self) -> usize {
+        self.counts.iter().flatten().filter(|&&n| n > 0).count()
+    }
+
+    pub fn percent(&self) -> f64 {
+        self.covered() as f64 * 100.0 / self.commands().max(1) as f64
+    }
+
+    // Lines with their counts in front, gcov style, `#####` for lines that never ran and a
+    // row of `^` under commands that never ran on lines that otherwise did
+    pub fn annotate(&self, source: &str) -> String {
+        let mut out = String::new();
+        let mut offset = 0;
+        for line in source.split_inclusive('\n') {
+            let text = line.trim_end_matches(['\n', '\r']);
+            let counts: Vec<(usize, u64)> = text
+                .char_indices()
+                .filter_map(|(idx, _)| Some((idx, self.counts[offset + idx]?)))
+                .collect();
+            let hits = counts.iter().map(|&(_, n)| n).max();
+            let gutter = match hits {
+                None => "-".to_string(),
+                Some(0) => "#####".to_string(),
+                Some(n) => n.to_string(),
+            };
+            let _ = writeln!(out, "{gutter:>9}: {text}");
+            if hits.is_some_and(|n| n > 0) && counts.iter().any(|&(_, n)| n == 0) {
+                let mut marks = String::new();
+                for (idx, c) in text.char_indices() {
+                    let dead = self.counts[offset + idx] == Some(0);
+                    marks.push(match (dead, c) {
+                        (true, _) => '^',
+                        (false, '\t') => '\t',
+                        _ => ' ',
+                    });
+                }
+                let _ = writeln!(out, "{:>9}  {}", "", marks.trim_end());
+            }
+            offset += line.len();
+        }
+        out
+    }
+
+    // The lcov tracefile format, one `DA` record per line holding commands
+    pub fn lcov(&self, name: &str, source: &str) -> String {
+        let mut out = format!("TN:\nSF:{name}\n");
+        let (mut found, mut hit) = (0, 0);
+        let mut offset = 0;
+        for (idx, line) in source.split_inclusive('\n').enumerate() {
+            let hits = self.counts[offset..offset + line.len()]
+                .iter()
+                .flatten()
+                .max();
+            if let Some(&hits) = hits {
+                let _ = writeln!(out, "DA:{},{hits}", idx + 1);
+                found += 1;
+                hit += (hits > 0) as usize;
+            }
+            offset += line.len();
+        }
+        let _ = write!(out, "LF:{found}\nLH:{hit}\nend_of_record\n");
+        out
+    }
+}
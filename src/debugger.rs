@@ -0,0 +1,94 @@
+// Runs a machine in controlled amounts for an interactive debugger: a number of
+// instructions, to the end of the current loop or iteration, or up to a given instruction.
+//
+// Every method runs at least one instruction and stops early when the program halts, fails,
+// waits for input or its `CancelToken` is cancelled, reporting which as a `RunState`. `Paused`
+// means it got where it was asked to go.
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use crate::cancel::CancelToken;
+use crate::error::BfError;
+use crate::machine::{Machine, RunState};
+
+pub struct Debugger {
+    machine: Machine,
+    cancel: Option<CancelToken>,
+}
+
+impl Debugger {
+    pub fn new(machine: Machine) -> Self {
+        Self {
+            machine,
+            cancel: None,
+        }
+    }
+
+    // Once `token` is cancelled, a run stops before the next instruction with
+    // `BfError::Interrupted`
+    pub fn set_cancel(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
+
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.set_cancel(flag.into());
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    // The `[` and `]` of the loop the next instruction is in, if any
+    pub fn current_loop(&self) -> Option<(usize, usize)> {
+        let program = self.machine.program();
+        let open = program.enclosing_loop(self.machine.ip())?;
+        Some((open, program.jumps[open]))
+    }
+
+    pub fn step(&mut self, count: u64) -> RunState {
+        let mut left = count;
+        self.run_while(|_| {
+            left = left.saturating_sub(1);
+            left > 0
+        })
+    }
+
+    // Runs until the current loop exits, `None` outside of any loop
+    pub fn next_loop(&mut self) -> Option<RunState> {
+        let (open, close) = self.current_loop()?;
+        Some(self.run_while(|machine| (open + 1..=close).contains(&machine.ip())))
+    }
+
+    // Runs to the `]` of the current loop, or out of the loop when already there and it ends
+    pub fn finish(&mut self) -> Option<RunState> {
+        let (open, close) = self.current_loop()?;
+        Some(self.run_while(|machine| (open + 1..close).contains(&machine.ip())))
+    }
+
+    // Runs until the instruction at `target` is next
+    pub fn until(&mut self, target: usize) -> RunState {
+        self.run_while(|machine| machine.ip() != target)
+    }
+
+    // Runs to the end
+    pub fn resume(&mut self) -> RunState {
+        self.run_while(|_| true)
+    }
+
+    // Executes instructions for as long as `go_on` holds after each one
+    fn run_while(&mut self, mut go_on: impl FnMut(&Machine) -> bool) -> RunState {
+        loop {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return RunState::Error(BfError::Interrupted);
+            }
+            match self.machine.run_for(1) {
+                RunState::Paused if go_on(&self.machine) => (),
+                state => return state,
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+// Errors and warnings rendered against the source they came from, with labelled snippets.
+use std::fmt::Write;
+
+use crate::error::BfError;
+use crate::program::Span;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note, // Something done to the program that may not be expected
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            labels: vec![],
+            help: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(message)
+        }
+    }
+
+    pub fn note(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Note,
+            ..Self::error(message)
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        let primary = self.labels.is_empty();
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary,
+        });
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    // Describes `err`, pointing runtime errors at the instruction they happened on
+    pub fn from_error(err: &BfError, source: &str, at: Option<Span>) -> Self {
+        match err {
+            BfError::UnopenedBracket(pos) => {
+                let diagnostic = Self::error("unmatched closing bracket")
+                    .with_label(byte_span(source, *pos), "this `]` has no matching `[`");
+                match last_closed_loop(source, *pos) {
+                    Some(open) => diagnostic
+                        .with_label(
+                            byte_span(source, open),
+                            "the loop opened here was already closed",
+                        )
+                        .with_help("remove the extra `]`, or add a `[` before it"),
+                    None => diagnostic.with_help("remove the `]`, or add a `[` before it"),
+                }
+            }
+            BfError::UnclosedBracket(pos) => {
+                let end = source.len();
+                Self::error("unclosed bracket")
+                    .with_label(byte_span(source, *pos), "this `[` is never closed")
+                    .with_label(
+                        Span { start: end, end },
+                        "expected a matching `]` before the end",
+                    )
+                    .with_help("add a `]` to close the loop, or remove the `[`")
+            }
+            BfError::NestingLimit(pos, limit) => {
+                Self::error(format!("loops nested more than {limit} deep"))
+                    .with_label(byte_span(source, *pos), "this `[` is one too many")
+                    .with_help("raise the limit with --max-depth, or use 0 for no limit")
+            }
+            BfError::InvalidIr(pos, message) => {
+                let end = source[*pos..]
+                    .find('\n')
+                    .map_or(source.len(), |len| pos + len);
+                Self::error("invalid IR").with_label(Span { start: *pos, end }, message.clone())
+            }
+            BfError::Syntax(pos, message) => {
+                Self::error("syntax error").with_label(byte_span(source, *pos), message.clone())
+            }
+            BfError::StepLimit(limit) => {
+                let diagnostic = Self::error(format!("step limit of {limit} exceeded"))
+                    .with_help("raise the limit with --max-steps, or use 0 for no limit");
+                match at {
+                    Some(span) => diagnostic.with_label(span, "stopped before running this"),
+                    None => diagnostic,
+                }
+            }
+            BfError::NonTerminating(_) => {
+                let diagnostic = Self::error("non-terminating loop detected").with_help(
+                    "the loop's last pass left the pointer, the tape and the input as they were",
+                );
+                match at {
+                    Some(span) => diagnostic.with_label(span, "this loop can never end"),
+                    None => diagnostic,
+                }
+            }
+            BfError::TapeBounds(cell) => {
+                let diagnostic = Self::error(format!("pointer moved off the tape to cell {cell}"))
+                    .with_help("use a longer --tape fixed:N, or a dynamic or sparse tape");
+                match at {
+                    Some(span) => diagnostic.with_label(span, "this move left the tape"),
+                    None => diagnostic,
+                }
+            }
+            BfError::Interrupted => {
+                let diagnostic = Self::error("interrupted");
+                match at {
+                    Some(span) => diagnostic.with_label(span, "stopped before running this"),
+                    None => diagnostic,
+                }
+            }
+            err => Self::error(err.to_string()),
+        }
+    }
+
+    pub fn render(&self, name: &str, source: &str, color: bool) -> String {
+        let paint = |code: &str, text: &str| match color {
+            true => format!("\x1b[{code}m{text}\x1b[0m"),
+            false => text.to_string(),
+        };
+        let (title, accent) = match self.severity {
+            Severity::Error => ("error", "1;31"),
+            Severity::Warning => ("warning", "1;33"),
+            Severity::Note => ("note", "1;36"),
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}: {}",
+            paint(accent, title),
+            paint("1", &self.message)
+        );
+
+        let mut labels: Vec<(usize, usize, &Label)> = self
+            .labels
+            .iter()
+            .map(|label| {
+                let (line, col) = line_col(source, label.span.start);
+                (line, col, label)
+            })
+            .collect();
+        labels.sort_by_key(|&(line, col, _)| (line, col));
+
+        let gutter = labels
+            .iter()
+            .map(|&(line, _, _)| (line + 1).to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(gutter);
+        let bar = paint("1;34", "|");
+
+        match labels
+            .iter()
+            .find(|(_, _, label)| label.primary)
+            .or(labels.first())
+        {
+            Some(&(line, col, _)) => {
+                let _ = writeln!(
+                    out,
+                    "{pad}{} {name}:{}:{}",
+                    paint("1;34", "-->"),
+                    line + 1,
+                    col + 1
+                );
+            }
+            None => {
+                let _ = writeln!(out, "{pad}{} {name}", paint("1;34", "-->"));
+            }
+        }
+        if !labels.is_empty() {
+            let _ = writeln!(out, "{pad} {bar}");
+        }
+
+        let lines: Vec<&str> = source.split('\n').collect();
+        let mut last_line = None;
+        for &(line, col, label) in &labels {
+            if last_line.is_some_and(|last| line > last + 1) {
+                let _ = writeln!(out, "{}", paint("1;34", "..."));
+            }
+            let text = lines
+                .get(line)
+                .copied()
+                .unwrap_or("")
+                .trim_end_matches('\r');
+            if last_line != Some(line) {
+                let number = format!("{:>gutter$}", line + 1);
+                let _ = writeln!(out, "{} {bar} {text}", paint("1;34", &number));
+            }
+            last_line = Some(line);
+
+            // Keep tabs in the indent so the marker lines up with the source
+            let indent: String = text
+                .chars()
+                .take(col)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = source
+                .get(label.span.start..label.span.end)
+                .map(|s| s.chars().take_while(|&c| c != '\n').count())
+                .unwrap_or(0)
+                .max(1);
+            let (marker, code) = match label.primary {
+                true => ('^', accent),
+                false => ('-', "1;34"),
+            };
+            let underline: String = std::iter::repeat_n(marker, width).collect();
+            let _ = writeln!(
+                out,
+                "{pad} {bar} {indent}{} {}",
+                paint(code, &underline),
+                paint(code, &label.message)
+            );
+        }
+
+        if let Some(help) = &self.help {
+            let _ = writeln!(
+                out,
+                "{pad} {} {}: {help}",
+                paint("1;34", "="),
+                paint("1", "help")
+            );
+        }
+        out
+    }
+}
+
+// Zero-based line and column (in characters) of a byte offset
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count();
+    let start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (line, before[start..].chars().count())
+}
+
+// The span of the single character at `pos`
+fn byte_span(source: &str, pos: usize) -> Span {
+    let len = source[pos..].chars().next().map_or(0, char::len_utf8);
+    Span {
+        start: pos,
+        end: pos + len,
+    }
+}
+
+// The `[` of the loop closed most recently before the stray `]` at `pos`
+fn last_closed_loop(source: &str, pos: usize) -> Option<usize> {
+    let mut queue = vec![];
+    let mut last = None;
+    for (idx, c) in source[..pos].char_indices() {
+        match c {
+            '[' => queue.push(idx),
+            ']' => last = queue.pop().or(last),
+            _ => (),
+        }
+    }
+    last
+}
@@ -0,0 +1,243 @@
+// Other spellings of Brainfuck, converted through the same compiled program as everything
+// else. A dialect's reader turns source into standard commands, remembering where each one
+// was spelled, and its writer spells a program's instructions, so any two dialects convert
+// into each other and whatever the optimizer produces can be written in each.
+//
+// In `ook`, Ook!, each command is a pair of the words `Ook.`, `Ook?` and `Ook!`, and any other
+// word is a comment. A substitution map is a text file with a line per command, like
+// `+ = Increment`, giving each of the eight (and `?` or `Y` for the extensions) a word of its
+// own. Reading one takes the longest word at each position and skips anything else.
+use crate::error::BfError;
+use crate::extension::Extensions;
+use crate::program::{Program, Span};
+
+const OOK: [(char, &str); 8] = [
+    ('>', "Ook. Ook?"),
+    ('<', "Ook? Ook."),
+    ('+', "Ook. Ook."),
+    ('-', "Ook! Ook!"),
+    ('.', "Ook! Ook."),
+    (',', "Ook. Ook!"),
+    ('[', "Ook! Ook?"),
+    (']', "Ook? Ook!"),
+];
+
+// Pairs written on a line of Ook!
+const OOK_LINE: usize = 8;
+// Where the words of a substitution map are wrapped
+const WIDTH: usize = 80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dialect {
+    Brainfuck,
+    Ook,
+    Map(Substitution),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    words: Vec<(char, String)>,
+}
+
+impl Substitution {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut words: Vec<(char, String)> = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let wrong = |message: &str| format!("line {}: {message}", number + 1);
+            let (command, word) = line
+                .split_once('=')
+                .ok_or_else(|| wrong("expected `COMMAND = WORD`"))?;
+            let command = match command.trim() {
+                c if c.len() == 1 && "+-<>[].,?Y".contains(c) => c.chars().next().unwrap(),
+                _ => return Err(wrong("commands are one of `+-<>[].,?Y`")),
+            };
+            let word = word.trim();
+            if word.is_empty() {
+                return Err(wrong("the word can't be empty"));
+            }
+            if words.iter().any(|(c, w)| *c == command || w == word) {
+                return Err(wrong("each command needs a word of its own"));
+            }
+            words.push((command, word.to_string()));
+        }
+        match "+-<>[].,"
+            .chars()
+            .find(|&c| words.iter().all(|(w, _)| *w != c))
+        {
+            Some(missing) => Err(format!("no word for `{missing}`")),
+            None => Ok(Self { words }),
+        }
+    }
+}
+
+impl Dialect {
+    // `bf` or `ook`, maps are read from their file
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "bf" | "brainfuck" => Some(Self::Brainfuck),
+            "ook" => Some(Self::Ook),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Brainfuck => "Brainfuck",
+            Self::Ook => "Ook!",
+            Self::Map(_) => "the substitution map",
+        }
+    }
+
+    // Compiles source in this dialect, with spans and errors pointing into it
+    pub fn compile(
+        &self,
+        source: &str,
+        opt_level: u8,
+        extensions: Extensions,
+    ) -> Result<Program, BfError> {
+        let (code, origins) = match self {
+            Self::Brainfuck => return Program::with_extensions(source, opt_level, extensions),
+            Self::Ook => read_ook(source)?,
+            Self::Map(map) => read_map(map, source),
+        };
+        // Each command is one byte of `code`
+        let origin = |at: usize| origins.get(at).map_or(source.len(), |span| span.start);
+        let mut program =
+            Program::with_extensions(&code, opt_level, extensions).map_err(|err| match err {
+                BfError::UnopenedBracket(at) => BfError::UnopenedBracket(origin(at)),
+                BfError::UnclosedBracket(at) => BfError::UnclosedBracket(origin(at)),
+                err => err,
+            })?;
+        for span in &mut program.spans {
+            if span.end > span.start {
+                *span = Span {
+                    start: origins[span.start].start,
+                    end: origins[span.end - 1].end,
+                };
+            }
+        }
+        Ok(program)
+    }
+
+    // The program spelled in this dialect, failing on a command it has no word for
+    pub fn write(&self, program: &Program) -> Result<String, BfError> {
+        let mut commands = String::new();
+        for &token in &program.tokens {
+            commands.push_str(&String::from(token));
+        }
+        let missing =
+            |c: char| BfError::Unsupported(format!("{} has no command for `{c}`", self.name()));
+        match self {
+            Self::Brainfuck => Ok(commands),
+            Self::Ook => {
+                let mut out = String::new();
+                for (idx, c) in commands.chars().enumerate() {
+                    let (_, pair) = OOK
+                        .iter()
+                        .find(|(command, _)| *command == c)
+                        .ok_or_else(|| missing(c))?;
+                    if idx > 0 {
+                        out.push(if idx % OOK_LINE == 0 { '\n' } else { ' ' });
+                    }
+                    out.push_str(pair);
+                }
+                Ok(out)
+            }
+            Self::Map(map) => {
+                // Single characters don't need spaces between them
+                let spaced = map.words.iter().any(|(_, word)| word.chars().count() > 1);
+                let (mut out, mut line) = (String::new(), 0);
+                for c in commands.chars() {
+                    let (_, word) = map
+                        .words
+                        .iter()
+                        .find(|(command, _)| *command == c)
+                        .ok_or_else(|| missing(c))?;
+                    let len = word.chars().count();
+                    if line > 0 && line + len + spaced as usize > WIDTH {
+                        out.push('\n');
+                        line = 0;
+                    } else if line > 0 && spaced {
+                        out.push(' ');
+                        line += 1;
+                    }
+                    out.push_str(word);
+                    line += len;
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+fn read_ook(source: &str) -> Result<(String, Vec<Span>), BfError> {
+    let mut words = vec![];
+    let mut start = None;
+    for (at, c) in source.char_indices().chain([(source.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(at),
+            (true, Some(from)) => {
+                if matches!(&source[from..at], "Ook." | "Ook?" | "Ook!") {
+                    words.push(Span {
+                        start: from,
+                        end: at,
+                    });
+                }
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    let (mut code, mut origins) = (String::new(), vec![]);
+    for pair in words.chunks(2) {
+        let &[first, second] = pair else {
+            let message = "an Ook! command needs a second word".to_string();
+            return Err(BfError::Syntax(pair[0].start, message));
+        };
+        let spelled = format!(
+            "{} {}",
+            &source[first.start..first.end],
+            &source[second.start..second.end]
+        );
+        let Some((command, _)) = OOK.iter().find(|(_, pair)| *pair == spelled) else {
+            return Err(BfError::Syntax(
+                first.start,
+                format!("`{spelled}` isn't a command"),
+            ));
+        };
+        code.push(*command);
+        origins.push(Span {
+            start: first.start,
+            end: second.end,
+        });
+    }
+    Ok((code, origins))
+}
+
+fn read_map(map: &Substitution, source: &str) -> (String, Vec<Span>) {
+    let (mut code, mut origins) = (String::new(), vec![]);
+    let mut at = 0;
+    while let Some(c) = source[at..].chars().next() {
+        let longest = map
+            .words
+            .iter()
+            .filter(|(_, word)| source[at..].starts_with(word.as_str()))
+            .max_by_key(|(_, word)| word.len());
+        match longest {
+            Some((command, word)) => {
+                code.push(*command);
+                origins.push(Span {
+                    start: at,
+                    end: at + word.len(),
+                });
+                at += word.len();
+            }
+            None => at += c.len_utf8(),
+        }
+    }
+    (code, origins)
+}
@@ -0,0 +1,157 @@
+// Runs two programs side by side, one instruction each at a time, to find where they stop
+// behaving alike: the first output byte that differs, or with `tapes` the first cell that
+// holds a different value, counting cells from where each pointer started.
+//
+// Steps only line up for programs that do the same things in the same order, like one that
+// was refactored without optimizing it, so tapes are compared only when asked. Output is
+// compared whenever both sides have written a byte at the same position, however many steps
+// apart.
+use crate::error::BfError;
+use crate::machine::{Machine, RunState};
+use crate::token::BfToken;
+
+#[derive(Debug)]
+pub enum Verdict {
+    Same,
+    // Byte `at` of the output, `None` for the side that finished without writing it
+    Output {
+        at: usize,
+        left: Option<u8>,
+        right: Option<u8>,
+    },
+    Tape {
+        cell: isize,
+        left: u32,
+        right: u32,
+    },
+    Failed {
+        left: bool, // Which side, true for the left one
+        error: BfError,
+    },
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub verdict: Verdict,
+    pub step: u64, // Rounds run until the verdict, a side that finished sits the rest out
+    pub ips: [usize; 2],
+    pub outputs: [Vec<u8>; 2],
+}
+
+// One of the two programs, with where its pointer is relative to the cell it started on
+struct Side {
+    machine: Machine,
+    output: Vec<u8>,
+    position: isize,
+    finished: bool,
+}
+
+impl Side {
+    fn new(machine: Machine) -> Self {
+        Self {
+            machine,
+            output: vec![],
+            position: 0,
+            finished: false,
+        }
+    }
+
+    fn step(&mut self) -> Result<(), BfError> {
+        if self.finished {
+            return Ok(());
+        }
+        let token = self
+            .machine
+            .program()
+            .tokens
+            .get(self.machine.ip())
+            .copied();
+        match self.machine.run_for(1) {
+            RunState::Finished => self.finished = true,
+            RunState::Error(err) => return Err(err),
+            // Input is fed up front and closed, so `,` never waits
+            RunState::Paused | RunState::NeedsInput => (),
+        }
+        self.output.extend(self.machine.take_output());
+        self.position += match token {
+            Some(BfToken::MOV(n)) => n,
+            Some(BfToken::SCN(_)) => self.machine.scanned(),
+            _ => 0,
+        };
+        Ok(())
+    }
+
+    fn cell(&self, position: isize) -> u32 {
+        self.machine.memory().peek(position - self.position)
+    }
+}
+
+// Both machines should have been fed the same input and had it closed
+pub fn lockstep(left: Machine, right: Machine, tapes: bool) -> Report {
+    let mut sides = [Side::new(left), Side::new(right)];
+    let mut compared = 0; // Output bytes known to match
+    let mut step = 0;
+    let verdict = loop {
+        if sides.iter().all(|side| side.finished) {
+            break match sides[0].output.len() == sides[1].output.len() {
+                true => Verdict::Same,
+                false => mismatch(&sides, compared),
+            };
+        }
+        step += 1;
+        if let Some((idx, error)) = sides
+            .iter_mut()
+            .enumerate()
+            .find_map(|(idx, side)| side.step().err().map(|err| (idx, err)))
+        {
+            break Verdict::Failed {
+                left: idx == 0,
+                error,
+            };
+        }
+        let common = sides[0].output.len().min(sides[1].output.len());
+        if let Some(at) = (compared..common).find(|&at| sides[0].output[at] != sides[1].output[at])
+        {
+            break mismatch(&sides, at);
+        }
+        compared = common;
+        // A side that finished behind won't write the bytes the other has gone on to
+        let behind = match sides[0].output.len() < sides[1].output.len() {
+            true => &sides[0],
+            false => &sides[1],
+        };
+        let ahead = sides[0].output.len().max(sides[1].output.len());
+        if behind.finished && ahead > compared {
+            break mismatch(&sides, compared);
+        }
+        if tapes {
+            // Cells only differ once one side writes, and it writes where its pointer is
+            let at = sides
+                .iter()
+                .map(|side| side.position)
+                .find(|&position| sides[0].cell(position) != sides[1].cell(position));
+            if let Some(cell) = at {
+                break Verdict::Tape {
+                    cell,
+                    left: sides[0].cell(cell),
+                    right: sides[1].cell(cell),
+                };
+            }
+        }
+    };
+    let [left, right] = sides;
+    Report {
+        verdict,
+        step,
+        ips: [left.machine.ip(), right.machine.ip()],
+        outputs: [left.output, right.output],
+    }
+}
+
+fn mismatch(sides: &[Side; 2], at: usize) -> Verdict {
+    Verdict::Output {
+        at,
+        left: sides[0].output.get(at).copied(),
+        right: sides[1].output.get(at).copied(),
+    }
+}
@@ -0,0 +1,86 @@
+// Draws a program's control flow as a Graphviz graph, one node per basic block.
+//
+// A block runs straight through and ends at a bracket, which branches one way on a zero cell
+// and the other way otherwise. Given per-instruction hit counts the blocks are shaded by how
+// often they ran.
+use std::fmt::Write;
+
+use crate::ir::mnemonic;
+use crate::program::Program;
+use crate::token::BfToken;
+
+// Instructions listed in a node before the rest are summarized
+const SHOWN: usize = 6;
+
+pub fn to_dot(program: &Program, hits: Option<&[u64]>) -> String {
+    let len = program.len();
+    // A block starts at the beginning and after every bracket, since both can branch
+    let mut starts = match len {
+        0 => vec![],
+        _ => vec![0],
+    };
+    for (idx, token) in program.tokens.iter().enumerate() {
+        if matches!(token, BfToken::JUM | BfToken::BAC) && idx + 1 < len {
+            starts.push(idx + 1);
+        }
+    }
+    let block_of = |idx: usize| match idx >= len {
+        true => "end".to_string(),
+        false => format!("b{}", starts.partition_point(|&start| start <= idx) - 1),
+    };
+    let max = hits.map_or(0, |hits| hits.iter().copied().max().unwrap_or(0));
+
+    let mut out = String::new();
+    let _ = writeln!(out, "digraph program {{");
+    let _ = writeln!(out, "  node [shape=box, fontname=\"monospace\"];");
+    let _ = writeln!(out, "  end [shape=oval];");
+    for (number, &start) in starts.iter().enumerate() {
+        let end = starts.get(number + 1).copied().unwrap_or(len);
+        let last = end - 1;
+        let span = (program.spans[start].start, program.spans[last].end);
+        let mut label = format!("{start}..{end}  @{}..{}", span.0, span.1);
+        for idx in start..end.min(start + SHOWN) {
+            let _ = write!(label, "\\l{}", mnemonic(program, idx));
+        }
+        if end - start > SHOWN {
+            let _ = write!(label, "\\l... {} more", end - start - SHOWN);
+        }
+        let mut style = String::new();
+        if let Some(hits) = hits {
+            let count = hits[start];
+            let _ = write!(label, "\\lhits: {count}");
+            // Shaded on a log scale, so the hottest loop doesn't wash out everything else
+            let heat = match count {
+                0 => 0.0,
+                _ => (count as f64).ln_1p() / (max as f64).ln_1p(),
+            };
+            let _ = write!(style, ", style=filled, fillcolor=\"0.0 {heat:.3} 1.0\"");
+        }
+        let _ = writeln!(out, "  b{number} [label=\"{label}\\l\"{style}];");
+
+        match program.tokens[last] {
+            BfToken::JUM => {
+                let _ = writeln!(out, "  b{number} -> {} [label=\"nonzero\"];", block_of(end));
+                let after = program.jumps[last] + 1;
+                let _ = writeln!(out, "  b{number} -> {} [label=\"zero\"];", block_of(after));
+            }
+            BfToken::BAC => {
+                let body = program.jumps[last] + 1;
+                let _ = writeln!(
+                    out,
+                    "  b{number} -> {} [label=\"nonzero\"];",
+                    block_of(body)
+                );
+                let _ = writeln!(out, "  b{number} -> {} [label=\"zero\"];", block_of(end));
+            }
+            _ => {
+                let _ = writeln!(out, "  b{number} -> {};", block_of(end));
+            }
+        }
+    }
+    if len == 0 {
+        let _ = writeln!(out, "  start [shape=oval];\n  start -> end;");
+    }
+    let _ = writeln!(out, "}}");
+    out
+}
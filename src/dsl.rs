@@ -0,0 +1,489 @@
+// A tiny imperative language that compiles down to Brainfuck through the token IR.
+//
+//     # comments run to the end of the line
+//     var n = 5;                 # `var` is optional, a name is declared by assigning it
+//     while n {                  # loops while the value isn't zero
+//         print 'a' + n - 1;     # writes the value as a byte
+//         n = n - 1;
+//     }
+//     if n { print "set\n"; } else { print "done\n"; }
+//     read c;                    # reads one byte of input
+//
+// Expressions are numbers, characters, names, `+`, `-` and parentheses. A name has to be
+// assigned or read before it appears in one. Every variable gets its own cell, temporaries
+// live after them and are always zero when not in use.
+use std::collections::HashMap;
+
+use crate::error::BfError;
+use crate::token::BfToken;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Number(isize),
+    Str(Vec<u8>),
+    Sym(char),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(isize),
+    Var(String, usize), // With where it appears, for reporting it undeclared
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Assign(String, Expr),
+    Print(Expr),
+    PrintStr(Vec<u8>),
+    Read(String),
+    While(Expr, Vec<Stmt>),
+    If(Expr, Vec<Stmt>, Option<Vec<Stmt>>),
+}
+
+// Compiles a program to Brainfuck source
+pub fn compile(source: &str) -> Result<String, BfError> {
+    Ok(compile_tokens(source)?
+        .into_iter()
+        .map(String::from)
+        .collect())
+}
+
+// Compiles a program to the token stream, without source spans
+pub fn compile_tokens(source: &str) -> Result<Vec<BfToken>, BfError> {
+    let tokens = lex(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        end: source.len(),
+    };
+    let mut stmts = vec![];
+    while parser.pos < tokens.len() {
+        stmts.push(parser.stmt()?);
+    }
+
+    let mut vars = HashMap::new();
+    collect_vars(&stmts, &mut vars)?;
+    let mut gen = Codegen {
+        out: vec![],
+        pointer: 0,
+        next_temp: vars.len(),
+        vars,
+    };
+    for stmt in &stmts {
+        gen.stmt(stmt);
+    }
+    Ok(gen.out)
+}
+
+fn lex(source: &str) -> Result<Vec<(Tok, usize)>, BfError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let err = |message: &str| BfError::Syntax(at, message.to_string());
+        match c {
+            _ if c.is_whitespace() => (),
+            '#' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some((_, c)) =
+                    chars.next_if(|&(_, c)| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    ident.push(c);
+                }
+                tokens.push((Tok::Ident(ident), at));
+            }
+            _ if c.is_ascii_digit() => {
+                let mut number = c.to_string();
+                while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_ascii_digit()) {
+                    number.push(c);
+                }
+                let value = number.parse().map_err(|_| err("number is too large"))?;
+                tokens.push((Tok::Number(value), at));
+            }
+            '\'' | '"' => {
+                let mut bytes = vec![];
+                loop {
+                    let (_, next) = chars.next().ok_or_else(|| err("unterminated literal"))?;
+                    let next = match next {
+                        _ if next == c => break,
+                        '\\' => match chars.next().map(|(_, c)| c) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('0') => '\0',
+                            Some(c @ ('\\' | '\'' | '"')) => c,
+                            _ => return Err(err("unknown escape")),
+                        },
+                        next => next,
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(next.encode_utf8(&mut buf).as_bytes());
+                }
+                match c {
+                    '"' => tokens.push((Tok::Str(bytes), at)),
+                    _ if bytes.len() == 1 => tokens.push((Tok::Number(bytes[0] as isize), at)),
+                    _ => return Err(err("character literals hold a single byte")),
+                }
+            }
+            '=' | '+' | '-' | ';' | '{' | '}' | '(' | ')' => tokens.push((Tok::Sym(c), at)),
+            _ => return Err(err("unexpected character")),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Tok, usize)],
+    pos: usize,
+    end: usize, // Where errors at the end of the source point
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|(tok, _)| tok)
+    }
+
+    fn error(&self, message: String) -> BfError {
+        let at = self.tokens.get(self.pos).map_or(self.end, |&(_, at)| at);
+        BfError::Syntax(at, message)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let tok = self.peek().cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn expect(&mut self, sym: char) -> Result<(), BfError> {
+        match self.peek() {
+            Some(Tok::Sym(c)) if *c == sym => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error(format!("expected `{sym}`"))),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, BfError> {
+        match self.peek() {
+            Some(Tok::Ident(name)) if !is_keyword(name) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("expected a variable name".to_string())),
+        }
+    }
+
+    fn stmt(&mut self) -> Result<Stmt, BfError> {
+        let Some(Tok::Ident(word)) = self.peek().cloned() else {
+            return Err(self.error("expected a statement".to_string()));
+        };
+        let stmt = match word.as_str() {
+            "while" | "if" => {
+                self.pos += 1;
+                let cond = self.expr()?;
+                let body = self.block()?;
+                if word == "while" {
+                    return Ok(Stmt::While(cond, body));
+                }
+                let otherwise = match self.peek() {
+                    Some(Tok::Ident(word)) if word == "else" => {
+                        self.pos += 1;
+                        Some(self.block()?)
+                    }
+                    _ => None,
+                };
+                return Ok(Stmt::If(cond, body, otherwise));
+            }
+            "print" => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(Tok::Str(bytes)) => {
+                        let bytes = bytes.clone();
+                        self.pos += 1;
+                        Stmt::PrintStr(bytes)
+                    }
+                    _ => Stmt::Print(self.expr()?),
+                }
+            }
+            "read" => {
+                self.pos += 1;
+                Stmt::Read(self.ident()?)
+            }
+            _ => {
+                if word == "var" {
+                    self.pos += 1;
+                }
+                let name = self.ident()?;
+                self.expect('=')?;
+                Stmt::Assign(name, self.expr()?)
+            }
+        };
+        self.expect(';')?;
+        Ok(stmt)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, BfError> {
+        self.expect('{')?;
+        let mut stmts = vec![];
+        while self.peek() != Some(&Tok::Sym('}')) {
+            if self.peek().is_none() {
+                return Err(self.error("expected `}`".to_string()));
+            }
+            stmts.push(self.stmt()?);
+        }
+        self.pos += 1;
+        Ok(stmts)
+    }
+
+    fn expr(&mut self) -> Result<Expr, BfError> {
+        let mut expr = self.term()?;
+        loop {
+            match self.peek() {
+                Some(Tok::Sym('+')) => {
+                    self.pos += 1;
+                    expr = Expr::Add(Box::new(expr), Box::new(self.term()?));
+                }
+                Some(Tok::Sym('-')) => {
+                    self.pos += 1;
+                    expr = Expr::Sub(Box::new(expr), Box::new(self.term()?));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, BfError> {
+        match self.peek() {
+            Some(Tok::Sym('(')) => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some(Tok::Number(_)) => match self.next() {
+                Some(Tok::Number(n)) => Ok(Expr::Number(n)),
+                _ => unreachable!(),
+            },
+            Some(Tok::Ident(_)) => {
+                let at = self.tokens[self.pos].1;
+                Ok(Expr::Var(self.ident()?, at))
+            }
+            _ => Err(self.error("expected an expression".to_string())),
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    matches!(word, "var" | "while" | "if" | "else" | "print" | "read")
+}
+
+// Gives every variable a cell, in order of first appearance, failing on a name used before
+// anything declares it
+fn collect_vars(stmts: &[Stmt], vars: &mut HashMap<String, usize>) -> Result<(), BfError> {
+    fn expr_vars(expr: &Expr, vars: &HashMap<String, usize>) -> Result<(), BfError> {
+        match expr {
+            Expr::Number(_) => Ok(()),
+            Expr::Var(name, _) if vars.contains_key(name) => Ok(()),
+            Expr::Var(name, at) => Err(BfError::Syntax(
+                *at,
+                format!("undeclared variable `{name}`"),
+            )),
+            Expr::Add(a, b) | Expr::Sub(a, b) => {
+                expr_vars(a, vars)?;
+                expr_vars(b, vars)
+            }
+        }
+    }
+    fn declare(name: &str, vars: &mut HashMap<String, usize>) {
+        let next = vars.len();
+        vars.entry(name.to_string()).or_insert(next);
+    }
+
+    for stmt in stmts {
+        match stmt {
+            // `x = x + 1` reads `x` before assigning it
+            Stmt::Assign(name, expr) => {
+                expr_vars(expr, vars)?;
+                declare(name, vars);
+            }
+            Stmt::Print(expr) => expr_vars(expr, vars)?,
+            Stmt::PrintStr(_) => (),
+            Stmt::Read(name) => declare(name, vars),
+            Stmt::While(cond, body) => {
+                expr_vars(cond, vars)?;
+                collect_vars(body, vars)?;
+            }
+            Stmt::If(cond, body, otherwise) => {
+                expr_vars(cond, vars)?;
+                collect_vars(body, vars)?;
+                if let Some(otherwise) = otherwise {
+                    collect_vars(otherwise, vars)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Emits tokens, keeping track of where the pointer is so every move is relative
+struct Codegen {
+    out: Vec<BfToken>,
+    pointer: usize,
+    next_temp: usize,
+    vars: HashMap<String, usize>,
+}
+
+impl Codegen {
+    fn goto(&mut self, cell: usize) {
+        if cell != self.pointer {
+            self.out
+                .push(BfToken::MOV(cell as isize - self.pointer as isize));
+            self.pointer = cell;
+        }
+    }
+
+    fn add(&mut self, cell: usize, n: isize) {
+        self.goto(cell);
+        if n != 0 {
+            self.out.push(BfToken::CEL(n));
+        }
+    }
+
+    fn clear(&mut self, cell: usize) {
+        self.goto(cell);
+        self.out
+            .extend([BfToken::JUM, BfToken::CEL(-1), BfToken::BAC]);
+    }
+
+    // Empties `src` into each of `dsts`, adding or subtracting its value
+    fn drain(&mut self, src: usize, dsts: &[(usize, isize)]) {
+        self.goto(src);
+        self.out.push(BfToken::JUM);
+        self.out.push(BfToken::CEL(-1));
+        for &(dst, sign) in dsts {
+            self.add(dst, sign);
+        }
+        self.goto(src);
+        self.out.push(BfToken::BAC);
+    }
+
+    // Temporaries are handed out and released in stack order
+    fn temp(&mut self) -> usize {
+        self.next_temp += 1;
+        self.next_temp - 1
+    }
+
+    fn release(&mut self, cell: usize) {
+        debug_assert_eq!(cell + 1, self.next_temp);
+        self.next_temp -= 1;
+    }
+
+    // Evaluates `expr` into `dst`, which must be zero
+    fn eval(&mut self, expr: &Expr, dst: usize) {
+        match expr {
+            Expr::Number(n) => self.add(dst, *n),
+            Expr::Var(name, _) => {
+                let var = self.vars[name];
+                let tmp = self.temp();
+                self.drain(var, &[(dst, 1), (tmp, 1)]);
+                self.drain(tmp, &[(var, 1)]);
+                self.release(tmp);
+            }
+            Expr::Add(a, b) | Expr::Sub(a, b) => {
+                self.eval(a, dst);
+                let tmp = self.temp();
+                self.eval(b, tmp);
+                let sign = if matches!(expr, Expr::Add(..)) { 1 } else { -1 };
+                self.drain(tmp, &[(dst, sign)]);
+                self.release(tmp);
+            }
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assign(name, expr) => {
+                let var = self.vars[name];
+                let tmp = self.temp();
+                self.eval(expr, tmp);
+                self.clear(var);
+                self.drain(tmp, &[(var, 1)]);
+                self.release(tmp);
+            }
+            Stmt::Print(expr) => {
+                let tmp = self.temp();
+                self.eval(expr, tmp);
+                self.goto(tmp);
+                self.out.push(BfToken::OUT);
+                self.clear(tmp);
+                self.release(tmp);
+            }
+            Stmt::PrintStr(bytes) => {
+                let tmp = self.temp();
+                let mut value = 0;
+                for &byte in bytes {
+                    self.add(tmp, byte as isize - value);
+                    self.out.push(BfToken::OUT);
+                    value = byte as isize;
+                }
+                self.clear(tmp);
+                self.release(tmp);
+            }
+            Stmt::Read(name) => {
+                let var = self.vars[name];
+                self.goto(var);
+                self.out.push(BfToken::ACC);
+            }
+            Stmt::While(cond, body) => {
+                let tmp = self.temp();
+                self.eval(cond, tmp);
+                self.goto(tmp);
+                self.out.push(BfToken::JUM);
+                self.clear(tmp);
+                self.stmts(body);
+                self.eval(cond, tmp);
+                self.goto(tmp);
+                self.out.push(BfToken::BAC);
+                self.release(tmp);
+            }
+            Stmt::If(cond, body, otherwise) => {
+                // `flag` stays set only if the body didn't run
+                let flag = self.temp();
+                let tmp = self.temp();
+                if otherwise.is_some() {
+                    self.add(flag, 1);
+                }
+                self.eval(cond, tmp);
+                self.goto(tmp);
+                self.out.push(BfToken::JUM);
+                self.clear(tmp);
+                if otherwise.is_some() {
+                    self.clear(flag);
+                }
+                self.stmts(body);
+                self.goto(tmp);
+                self.out.push(BfToken::BAC);
+                if let Some(otherwise) = otherwise {
+                    self.goto(flag);
+                    self.out.push(BfToken::JUM);
+                    self.clear(flag);
+                    self.stmts(otherwise);
+                    self.goto(flag);
+                    self.out.push(BfToken::BAC);
+                }
+                self.release(tmp);
+                self.release(flag);
+            }
+        }
+    }
+}
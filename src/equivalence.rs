@@ -0,0 +1,45 @@
+// Cheap semantic equivalence by comparing normal forms of the optimized instructions.
+//
+// Adjacent runs are folded, no-op pairs like `+-` or `><` disappear and clear loops become SET,
+// so rewrites that only add noise, split runs or change comments compare equal. Programs that
+// differ in any other way are reported as different even if they happen to behave the same.
+use crate::error::BfError;
+use crate::passes::clear_loops;
+use crate::program::{Program, Span};
+use crate::token::BfToken;
+
+pub fn normalize(source: &str) -> Result<Vec<BfToken>, BfError> {
+    let program = Program::compile(source, 1)?;
+    let mut tokens: Vec<BfToken> = vec![];
+    for token in program.tokens {
+        match (tokens.last_mut(), token) {
+            (_, BfToken::NAN) => (),
+            (Some(last), next) if last.same_kind(&next) => *last += next,
+            (_, next) => tokens.push(next),
+        }
+        if matches!(tokens.last(), Some(BfToken::CEL(0) | BfToken::MOV(0))) {
+            tokens.pop();
+        }
+    }
+    // A loop that opens the program never runs, whether or not compiling left it out as a
+    // comment, which depends on what the moves before it folded to
+    while tokens.first() == Some(&BfToken::JUM) {
+        let mut depth = 0;
+        let end = tokens.iter().position(|token| {
+            match token {
+                BfToken::JUM => depth += 1,
+                BfToken::BAC => depth -= 1,
+                _ => (),
+            }
+            depth == 0
+        });
+        tokens.drain(..=end.unwrap_or(tokens.len() - 1));
+    }
+    let mut spans = vec![Span { start: 0, end: 0 }; tokens.len()];
+    clear_loops(&mut tokens, &mut spans);
+    Ok(tokens)
+}
+
+pub fn equivalent(a: &str, b: &str) -> Result<bool, BfError> {
+    Ok(normalize(a)? == normalize(b)?)
+}
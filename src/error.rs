@@ -0,0 +1,57 @@
+use std::fmt;
+use std::io;
+
+// Everything that can go wrong while compiling or running a program.
+#[derive(Debug)]
+pub enum BfError {
+    UnopenedBracket(usize), // A `]` with no matching `[`, at this source offset
+    UnclosedBracket(usize), // A `[` with no matching `]`, at this source offset
+    NestingLimit(usize, usize), // A `[` nested deeper than the limit, at this source offset
+    InvalidIr(usize, String), // A line of textual IR that couldn't be assembled, at this offset
+    Syntax(usize, String),  // Source of the mini language that doesn't parse, at this offset
+    StepLimit(u64),         // The program ran for more steps than allowed
+    Unsupported(String),    // A backend can't run this program or these settings
+    Interrupted,            // Execution was stopped from outside, e.g. by Ctrl-C
+    NonTerminating(usize),  // A loop came back around in the same state, at this instruction
+    TapeBounds(isize),      // The pointer moved off a fixed tape, to this cell
+    Checkpoint(String),     // A checkpoint file that can't be read back
+    Io(io::Error),          // Reading input or writing output failed
+}
+
+impl fmt::Display for BfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnopenedBracket(at) => write!(f, "Unopened bracket at {at}"),
+            Self::UnclosedBracket(at) => write!(f, "Unclosed bracket at {at}"),
+            Self::NestingLimit(at, limit) => {
+                write!(f, "Loop at {at} is nested more than {limit} deep")
+            }
+            Self::InvalidIr(at, message) => write!(f, "Invalid IR at {at}: {message}"),
+            Self::Syntax(at, message) => write!(f, "Syntax error at {at}: {message}"),
+            Self::StepLimit(limit) => write!(f, "Step limit of {limit} exceeded"),
+            Self::Unsupported(reason) => write!(f, "Unsupported: {reason}"),
+            Self::Interrupted => write!(f, "Interrupted"),
+            Self::NonTerminating(at) => {
+                write!(f, "Non-terminating loop detected at instruction {at}")
+            }
+            Self::TapeBounds(at) => write!(f, "Pointer moved off the tape to cell {at}"),
+            Self::Checkpoint(reason) => write!(f, "Invalid checkpoint: {reason}"),
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BfError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
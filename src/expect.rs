@@ -0,0 +1,223 @@
+// Scripted interaction with a program that mixes prompts and input reads, for testing it like
+// `expect` would a terminal program.
+//
+// A script is a list of steps separated by newlines or `;`: `expect "TEXT"` runs the program
+// until TEXT shows up in the output written since the previous match, `send "TEXT"` feeds it
+// TEXT as input and `eof` closes the input. `#` starts a comment. Strings take the escapes
+// `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\xHH`.
+//
+//     expect "Name?"; send "Bob\n"
+//     expect "Hello Bob"
+//
+// Once the script is done the input is closed and the program must run to the end.
+use std::fmt;
+
+use crate::error::BfError;
+use crate::machine::{Machine, RunState};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Expect(Vec<u8>),
+    Send(Vec<u8>),
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub actions: Vec<Action>,
+}
+
+// Why a script didn't pass
+#[derive(Debug)]
+pub enum Failure {
+    // The program stopped producing output before the text of the expect at `step` showed
+    // up, either finishing or waiting for input the script doesn't give yet
+    Mismatch {
+        step: usize,
+        expected: Vec<u8>,
+        output: Vec<u8>, // What was written since the previous match
+        finished: bool,
+    },
+    Error(BfError), // The program itself failed
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mismatch {
+                step,
+                expected,
+                output,
+                finished,
+            } => {
+                let stopped = match finished {
+                    true => "the program finished",
+                    false => "the program waited for input",
+                };
+                write!(
+                    f,
+                    "step {}: expected {:?} but {stopped}, having written {:?}",
+                    step + 1,
+                    String::from_utf8_lossy(expected),
+                    String::from_utf8_lossy(output)
+                )
+            }
+            Self::Error(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Self, BfError> {
+        let bytes = text.as_bytes();
+        let mut actions = vec![];
+        let mut at = 0;
+        loop {
+            while let Some(b' ' | b'\t' | b'\r' | b'\n' | b';') = bytes.get(at) {
+                at += 1;
+            }
+            match bytes.get(at) {
+                None => break,
+                Some(b'#') => {
+                    while !matches!(bytes.get(at), None | Some(b'\n')) {
+                        at += 1;
+                    }
+                    continue;
+                }
+                Some(_) => (),
+            }
+            let start = at;
+            while bytes.get(at).is_some_and(u8::is_ascii_alphabetic) {
+                at += 1;
+            }
+            let command = &text[start..at];
+            let action = match command {
+                "eof" => Action::Eof,
+                "expect" | "send" => {
+                    while let Some(b' ' | b'\t') = bytes.get(at) {
+                        at += 1;
+                    }
+                    let string = string(bytes, &mut at)?;
+                    match command {
+                        "expect" if string.is_empty() => {
+                            return Err(BfError::Syntax(start, "empty expect".to_string()))
+                        }
+                        "expect" => Action::Expect(string),
+                        _ => Action::Send(string),
+                    }
+                }
+                _ => {
+                    let message = "expected `expect`, `send` or `eof`".to_string();
+                    return Err(BfError::Syntax(start, message));
+                }
+            };
+            actions.push(action);
+            // Steps share a line only when separated by `;`
+            while let Some(b' ' | b'\t' | b'\r') = bytes.get(at) {
+                at += 1;
+            }
+            if !matches!(bytes.get(at), None | Some(b'\n' | b';' | b'#')) {
+                let message = "expected a new line or `;`".to_string();
+                return Err(BfError::Syntax(at, message));
+            }
+        }
+        Ok(Self { actions })
+    }
+
+    // Drives `machine` through the script, its step limit bounding how long an expect waits
+    pub fn run(&self, machine: &mut Machine) -> Result<(), Failure> {
+        let mut output = vec![];
+        for (step, action) in self.actions.iter().enumerate() {
+            match action {
+                Action::Send(bytes) => machine.feed(bytes),
+                Action::Eof => machine.close_input(),
+                Action::Expect(expected) => loop {
+                    if let Some(at) = find(&output, expected) {
+                        output.drain(..at + expected.len());
+                        break;
+                    }
+                    let state = machine.run_for(CHUNK);
+                    output.extend(machine.take_output());
+                    match state {
+                        RunState::Paused => (),
+                        RunState::Error(err) => return Err(Failure::Error(err)),
+                        // Nothing more comes out before the script does something
+                        RunState::Finished | RunState::NeedsInput
+                            if find(&output, expected).is_none() =>
+                        {
+                            return Err(Failure::Mismatch {
+                                step,
+                                expected: expected.clone(),
+                                output,
+                                finished: matches!(state, RunState::Finished),
+                            });
+                        }
+                        RunState::Finished | RunState::NeedsInput => (),
+                    }
+                },
+            }
+        }
+        machine.close_input();
+        loop {
+            match machine.run_for(CHUNK) {
+                RunState::Finished => return Ok(()),
+                RunState::Error(err) => return Err(Failure::Error(err)),
+                RunState::Paused | RunState::NeedsInput => machine.take_output(),
+            };
+        }
+    }
+}
+
+// Instructions run between looks at the output
+const CHUNK: u64 = 10_000;
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// A double-quoted string starting at `at`, leaving `at` after its closing quote
+fn string(bytes: &[u8], at: &mut usize) -> Result<Vec<u8>, BfError> {
+    let err = |at: usize, message: &str| BfError::Syntax(at, message.to_string());
+    if bytes.get(*at) != Some(&b'"') {
+        return Err(err(*at, "expected a string in double quotes"));
+    }
+    *at += 1;
+    let mut out = vec![];
+    loop {
+        let byte = match bytes.get(*at) {
+            None | Some(b'\n') => return Err(err(*at, "unterminated string")),
+            Some(b'"') => {
+                *at += 1;
+                return Ok(out);
+            }
+            Some(b'\\') => {
+                *at += 1;
+                match bytes.get(*at) {
+                    Some(b'n') => b'\n',
+                    Some(b't') => b'\t',
+                    Some(b'r') => b'\r',
+                    Some(b'0') => 0,
+                    Some(b'\\') => b'\\',
+                    Some(b'"') => b'"',
+                    Some(b'x') => {
+                        let byte = bytes
+                            .get(*at + 1..*at + 3)
+                            .and_then(|hex| std::str::from_utf8(hex).ok())
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                            .ok_or_else(|| err(*at, "expected two hex digits after `\\x`"))?;
+                        *at += 2;
+                        byte
+                    }
+                    _ => return Err(err(*at, "unknown escape")),
+                }
+            }
+            Some(&byte) => byte,
+        };
+        out.push(byte);
+        *at += 1;
+    }
+}
@@ -0,0 +1,141 @@
+// Commands beyond the standard eight, each one off unless asked for by name.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::token::BfToken;
+
+// Every extension with the name it is enabled by.
+const NAMES: &[&str] = &["random", "fork", "numbers"];
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Extensions {
+    pub random: bool,  // `?` stores a pseudo-random byte in the current cell
+    pub fork: bool,    // `Y` splits the program into two threads, see `Machine::fork`
+    pub numbers: bool, // `:` writes the current cell in decimal and `;` reads a number, see `Number`
+    pub opcodes: u128, // One bit per ASCII character registered as a host opcode, see `opcode::Opcodes`
+}
+
+impl Extensions {
+    // The token for an extension command, if `c` is one and it is enabled
+    pub fn token(self, c: char) -> Option<BfToken> {
+        match c {
+            '?' if self.random => Some(BfToken::RND),
+            'Y' if self.fork => Some(BfToken::FRK),
+            ':' if self.numbers => Some(BfToken::DEC),
+            ';' if self.numbers => Some(BfToken::NUM),
+            c if c.is_ascii() && self.opcodes & 1 << c as u32 != 0 => Some(BfToken::EXT(c as u8)),
+            _ => None,
+        }
+    }
+
+    // The characters that are commands rather than comments
+    pub fn commands(self) -> String {
+        let mut commands = "+-<>[].,".to_string();
+        if self.random {
+            commands.push('?');
+        }
+        if self.fork {
+            commands.push('Y');
+        }
+        if self.numbers {
+            commands.push_str(":;");
+        }
+        commands.extend(
+            (0..128u8)
+                .filter(|&c| self.opcodes & 1 << c != 0)
+                .map(char::from),
+        );
+        commands
+    }
+
+    fn flag(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "random" => Some(&mut self.random),
+            "fork" => Some(&mut self.fork),
+            "numbers" => Some(&mut self.numbers),
+            _ => None,
+        }
+    }
+
+    fn enabled(self) -> Vec<&'static str> {
+        let flags = [self.random, self.fork, self.numbers];
+        NAMES
+            .iter()
+            .zip(flags)
+            .filter_map(|(&name, on)| on.then_some(name))
+            .collect()
+    }
+}
+
+// A comma separated list of names, or `none`
+impl FromStr for Extensions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut extensions = Self::default();
+        for name in s.split(',').map(str::trim).filter(|name| *name != "none") {
+            let flag = extensions.flag(name).ok_or_else(|| {
+                format!(
+                    "Unknown extension {name:?}, expected none or {}",
+                    NAMES.join(", ")
+                )
+            })?;
+            *flag = true;
+        }
+        Ok(extensions)
+    }
+}
+
+impl fmt::Display for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.enabled()[..] {
+            [] => write!(f, "none"),
+            ref names => write!(f, "{}", names.join(",")),
+        }
+    }
+}
+
+// A number being read by `;`. Leading whitespace is skipped, then an optional sign and the
+// digits are read up to the first byte that can't continue them, which is consumed too, so
+// a number on a line of its own takes its newline with it. The value wraps to the cell size
+// like `+` would, and is 0 if no digits came before that byte. EOF before anything but
+// whitespace is EOF as `,` sees it.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Number {
+    pub(crate) value: u64,
+    pub(crate) negative: bool,
+    pub(crate) started: bool, // A sign or digit was read
+}
+
+impl Number {
+    // Takes the next byte or EOF, giving the number once it has ended, `None` inside
+    // `Some` meaning EOF
+    pub fn push(&mut self, byte: Option<u8>) -> Option<Option<u64>> {
+        match byte {
+            None if !self.started => Some(None),
+            None => Some(Some(self.value())),
+            Some(byte) if byte.is_ascii_whitespace() && !self.started => None,
+            Some(b'-' | b'+') if !self.started => {
+                self.negative = byte == Some(b'-');
+                self.started = true;
+                None
+            }
+            Some(byte @ b'0'..=b'9') => {
+                self.value = self
+                    .value
+                    .wrapping_mul(10)
+                    .wrapping_add((byte - b'0') as u64);
+                self.started = true;
+                None
+            }
+            Some(_) => Some(Some(self.value())),
+        }
+    }
+
+    fn value(&self) -> u64 {
+        match self.negative {
+            true => self.value.wrapping_neg(),
+            false => self.value,
+        }
+    }
+}
@@ -0,0 +1,303 @@
+// Fuzzing a program through its input: many runs, each fed bytes that are random or
+// generated from a grammar and then EOF, keeping the inputs that made a run fail, hit one of
+// the `Limits` or take far more steps than the median.
+//
+// A grammar is a text file of rules like `line = word "\n" | word " " line`, generation
+// starting from the first. Each alternative is a sequence of quoted strings, which may use
+// `\n`, `\r`, `\t`, `\\`, `\"` and `\xHH`, and names of rules; `#` starts a comment. Past
+// `DEPTH` nested rules each rule takes its alternative with the fewest rules in it, so
+// recursive grammars still come to an end.
+use std::collections::HashSet;
+
+use crate::judge::{run_within, Ending, Limits};
+use crate::program::Program;
+use crate::rng::Rng;
+use crate::settings::Settings;
+
+const DEPTH: usize = 32;
+// Rules expanded for one input before giving up on the rest, for grammars that never end
+const EXPANSIONS: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Symbol {
+    Bytes(Vec<u8>),
+    Rule(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grammar {
+    rules: Vec<Vec<Vec<Symbol>>>, // Each rule's alternatives, the first rule being the start
+}
+
+impl Grammar {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut names: Vec<&str> = vec![];
+        let mut bodies = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let wrong = |message: &str| format!("line {}: {message}", number + 1);
+            let (name, body) = line
+                .split_once('=')
+                .ok_or_else(|| wrong("expected `NAME = ALTERNATIVES`"))?;
+            let name = name.trim();
+            if !is_name(name) {
+                return Err(wrong("rule names are letters, digits and `_`"));
+            }
+            if names.contains(&name) {
+                return Err(wrong(&format!("`{name}` is defined twice")));
+            }
+            names.push(name);
+            bodies.push((number + 1, body));
+        }
+        if names.is_empty() {
+            return Err("the grammar has no rules".to_string());
+        }
+        let mut rules = vec![];
+        for (line, body) in bodies {
+            let alternatives =
+                alternatives(body, &names).map_err(|message| format!("line {line}: {message}"))?;
+            rules.push(alternatives);
+        }
+        Ok(Self { rules })
+    }
+
+    // An input of at most `max_len` bytes
+    pub fn generate(&self, rng: &mut Rng, max_len: usize) -> Vec<u8> {
+        let mut out = vec![];
+        let mut pending = vec![(Symbol::Rule(0), 0)];
+        let mut expansions = 0;
+        while let Some((symbol, depth)) = pending.pop() {
+            match symbol {
+                Symbol::Bytes(bytes) => out.extend(bytes),
+                Symbol::Rule(_) if expansions == EXPANSIONS => break,
+                Symbol::Rule(rule) => {
+                    expansions += 1;
+                    let alternatives = &self.rules[rule];
+                    let chosen = match depth < DEPTH {
+                        true => &alternatives[rng.below(alternatives.len())],
+                        false => alternatives
+                            .iter()
+                            .min_by_key(|symbols| {
+                                symbols
+                                    .iter()
+                                    .filter(|symbol| matches!(symbol, Symbol::Rule(_)))
+                                    .count()
+                            })
+                            .unwrap(),
+                    };
+                    pending.extend(
+                        chosen
+                            .iter()
+                            .rev()
+                            .map(|symbol| (symbol.clone(), depth + 1)),
+                    );
+                }
+            }
+            if out.len() >= max_len {
+                break;
+            }
+        }
+        out.truncate(max_len);
+        out
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn alternatives(body: &str, names: &[&str]) -> Result<Vec<Vec<Symbol>>, String> {
+    let mut alternatives = vec![vec![]];
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => (),
+            '#' => break,
+            '|' => alternatives.push(vec![]),
+            '"' => {
+                let mut bytes = vec![];
+                loop {
+                    let c = chars.next().ok_or("unterminated string")?;
+                    match c {
+                        '"' => break,
+                        '\\' => bytes.push(match chars.next().ok_or("unterminated string")? {
+                            'n' => b'\n',
+                            'r' => b'\r',
+                            't' => b'\t',
+                            '\\' => b'\\',
+                            '"' => b'"',
+                            'x' => {
+                                let hex: String = chars.by_ref().take(2).collect();
+                                u8::from_str_radix(&hex, 16)
+                                    .map_err(|_| format!("`\\x{hex}` isn't a byte"))?
+                            }
+                            other => return Err(format!("unknown escape `\\{other}`")),
+                        }),
+                        c => bytes.extend(c.to_string().as_bytes()),
+                    }
+                }
+                alternatives.last_mut().unwrap().push(Symbol::Bytes(bytes));
+            }
+            _ => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+                {
+                    name.push(c);
+                    chars.next();
+                }
+                let rule = names
+                    .iter()
+                    .position(|known| *known == name)
+                    .ok_or_else(|| format!("no rule named `{name}`"))?;
+                alternatives.last_mut().unwrap().push(Symbol::Rule(rule));
+            }
+        }
+    }
+    Ok(alternatives)
+}
+
+// Where inputs come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inputs {
+    // Any length up to the maximum, mostly printable ASCII and newlines with some of every
+    // other byte, which is what interactive programs get and what they forget to expect
+    Random,
+    Grammar(Grammar),
+}
+
+impl Inputs {
+    pub fn generate(&self, rng: &mut Rng, max_len: usize) -> Vec<u8> {
+        match self {
+            Self::Random => {
+                let len = rng.below(max_len + 1);
+                (0..len)
+                    .map(|_| match rng.below(8) {
+                        0 => rng.byte(),
+                        1 => b'\n',
+                        _ => b' ' + rng.below(95) as u8,
+                    })
+                    .collect()
+            }
+            Self::Grammar(grammar) => grammar.generate(rng, max_len),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub runs: usize,
+    pub seed: u64,
+    pub max_len: usize, // Bytes in any one input
+    pub limits: Limits,
+    pub slow: u64, // How many times the median steps make a finished run worth keeping
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            runs: 1000,
+            seed: 0,
+            max_len: 64,
+            limits: Limits {
+                max_steps: 1_000_000,
+                ..Limits::default()
+            },
+            slow: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    StepLimit,
+    TimeLimit,
+    OutputLimit,
+    Error(String),
+    Slow,
+}
+
+impl Kind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::StepLimit => "step limit",
+            Self::TimeLimit => "time limit",
+            Self::OutputLimit => "output limit",
+            Self::Error(_) => "error",
+            Self::Slow => "slow",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub run: usize,
+    pub input: Vec<u8>,
+    pub kind: Kind,
+    pub steps: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub runs: usize, // Inputs generated more than once only run the first time
+    pub finished: usize,
+    pub median: u64,            // Of the runs that finished
+    pub findings: Vec<Finding>, // In the order of the runs
+}
+
+// The same seed and options give the same inputs, and apart from time limits the same report
+pub fn fuzz(program: &Program, settings: &Settings, inputs: &Inputs, options: &Options) -> Report {
+    let mut rng = Rng::new(options.seed);
+    let mut findings = vec![];
+    let mut finished = vec![];
+    let mut seen = HashSet::new();
+    for run in 0..options.runs {
+        let input = inputs.generate(&mut rng, options.max_len);
+        if !seen.insert(input.clone()) {
+            continue;
+        }
+        let (ending, _, steps) = run_within(program, settings, &input, options.limits);
+        let kind = match ending {
+            Ending::Finished(_) => {
+                finished.push((run, input, steps));
+                continue;
+            }
+            Ending::StepLimit => Kind::StepLimit,
+            Ending::TimeLimit => Kind::TimeLimit,
+            Ending::OutputLimit => Kind::OutputLimit,
+            Ending::Error(err) => Kind::Error(err.to_string()),
+        };
+        findings.push(Finding {
+            run,
+            input,
+            kind,
+            steps,
+        });
+    }
+
+    let mut steps: Vec<u64> = finished.iter().map(|&(_, _, steps)| steps).collect();
+    steps.sort_unstable();
+    let median = steps.get(steps.len() / 2).copied().unwrap_or(0);
+    let count = finished.len();
+    for (run, input, steps) in finished {
+        if steps > median.max(1).saturating_mul(options.slow) {
+            findings.push(Finding {
+                run,
+                input,
+                kind: Kind::Slow,
+                steps,
+            });
+        }
+    }
+    findings.sort_by_key(|finding| finding.run);
+    Report {
+        runs: seen.len(),
+        finished: count,
+        median,
+        findings,
+    }
+}
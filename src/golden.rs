@@ -0,0 +1,184 @@
+// Golden runs: a hash chain over the machine states a run goes through, recorded once and
+// checked against later runs, so a change to the optimizer or the interpreter that leaves a
+// different tape behind is caught even when the output still matches.
+//
+// Steps can't line a run up with one recorded before, as optimizing changes how many a
+// program takes. Input and output can: every level reads and writes the same bytes in the
+// same order, so a state is taken after every `every`-th `,`, `.`, `;` or `:` and once the
+// program halts. A state is the pointer and the tape between its first and last nonzero cell,
+// with the bytes read and written so far, and each link of the chain hashes a state with the
+// link before it. `-O3` stores values it worked out ahead of time where the program first
+// needs them, so its tape between I/O can differ from the lower levels', it only matches
+// runs at `-O3`.
+use std::fmt::Write;
+
+use crate::machine::Machine;
+
+const HEADER: &str = "bf-rust golden 1";
+
+// FNV-1a, 64-bit
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0100_0000_01b3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub hash: u64,  // Of this state and every one before it
+    pub io: u64,    // Input and output instructions run by then
+    pub steps: u64, // Only for reports, it differs between optimization levels
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Golden {
+    every: u64,
+    io: u64,
+    links: Vec<Link>,
+}
+
+// Where a run stopped following a golden one, `None` for the run that ended first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    pub state: usize,
+    pub expected: Option<Link>,
+    pub found: Option<Link>,
+}
+
+impl Golden {
+    pub fn new(every: u64) -> Self {
+        Self {
+            every: every.max(1),
+            io: 0,
+            links: vec![],
+        }
+    }
+
+    pub fn every(&self) -> u64 {
+        self.every
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    // After an input or output instruction of `machine` completes
+    pub fn observe(&mut self, machine: &Machine) {
+        self.io += 1;
+        if self.io.is_multiple_of(self.every) {
+            self.link(machine);
+        }
+    }
+
+    // Once `machine` has halted
+    pub fn finish(&mut self, machine: &Machine) {
+        self.link(machine);
+    }
+
+    fn link(&mut self, machine: &Machine) {
+        // Read by the cells holding something, so a sparse tape is never gathered whole
+        let nonzero = machine.memory().nonzero();
+        let first = nonzero.first().map_or(machine.pointer(), |&(at, _)| at);
+        let end = nonzero.last().map_or(first, |&(last, _)| last + 1);
+        let usage = machine.usage();
+        let mut hash = self.links.last().map_or(OFFSET_BASIS, |link| link.hash);
+        let pointer = machine.pointer() as i64 - first as i64;
+        let state = [
+            pointer as u64,
+            usage.read,
+            usage.written,
+            (end - first) as u64,
+        ];
+        for word in state {
+            hash = mix(hash, word);
+        }
+        let mut next = first;
+        for (at, cell) in nonzero {
+            hash = mix(zeros(hash, (at - next) as u64), cell as u64);
+            next = at + 1;
+        }
+        self.links.push(Link {
+            hash,
+            io: self.io,
+            steps: machine.steps(),
+        });
+    }
+
+    // The first state where `self`, a fresh run, differs from `golden`
+    pub fn compare(&self, golden: &Golden) -> Option<Divergence> {
+        let len = self.links.len().max(golden.links.len());
+        (0..len).find_map(|state| {
+            let (found, expected) = (self.links.get(state), golden.links.get(state));
+            match found.map(|link| link.hash) == expected.map(|link| link.hash) {
+                true => None,
+                false => Some(Divergence {
+                    state,
+                    expected: expected.copied(),
+                    found: found.copied(),
+                }),
+            }
+        })
+    }
+
+    // A header, then a line per state with its hash in hex, I/O count and steps
+    pub fn to_text(&self) -> String {
+        let mut out = format!("{HEADER}\nevery {}\n", self.every);
+        for link in &self.links {
+            let _ = writeln!(out, "{:016x} {} {}", link.hash, link.io, link.steps);
+        }
+        out
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("not a golden run, it doesn't start with the header".to_string());
+        }
+        let every = lines
+            .next()
+            .and_then(|line| line.strip_prefix("every "))
+            .and_then(|every| every.parse().ok())
+            .ok_or("missing how often states were taken")?;
+        let mut golden = Self::new(every);
+        for (number, line) in lines.enumerate() {
+            let invalid = || format!("invalid state on line {}", number + 3);
+            let mut fields = line.split(' ');
+            let mut field = |radix| {
+                fields
+                    .next()
+                    .and_then(|field| u64::from_str_radix(field, radix).ok())
+                    .ok_or_else(invalid)
+            };
+            let link = Link {
+                hash: field(16)?,
+                io: field(10)?,
+                steps: field(10)?,
+            };
+            if fields.next().is_some() {
+                return Err(invalid());
+            }
+            golden.io = link.io;
+            golden.links.push(link);
+        }
+        Ok(golden)
+    }
+}
+
+// `hash` with the bytes of `word` mixed in
+fn mix(mut hash: u64, word: u64) -> u64 {
+    for byte in word.to_le_bytes() {
+        hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+    }
+    hash
+}
+
+// `hash` with `count` zero words mixed in, all at once: a zero byte only multiplies by the
+// prime, so the cells between those holding something cost nothing to hash however many
+fn zeros(hash: u64, count: u64) -> u64 {
+    let (mut factor, mut base, mut exp) = (1u64, PRIME, count.wrapping_mul(8));
+    while exp > 0 {
+        if exp & 1 == 1 {
+            factor = factor.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+    hash.wrapping_mul(factor)
+}
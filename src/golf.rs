@@ -0,0 +1,157 @@
+// Shortens programs by replacing long `+`/`-` runs with multiplication loops.
+//
+// `>a[<b>-]<c` adds `a * b + c` to the current cell and leaves the cell to the right as it found
+// it, as long as that cell was zero to begin with. The golfer tracks which cells are known to be
+// zero (everything at the start, less whatever gets touched) and only rewrites runs next to one.
+// A zero cell to the left only serves while the pointer is known to be past the first cell, so
+// golfed programs never reach left of where a fixed tape starts.
+// Arithmetic is modulo 256, so the result assumes 8-bit wrapping cells.
+use std::collections::HashMap;
+
+use crate::error::BfError;
+use crate::program::Program;
+use crate::token::BfToken;
+
+const MAX_FACTOR: usize = 24;
+const MAX_REST: isize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Golfed {
+    pub code: String,
+    pub original: usize, // Characters in the original source
+    pub minified: usize, // Characters left after dropping comments
+    pub rewrites: usize, // Runs replaced with a multiplication loop
+}
+
+// The shortest way found to add `delta` to a cell, using `right`/`left` to reach a zero temp cell
+pub fn shortest_add(delta: u8, right: char, left: char) -> String {
+    let plain = match delta {
+        0..=128 => "+".repeat(delta as usize),
+        _ => "-".repeat(256 - delta as usize),
+    };
+    let mut best = plain;
+    for a in 2..=MAX_FACTOR {
+        for b in 1..=MAX_FACTOR {
+            for sign in [1isize, -1] {
+                let product = (a * b) as isize * sign;
+                let rest = (delta as isize - product).rem_euclid(256);
+                let rest = if rest > 128 { rest - 256 } else { rest };
+                if rest.abs() > MAX_REST {
+                    continue;
+                }
+                let len = 7 + a + b + rest.unsigned_abs();
+                if len >= best.len() {
+                    continue;
+                }
+                let body = if sign > 0 { "+" } else { "-" };
+                let fix = if rest > 0 { "+" } else { "-" };
+                best = format!(
+                    "{right}{}[{left}{}{right}-]{left}{}",
+                    "+".repeat(a),
+                    body.repeat(b),
+                    fix.repeat(rest.unsigned_abs())
+                );
+            }
+        }
+    }
+    best
+}
+
+// Statically known cell values, relative to where tracking last restarted
+struct Cells {
+    values: HashMap<isize, Option<u8>>,
+    untouched_zero: bool, // Cells missing from `values` still hold their initial zero
+}
+
+impl Cells {
+    fn get(&self, at: isize) -> Option<u8> {
+        match self.values.get(&at) {
+            Some(&value) => value,
+            None if self.untouched_zero => Some(0),
+            None => None,
+        }
+    }
+
+    fn set(&mut self, at: isize, value: Option<u8>) {
+        self.values.insert(at, value);
+    }
+
+    fn forget(&mut self) {
+        self.values.clear();
+        self.untouched_zero = false;
+    }
+}
+
+pub fn golf(source: &str) -> Result<Golfed, BfError> {
+    let program = Program::compile(source, 1)?;
+    let mut cells = Cells {
+        values: HashMap::new(),
+        untouched_zero: true,
+    };
+    let mut offset = 0isize;
+    // The cell `offset` 0 is, counted from the first, while it's known
+    let mut origin = Some(0isize);
+
+    let mut code = String::new();
+    let mut rewrites = 0;
+    for &token in &program.tokens {
+        match token {
+            BfToken::CEL(n) => {
+                let delta = n.rem_euclid(256) as u8;
+                let plain = String::from(BfToken::CEL(n));
+                let rewrite = if cells.get(offset + 1) == Some(0) {
+                    Some(shortest_add(delta, '>', '<'))
+                } else if cells.get(offset - 1) == Some(0)
+                    && origin.is_some_and(|origin| origin + offset > 0)
+                {
+                    Some(shortest_add(delta, '<', '>'))
+                } else {
+                    None
+                };
+                match rewrite.filter(|rewrite| rewrite.len() < plain.len()) {
+                    Some(rewrite) => {
+                        code.push_str(&rewrite);
+                        rewrites += 1;
+                    }
+                    None => code.push_str(&plain),
+                }
+                cells.set(offset, cells.get(offset).map(|v| v.wrapping_add(delta)));
+            }
+            BfToken::MOV(n) => {
+                offset += n;
+                code.push_str(&String::from(token));
+            }
+            BfToken::ACC | BfToken::NUM | BfToken::RND | BfToken::EXT(_) => {
+                cells.set(offset, None);
+                code.push_str(&String::from(token));
+            }
+            BfToken::FRK => {
+                // The two threads carry on with different cells
+                cells.forget();
+                origin = None;
+                code.push_str(&String::from(token));
+            }
+            BfToken::JUM => {
+                // Nothing is known inside a loop
+                cells.forget();
+                code.push('[');
+            }
+            BfToken::BAC => {
+                // But a loop only ever exits on a zero cell
+                cells.forget();
+                offset = 0;
+                origin = None;
+                cells.set(0, Some(0));
+                code.push(']');
+            }
+            _ => code.push_str(&String::from(token)),
+        }
+    }
+
+    Ok(Golfed {
+        original: source.chars().count(),
+        minified: program.tokens.iter().map(|&t| String::from(t).len()).sum(),
+        rewrites,
+        code,
+    })
+}
@@ -0,0 +1,211 @@
+// A standalone HTML page for sharing a run: the source, a timeline to scrub through, the tape
+// around the pointer and the output written so far at each point.
+//
+// Everything comes from the trace of the run, replayed from an all-zero tape, so the page
+// needs nothing but the events. Long runs are sampled down to `SNAPSHOTS` points.
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::program::Program;
+use crate::token::BfToken;
+use crate::trace::Event;
+
+const SNAPSHOTS: usize = 500;
+// Cells shown either side of the pointer
+const WINDOW: isize = 12;
+
+pub fn run_report(name: &str, source: &str, program: &Program, events: &[Event]) -> String {
+    let every = events.len().div_ceil(SNAPSHOTS).max(1);
+    let mut tape: HashMap<isize, u32> = HashMap::new();
+    let mut output = String::new();
+    let mut written = 0;
+    let mut snapshots = vec![snapshot(0, None, 0, &tape, 0)];
+    for (idx, event) in events.iter().enumerate() {
+        if let Some(value) = event.write {
+            tape.insert(event.pointer, value);
+        }
+        if program.tokens[event.ip] == BfToken::OUT {
+            // One character per byte, so the page can count output by bytes
+            let byte = tape.get(&event.pointer).copied().unwrap_or(0) as u8;
+            output.push(byte as char);
+            written += 1;
+        }
+        if (idx + 1) % every == 0 || idx + 1 == events.len() {
+            snapshots.push(snapshot(
+                idx + 1,
+                Some(event.ip),
+                event.pointer,
+                &tape,
+                written,
+            ));
+        }
+    }
+    // The page slices the source by characters, not bytes
+    let mut chars = vec![0; source.len() + 1];
+    for (count, (at, c)) in source.char_indices().enumerate() {
+        chars[at..at + c.len_utf8()].fill(count);
+        chars[at + c.len_utf8()] = count + 1;
+    }
+    let spans: Vec<String> = program
+        .spans
+        .iter()
+        .map(|span| format!("[{},{}]", chars[span.start], chars[span.end]))
+        .collect();
+    let data = format!(
+        "{{\"name\":{},\"source\":{},\"spans\":[{}],\"steps\":{},\"window\":{WINDOW},\"output\":{},\"snapshots\":[{}]}}",
+        js_string(name),
+        js_string(source),
+        spans.join(","),
+        events.len(),
+        js_string(&output),
+        snapshots.join(",")
+    );
+    PAGE.replace("{{TITLE}}", &html_escape(name))
+        .replace("{{DATA}}", &data)
+}
+
+// The state after `step` events as a JSON object, `ip` being the instruction that just ran
+fn snapshot(
+    step: usize,
+    ip: Option<usize>,
+    pointer: isize,
+    tape: &HashMap<isize, u32>,
+    written: usize,
+) -> String {
+    let cells: Vec<String> = (pointer - WINDOW..=pointer + WINDOW)
+        .map(|at| tape.get(&at).copied().unwrap_or(0).to_string())
+        .collect();
+    let ip = ip.map_or("null".to_string(), |ip| ip.to_string());
+    format!(
+        "{{\"step\":{step},\"ip\":{ip},\"pointer\":{pointer},\"cells\":[{}],\"out\":{written}}}",
+        cells.join(",")
+    )
+}
+
+// A JSON string literal that is also safe inside a `<script>` element
+fn js_string(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '<' => out.push_str("\\u003c"),
+            c if (c as u32) < 0x20 || c == '\u{2028}' || c == '\u{2029}' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{TITLE}} - bf-rust run report</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 1.5em; color: #222; }
+pre { background: #f6f6f6; padding: .8em; overflow: auto; max-height: 22em; white-space: pre-wrap; word-break: break-all; }
+#controls { display: flex; gap: .8em; align-items: center; margin: 1em 0; }
+#scrub { flex: 1; }
+mark { background: #ffd54f; }
+.later { color: #bbb; }
+table { border-collapse: collapse; font-family: monospace; }
+td { border: 1px solid #ccc; padding: .2em .5em; text-align: right; min-width: 2.5em; }
+td.here { background: #ffd54f; font-weight: bold; }
+tr.offsets td { border: none; color: #888; font-size: .8em; }
+</style>
+</head>
+<body>
+<h1>{{TITLE}}</h1>
+<p id="summary"></p>
+<div id="controls">
+<button id="play">Play</button>
+<input id="scrub" type="range" min="0" value="0">
+<span id="where"></span>
+</div>
+<h2>Source</h2>
+<pre id="source"></pre>
+<h2>Tape</h2>
+<table><tr class="offsets" id="offsets"></tr><tr id="cells"></tr></table>
+<h2>Output</h2>
+<pre id="output"></pre>
+<script>
+const data = {{DATA}};
+const $ = (id) => document.getElementById(id);
+const text = (s) => document.createTextNode(s);
+const source = [...data.source];
+const scrub = $("scrub");
+scrub.max = data.snapshots.length - 1;
+$("summary").textContent = `${data.steps} steps, ${data.output.length} bytes of output, ${data.snapshots.length} snapshots`;
+
+function show(idx) {
+  const snap = data.snapshots[idx];
+  $("where").textContent = snap.ip === null
+    ? "before the first step"
+    : `step ${snap.step}, instruction ${snap.ip}, pointer at ${snap.pointer}`;
+
+  const src = $("source");
+  src.replaceChildren();
+  if (snap.ip === null) {
+    src.append(text(data.source));
+  } else {
+    const [start, end] = data.spans[snap.ip];
+    const mark = document.createElement("mark");
+    mark.textContent = source.slice(start, end).join("");
+    src.append(text(source.slice(0, start).join("")), mark, text(source.slice(end).join("")));
+    mark.scrollIntoView({ block: "nearest" });
+  }
+
+  const offsets = $("offsets"), cells = $("cells");
+  offsets.replaceChildren();
+  cells.replaceChildren();
+  snap.cells.forEach((value, i) => {
+    const at = snap.pointer - data.window + i;
+    const label = document.createElement("td");
+    label.textContent = at;
+    offsets.append(label);
+    const cell = document.createElement("td");
+    cell.textContent = value;
+    if (at === snap.pointer) cell.className = "here";
+    cells.append(cell);
+  });
+
+  const later = document.createElement("span");
+  later.className = "later";
+  later.textContent = data.output.slice(snap.out);
+  $("output").replaceChildren(text(data.output.slice(0, snap.out)), later);
+}
+
+let timer = null;
+$("play").onclick = () => {
+  if (timer) {
+    clearInterval(timer);
+    timer = null;
+    $("play").textContent = "Play";
+    return;
+  }
+  if (+scrub.value === +scrub.max) scrub.value = 0;
+  $("play").textContent = "Pause";
+  timer = setInterval(() => {
+    if (+scrub.value >= +scrub.max) return $("play").onclick();
+    scrub.value = +scrub.value + 1;
+    show(+scrub.value);
+  }, 100);
+};
+scrub.oninput = () => show(+scrub.value);
+show(0);
+</script>
+</body>
+</html>
+"#;
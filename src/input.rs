@@ -0,0 +1,125 @@
+// Ways of feeding binary data to a program: several bytes to each `,` as one cell value,
+// and input that starts over from the beginning once it runs out.
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl FromStr for Endian {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "little" | "le" => Ok(Self::Little),
+            "big" | "be" => Ok(Self::Big),
+            _ => Err(format!("Invalid endianness {s:?}, expected little or big")),
+        }
+    }
+}
+
+impl fmt::Display for Endian {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Little => write!(f, "little"),
+            Self::Big => write!(f, "big"),
+        }
+    }
+}
+
+// How many bytes of input one `,` reads, and in which order they make up the cell value
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Word {
+    pub bytes: usize, // 1, 2 or 4
+    pub endian: Endian,
+}
+
+impl Default for Word {
+    fn default() -> Self {
+        Self {
+            bytes: 1,
+            endian: Endian::Little,
+        }
+    }
+}
+
+impl Word {
+    // Bits as `--input-width` takes them
+    pub fn with_bits(bits: &str, endian: Endian) -> Result<Self, String> {
+        let bytes = match bits {
+            "8" => 1,
+            "16" => 2,
+            "32" => 4,
+            _ => {
+                return Err(format!(
+                    "Invalid input width {bits:?}, expected 8, 16 or 32"
+                ))
+            }
+        };
+        Ok(Self { bytes, endian })
+    }
+
+    pub fn bits(self) -> u32 {
+        self.bytes as u32 * 8
+    }
+
+    // The value of a word, a short one at the end of input padded with zero bytes
+    pub fn value(self, bytes: &[u8]) -> u32 {
+        let mut word = [0u8; 4];
+        word[..bytes.len()].copy_from_slice(bytes);
+        let word = &mut word[..self.bytes];
+        if self.endian == Endian::Big {
+            word.reverse();
+        }
+        word.iter()
+            .rev()
+            .fold(0, |value, &byte| value << 8 | byte as u32)
+    }
+}
+
+// Reads `inner` to its end, then the same bytes over and over. Input that was empty stays
+// empty rather than repeating nothing forever.
+pub struct Repeat<R> {
+    inner: Option<R>, // Until it runs out
+    seen: Vec<u8>,
+    at: usize, // Where the replay of `seen` is
+}
+
+impl<R: Read> Repeat<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner: Some(inner),
+            seen: vec![],
+            at: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Repeat<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(inner) = &mut self.inner {
+            let read = inner.read(buf)?;
+            if read > 0 {
+                self.seen.extend(&buf[..read]);
+                return Ok(read);
+            }
+            self.inner = None;
+        }
+        if self.seen.is_empty() {
+            return Ok(0);
+        }
+        let rest = &self.seen[self.at..];
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        self.at = (self.at + len) % self.seen.len();
+        Ok(len)
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cancel::CancelToken;
+use crate::error::BfError;
+use crate::golden::Golden;
+use crate::live::LiveTape;
+use crate::machine::{Machine, Step};
+use crate::pace::Pace;
+use crate::profile::Profiler;
+use crate::program::Program;
+use crate::settings::{Flush, Settings};
+use crate::trace::Tracer;
+
+// The longest a paced run sleeps before looking for a cancel again
+const SLICE: Duration = Duration::from_millis(50);
+
+// Runs a program to completion, reading `,` from `input` and writing `.` to `output`.
+pub struct Interpreter<R, W> {
+    machine: Machine,
+    input: R,
+    output: W,
+    cancel: Option<CancelToken>,
+    flush: Flush,
+    trace: Option<Tracer>,
+    profiler: Option<Profiler>,
+    live: Option<LiveTape>,
+    pace: Option<Pace>,
+    golden: Option<Golden>,
+    threads: Vec<Machine>,  // Children forked by `Y`
+    queue: VecDeque<usize>, // Threads waiting for their turn, see `execute`
+}
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+    pub fn new(program: Program, input: R, output: W) -> Self {
+        Self::from_machine(Machine::new(program), input, output)
+    }
+
+    pub fn with_settings(program: Program, settings: &Settings, input: R, output: W) -> Self {
+        let mut interpreter =
+            Self::from_machine(Machine::with_settings(program, settings), input, output);
+        interpreter.flush = settings.flush;
+        interpreter
+    }
+
+    pub fn from_machine(machine: Machine, input: R, output: W) -> Self {
+        Self {
+            machine,
+            input,
+            output,
+            cancel: None,
+            flush: Flush::Line,
+            trace: None,
+            profiler: None,
+            live: None,
+            pace: None,
+            golden: None,
+            threads: vec![],
+            queue: VecDeque::new(),
+        }
+    }
+
+    pub fn set_flush(&mut self, flush: Flush) {
+        self.flush = flush;
+    }
+
+    // Once `token` is cancelled, `run` stops before the next instruction with
+    // `BfError::Interrupted`, leaving the machine in a state that can be resumed
+    pub fn set_cancel(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
+
+    // The same with a bare flag
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.set_cancel(flag.into());
+    }
+
+    // Records every instruction `run` executes, see `take_trace` for getting it back
+    pub fn set_trace(&mut self, tracer: Tracer) {
+        self.trace = Some(tracer);
+    }
+
+    pub fn take_trace(&mut self) -> Option<Tracer> {
+        self.trace.take()
+    }
+
+    // Times every instruction `run` executes, see `profile::Profiler::report`
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    // Keeps `live` up to date with the first thread's tape, for other threads to look at
+    pub fn set_live_tape(&mut self, live: LiveTape) {
+        live.publish(&self.machine);
+        self.live = Some(live);
+    }
+
+    // Runs at most `rate` instructions a second, over every thread, or as fast as it can
+    // with `None`
+    pub fn set_speed(&mut self, rate: Option<u64>) {
+        self.pace = rate.map(Pace::new);
+    }
+
+    // Takes the states of a golden run as this one goes, see `golden::Golden`
+    pub fn set_golden(&mut self, golden: Golden) {
+        self.golden = Some(golden);
+    }
+
+    pub fn take_golden(&mut self) -> Option<Golden> {
+        self.golden.take()
+    }
+
+    pub fn run(&mut self) -> Result<(), BfError> {
+        telemetry!(let _span = crate::telemetry::span("run"););
+        let result = self.execute();
+        if let (Some(golden), Ok(())) = (&mut self.golden, &result) {
+            golden.finish(&self.machine);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.pause();
+        }
+        if let Some(live) = &self.live {
+            live.publish(&self.machine);
+        }
+        telemetry!(
+            use crate::telemetry::{event, Level};
+            let steps = ("steps", self.machine.steps().into());
+            match &result {
+                Ok(()) => event(Level::Info, "finished", &[steps]),
+                Err(err) => {
+                    let error = ("error", err.to_string().into());
+                    event(Level::Info, "failed", &[steps, error])
+                }
+            }
+        );
+        // Output written before a failure is still shown before the error
+        self.output.flush()?;
+        result
+    }
+
+    // With the `fork` extension there can be several threads. They take turns, each running
+    // until it reads, writes, forks or halts, so the order of their I/O doesn't depend on how
+    // the program was optimized. A forked child is queued just ahead of its parent.
+    fn execute(&mut self) -> Result<(), BfError> {
+        // 0 is `machine`, the rest index `threads` from 1
+        let mut current = 0;
+        loop {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(BfError::Interrupted);
+            }
+            self.wait()?;
+            let machine = match current {
+                0 => &mut self.machine,
+                n => &mut self.threads[n - 1],
+            };
+            let at = machine.ip();
+            // Input and output count towards the instruction that asked for them
+            if let (Some(profiler), true) = (&mut self.profiler, at < machine.program().len()) {
+                profiler.enter(at);
+            }
+            let step = machine.step()?;
+            if let Step::Fork = step {
+                let child = machine.fork();
+                self.threads.push(child);
+                self.queue.push_back(self.threads.len());
+                telemetry!(crate::telemetry::event(
+                    crate::telemetry::Level::Trace,
+                    "forked",
+                    &[("threads", (self.threads.len() + 1).into())],
+                ););
+            }
+            if let (Some(live), 0) = (&self.live, current) {
+                if live.due(self.machine.steps()) {
+                    live.publish(&self.machine);
+                }
+            }
+            // A `,` only counts once it has its byte, a `;` once it has its number, and only
+            // the first thread is traced
+            if let (Some(tracer), 0) = (&mut self.trace, current) {
+                if !matches!(step, Step::Input | Step::Halted) {
+                    tracer.record(&self.machine, at)?;
+                }
+            }
+            match step {
+                Step::Continue => continue,
+                Step::Output(byte) => {
+                    // Bytes go out untouched, escape sequences included
+                    self.output.write_all(&[byte])?;
+                    match self.flush {
+                        Flush::Always => self.output.flush()?,
+                        Flush::Line if byte == b'\n' => self.output.flush()?,
+                        _ => (),
+                    }
+                }
+                Step::Number(value) => {
+                    self.output.write_all(value.to_string().as_bytes())?;
+                    if self.flush == Flush::Always {
+                        self.output.flush()?;
+                    }
+                }
+                Step::Input => {
+                    let byte = self.read_byte()?;
+                    let machine = match current {
+                        0 => &mut self.machine,
+                        n => &mut self.threads[n - 1],
+                    };
+                    machine.input(byte);
+                    if machine.ip() == at {
+                        continue;
+                    }
+                    if let (Some(tracer), 0) = (&mut self.trace, current) {
+                        tracer.record(&self.machine, at)?;
+                    }
+                }
+                Step::Fork => (),
+                Step::Halted => {
+                    match self.queue.pop_front() {
+                        Some(next) => current = next,
+                        None => return Ok(()),
+                    }
+                    continue;
+                }
+            }
+            // Golden states follow the first thread's I/O
+            if let (Some(golden), 0) = (&mut self.golden, current) {
+                if !matches!(step, Step::Fork) {
+                    golden.observe(&self.machine);
+                }
+            }
+            // The turn is over
+            if let Some(next) = self.queue.pop_front() {
+                self.queue.push_back(current);
+                current = next;
+            }
+        }
+    }
+
+    // Holds the next instruction back until `pace` says it's due
+    fn wait(&mut self) -> Result<(), BfError> {
+        let Some(pace) = &mut self.pace else {
+            return Ok(());
+        };
+        while let Some(ahead) = pace.ahead() {
+            // What was written shows while the run waits
+            self.output.flush()?;
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(BfError::Interrupted);
+            }
+            std::thread::sleep(ahead.min(SLICE));
+        }
+        pace.step();
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, BfError> {
+        // Make sure a prompt written by the program is visible before blocking on input
+        self.output.flush()?;
+        let mut buf = [0u8];
+        match self.input.read_exact(&mut buf) {
+            Ok(()) => Ok(Some(buf[0])),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    pub fn into_parts(self) -> (Machine, R, W) {
+        (self.machine, self.input, self.output)
+    }
+}
@@ -0,0 +1,134 @@
+// A readable listing of the optimized instruction stream, one instruction per line.
+use std::fmt::Write;
+
+use crate::error::BfError;
+use crate::program::{find_jumps, Program, Span};
+use crate::settings::MAX_DEPTH;
+use crate::token::BfToken;
+
+// The mnemonic and operand of an instruction, jumps show the index they go to
+pub fn mnemonic(program: &Program, idx: usize) -> String {
+    match program.tokens[idx] {
+        BfToken::CEL(n) => format!("ADD {n}"),
+        BfToken::MOV(n) => format!("MOV {n}"),
+        BfToken::SET(n) => format!("SET {n}"),
+        BfToken::SCN(n) => format!("SCAN {n}"),
+        BfToken::JUM => format!("JZ {}", program.jumps[idx]),
+        BfToken::BAC => format!("JNZ {}", program.jumps[idx]),
+        BfToken::ACC => "IN".to_string(),
+        BfToken::OUT => "OUT".to_string(),
+        BfToken::RND => "RND".to_string(),
+        BfToken::FRK => "FORK".to_string(),
+        BfToken::DEC => "OUTNUM".to_string(),
+        BfToken::NUM => "INNUM".to_string(),
+        BfToken::NAN => "NOP".to_string(),
+        BfToken::EXT(c) => format!("HOST {c}"),
+    }
+}
+
+// Lists every instruction with its index and the source bytes it was built from, e.g.
+//     3  JZ 7          ; @12..13
+pub fn disassemble(program: &Program) -> String {
+    let width = program.len().saturating_sub(1).to_string().len();
+    let mut out = String::new();
+    for (idx, span) in program.spans.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{idx:>width$}  {:<12} ; @{}..{}",
+            mnemonic(program, idx),
+            span.start,
+            span.end
+        );
+    }
+    out
+}
+
+// Reads a listing back into a program. The leading index and `;` comments are optional, and so
+// are jump targets, but a target that is given has to match the bracket structure.
+pub fn assemble(text: &str) -> Result<Program, BfError> {
+    let mut tokens = vec![];
+    let mut spans = vec![];
+    let mut targets = vec![];
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let code = line.split(';').next().unwrap_or("");
+        let mut words = code.split_whitespace().peekable();
+        if words
+            .peek()
+            .is_some_and(|word| word.parse::<usize>().is_ok())
+        {
+            words.next();
+        }
+        let Some(op) = words.next() else {
+            continue;
+        };
+        let err = |message: String| BfError::InvalidIr(start, message);
+        let operand = words.next();
+        if let Some(extra) = words.next() {
+            return Err(err(format!("unexpected {extra:?} after the operand")));
+        }
+        let number = |name: &str| -> Result<isize, BfError> {
+            let value = operand.ok_or_else(|| err(format!("{name} needs an operand")))?;
+            value
+                .parse()
+                .map_err(|_| err(format!("invalid operand {value:?}")))
+        };
+        let no_operand = |token: BfToken| match operand {
+            Some(value) => Err(err(format!("unexpected operand {value:?}"))),
+            None => Ok(token),
+        };
+
+        let mut target = None;
+        let token = match op.to_ascii_uppercase().as_str() {
+            "ADD" => BfToken::CEL(number("ADD")?),
+            "MOV" => BfToken::MOV(number("MOV")?),
+            "SET" => BfToken::SET(number("SET")?),
+            "SCAN" => BfToken::SCN(number("SCAN")?),
+            "JZ" | "JNZ" => {
+                if operand.is_some() {
+                    target = Some(number(op)? as usize);
+                }
+                match op.len() {
+                    2 => BfToken::JUM,
+                    _ => BfToken::BAC,
+                }
+            }
+            "IN" => no_operand(BfToken::ACC)?,
+            "OUT" => no_operand(BfToken::OUT)?,
+            "RND" => no_operand(BfToken::RND)?,
+            "FORK" => no_operand(BfToken::FRK)?,
+            "OUTNUM" => no_operand(BfToken::DEC)?,
+            "INNUM" => no_operand(BfToken::NUM)?,
+            "NOP" => no_operand(BfToken::NAN)?,
+            "HOST" => match u8::try_from(number("HOST")?) {
+                Ok(c) if c.is_ascii() => BfToken::EXT(c),
+                _ => return Err(err("HOST needs an ASCII character code".to_string())),
+            },
+            _ => return Err(err(format!("unknown instruction {op:?}"))),
+        };
+        tokens.push(token);
+        targets.push(target);
+        spans.push(Span {
+            start,
+            end: start + line.trim_end().len(),
+        });
+    }
+
+    let jumps = find_jumps(&tokens, &spans, Some(MAX_DEPTH))?;
+    for (idx, target) in targets.into_iter().enumerate() {
+        if let Some(target) = target.filter(|&target| target != jumps[idx]) {
+            let message = format!(
+                "jumps to {target}, but its matching bracket is {}",
+                jumps[idx]
+            );
+            return Err(BfError::InvalidIr(spans[idx].start, message));
+        }
+    }
+    Ok(Program {
+        tokens,
+        jumps,
+        spans,
+    })
+}
@@ -0,0 +1,231 @@
+// Contest judging: running submitted programs against test cases and ranking them.
+//
+// A test directory holds `NAME.out`, the output a case expects, and optionally `NAME.in` to
+// feed it. Every submission runs every case on its own machine within `Limits`; running out
+// of input reads EOF. Submissions rank by cases passed, then by fewest instructions over all
+// cases, which unlike time doesn't depend on what else the machine was doing.
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::error::BfError;
+use crate::machine::{Machine, RunState};
+use crate::program::Program;
+use crate::quine::Normalize;
+use crate::settings::Settings;
+use crate::stdlib;
+
+// Instructions between checks of the clock and the output size
+const SLICE: u64 = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Test {
+    pub name: String,
+    pub input: Vec<u8>,
+    pub expected: Vec<u8>,
+}
+
+// Every case in `dir` that has an expected output, sorted by name
+pub fn load(dir: &Path) -> io::Result<Vec<Test>> {
+    let mut tests = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "out") {
+            continue;
+        }
+        let input = match fs::read(path.with_extension("in")) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err),
+        };
+        tests.push(Test {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            input,
+            expected: fs::read(&path)?,
+        });
+    }
+    tests.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tests)
+}
+
+// What one case may use
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+    pub max_steps: u64,
+    pub timeout: Duration,
+    pub max_output: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_steps: 10_000_000,
+            timeout: Duration::from_secs(5),
+            max_output: 1 << 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer(usize), // The first byte, after normalizing, that differs
+    StepLimit,
+    TimeLimit,
+    OutputLimit,
+    Error(String), // Failed while running, or didn't compile
+}
+
+impl Verdict {
+    // Short enough for a scoreboard column
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::WrongAnswer(_) => "wrong answer",
+            Self::StepLimit => "step limit",
+            Self::TimeLimit => "time limit",
+            Self::OutputLimit => "output limit",
+            Self::Error(_) => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseResult {
+    pub test: String,
+    pub verdict: Verdict,
+    pub time: Duration,
+    pub steps: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submission {
+    pub name: String,
+    pub cases: Vec<CaseResult>, // In the order of the tests
+}
+
+impl Submission {
+    pub fn passed(&self) -> usize {
+        let accepted = |case: &&CaseResult| case.verdict == Verdict::Accepted;
+        self.cases.iter().filter(accepted).count()
+    }
+
+    pub fn time(&self) -> Duration {
+        self.cases.iter().map(|case| case.time).sum()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.cases.iter().map(|case| case.steps).sum()
+    }
+}
+
+// Runs `source` against every test, with `settings` apart from the step limit
+pub fn judge(
+    name: &str,
+    source: &str,
+    settings: &Settings,
+    tests: &[Test],
+    limits: Limits,
+    normalize: Normalize,
+) -> Submission {
+    let program = stdlib::expand(source).and_then(|code| Program::with_settings(&code, settings));
+    let cases = tests
+        .iter()
+        .map(|test| match &program {
+            Ok(program) => run_case(program, settings, test, limits, normalize),
+            Err(err) => CaseResult {
+                test: test.name.clone(),
+                verdict: Verdict::Error(err.to_string()),
+                time: Duration::ZERO,
+                steps: 0,
+            },
+        })
+        .collect();
+    Submission {
+        name: name.to_string(),
+        cases,
+    }
+}
+
+fn run_case(
+    program: &Program,
+    settings: &Settings,
+    test: &Test,
+    limits: Limits,
+    normalize: Normalize,
+) -> CaseResult {
+    let (ending, time, steps) = run_within(program, settings, &test.input, limits);
+    let verdict = match ending {
+        Ending::Finished(output) => {
+            let (expected, output) = (normalize.apply(&test.expected), normalize.apply(&output));
+            match output.iter().zip(&expected).position(|(a, b)| a != b) {
+                Some(at) => Verdict::WrongAnswer(at),
+                None if output.len() != expected.len() => {
+                    Verdict::WrongAnswer(output.len().min(expected.len()))
+                }
+                None => Verdict::Accepted,
+            }
+        }
+        Ending::StepLimit => Verdict::StepLimit,
+        Ending::TimeLimit => Verdict::TimeLimit,
+        Ending::OutputLimit => Verdict::OutputLimit,
+        Ending::Error(err) => Verdict::Error(err.to_string()),
+    };
+    CaseResult {
+        test: test.name.clone(),
+        verdict,
+        time,
+        steps,
+    }
+}
+
+// How a run within `Limits` ended
+pub(crate) enum Ending {
+    Finished(Vec<u8>), // With its output
+    StepLimit,
+    TimeLimit,
+    OutputLimit,
+    Error(BfError),
+}
+
+// Runs `program` on `input` followed by EOF, with `settings` apart from the step limit,
+// giving how it ended, how long it took and the steps it ran
+pub(crate) fn run_within(
+    program: &Program,
+    settings: &Settings,
+    input: &[u8],
+    limits: Limits,
+) -> (Ending, Duration, u64) {
+    let settings = Settings {
+        max_steps: Some(limits.max_steps),
+        ..*settings
+    };
+    let mut machine = Machine::with_settings(program.clone(), &settings);
+    machine.feed(input);
+    machine.close_input();
+    let mut output = vec![];
+    let start = Instant::now();
+    let ending = loop {
+        let state = machine.run_for(SLICE);
+        output.extend(machine.take_output());
+        if output.len() > limits.max_output {
+            break Ending::OutputLimit;
+        }
+        match state {
+            // A closed input never waits
+            RunState::Finished | RunState::NeedsInput => break Ending::Finished(output),
+            RunState::Error(BfError::StepLimit(_)) => break Ending::StepLimit,
+            RunState::Error(err) => break Ending::Error(err),
+            RunState::Paused if start.elapsed() > limits.timeout => break Ending::TimeLimit,
+            RunState::Paused => (),
+        }
+    };
+    (ending, start.elapsed(), machine.steps())
+}
+
+// Best first, ties keeping their order
+pub fn rank(submissions: &mut [Submission]) {
+    submissions
+        .sort_by_key(|submission| (std::cmp::Reverse(submission.passed()), submission.steps()));
+}
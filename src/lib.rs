@@ -0,0 +1,93 @@
+// Its statements are only compiled in with the `telemetry` feature
+#[cfg(feature = "telemetry")]
+macro_rules! telemetry {
+    ($($body:tt)*) => { $($body)* };
+}
+#[cfg(not(feature = "telemetry"))]
+macro_rules! telemetry {
+    ($($body:tt)*) => {};
+}
+
+pub mod analysis;
+pub mod backend;
+pub mod builder;
+pub mod cache;
+pub mod cancel;
+pub mod checkpoint;
+pub mod conformance;
+pub mod const_eval;
+pub mod corpus;
+pub mod coverage;
+pub mod debugger;
+pub mod diagnostic;
+pub mod dialect;
+pub mod diff;
+pub mod dot;
+pub mod dsl;
+pub mod equivalence;
+pub mod error;
+pub mod expect;
+pub mod extension;
+pub mod fuzz;
+pub mod golden;
+pub mod golf;
+pub mod html;
+pub mod input;
+pub mod interpreter;
+pub mod ir;
+pub mod judge;
+pub mod link;
+pub mod live;
+pub mod machine;
+pub mod newline;
+pub mod obfuscate;
+pub mod opcode;
+pub mod pace;
+pub mod passes;
+pub mod pipeline;
+pub mod profile;
+pub mod program;
+pub mod propagate;
+pub mod quine;
+pub mod reference;
+pub mod rng;
+pub mod settings;
+pub mod stdlib;
+pub mod tape;
+pub mod threaded;
+pub mod tiered;
+pub mod token;
+pub mod trace;
+pub mod transpile;
+pub mod wasm;
+
+#[cfg(feature = "async")]
+pub mod async_io;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+#[cfg(feature = "wasm")]
+pub mod wasm_vm;
+
+pub use analysis::{check, inspect, warnings, Inspection, Loop, ProgramInfo};
+pub use backend::{ExecBackend, RunReport};
+pub use builder::InterpreterBuilder;
+pub use cancel::CancelToken;
+pub use checkpoint::Checkpoint;
+pub use const_eval::bf_eval;
+pub use debugger::Debugger;
+pub use diagnostic::{Diagnostic, Severity};
+pub use equivalence::equivalent;
+pub use error::BfError;
+pub use extension::Extensions;
+pub use interpreter::Interpreter;
+pub use machine::{Machine, RunState, Step, Usage};
+pub use obfuscate::Obfuscator;
+pub use passes::Pass;
+pub use pipeline::Pipeline;
+pub use program::{Program, Span};
+pub use settings::{CellSize, CellWidth, Eof, Flush, Settings};
+pub use tape::{Memory, Tape};
+pub use token::BfToken;
+
+#[cfg(feature = "async")]
+pub use async_io::AsyncInterpreter;
@@ -0,0 +1,172 @@
+// Compiled units that are linked into one program without compiling their sources again, so a
+// large generated program can be built a piece at a time.
+//
+// Linking runs the units one after another on the same tape. How one hands the tape to the
+// next is the `Handoff`: `continue` starts the next unit on the cell the last one ended on,
+// as joining their sources would, and `origin` moves the pointer back to the cell the last
+// one started on first, for units written as if each had the tape to itself. The only units
+// that can't follow another are those compiled as if the tape were blank, by `unroll` and
+// `propagate` or by leaving out a comment loop that opens them, which is `blank_start`.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cache::{read_records, write_records};
+use crate::error::BfError;
+use crate::extension::Extensions;
+use crate::passes::{Pass, BLANK};
+use crate::program::{comment_loops, find_jumps, Program, Span};
+use crate::token::BfToken;
+
+const MAGIC: &[u8; 8] = b"BFUNIT01";
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Handoff {
+    #[default]
+    Continue,
+    Origin,
+}
+
+impl FromStr for Handoff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "continue" => Ok(Self::Continue),
+            "origin" => Ok(Self::Origin),
+            _ => Err(format!(
+                "Invalid handoff {s:?}, expected continue or origin"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Handoff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Continue => write!(f, "continue"),
+            Self::Origin => write!(f, "origin"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Unit {
+    pub program: Program,
+    pub source_len: usize, // Spans of a linked unit index its sources laid end to end
+    pub blank_start: bool,
+}
+
+impl Unit {
+    // Compiles `code` like `Program::with_max_depth`, noting whether it needs a blank tape
+    pub fn compile(
+        code: &str,
+        extensions: Extensions,
+        passes: &[Box<dyn Pass>],
+        max_depth: Option<usize>,
+    ) -> Result<Self, BfError> {
+        let (program, _) = Program::with_max_depth(code, extensions, passes, max_depth)?;
+        let commands = extensions.commands();
+        let first = code.find(|c| commands.contains(c));
+        let opening_comment = first.is_some()
+            && comment_loops(code, extensions)?
+                .first()
+                .map(|span| span.start)
+                == first;
+        Ok(Self {
+            program,
+            source_len: code.len(),
+            blank_start: opening_comment || passes.iter().any(|pass| BLANK.contains(&pass.name())),
+        })
+    }
+
+    // The magic `BFUNIT01`, a flags byte with `blank_start` as its lowest bit, the source
+    // length as a little-endian u64, then the instructions as `cache` writes them
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(self.blank_start as u8);
+        out.extend((self.source_len as u64).to_le_bytes());
+        write_records(&self.program, &mut out);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or("not a compiled unit, it doesn't start with BFUNIT01")?;
+        let invalid = || "damaged compiled unit".to_string();
+        let (&flags, rest) = rest.split_first().ok_or_else(invalid)?;
+        let (len, records) = rest.split_at_checked(8).ok_or_else(invalid)?;
+        let source_len = u64::from_le_bytes(len.try_into().unwrap());
+        Ok(Self {
+            program: read_records(records).ok_or_else(invalid)?,
+            source_len: usize::try_from(source_len).map_err(|_| invalid())?,
+            blank_start: flags & 1 != 0,
+        })
+    }
+}
+
+// Whether `bytes` are meant to be a unit, damaged or not
+pub fn is_unit(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+// Joins `units` in order, failing with the index of a unit that can't take its place
+pub fn link(units: &[Unit], handoff: Handoff) -> Result<Unit, (usize, BfError)> {
+    let mut tokens = vec![];
+    let mut spans = vec![];
+    let mut offset = 0;
+    for (idx, unit) in units.iter().enumerate() {
+        if idx > 0 && unit.blank_start {
+            let reason = "it was compiled for a blank tape (with unroll or propagate, or \
+                          leaving out the comment loop it opens with), so it can only come first";
+            return Err((idx, BfError::Unsupported(reason.to_string())));
+        }
+        tokens.extend(&unit.program.tokens);
+        spans.extend(unit.program.spans.iter().map(|span| Span {
+            start: span.start + offset,
+            end: span.end + offset,
+        }));
+        offset += unit.source_len;
+        if handoff == Handoff::Origin && idx + 1 < units.len() {
+            let Some(moved) = displacement(&unit.program) else {
+                let reason = "where its pointer ends up depends on the run, so it can't hand \
+                              off at its origin";
+                return Err((idx, BfError::Unsupported(reason.to_string())));
+            };
+            if moved != 0 {
+                tokens.push(BfToken::MOV(-moved));
+                spans.push(Span {
+                    start: offset,
+                    end: offset,
+                });
+            }
+        }
+    }
+    let jumps = find_jumps(&tokens, &spans, None).map_err(|err| (0, err))?;
+    Ok(Unit {
+        program: Program {
+            tokens,
+            jumps,
+            spans,
+        },
+        source_len: offset,
+        blank_start: units.first().is_some_and(|unit| unit.blank_start),
+    })
+}
+
+// How far `program` moves the pointer, if every run moves it as far: each loop has to come
+// back to the cell it started on, and there can be no scans or forks
+pub fn displacement(program: &Program) -> Option<isize> {
+    let mut starts = vec![];
+    let mut pointer = 0;
+    for token in &program.tokens {
+        match token {
+            BfToken::MOV(n) => pointer += n,
+            BfToken::JUM => starts.push(pointer),
+            BfToken::BAC if starts.pop() != Some(pointer) => return None,
+            BfToken::SCN(_) | BfToken::FRK => return None,
+            _ => (),
+        }
+    }
+    Some(pointer)
+}
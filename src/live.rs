@@ -0,0 +1,73 @@
+// Read-only views of a tape while another thread runs it, for live visualizations.
+//
+// The interpreter copies its tape into one of two buffers every `every` steps and when a run
+// stops, then makes that buffer the one readers get. A reader only ever locks the latest
+// buffer and the interpreter only the other one, skipping a copy instead of waiting when a
+// slow reader still holds it, so execution never pauses for a reader and every snapshot is
+// the state between two whole instructions.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::machine::Machine;
+use crate::tape::MAX_GATHERED;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TapeSnapshot {
+    pub cells: Vec<u32>, // As `Machine::tape` has them
+    pub pointer: usize,
+    pub ip: usize,
+    pub steps: u64, // 0 until the first copy
+}
+
+#[derive(Debug, Default)]
+struct Buffers {
+    snapshots: [Mutex<TapeSnapshot>; 2],
+    latest: AtomicUsize,
+}
+
+// Clones share the buffers, give one to `Interpreter::set_live_tape` and keep the others
+#[derive(Debug, Clone)]
+pub struct LiveTape {
+    buffers: Arc<Buffers>,
+    every: u64,
+}
+
+impl LiveTape {
+    pub fn new(every: u64) -> Self {
+        Self {
+            buffers: Arc::default(),
+            every: every.max(1),
+        }
+    }
+
+    // The latest copy of the tape
+    pub fn snapshot(&self) -> TapeSnapshot {
+        let latest = self.buffers.latest.load(Ordering::Acquire);
+        let snapshot = self.buffers.snapshots[latest].lock();
+        snapshot
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn due(&self, steps: u64) -> bool {
+        steps.is_multiple_of(self.every)
+    }
+
+    pub(crate) fn publish(&self, machine: &Machine) {
+        // A sparse tape too long to gather isn't copied, readers keep the last copy
+        if machine.memory().cell_count() > MAX_GATHERED {
+            return;
+        }
+        let back = 1 - self.buffers.latest.load(Ordering::Relaxed);
+        let Ok(mut snapshot) = self.buffers.snapshots[back].try_lock() else {
+            return;
+        };
+        snapshot.cells.clear();
+        snapshot.cells.extend(machine.tape().iter());
+        snapshot.pointer = machine.pointer();
+        snapshot.ip = machine.ip();
+        snapshot.steps = machine.steps();
+        drop(snapshot);
+        self.buffers.latest.store(back, Ordering::Release);
+    }
+}
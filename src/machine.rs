@@ -0,0 +1,585 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::error::BfError;
+use crate::extension::Number;
+use crate::input::Word;
+use crate::opcode::{MachineState, Opcodes};
+use crate::program::Program;
+use crate::rng::Rng;
+use crate::settings::{Eof, Settings};
+use crate::tape::Memory;
+use crate::token::BfToken;
+
+// What happened while executing a single instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Step {
+    Continue,    // An instruction ran and there is more to do
+    Output(u8),  // The program wrote this byte
+    Number(u32), // The program wrote this cell value in decimal, with `:`
+    Input,       // The program is waiting on `input` before it can continue
+    Fork,        // A `Y` ran, call `fork` to split off the new thread
+    Halted,      // There are no instructions left
+}
+
+// Where a budgeted run stopped, see `Machine::run_for`.
+#[derive(Debug)]
+pub enum RunState {
+    Paused,         // The budget ran out, call `run_for` again to continue
+    Finished,       // The program halted
+    NeedsInput,     // A `,` or `;` is waiting, `feed` it bytes or `close_input`
+    Error(BfError), // Execution failed and cannot continue
+}
+
+// What a run has used so far, see `Machine::usage`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub cells: usize,       // Length of the tape, the longest it got as it never shrinks
+    pub allocated: usize,   // Bytes held for the tape
+    pub reallocations: u64, // See `Memory::reallocations`
+    pub read: u64,          // Input bytes taken by `,` and `;`, not counting EOF
+    pub written: u64,       // Bytes written by `.` and `:`
+}
+
+// The "system" state of a running program, independent of where its I/O goes.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    program: Program,
+    tape: Memory,
+    mask: u32,
+    eof: Eof,
+    ip: usize,
+    steps: u64,
+    max_steps: Option<u64>,
+    input: VecDeque<u8>,
+    input_closed: bool,
+    output: Vec<u8>,
+    hits: Option<Vec<u64>>, // Times each instruction ran, once coverage is tracked
+    hangs: Option<Hangs>,
+    history: Option<History>,
+    rng: Rng,         // Behind `?`
+    scanned: isize,   // How far the last SCN moved the pointer
+    number: Number,   // What a `;` has read so far
+    word: Word,       // How many bytes a `,` reads
+    partial: Vec<u8>, // The bytes of a word read so far
+    opcodes: Opcodes,
+    read: u64,
+    written: u64,
+}
+
+impl Machine {
+    pub fn new(program: Program) -> Self {
+        Self::with_settings(program, &Settings::default())
+    }
+
+    pub fn with_settings(program: Program, settings: &Settings) -> Self {
+        Self {
+            program,
+            tape: Memory::new(settings.tape),
+            mask: settings.cell_size.mask(),
+            eof: settings.eof,
+            ip: 0,
+            steps: 0,
+            max_steps: settings.max_steps,
+            input: VecDeque::new(),
+            input_closed: false,
+            output: vec![],
+            hits: None,
+            hangs: None,
+            history: None,
+            rng: settings.seed.map_or_else(Rng::from_time, Rng::new),
+            scanned: 0,
+            number: Number::default(),
+            word: Word::default(),
+            partial: vec![],
+            opcodes: Opcodes::default(),
+            read: 0,
+            written: 0,
+        }
+    }
+
+    // Optional iteration cap, checked before every instruction
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.max_steps = max_steps;
+    }
+
+    // Reads every `,` as a word of several input bytes rather than a single one, see
+    // `input::Word`. A word wider than the cells keeps only its low bits.
+    pub fn set_input_word(&mut self, word: Word) {
+        self.word = word;
+    }
+
+    // The handlers called by the host opcodes in the program, see `opcode::Opcodes`
+    pub fn set_opcodes(&mut self, opcodes: Opcodes) {
+        self.opcodes = opcodes;
+    }
+
+    // Starts counting how many times each instruction runs, see `hits`
+    pub fn track_coverage(&mut self) {
+        self.hits.get_or_insert_with(|| vec![0; self.program.len()]);
+    }
+
+    // Per instruction execution counts, if `track_coverage` was called
+    pub fn hits(&self) -> Option<&[u64]> {
+        self.hits.as_deref()
+    }
+
+    // Starts remembering the last `len` instructions executed, see `history`
+    pub fn keep_history(&mut self, len: usize) {
+        if len > 0 {
+            self.history = Some(History {
+                ips: Vec::with_capacity(len),
+                len,
+                next: 0,
+            });
+        }
+    }
+
+    // The instructions executed most recently, oldest first, if `keep_history` was called
+    pub fn history(&self) -> Option<Vec<usize>> {
+        let history = self.history.as_ref()?;
+        let (newer, older) = history.ips.split_at(history.next);
+        Some(older.iter().chain(newer).copied().collect())
+    }
+
+    // Fails with `BfError::NonTerminating` once a loop jumps back in a state it was already
+    // in, which means it will never end. Costs a hash update on every write.
+    pub fn detect_hangs(&mut self) {
+        if self.hangs.is_none() {
+            let pointer = self.tape.pointer() as isize;
+            let mut hangs = Hangs {
+                position: 0,
+                hash: 0,
+                reads: 0,
+                seen: vec![None; self.program.len()],
+            };
+            for (idx, cell) in self.tape.nonzero() {
+                hangs.hash ^= footprint(idx as isize - pointer, cell);
+            }
+            self.hangs = Some(hangs);
+        }
+    }
+
+    // Writes `data` into the cells from the pointer rightwards, one byte per cell, leaving
+    // the pointer where it was. Call before `detect_hangs`.
+    pub fn preload(&mut self, data: &[u8]) {
+        // A fixed tape takes as much as fits
+        let mut written = 0;
+        for &byte in data {
+            *self.tape.get_mut() = byte as u32;
+            written += 1;
+            if self.tape.shift(1).is_err() {
+                break;
+            }
+        }
+        let _ = self.tape.shift(-(written as isize));
+    }
+
+    // Puts the machine back where a checkpoint left it
+    pub(crate) fn restore(
+        &mut self,
+        ip: usize,
+        steps: u64,
+        tape: Memory,
+        rng: Rng,
+        number: Number,
+        partial: Vec<u8>,
+    ) {
+        self.ip = ip;
+        self.steps = steps;
+        self.tape = tape;
+        self.rng = rng;
+        self.number = number;
+        self.partial = partial;
+    }
+
+    // What a checkpoint saves besides the tape: the generator behind `?`, the number a `;`
+    // is partway through reading and the bytes a `,` has read of its word
+    pub(crate) fn resumable(&self) -> (&Rng, Number, &[u8]) {
+        (&self.rng, self.number, &self.partial)
+    }
+
+    // Executes the instruction under the instruction pointer.
+    pub fn step(&mut self) -> Result<Step, BfError> {
+        let Some(&token) = self.program.tokens.get(self.ip) else {
+            return Ok(Step::Halted);
+        };
+        if let Some(limit) = self.max_steps {
+            if self.steps >= limit {
+                return Err(BfError::StepLimit(limit));
+            }
+        }
+
+        let at = self.ip;
+        let before = match self.hangs {
+            Some(_) => self.tape.get(),
+            None => 0,
+        };
+        let mut step = Step::Continue;
+        match token {
+            BfToken::MOV(n) => self.tape.shift(n)?,
+            BfToken::CEL(n) => {
+                let cell = self.tape.get_mut();
+                *cell = cell.wrapping_add(n as u32) & self.mask;
+            }
+            BfToken::SET(n) => *self.tape.get_mut() = n as u32 & self.mask,
+            BfToken::SCN(n) => {
+                let mut moved = 0;
+                while self.tape.get() != 0 {
+                    self.tape.shift(n)?;
+                    moved += n;
+                }
+                self.scanned = moved;
+            }
+            BfToken::JUM => {
+                if self.tape.get() == 0 {
+                    self.ip = self.program.jumps[self.ip]
+                }
+            }
+            BfToken::BAC => {
+                if self.tape.get() != 0 {
+                    if let Some(hangs) = &mut self.hangs {
+                        hangs.check(at)?;
+                    }
+                    self.ip = self.program.jumps[self.ip]
+                }
+            }
+            BfToken::ACC if self.word.bytes == 1 => match self.input.pop_front() {
+                Some(byte) => self.store_input(Some(byte)),
+                None if self.input_closed => self.store_input(None),
+                // Leave the instruction pointer on the `,` until the byte arrives
+                None => return Ok(Step::Input),
+            },
+            // Like `;`, takes the bytes of the word that are queued and waits for the rest
+            BfToken::ACC => loop {
+                let byte = match self.input.pop_front() {
+                    Some(byte) => Some(byte),
+                    None if self.input_closed => None,
+                    None => return Ok(Step::Input),
+                };
+                if self.read_word(byte) {
+                    break;
+                }
+            },
+            BfToken::OUT => {
+                self.written += 1;
+                step = Step::Output(self.tape.get() as u8);
+            }
+            BfToken::DEC => {
+                let value = self.tape.get();
+                self.written += value.to_string().len() as u64;
+                step = Step::Number(value);
+            }
+            // Takes bytes as long as they're queued, then waits like `,` for the rest
+            BfToken::NUM => loop {
+                let byte = match self.input.pop_front() {
+                    Some(byte) => Some(byte),
+                    None if self.input_closed => None,
+                    None => return Ok(Step::Input),
+                };
+                if self.read_number(byte) {
+                    break;
+                }
+            },
+            BfToken::RND => {
+                // Like input, a random byte moves the state on even if the cell ends up the same
+                if let Some(hangs) = &mut self.hangs {
+                    hangs.reads += 1;
+                }
+                *self.tape.get_mut() = self.rng.byte() as u32;
+            }
+            BfToken::FRK => step = Step::Fork,
+            BfToken::EXT(c) => {
+                let Some(handler) = self.opcodes.get(c) else {
+                    let reason = format!("no opcode is registered for {:?}", c as char);
+                    return Err(BfError::Unsupported(reason));
+                };
+                // Like input, the host moves the state on even if the cell ends up the same
+                if let Some(hangs) = &mut self.hangs {
+                    hangs.reads += 1;
+                }
+                let mut state = MachineState {
+                    tape: &mut self.tape,
+                    mask: self.mask,
+                    steps: self.steps,
+                };
+                handler(&mut state)?;
+            }
+            BfToken::NAN => (),
+        }
+        if let Some(hangs) = &mut self.hangs {
+            let token = match token {
+                BfToken::SCN(_) => BfToken::MOV(self.scanned),
+                token => token,
+            };
+            hangs.observe(token, before, self.tape.get());
+        }
+        self.count(at);
+        if let Some(history) = &mut self.history {
+            history.push(at);
+        }
+        self.ip += 1;
+        self.steps += 1;
+        Ok(step)
+    }
+
+    // Completes a pending `,` with the next input byte, or `None` at end of input. A `;`
+    // only completes once the byte ends its number, and a `,` reading words once the byte
+    // ends its word, until then they ask for more.
+    pub fn input(&mut self, byte: Option<u8>) {
+        let Some(&token @ (BfToken::ACC | BfToken::NUM)) = self.program.tokens.get(self.ip) else {
+            return;
+        };
+        let before = self.tape.get();
+        match token {
+            BfToken::ACC if self.word.bytes == 1 => self.store_input(byte),
+            BfToken::ACC if self.read_word(byte) => (),
+            BfToken::NUM if self.read_number(byte) => (),
+            _ => return,
+        }
+        if let Some(hangs) = &mut self.hangs {
+            hangs.observe(token, before, self.tape.get());
+        }
+        self.count(self.ip);
+        if let Some(history) = &mut self.history {
+            history.push(self.ip);
+        }
+        self.ip += 1;
+        self.steps += 1;
+    }
+
+    // Completes a `Y` by splitting off a child thread. The child starts as a copy of this
+    // machine, then moves one cell right and stores 1 there, while this machine stores 0 in
+    // the current cell, so each side can tell which one it is.
+    pub fn fork(&mut self) -> Machine {
+        let mut child = self.clone();
+        self.write(0);
+        child.shift(1);
+        child.write(1);
+        child
+    }
+
+    // Changes the state outside of an instruction, keeping hang detection up to date
+    fn write(&mut self, value: u32) {
+        let before = self.tape.get();
+        *self.tape.get_mut() = value;
+        if let Some(hangs) = &mut self.hangs {
+            hangs.observe(BfToken::SET(0), before, value);
+            hangs.reads += 1;
+        }
+    }
+
+    // A child forked at the end of a fixed tape stays on the last cell
+    fn shift(&mut self, n: isize) {
+        let _ = self.tape.shift(n);
+        if let Some(hangs) = &mut self.hangs {
+            hangs.observe(BfToken::MOV(n), 0, 0);
+        }
+    }
+
+    fn count(&mut self, at: usize) {
+        if let Some(hits) = &mut self.hits {
+            hits[at] += 1;
+        }
+    }
+
+    // Gives the number being read by `;` its next byte, storing it once it ends
+    fn read_number(&mut self, byte: Option<u8>) -> bool {
+        if let (Some(hangs), Some(_)) = (&mut self.hangs, byte) {
+            hangs.reads += 1;
+        }
+        self.read += byte.is_some() as u64;
+        let Some(number) = self.number.push(byte) else {
+            return false;
+        };
+        self.number = Number::default();
+        match number {
+            Some(value) => *self.tape.get_mut() = value as u32 & self.mask,
+            None => self.store_input(None),
+        }
+        true
+    }
+
+    // Gives the word being read by `,` its next byte, storing it once it's whole. A word cut
+    // short by the end of input is padded with zeros, one not started at all reads as EOF.
+    fn read_word(&mut self, byte: Option<u8>) -> bool {
+        if let Some(byte) = byte {
+            if let Some(hangs) = &mut self.hangs {
+                hangs.reads += 1;
+            }
+            self.read += 1;
+            self.partial.push(byte);
+            if self.partial.len() < self.word.bytes {
+                return false;
+            }
+        }
+        match self.partial.is_empty() {
+            true => self.store_input(None),
+            false => *self.tape.get_mut() = self.word.value(&self.partial) & self.mask,
+        }
+        self.partial.clear();
+        true
+    }
+
+    fn store_input(&mut self, byte: Option<u8>) {
+        if let (Some(hangs), Some(_)) = (&mut self.hangs, byte) {
+            hangs.reads += 1;
+        }
+        self.read += byte.is_some() as u64;
+        let cell = self.tape.get_mut();
+        match (byte, self.eof) {
+            (Some(byte), _) => *cell = byte as u32,
+            (None, Eof::Zero) => *cell = 0,
+            (None, Eof::Minus1) => *cell = self.mask,
+            (None, Eof::Unchanged) => (),
+        }
+    }
+
+    // Runs at most `budget` instructions, buffering output for `take_output`.
+    pub fn run_for(&mut self, budget: u64) -> RunState {
+        for _ in 0..budget {
+            match self.step() {
+                Ok(Step::Continue) => (),
+                Ok(Step::Output(byte)) => self.output.push(byte),
+                Ok(Step::Number(value)) => self.output.extend(value.to_string().bytes()),
+                Ok(Step::Input) => return RunState::NeedsInput,
+                Ok(Step::Fork) => {
+                    let reason = "forking with `Y` needs an Interpreter to run the threads";
+                    return RunState::Error(BfError::Unsupported(reason.to_string()));
+                }
+                Ok(Step::Halted) => return RunState::Finished,
+                Err(err) => return RunState::Error(err),
+            }
+        }
+        match self.is_halted() {
+            true => RunState::Finished,
+            false => RunState::Paused,
+        }
+    }
+
+    // Queues bytes for upcoming `,` instructions
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.input.extend(bytes);
+    }
+
+    // Marks the end of input, once the queue drains `,` reads EOF instead of waiting
+    pub fn close_input(&mut self) {
+        self.input_closed = true;
+    }
+
+    // Drains the output produced by `run_for` so far
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    // The visited part of the tape, `pointer` indexes into it
+    pub fn tape(&self) -> Cow<'_, [u32]> {
+        self.tape.cells()
+    }
+
+    // The tape itself, for reading cells without gathering them all as `tape` does
+    pub fn memory(&self) -> &Memory {
+        &self.tape
+    }
+
+    // The value under the pointer
+    pub fn cell(&self) -> u32 {
+        self.tape.get()
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.tape.pointer()
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    // How far the most recent SCN moved the pointer, which the instruction alone doesn't say
+    pub fn scanned(&self) -> isize {
+        self.scanned
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn usage(&self) -> Usage {
+        Usage {
+            cells: self.tape.cell_count(),
+            allocated: self.tape.allocated(),
+            reallocations: self.tape.reallocations(),
+            read: self.read,
+            written: self.written,
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.ip >= self.program.len()
+    }
+}
+
+// A ring of the last instruction pointers, overwriting the oldest once full.
+#[derive(Debug, Clone)]
+struct History {
+    ips: Vec<usize>,
+    len: usize,
+    next: usize, // Where the next one goes, the oldest once the ring is full
+}
+
+impl History {
+    fn push(&mut self, ip: usize) {
+        if self.ips.len() < self.len {
+            self.ips.push(ip);
+        } else {
+            self.ips[self.next] = ip;
+            self.next = (self.next + 1) % self.len;
+        }
+    }
+}
+
+// A rolling hash of the machine state, compared every time a loop jumps back.
+#[derive(Debug, Clone)]
+struct Hangs {
+    position: isize,        // The pointer, unaffected by the tape growing to the left
+    hash: u64,              // XOR of the footprint of every cell
+    reads: u64, // Input bytes consumed, reading moves the state on even if no cell changes
+    seen: Vec<Option<u64>>, // State at each `]` the last time it jumped back
+}
+
+impl Hangs {
+    fn observe(&mut self, token: BfToken, before: u32, after: u32) {
+        match token {
+            BfToken::MOV(n) => self.position += n,
+            _ => self.hash ^= footprint(self.position, before) ^ footprint(self.position, after),
+        }
+    }
+
+    fn check(&mut self, at: usize) -> Result<(), BfError> {
+        let state =
+            self.hash ^ mix(self.position as u64 ^ 0x5851_f42d_4c95_7f2d) ^ mix(!self.reads);
+        match self.seen[at].replace(state) {
+            Some(seen) if seen == state => Err(BfError::NonTerminating(at)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Empty cells leave no footprint, so the hash only depends on the cells holding something
+fn footprint(position: isize, cell: u32) -> u64 {
+    match cell {
+        0 => 0,
+        _ => mix((position as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ cell as u64),
+    }
+}
+
+// The splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
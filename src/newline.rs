@@ -0,0 +1,145 @@
+// Line endings for programs written for other systems. A program that expects CR or CRLF
+// reads every line ending of its input, whichever it is, as the one it expects, and what it
+// writes with its own line endings comes out with LF. `Lf` leaves bytes as they are.
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Newline {
+    #[default]
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl Newline {
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Self::Lf => b"\n",
+            Self::Crlf => b"\r\n",
+            Self::Cr => b"\r",
+        }
+    }
+}
+
+impl FromStr for Newline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(Self::Lf),
+            "crlf" => Ok(Self::Crlf),
+            "cr" => Ok(Self::Cr),
+            _ => Err(format!("Invalid newline {s:?}, expected lf, crlf or cr")),
+        }
+    }
+}
+
+// Input with each LF, CRLF or lone CR given as `newline`
+pub struct InputNewlines<R: Read> {
+    inner: R,
+    newline: Newline,
+    pending: VecDeque<u8>,
+    after_cr: bool, // An LF straight after is part of the same line ending
+}
+
+impl<R: Read> InputNewlines<R> {
+    pub fn new(inner: R, newline: Newline) -> Self {
+        Self {
+            inner,
+            newline,
+            pending: VecDeque::new(),
+            after_cr: false,
+        }
+    }
+}
+
+impl<R: Read> Read for InputNewlines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.newline == Newline::Lf {
+            return self.inner.read(buf);
+        }
+        let mut chunk = [0; 4096];
+        // A chunk can be nothing but the LF of a CRLF
+        while self.pending.is_empty() {
+            let read = self.inner.read(&mut chunk[..buf.len().min(4096)])?;
+            if read == 0 {
+                return Ok(0);
+            }
+            for &byte in &chunk[..read] {
+                match byte {
+                    b'\n' if self.after_cr => (),
+                    b'\n' | b'\r' => self.pending.extend(self.newline.bytes()),
+                    _ => self.pending.push_back(byte),
+                }
+                self.after_cr = byte == b'\r';
+            }
+        }
+        let len = buf.len().min(self.pending.len());
+        for (slot, byte) in buf.iter_mut().zip(self.pending.drain(..len)) {
+            *slot = byte;
+        }
+        Ok(len)
+    }
+}
+
+// Output with each of the program's `newline`s written as LF
+pub struct OutputNewlines<W: Write> {
+    inner: W,
+    newline: Newline,
+    held_cr: bool, // With CRLF, a CR that may be the start of one
+}
+
+impl<W: Write> OutputNewlines<W> {
+    pub fn new(inner: W, newline: Newline) -> Self {
+        Self {
+            inner,
+            newline,
+            held_cr: false,
+        }
+    }
+}
+
+impl<W: Write> Write for OutputNewlines<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.newline {
+            Newline::Lf => self.inner.write_all(buf)?,
+            Newline::Cr => {
+                let text: Vec<u8> = buf
+                    .iter()
+                    .map(|&byte| if byte == b'\r' { b'\n' } else { byte })
+                    .collect();
+                self.inner.write_all(&text)?;
+            }
+            Newline::Crlf => {
+                let mut text = Vec::with_capacity(buf.len() + 1);
+                for &byte in buf {
+                    if self.held_cr && byte != b'\n' {
+                        text.push(b'\r');
+                    }
+                    self.held_cr = byte == b'\r';
+                    if !self.held_cr {
+                        text.push(byte);
+                    }
+                }
+                self.inner.write_all(&text)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for OutputNewlines<W> {
+    // A CR the output ended on was never part of a line ending
+    fn drop(&mut self) {
+        if self.held_cr {
+            let _ = self.inner.write_all(b"\r");
+            let _ = self.inner.flush();
+        }
+    }
+}
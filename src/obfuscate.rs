@@ -0,0 +1,91 @@
+// Rewrites a program into a noisier one that does exactly the same thing.
+use crate::equivalence::equivalent;
+use crate::error::BfError;
+use crate::program::Program;
+use crate::rng::Rng;
+use crate::token::BfToken;
+
+// Filler words for junk comments, none of them contain a command character
+const WORDS: &[&str] = &[
+    "the", "tape", "loops", "quietly", "while", "nothing", "happens", "here", "cell", "moves",
+    "around", "again", "carry", "this", "value", "home", "maybe", "never", "ask", "why",
+];
+
+// Pairs that cancel out wherever they are inserted
+const NOOPS: &[&str] = &["+-", "-+", "><", "<>"];
+
+pub struct Obfuscator {
+    rng: Rng,
+    density: u32, // Percent chance of noise between any two instructions
+}
+
+impl Obfuscator {
+    pub fn new(rng: Rng, density: u32) -> Self {
+        Self {
+            rng,
+            density: density.min(100),
+        }
+    }
+
+    pub fn obfuscate(&mut self, source: &str) -> Result<String, BfError> {
+        let program = Program::compile(source, 1)?;
+        let mut out = String::new();
+        for &token in &program.tokens {
+            match token {
+                BfToken::CEL(n) | BfToken::MOV(n) if n.abs() > 1 => self.split(&mut out, token, n),
+                _ => out.push_str(&String::from(token)),
+            }
+            self.noise(&mut out);
+        }
+
+        // Noise never changes the normal form, anything else would be a bug in the rewrite
+        assert!(
+            equivalent(source, &out)?,
+            "obfuscated program is not equivalent to its source"
+        );
+        Ok(out)
+    }
+
+    // Emits a run in several pieces with noise between them
+    fn split(&mut self, out: &mut String, token: BfToken, n: isize) {
+        let step = n.signum();
+        let mut left = n;
+        while left != 0 {
+            let take = step * (1 + self.rng.below(left.unsigned_abs()) as isize);
+            let piece = match token {
+                BfToken::CEL(_) => BfToken::CEL(take),
+                _ => BfToken::MOV(take),
+            };
+            out.push_str(&String::from(piece));
+            left -= take;
+            if left != 0 {
+                self.noise(out);
+            }
+        }
+    }
+
+    fn noise(&mut self, out: &mut String) {
+        if !self.rng.chance(self.density) {
+            return;
+        }
+        match self.rng.below(3) {
+            0 => out.push_str(NOOPS[self.rng.below(NOOPS.len())]),
+            1 => {
+                let words = 1 + self.rng.below(4);
+                let comment: Vec<&str> = (0..words)
+                    .map(|_| WORDS[self.rng.below(WORDS.len())])
+                    .collect();
+                out.push(' ');
+                out.push_str(&comment.join(" "));
+                out.push(if self.rng.chance(30) { '\n' } else { ' ' });
+            }
+            _ => {
+                let noop = NOOPS[self.rng.below(NOOPS.len())];
+                let (a, b) = noop.split_at(1);
+                out.push_str(a);
+                out.push_str(WORDS[self.rng.below(WORDS.len())]);
+                out.push_str(b);
+            }
+        }
+    }
+}
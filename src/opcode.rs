@@ -0,0 +1,108 @@
+// Commands a host program adds to the language, each an ASCII character calling back into
+// Rust, e.g. to draw a pixel or read a sensor without forking the interpreter:
+//
+//     let mut opcodes = Opcodes::new();
+//     opcodes.register('#', move |state| {
+//         screen.plot(state.peek(-2), state.peek(-1), state.cell());
+//         Ok(())
+//     })?;
+//     InterpreterBuilder::new().opcodes(opcodes).source(code).build()?.run()?;
+//
+// An opcode is to the optimizer what `,` is: it can look at the whole tape but only changes
+// the current cell, and its effect is unknown until it runs. Handlers are shared between
+// clones of a machine and the threads of `Y`, so state they keep needs its own locking.
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::BfError;
+use crate::extension::Extensions;
+use crate::tape::Memory;
+
+type Handler = Arc<dyn Fn(&mut MachineState) -> Result<(), BfError> + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct Opcodes {
+    handlers: HashMap<u8, Handler>,
+}
+
+impl Opcodes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Makes `c` call `handler`, replacing what it was registered with before. Only ASCII
+    // characters that aren't commands already, extensions included, can be opcodes.
+    pub fn register(
+        &mut self,
+        c: char,
+        handler: impl Fn(&mut MachineState) -> Result<(), BfError> + Send + Sync + 'static,
+    ) -> Result<(), BfError> {
+        let builtin = Extensions {
+            random: true,
+            fork: true,
+            numbers: true,
+            opcodes: 0,
+        };
+        if !c.is_ascii() || builtin.commands().contains(c) {
+            return Err(BfError::Unsupported(format!(
+                "{c:?} can't be an opcode, it must be ASCII and not a command already"
+            )));
+        }
+        self.handlers.insert(c as u8, Arc::new(handler));
+        Ok(())
+    }
+
+    pub fn get(&self, c: u8) -> Option<&Handler> {
+        self.handlers.get(&c)
+    }
+
+    // `extensions` with these opcodes registered, for compiling source that uses them
+    pub fn extend(&self, mut extensions: Extensions) -> Extensions {
+        for &c in self.handlers.keys() {
+            extensions.opcodes |= 1 << c;
+        }
+        extensions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+impl fmt::Debug for Opcodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut chars: Vec<char> = self.handlers.keys().map(|&c| c as char).collect();
+        chars.sort_unstable();
+        f.debug_tuple("Opcodes").field(&chars).finish()
+    }
+}
+
+// What a handler sees of the machine running it
+pub struct MachineState<'a> {
+    pub(crate) tape: &'a mut Memory,
+    pub(crate) mask: u32,
+    pub(crate) steps: u64,
+}
+
+impl MachineState<'_> {
+    // The value under the pointer
+    pub fn cell(&self) -> u32 {
+        self.tape.get()
+    }
+
+    // Stores `value` under the pointer, wrapped to the cell size
+    pub fn set_cell(&mut self, value: u32) {
+        *self.tape.get_mut() = value & self.mask;
+    }
+
+    // The cell `offset` away from the pointer, 0 for one the program never visited
+    pub fn peek(&self, offset: isize) -> u32 {
+        self.tape.peek(offset)
+    }
+
+    // Instructions run before this one
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
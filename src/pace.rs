@@ -0,0 +1,48 @@
+// Holding a run to a number of instructions per second, so it can be watched. Every
+// instruction is due at its own time counted from the start, rather than after a fixed
+// sleep, so the time spent running and drawing doesn't add up and slow the run down.
+use std::time::{Duration, Instant};
+
+// How far behind a run can fall, waiting on input say, before the schedule starts over
+// instead of racing through the instructions it missed
+const SLACK: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub struct Pace {
+    rate: u64, // Instructions per second
+    start: Instant,
+    steps: u64, // Run since `start`
+}
+
+impl Pace {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate: rate.max(1),
+            start: Instant::now(),
+            steps: 0,
+        }
+    }
+
+    // How long until the next instruction is due, `None` once it is
+    pub fn ahead(&mut self) -> Option<Duration> {
+        let due = self.start + Duration::from_secs_f64(self.steps as f64 / self.rate as f64);
+        let now = Instant::now();
+        if now.saturating_duration_since(due) > SLACK {
+            self.start = now;
+            self.steps = 0;
+        }
+        due.checked_duration_since(now)
+            .filter(|ahead| !ahead.is_zero())
+    }
+
+    // Counts the instruction that was due as run
+    pub fn step(&mut self) {
+        self.steps += 1;
+    }
+
+    // Starts the schedule over from now, after a pause
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+        self.steps = 0;
+    }
+}
@@ -0,0 +1,534 @@
+// The optimizations, each a rewrite of the token stream that `Program` runs in order.
+//
+// `fold` merges runs of `+-` and of `<>` into one instruction, `clear-loops` turns `[-]` and
+// `[+]` into SET and folds the increments after it in, `scan` turns loops that only move the
+// pointer, like `[>]` or `[<<]`, into a single SCN that moves until it finds a zero cell, and
+// `unroll` replaces loops whose number of iterations is known, like the `[>++++<-]` in
+// `+++++[>++++<-]`, with their effect, and `propagate` uses what `propagate::analyze` knows
+// about cell values to store constants, drop loops that never run and work out the start of
+// the program ahead of time.
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::program::Span;
+use crate::propagate::{self, Fact, Prefix};
+use crate::token::BfToken;
+
+pub trait Pass {
+    // What `--passes` calls it
+    fn name(&self) -> &'static str;
+
+    // Rewrites `tokens`, keeping `spans` in step so each instruction still knows its source
+    fn run(&self, tokens: &mut Vec<BfToken>, spans: &mut Vec<Span>);
+}
+
+// Every pass, in the order the optimization levels add them
+pub const NAMES: [&str; 5] = ["fold", "clear-loops", "scan", "unroll", "propagate"];
+
+// The passes that work out values from a blank tape, see `Unroll`, and go wrong on one that
+// starts out holding something
+pub const BLANK: [&str; 2] = ["unroll", "propagate"];
+
+// `passes` less the `BLANK` ones, for a program run on a preloaded tape
+pub fn without_blank(passes: Vec<Box<dyn Pass>>) -> Vec<Box<dyn Pass>> {
+    passes
+        .into_iter()
+        .filter(|pass| !BLANK.contains(&pass.name()))
+        .collect()
+}
+
+// The passes an optimization level runs: none at 0, `fold` at 1, `clear-loops` too at 2 and
+// everything from 3 on
+pub fn for_level(level: u8) -> Vec<Box<dyn Pass>> {
+    let count = match level {
+        0..=2 => level as usize,
+        _ => NAMES.len(),
+    };
+    NAMES
+        .iter()
+        .take(count)
+        .map(|name| by_name(name).unwrap())
+        .collect()
+}
+
+// The passes in a comma separated list, in the order given
+pub fn parse(list: &str) -> Result<Vec<Box<dyn Pass>>, String> {
+    if list == "none" {
+        return Ok(vec![]);
+    }
+    list.split(',')
+        .map(|name| {
+            by_name(name.trim()).ok_or_else(|| {
+                format!(
+                    "Unknown pass {name:?}, expected none or some of {}",
+                    NAMES.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+fn by_name(name: &str) -> Option<Box<dyn Pass>> {
+    match name {
+        "fold" => Some(Box::new(Fold)),
+        "clear-loops" => Some(Box::new(ClearLoops)),
+        "scan" => Some(Box::new(Scan)),
+        "unroll" => Some(Box::new(Unroll)),
+        "propagate" => Some(Box::new(Propagate)),
+        _ => None,
+    }
+}
+
+// What running one pass did, for verbose output
+#[derive(Debug, Clone)]
+pub struct PassStats {
+    pub name: &'static str,
+    pub elapsed: Duration,
+    pub before: usize, // Instructions going in
+    pub after: usize,  // and coming out
+}
+
+pub fn run(
+    passes: &[Box<dyn Pass>],
+    tokens: &mut Vec<BfToken>,
+    spans: &mut Vec<Span>,
+) -> Vec<PassStats> {
+    passes
+        .iter()
+        .map(|pass| {
+            let before = tokens.len();
+            let start = Instant::now();
+            pass.run(tokens, spans);
+            let stats = PassStats {
+                name: pass.name(),
+                elapsed: start.elapsed(),
+                before,
+                after: tokens.len(),
+            };
+            telemetry!(crate::telemetry::event(
+                crate::telemetry::Level::Debug,
+                "pass",
+                &[
+                    ("name", stats.name.into()),
+                    ("before", stats.before.into()),
+                    ("after", stats.after.into()),
+                    ("elapsed_us", (stats.elapsed.as_micros() as u64).into()),
+                ],
+            ););
+            stats
+        })
+        .collect()
+}
+
+pub struct Fold;
+
+impl Pass for Fold {
+    fn name(&self) -> &'static str {
+        "fold"
+    }
+
+    fn run(&self, tokens: &mut Vec<BfToken>, spans: &mut Vec<Span>) {
+        let mut out: Vec<BfToken> = Vec::with_capacity(tokens.len());
+        let mut out_spans: Vec<Span> = Vec::with_capacity(spans.len());
+        for (&token, &span) in tokens.iter().zip(spans.iter()) {
+            match out.last_mut() {
+                // Combine successive instances of the same operation into a single instance with the sum of their values.
+                Some(last) if last.same_kind(&token) => {
+                    *last += token;
+                    out_spans.last_mut().unwrap().end = span.end;
+                }
+                _ => {
+                    out.push(token);
+                    out_spans.push(span);
+                }
+            }
+        }
+        *tokens = out;
+        *spans = out_spans;
+    }
+}
+
+pub struct ClearLoops;
+
+impl Pass for ClearLoops {
+    fn name(&self) -> &'static str {
+        "clear-loops"
+    }
+
+    fn run(&self, tokens: &mut Vec<BfToken>, spans: &mut Vec<Span>) {
+        clear_loops(tokens, spans);
+    }
+}
+
+// Replace `[-]` and `[+]` (or any odd step, which hits zero eventually) with a single SET,
+// absorbing any increments that follow it.
+pub(crate) fn clear_loops(tokens: &mut Vec<BfToken>, spans: &mut Vec<Span>) {
+    let mut out: Vec<BfToken> = Vec::with_capacity(tokens.len());
+    let mut out_spans: Vec<Span> = Vec::with_capacity(spans.len());
+    for (&token, &span) in tokens.iter().zip(spans.iter()) {
+        let len = out.len();
+        match (&out[len.saturating_sub(2)..], token) {
+            ([BfToken::JUM, BfToken::CEL(n)], BfToken::BAC) if n % 2 != 0 => {
+                let start = out_spans[len - 2].start;
+                out.truncate(len - 2);
+                out_spans.truncate(len - 2);
+                out.push(BfToken::SET(0));
+                out_spans.push(Span {
+                    start,
+                    end: span.end,
+                });
+            }
+            (_, BfToken::CEL(n)) if matches!(out.last(), Some(BfToken::SET(_))) => {
+                if let Some(BfToken::SET(value)) = out.last_mut() {
+                    *value += n;
+                }
+                out_spans.last_mut().unwrap().end = span.end;
+            }
+            _ => {
+                out.push(token);
+                out_spans.push(span);
+            }
+        }
+    }
+    *tokens = out;
+    *spans = out_spans;
+}
+
+pub struct Scan;
+
+impl Pass for Scan {
+    fn name(&self) -> &'static str {
+        "scan"
+    }
+
+    // A loop of nothing but moves that don't cancel out, `[><]` would spin in place so it
+    // stays a loop
+    fn run(&self, tokens: &mut Vec<BfToken>, spans: &mut Vec<Span>) {
+        let mut out: Vec<BfToken> = Vec::with_capacity(tokens.len());
+        let mut out_spans: Vec<Span> = Vec::with_capacity(spans.len());
+        for (&token, &span) in tokens.iter().zip(spans.iter()) {
+            if token == BfToken::BAC {
+                let moves = out
+                    .iter()
+                    .rev()
+                    .take_while(|token| matches!(token, BfToken::MOV(_)))
+                    .count();
+                let open = out.len() - moves;
+                let distance: isize = out[open..]
+                    .iter()
+                    .map(|token| match token {
+                        BfToken::MOV(n) => *n,
+                        _ => 0,
+                    })
+                    .sum();
+                if moves > 0 && open > 0 && out[open - 1] == BfToken::JUM && distance != 0 {
+                    let start = out_spans[open - 1].start;
+                    out.truncate(open - 1);
+                    out_spans.truncate(open - 1);
+                    out.push(BfToken::SCN(distance));
+                    out_spans.push(Span {
+                        start,
+                        end: span.end,
+                    });
+                    continue;
+                }
+            }
+            out.push(token);
+            out_spans.push(span);
+        }
+        *tokens = out;
+        *spans = out_spans;
+    }
+}
+
+// Loops that write output or set cells are only copied out while they stay this short
+const UNROLL_LIMIT: usize = 64;
+
+pub struct Unroll;
+
+impl Pass for Unroll {
+    fn name(&self) -> &'static str {
+        "unroll"
+    }
+
+    // Follows the cell values that are known from the start of the program, where every cell
+    // is zero, up to the first loop that could run any number of times
+    fn run(&self, tokens: &mut Vec<BfToken>, spans: &mut Vec<Span>) {
+        let mut out: Vec<BfToken> = Vec::with_capacity(tokens.len());
+        let mut out_spans: Vec<Span> = Vec::with_capacity(spans.len());
+        let mut known = Known::new();
+        let mut idx = 0;
+        while idx < tokens.len() {
+            let token = tokens[idx];
+            if token == BfToken::JUM {
+                if let Some((close, count)) = counted_loop(tokens, idx, &known) {
+                    let body = &tokens[idx + 1..close];
+                    let plain = body
+                        .iter()
+                        .all(|token| matches!(token, BfToken::CEL(_) | BfToken::MOV(_)));
+                    let whole = Span {
+                        start: spans[idx].start,
+                        end: spans[close].end,
+                    };
+                    if plain {
+                        // Each cell the body touches gets the body's change `count` times
+                        let mut offset = 0;
+                        for (at, change) in changes(body) {
+                            let total = change * count as isize;
+                            if total == 0 {
+                                continue;
+                            }
+                            if at != offset {
+                                out.push(BfToken::MOV(at - offset));
+                                out_spans.push(whole);
+                            }
+                            out.push(BfToken::CEL(total));
+                            out_spans.push(whole);
+                            known.shift(at - offset);
+                            known.apply(BfToken::CEL(total));
+                            offset = at;
+                        }
+                        if offset != 0 {
+                            out.push(BfToken::MOV(-offset));
+                            out_spans.push(whole);
+                            known.shift(-offset);
+                        }
+                        idx = close + 1;
+                        continue;
+                    }
+                    if count as usize * body.len() <= UNROLL_LIMIT {
+                        for _ in 0..count {
+                            out.extend(body);
+                            out_spans.extend(&spans[idx + 1..close]);
+                            body.iter().for_each(|&token| known.apply(token));
+                        }
+                        idx = close + 1;
+                        continue;
+                    }
+                }
+            }
+            known.apply(token);
+            out.push(token);
+            out_spans.push(spans[idx]);
+            idx += 1;
+        }
+        *tokens = out;
+        *spans = out_spans;
+    }
+}
+
+// The `]` of the loop opening at `open` and how many times it runs, when its body has no
+// loops or input, ends where it started and counts its cell down from a known value to zero
+fn counted_loop(tokens: &[BfToken], open: usize, known: &Known) -> Option<(usize, u64)> {
+    let len = tokens[open + 1..]
+        .iter()
+        .take_while(|token| {
+            matches!(
+                token,
+                BfToken::CEL(_) | BfToken::MOV(_) | BfToken::SET(_) | BfToken::OUT
+            )
+        })
+        .count();
+    let close = open + 1 + len;
+    if tokens.get(close) != Some(&BfToken::BAC) {
+        return None;
+    }
+    let body = &tokens[open + 1..close];
+    let mut offset = 0;
+    let mut step = 0;
+    for token in body {
+        match token {
+            BfToken::MOV(n) => offset += n,
+            BfToken::CEL(n) if offset == 0 => step += n,
+            BfToken::SET(_) if offset == 0 => return None,
+            _ => (),
+        }
+    }
+    let start = known.get()?;
+    // Counting up would only reach zero by wrapping, which depends on the cell size
+    match (offset, step) {
+        (0, _) if start == 0 => Some((close, 0)),
+        (0, step) if step < 0 && start % -step as i64 == 0 => {
+            Some((close, (start / -step as i64) as u64))
+        }
+        _ => None,
+    }
+}
+
+// The total change a body of moves and increments makes to each cell it touches, by offset
+// and in the order it first touches them
+fn changes(body: &[BfToken]) -> Vec<(isize, isize)> {
+    let mut changes: Vec<(isize, isize)> = vec![];
+    let mut offset = 0;
+    for token in body {
+        match token {
+            BfToken::MOV(n) => offset += n,
+            BfToken::CEL(n) => match changes.iter_mut().find(|(at, _)| *at == offset) {
+                Some((_, change)) => *change += n,
+                None => changes.push((offset, *n)),
+            },
+            _ => (),
+        }
+    }
+    changes
+}
+
+// The cell values known at one point of the program. Values are only kept while they fit
+// every cell size, so adding to them never depends on where cells wrap.
+struct Known {
+    cells: HashMap<isize, Option<i64>>, // By offset from the start, `None` for unknown
+    pointer: isize,
+    rest_zero: bool, // Cells not in `cells` are still zero
+}
+
+impl Known {
+    fn new() -> Self {
+        Self {
+            cells: HashMap::new(),
+            pointer: 0,
+            rest_zero: true,
+        }
+    }
+
+    // The current cell
+    fn get(&self) -> Option<i64> {
+        match self.cells.get(&self.pointer) {
+            Some(&value) => value,
+            None if self.rest_zero => Some(0),
+            None => None,
+        }
+    }
+
+    fn set(&mut self, value: Option<i64>) {
+        let value = value.filter(|value| (0..=u8::MAX as i64).contains(value));
+        self.cells.insert(self.pointer, value);
+    }
+
+    fn shift(&mut self, n: isize) {
+        self.pointer += n;
+    }
+
+    // Nothing is known any more, apart from what the caller sets next
+    fn forget(&mut self) {
+        self.cells.clear();
+        self.rest_zero = false;
+    }
+
+    fn apply(&mut self, token: BfToken) {
+        match token {
+            BfToken::CEL(n) => self.set(self.get().map(|value| value + n as i64)),
+            BfToken::SET(n) => self.set(Some(n as i64)),
+            BfToken::MOV(n) => self.shift(n),
+            BfToken::ACC | BfToken::NUM | BfToken::RND | BfToken::EXT(_) => self.set(None),
+            // The child goes on a cell to the right of the parent, so from here offsets from
+            // the start mean a different cell in each thread
+            BfToken::FRK => self.forget(),
+            // A loop body can run any number of times, and once done only its cell is known
+            BfToken::JUM => self.forget(),
+            BfToken::BAC | BfToken::SCN(_) => {
+                self.forget();
+                self.set(Some(0));
+            }
+            BfToken::OUT | BfToken::DEC | BfToken::NAN => (),
+        }
+    }
+}
+
+pub struct Propagate;
+
+impl Pass for Propagate {
+    fn name(&self) -> &'static str {
+        "propagate"
+    }
+
+    // A `+-` or SET on a known value becomes a SET of the result, dropped when that is the
+    // value already there, and a loop on a cell known to be zero goes
+    fn run(&self, tokens: &mut Vec<BfToken>, spans: &mut Vec<Span>) {
+        let facts = propagate::analyze(tokens);
+        let jumps = propagate::jumps(tokens);
+        let mut out: Vec<BfToken> = Vec::with_capacity(tokens.len());
+        let mut out_spans: Vec<Span> = Vec::with_capacity(spans.len());
+        let mut idx = 0;
+        let prefix = &facts.prefix;
+        // A fixed tape would stop the prefix at its first move left of the start, which the
+        // replacement might make at a different point
+        if prefix.len > 0 && prefix.lowest >= 0 {
+            let replacement = precomputed(prefix);
+            if replacement.len() < prefix.len {
+                let whole = Span {
+                    start: spans[0].start,
+                    end: spans[prefix.len - 1].end,
+                };
+                out_spans.extend(std::iter::repeat_n(whole, replacement.len()));
+                out = replacement;
+                idx = prefix.len;
+            }
+        }
+        while idx < tokens.len() {
+            let stored = match (tokens[idx], facts.cells[idx]) {
+                (BfToken::JUM, Fact::Const(0)) => {
+                    idx = jumps[idx] + 1;
+                    continue;
+                }
+                (BfToken::CEL(n), Fact::Const(value)) => {
+                    u8::try_from(value as isize + n).ok().map(|to| (value, to))
+                }
+                (BfToken::SET(n), Fact::Const(value)) => u8::try_from(n).ok().map(|to| (value, to)),
+                _ => None,
+            };
+            match stored {
+                Some((value, to)) if value == to => (),
+                // Nothing jumps in between, so a SET right before this one is overwritten
+                Some((_, to)) if matches!(out.last(), Some(BfToken::SET(_))) => {
+                    *out.last_mut().unwrap() = BfToken::SET(to as isize);
+                    out_spans.last_mut().unwrap().end = spans[idx].end;
+                }
+                Some((_, to)) => {
+                    out.push(BfToken::SET(to as isize));
+                    out_spans.push(spans[idx]);
+                }
+                None => {
+                    out.push(tokens[idx]);
+                    out_spans.push(spans[idx]);
+                }
+            }
+            idx += 1;
+        }
+        *tokens = out;
+        *spans = out_spans;
+    }
+}
+
+// Straight-line code with the effect of `prefix`: its output written from the starting cell,
+// then the cells it leaves behind
+fn precomputed(prefix: &Prefix) -> Vec<BfToken> {
+    let mut out = vec![];
+    let mut here = 0;
+    for &byte in &prefix.output {
+        if byte != here {
+            out.push(BfToken::SET(byte as isize));
+            here = byte;
+        }
+        out.push(BfToken::OUT);
+    }
+    let mut cells = prefix.cells.clone();
+    if here != 0 && !cells.iter().any(|&(offset, _)| offset == 0) {
+        cells.push((0, 0));
+        cells.sort();
+    }
+    let mut pointer = 0;
+    for (offset, value) in cells {
+        if offset == 0 && value == here || offset != 0 && value == 0 {
+            continue;
+        }
+        if offset != pointer {
+            out.push(BfToken::MOV(offset - pointer));
+            pointer = offset;
+        }
+        out.push(BfToken::SET(value as isize));
+    }
+    if prefix.pointer != pointer {
+        out.push(BfToken::MOV(prefix.pointer - pointer));
+    }
+    out
+}
@@ -0,0 +1,154 @@
+// Chains programs together like shell pipes, each stage's output is the next one's input.
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::error::BfError;
+use crate::interpreter::Interpreter;
+use crate::program::Program;
+use crate::settings::Settings;
+
+pub struct Pipeline {
+    stages: Vec<Program>,
+    settings: Settings,
+}
+
+impl Pipeline {
+    pub fn new(stages: Vec<Program>) -> Self {
+        Self::with_settings(stages, Settings::default())
+    }
+
+    // Every stage runs with the same settings
+    pub fn with_settings(stages: Vec<Program>, settings: Settings) -> Self {
+        Self { stages, settings }
+    }
+
+    fn stage<R: Read, W: Write>(
+        &self,
+        program: &Program,
+        input: R,
+        output: W,
+    ) -> Result<(), BfError> {
+        Interpreter::with_settings(program.clone(), &self.settings, input, output).run()
+    }
+
+    // Runs the stages one after another, buffering each intermediate output in memory.
+    pub fn run<R: Read, W: Write>(&self, input: R, mut output: W) -> Result<(), BfError> {
+        let Some((last, rest)) = self.stages.split_last() else {
+            io::copy(&mut { input }, &mut output)?;
+            return Ok(());
+        };
+
+        let mut data: Option<Vec<u8>> = None;
+        let mut input = Some(input);
+        for program in rest {
+            let mut buffer = vec![];
+            match data.take() {
+                Some(data) => self.stage(program, &data[..], &mut buffer)?,
+                None => self.stage(program, input.take().unwrap(), &mut buffer)?,
+            }
+            data = Some(buffer);
+        }
+        match data {
+            Some(data) => self.stage(last, &data[..], output),
+            None => self.stage(last, input.unwrap(), output),
+        }
+    }
+
+    // Runs every stage on its own thread, streaming bytes between them as they are produced.
+    pub fn run_concurrent<R, W>(&self, input: R, output: W) -> Result<(), BfError>
+    where
+        R: Read + Send,
+        W: Write + Send,
+    {
+        let Some((first, rest)) = self.stages.split_first() else {
+            return self.run(input, output);
+        };
+        if rest.is_empty() {
+            return self.stage(first, input, output);
+        }
+
+        thread::scope(|scope| {
+            let mut handles = vec![];
+            let (sender, mut receiver) = channel();
+            handles.push(scope.spawn(move || {
+                let writer = BufWriter::new(ChannelWriter(sender));
+                self.stage(first, input, writer)
+            }));
+
+            for program in &rest[..rest.len() - 1] {
+                let (sender, next) = channel();
+                let reader = ChannelReader::new(std::mem::replace(&mut receiver, next));
+                handles.push(scope.spawn(move || {
+                    let writer = BufWriter::new(ChannelWriter(sender));
+                    self.stage(program, reader, writer)
+                }));
+            }
+
+            let last = rest.last().unwrap();
+            let reader = ChannelReader::new(receiver);
+            handles.push(scope.spawn(move || self.stage(last, reader, output)));
+
+            // A stage stopping early breaks the pipe for the ones feeding it, like `yes | head`
+            let last = handles.len() - 1;
+            for (idx, handle) in handles.into_iter().enumerate() {
+                match handle.join().unwrap() {
+                    Err(BfError::Io(err)) if err.kind() == ErrorKind::BrokenPipe && idx < last => {}
+                    result => result?,
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+// Sends written bytes to the next stage of a concurrent pipeline.
+struct ChannelWriter(Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::from(ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Receives bytes from the previous stage, reading EOF once it hangs up.
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            chunk: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
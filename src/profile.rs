@@ -0,0 +1,185 @@
+// Where the time of a run goes, by instruction and by loop, I/O included. Counts alone can't
+// tell a `.` that writes to a slow pipe or a scan over a long stretch of tape from an ADD that
+// just runs often.
+//
+// Every instruction is timed with the cheapest clock there is, the TSC on x86_64 and `Instant`
+// elsewhere. Ticks are only turned into time once the run is over, by how many the whole run
+// took, so the TSC's rate never needs to be known.
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::ir::mnemonic;
+use crate::program::Program;
+use crate::token::BfToken;
+
+pub struct Profiler {
+    ticks: Vec<u64>, // Spent in each instruction
+    counts: Vec<u64>,
+    current: Option<(usize, u64)>, // The instruction being timed and when it started
+    #[cfg(not(target_arch = "x86_64"))]
+    epoch: Instant, // Where `Instant` ticks count from
+    started: Option<Instant>,      // When the current run began
+    elapsed: Duration,
+    total: u64, // Ticks of every instruction timed
+}
+
+impl Profiler {
+    pub fn new(program: &Program) -> Self {
+        Self {
+            ticks: vec![0; program.len()],
+            counts: vec![0; program.len()],
+            current: None,
+            #[cfg(not(target_arch = "x86_64"))]
+            epoch: Instant::now(),
+            started: None,
+            elapsed: Duration::ZERO,
+            total: 0,
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn now(&self) -> u64 {
+        // Reading the TSC has no preconditions
+        unsafe { std::arch::x86_64::_rdtsc() }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    // Starts timing the instruction at `ip`, which ends the one before
+    pub(crate) fn enter(&mut self, ip: usize) {
+        let now = self.now();
+        self.started.get_or_insert_with(Instant::now);
+        self.end(now);
+        self.current = Some((ip, now));
+        self.counts[ip] += 1;
+    }
+
+    // Ends the last instruction, called when a run stops for any reason
+    pub(crate) fn pause(&mut self) {
+        let now = self.now();
+        self.end(now);
+        if let Some(start) = self.started.take() {
+            self.elapsed += start.elapsed();
+        }
+    }
+
+    fn end(&mut self, now: u64) {
+        if let Some((ip, start)) = self.current.take() {
+            let spent = now.saturating_sub(start);
+            self.ticks[ip] += spent;
+            self.total += spent;
+        }
+    }
+
+    pub fn report(&self, program: &Program) -> Profile {
+        let time = |ticks: u64| match self.total {
+            0 => Duration::ZERO,
+            total => self.elapsed.mul_f64(ticks as f64 / total as f64),
+        };
+        let instructions = (0..program.len())
+            .map(|ip| Sample {
+                time: time(self.ticks[ip]),
+                count: self.counts[ip],
+            })
+            .collect();
+        // Loops include everything nested in them
+        let loops = (0..program.len())
+            .filter(|&ip| program.tokens[ip] == BfToken::JUM)
+            .map(|open| {
+                let close = program.jumps[open];
+                LoopTime {
+                    open,
+                    close,
+                    time: time(self.ticks[open..=close].iter().sum()),
+                    entered: self.counts[open],
+                }
+            })
+            .collect();
+        Profile {
+            instructions,
+            loops,
+            elapsed: time(self.total),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub time: Duration,
+    pub count: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoopTime {
+    pub open: usize,
+    pub close: usize,
+    pub time: Duration,
+    pub entered: u64, // Times the `[` was reached, not iterations
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub instructions: Vec<Sample>, // By instruction pointer
+    pub loops: Vec<LoopTime>,      // In order of their `[`
+    pub elapsed: Duration,
+}
+
+impl Profile {
+    // A table of the `top` slowest instructions and loops, pointing into `source`, which has
+    // to be what the program was compiled from
+    pub fn render(&self, program: &Program, source: &str, top: usize) -> String {
+        let total = self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE);
+        let percent = |time: Duration| time.as_secs_f64() / total * 100.0;
+        let at = |ip: usize| {
+            let (line, col) = crate::diagnostic::line_col(source, program.spans[ip].start);
+            format!("{}:{}", line + 1, col + 1)
+        };
+        let mut out = String::new();
+        let _ = writeln!(out, "Profile: {:?} in instructions", self.elapsed);
+        let mut slowest: Vec<usize> = (0..self.instructions.len()).collect();
+        slowest.sort_by_key(|&ip| std::cmp::Reverse(self.instructions[ip].time));
+        let _ = writeln!(
+            out,
+            "  {:>6}  {:>12}  {:>12}  {:>9}  instruction",
+            "share", "time", "count", "each"
+        );
+        for &ip in slowest.iter().take(top) {
+            let sample = self.instructions[ip];
+            if sample.count == 0 {
+                break;
+            }
+            let each = Duration::from_secs_f64(sample.time.as_secs_f64() / sample.count as f64);
+            let _ = writeln!(
+                out,
+                "  {:>5.1}%  {:>12?}  {:>12}  {:>9?}  {ip} {} at {}",
+                percent(sample.time),
+                sample.time,
+                sample.count,
+                each,
+                mnemonic(program, ip),
+                at(ip)
+            );
+        }
+        let mut loops: Vec<&LoopTime> = self.loops.iter().filter(|l| l.entered > 0).collect();
+        loops.sort_by_key(|l| std::cmp::Reverse(l.time));
+        if !loops.is_empty() {
+            let _ = writeln!(out, "  loops");
+        }
+        for l in loops.iter().take(top) {
+            let _ = writeln!(
+                out,
+                "  {:>5.1}%  {:>12?}  {:>12}  instructions {}..={} at {}",
+                percent(l.time),
+                l.time,
+                l.entered,
+                l.open,
+                l.close,
+                at(l.open)
+            );
+        }
+        out
+    }
+}
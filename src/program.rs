@@ -0,0 +1,268 @@
+use crate::error::BfError;
+use crate::extension::Extensions;
+use crate::passes::{self, Pass, PassStats};
+use crate::settings::{Settings, MAX_DEPTH};
+use crate::token::BfToken;
+
+// The range of source bytes a token was built from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+// A tokenized program along with its jump table and source positions.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub tokens: Vec<BfToken>,
+    pub jumps: Vec<usize>,
+    pub spans: Vec<Span>,
+}
+
+impl Program {
+    // Converts a string of Brainfuck code to a vector of BfToken instances and a vector of jump positions.
+    pub fn from_source(code: &str) -> Result<Self, BfError> {
+        Self::compile(code, Settings::default().opt_level)
+    }
+
+    // Same as `from_source`, opt level 0 keeps one token per source character, 2 also folds
+    // clear loops.
+    pub fn compile(code: &str, opt_level: u8) -> Result<Self, BfError> {
+        Self::with_extensions(code, opt_level, Extensions::default())
+    }
+
+    // Same as `compile`, also reading the commands of the enabled `extensions`
+    pub fn with_extensions(
+        code: &str,
+        opt_level: u8,
+        extensions: Extensions,
+    ) -> Result<Self, BfError> {
+        let passes = passes::for_level(opt_level);
+        Self::with_passes(code, extensions, &passes).map(|(program, _)| program)
+    }
+
+    // Same as `with_extensions`, at the optimization level and within the nesting limit of
+    // `settings`
+    pub fn with_settings(code: &str, settings: &Settings) -> Result<Self, BfError> {
+        let passes = passes::for_level(settings.opt_level);
+        Self::with_max_depth(code, settings.extensions, &passes, settings.max_depth)
+            .map(|(program, _)| program)
+    }
+
+    // Reads one token per command, leaving out comment loops (see `comment_loops`), then runs
+    // `passes` over them in order, reporting on each
+    pub fn with_passes(
+        code: &str,
+        extensions: Extensions,
+        passes: &[Box<dyn Pass>],
+    ) -> Result<(Self, Vec<PassStats>), BfError> {
+        Self::with_max_depth(code, extensions, passes, Some(MAX_DEPTH))
+    }
+
+    // Same as `with_passes`, failing on loops nested deeper than `max_depth`, or never with None
+    pub fn with_max_depth(
+        code: &str,
+        extensions: Extensions,
+        passes: &[Box<dyn Pass>],
+        max_depth: Option<usize>,
+    ) -> Result<(Self, Vec<PassStats>), BfError> {
+        Self::with_tape_start(code, extensions, passes, max_depth, true)
+    }
+
+    // Same as `with_max_depth`, for a tape that may not start out `blank`. On one that doesn't
+    // a loop opening the program can run, so it's kept even when it reads as a comment.
+    pub fn with_tape_start(
+        code: &str,
+        extensions: Extensions,
+        passes: &[Box<dyn Pass>],
+        max_depth: Option<usize>,
+        blank: bool,
+    ) -> Result<(Self, Vec<PassStats>), BfError> {
+        telemetry!(
+            let _span = crate::telemetry::span("compile");
+            let start = std::time::Instant::now();
+        );
+        let (mut tokens, mut spans) = tokenize(code, extensions);
+        // Brackets and nesting are checked as written, before passes can fold loops away
+        let jumps = find_jumps(&tokens, &spans, max_depth)?;
+        let mut stripped = vec![false; tokens.len()];
+        for (open, close) in find_comments(code, &tokens, &spans, &jumps, extensions, blank) {
+            stripped[open..=close].fill(true);
+        }
+        let mut keep = stripped.iter().map(|&stripped| !stripped);
+        tokens.retain(|_| keep.next().unwrap());
+        let mut keep = stripped.iter().map(|&stripped| !stripped);
+        spans.retain(|_| keep.next().unwrap());
+        let stats = passes::run(passes, &mut tokens, &mut spans);
+
+        let jumps = find_jumps(&tokens, &spans, None)?;
+        let program = Self {
+            tokens,
+            jumps,
+            spans,
+        };
+        telemetry!(crate::telemetry::event(
+            crate::telemetry::Level::Debug,
+            "compiled",
+            &[
+                ("instructions", program.len().into()),
+                ("elapsed_us", (start.elapsed().as_micros() as u64).into()),
+            ],
+        ););
+        Ok((program, stats))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    // The `[` of the innermost loop whose body holds the instruction at `ip`, counting its
+    // `]` as inside and its `[` as outside
+    pub fn enclosing_loop(&self, ip: usize) -> Option<usize> {
+        let mut idx = ip.min(self.len());
+        while idx > 0 {
+            idx -= 1;
+            match self.tokens[idx] {
+                BfToken::JUM if self.jumps[idx] >= ip => return Some(idx),
+                // Step over whole loops that end before `ip`
+                BfToken::BAC => idx = self.jumps[idx],
+                _ => (),
+            }
+        }
+        None
+    }
+
+    // How many loops the instruction at `ip` is nested in
+    pub fn depth(&self, ip: usize) -> usize {
+        std::iter::successors(self.enclosing_loop(ip), |&open| self.enclosing_loop(open)).count()
+    }
+}
+
+// The source of every comment loop, the idiom of writing prose inside a loop that cannot
+// run because it opens the program or comes right after another loop, where the cell is
+// zero. Only loops holding something besides commands and whitespace count, so they are
+// left out of programs without changing what they do. Fails as compiling `code` would.
+pub fn comment_loops(code: &str, extensions: Extensions) -> Result<Vec<Span>, BfError> {
+    let (tokens, spans) = tokenize(code, extensions);
+    let jumps = find_jumps(&tokens, &spans, None)?;
+    Ok(
+        find_comments(code, &tokens, &spans, &jumps, extensions, true)
+            .into_iter()
+            .map(|(open, close)| Span {
+                start: spans[open].start,
+                end: spans[close].end,
+            })
+            .collect(),
+    )
+}
+
+// One token per command in `code`
+fn tokenize(code: &str, extensions: Extensions) -> (Vec<BfToken>, Vec<Span>) {
+    let mut tokens: Vec<BfToken> = vec![];
+    let mut spans: Vec<Span> = vec![];
+    for (offset, c) in code.char_indices() {
+        let next = extensions.token(c).unwrap_or(BfToken::from(c));
+        // Filter out invalid operations
+        if !matches!(next, BfToken::NAN) {
+            tokens.push(next);
+            spans.push(Span {
+                start: offset,
+                end: offset + c.len_utf8(),
+            });
+        }
+    }
+    (tokens, spans)
+}
+
+// The brackets of the comment loops among freshly read `tokens`, run on a tape that starts
+// out `blank` or not
+fn find_comments(
+    code: &str,
+    tokens: &[BfToken],
+    spans: &[Span],
+    jumps: &[usize],
+    extensions: Extensions,
+    blank: bool,
+) -> Vec<(usize, usize)> {
+    let commands = extensions.commands();
+    let mut loops = vec![];
+    let mut zero = blank; // The cell is known to be zero
+    let mut idx = 0;
+    while idx < tokens.len() {
+        match tokens[idx] {
+            BfToken::JUM if zero => {
+                let close = jumps[idx];
+                let text = &code[spans[idx].end..spans[close].start];
+                if text
+                    .chars()
+                    .any(|c| !c.is_whitespace() && !commands.contains(c))
+                {
+                    loops.push((idx, close));
+                    // Skipped, so the cell is still zero after it
+                    idx = close + 1;
+                    continue;
+                }
+                zero = false;
+            }
+            BfToken::BAC => zero = true,
+            _ => zero = false,
+        }
+        idx += 1;
+    }
+    loops
+}
+
+// Create a map of the jumps for the bracket commands, the open loops never growing past
+// `max_depth`
+pub(crate) fn find_jumps(
+    tokens: &[BfToken],
+    spans: &[Span],
+    max_depth: Option<usize>,
+) -> Result<Vec<usize>, BfError> {
+    let mut jumps = vec![0; tokens.len()];
+    let mut queue = vec![];
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            BfToken::JUM => {
+                if let Some(limit) = max_depth.filter(|&limit| queue.len() == limit) {
+                    return Err(BfError::NestingLimit(spans[idx].start, limit));
+                }
+                queue.push(idx);
+            }
+            BfToken::BAC => {
+                let temp = queue
+                    .pop()
+                    .ok_or(BfError::UnopenedBracket(spans[idx].start))?;
+
+                // Write the jump destination to the index of the token
+                jumps[temp] = idx;
+                jumps[idx] = temp;
+            }
+            _ => (),
+        }
+    }
+
+    match queue.pop() {
+        Some(idx) => Err(BfError::UnclosedBracket(spans[idx].start)),
+        None => Ok(jumps),
+    }
+}
+
+// Splits a file in the `PROGRAM!INPUT` convention at its first `!` outside any loop, giving
+// the program and, when there is a `!`, everything after it as input
+pub fn split_bang(code: &str) -> (&str, Option<&str>) {
+    let mut depth = 0usize;
+    for (idx, byte) in code.bytes().enumerate() {
+        match byte {
+            b'[' => depth += 1,
+            b']' => depth = depth.saturating_sub(1),
+            b'!' if depth == 0 => return (&code[..idx], Some(&code[idx + 1..])),
+            _ => (),
+        }
+    }
+    (code, None)
+}
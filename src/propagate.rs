@@ -0,0 +1,347 @@
+// Constant propagation: what is known about the current cell before each instruction, found
+// by running the program on cells that may hold unknown values.
+//
+// Cells start at zero. A loop whose condition is known runs as it would for real, within a
+// budget of steps. Any other loop may run any number of times: if its body comes back to the
+// cell it started on, the cells it writes become unknown and the rest keep their values,
+// otherwise nothing is known after it. Values are only trusted in 0..=255, so no fact depends
+// on the cell size. Forking programs aren't analyzed, every thread sees a different tape, and
+// neither are loops nested past `DEPTH`, each level of which runs on a stack frame of its own.
+//
+// Up to the first loop that can't be decided, or the first input, the program runs the same
+// way every time, so what that part writes and leaves on the tape is known too.
+use std::collections::HashMap;
+
+use crate::token::BfToken;
+
+// Steps of loops with known conditions run before giving up on running them
+const BUDGET: u64 = 1_000_000;
+// Nesting analyzed before giving up on the whole program
+const DEPTH: usize = 1_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fact {
+    Unreached,
+    Const(u8), // The value every time the instruction runs
+    Varies,
+}
+
+impl Fact {
+    fn merge(self, value: Option<u8>) -> Self {
+        match (self, value) {
+            (Self::Unreached, Some(value)) => Self::Const(value),
+            (Self::Const(known), Some(value)) if known == value => self,
+            _ => Self::Varies,
+        }
+    }
+}
+
+// The instructions at the start of a program that always do the same thing.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Prefix {
+    pub len: usize,              // Top-level instructions it covers, loops counting as one
+    pub output: Vec<u8>,         // What they write
+    pub cells: Vec<(isize, u8)>, // The cells they write and what they leave there, by offset
+    pub pointer: isize,          // Where they leave the pointer
+    pub lowest: isize,           // The leftmost cell they move to, below 0 would leave a fixed tape
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Facts {
+    pub cells: Vec<Fact>, // The current cell before each instruction
+    pub prefix: Prefix,
+}
+
+impl Facts {
+    pub fn known(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|fact| matches!(fact, Fact::Const(_)))
+            .count()
+    }
+}
+
+pub fn analyze(tokens: &[BfToken]) -> Facts {
+    let jumps = jumps(tokens);
+    if tokens.contains(&BfToken::FRK) || too_deep(tokens) {
+        return Facts {
+            cells: vec![Fact::Varies; tokens.len()],
+            prefix: Prefix::default(),
+        };
+    }
+    let mut analyzer = Analyzer {
+        tokens,
+        jumps: &jumps,
+        facts: vec![Fact::Unreached; tokens.len()],
+        state: State {
+            cells: HashMap::new(),
+            rest_zero: true,
+            pointer: 0,
+            lowest: 0,
+        },
+        budget: BUDGET,
+        exact: true,
+        output: vec![],
+    };
+    let mut prefix = None;
+    let mut idx = 0;
+    while idx < tokens.len() {
+        // Enough to go back to the state before this instruction, in case it is the first to
+        // leave anything to chance. Only loops and scans change more than the current cell.
+        let undo = match tokens[idx] {
+            _ if !analyzer.exact => None,
+            BfToken::JUM | BfToken::SCN(_) => Some(Undo::State(analyzer.state.clone())),
+            _ => Some(Undo::Cell(analyzer.state.cell())),
+        };
+        let written = analyzer.output.len();
+        let next = analyzer.step(idx);
+        if let (Some(undo), false) = (undo, analyzer.exact) {
+            let state = match undo {
+                Undo::State(state) => state,
+                Undo::Cell(cell) => {
+                    let mut state = analyzer.state.clone();
+                    state.restore(cell);
+                    state
+                }
+            };
+            prefix = Some(state.prefix(idx, &analyzer.output[..written]));
+        }
+        idx = next;
+    }
+    let prefix = prefix.unwrap_or_else(|| analyzer.state.prefix(tokens.len(), &analyzer.output));
+    Facts {
+        cells: analyzer.facts,
+        prefix,
+    }
+}
+
+// Where each bracket's partner is, for token streams that passes are still rewriting
+pub(crate) fn jumps(tokens: &[BfToken]) -> Vec<usize> {
+    let mut jumps = vec![0; tokens.len()];
+    let mut opens = vec![];
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            BfToken::JUM => opens.push(idx),
+            BfToken::BAC => {
+                let open = opens.pop().expect("brackets are balanced");
+                jumps[open] = idx;
+                jumps[idx] = open;
+            }
+            _ => (),
+        }
+    }
+    jumps
+}
+
+fn too_deep(tokens: &[BfToken]) -> bool {
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            BfToken::JUM if depth == DEPTH => return true,
+            BfToken::JUM => depth += 1,
+            BfToken::BAC => depth -= 1,
+            _ => (),
+        }
+    }
+    false
+}
+
+enum Undo {
+    State(State),
+    Cell((isize, Option<Option<u8>>)),
+}
+
+#[derive(Clone)]
+struct State {
+    cells: HashMap<isize, Option<u8>>, // By offset from the start, `None` for unknown
+    rest_zero: bool,                   // Cells not in `cells` are still zero
+    pointer: isize,
+    lowest: isize,
+}
+
+impl State {
+    fn get(&self) -> Option<u8> {
+        match self.cells.get(&self.pointer) {
+            Some(&value) => value,
+            None => self.rest_zero.then_some(0),
+        }
+    }
+
+    fn set(&mut self, value: Option<u8>) {
+        self.cells.insert(self.pointer, value);
+    }
+
+    // The current cell as stored, for `restore`
+    fn cell(&self) -> (isize, Option<Option<u8>>) {
+        (self.pointer, self.cells.get(&self.pointer).copied())
+    }
+
+    fn restore(&mut self, (pointer, value): (isize, Option<Option<u8>>)) {
+        match value {
+            Some(value) => self.cells.insert(pointer, value),
+            None => self.cells.remove(&pointer),
+        };
+    }
+
+    fn shift(&mut self, n: isize) {
+        self.pointer += n;
+        self.lowest = self.lowest.min(self.pointer);
+    }
+
+    fn forget(&mut self) {
+        self.cells.clear();
+        self.rest_zero = false;
+    }
+
+    // Only called while every cell is known
+    fn prefix(&self, len: usize, output: &[u8]) -> Prefix {
+        let mut cells: Vec<(isize, u8)> = self
+            .cells
+            .iter()
+            .map(|(&offset, value)| (offset, value.expect("cells are known")))
+            .collect();
+        cells.sort();
+        Prefix {
+            len,
+            output: output.to_vec(),
+            cells,
+            pointer: self.pointer,
+            lowest: self.lowest,
+        }
+    }
+}
+
+struct Analyzer<'a> {
+    tokens: &'a [BfToken],
+    jumps: &'a [usize],
+    facts: Vec<Fact>,
+    state: State,
+    budget: u64,
+    exact: bool, // Nothing has been left to chance yet
+    output: Vec<u8>,
+}
+
+impl Analyzer<'_> {
+    fn record(&mut self, idx: usize) {
+        self.facts[idx] = self.facts[idx].merge(self.state.get());
+    }
+
+    // Runs the instruction at `idx`, a whole loop for `[`, returning the next one
+    fn step(&mut self, idx: usize) -> usize {
+        self.record(idx);
+        self.budget = self.budget.saturating_sub(1);
+        let state = &mut self.state;
+        match self.tokens[idx] {
+            // Leaving 0..=255 makes the value depend on the cell size
+            BfToken::CEL(n) => {
+                let value = state.get().and_then(|value| add(value, n));
+                self.exact &= value.is_some();
+                state.set(value);
+            }
+            BfToken::SET(n) => {
+                let value = u8::try_from(n).ok();
+                self.exact &= value.is_some();
+                state.set(value);
+            }
+            BfToken::MOV(n) => state.shift(n),
+            // Stops at a known zero, or at latest where the cells written so far end
+            BfToken::SCN(n) => loop {
+                match state.get() {
+                    Some(0) => break,
+                    Some(_) => state.shift(n),
+                    None => {
+                        self.exact = false;
+                        state.forget();
+                        state.set(Some(0));
+                        break;
+                    }
+                }
+            },
+            BfToken::ACC | BfToken::NUM | BfToken::RND | BfToken::EXT(_) => {
+                self.exact = false;
+                state.set(None);
+            }
+            BfToken::OUT => match (self.exact, state.get()) {
+                (true, Some(byte)) => self.output.push(byte),
+                _ => self.exact = false,
+            },
+            BfToken::DEC => match (self.exact, state.get()) {
+                (true, Some(byte)) => self.output.extend(byte.to_string().bytes()),
+                _ => self.exact = false,
+            },
+            BfToken::JUM => {
+                self.run_loop(idx);
+                return self.jumps[idx] + 1;
+            }
+            BfToken::BAC | BfToken::FRK | BfToken::NAN => (),
+        }
+        idx + 1
+    }
+
+    fn run(&mut self, from: usize, to: usize) {
+        let mut idx = from;
+        while idx < to {
+            idx = self.step(idx);
+        }
+    }
+
+    fn run_loop(&mut self, open: usize) {
+        let close = self.jumps[open];
+        loop {
+            match self.state.get() {
+                Some(0) => return,
+                Some(_) if self.budget > 0 => {
+                    // Charged for the turn itself, or an empty body would go round forever
+                    self.budget -= 1;
+                    self.run(open + 1, close);
+                    self.record(close);
+                }
+                _ => break,
+            }
+        }
+        // From here on it runs any number of more times
+        self.exact = false;
+        let writes = self.writes(open, close);
+        let forget = |state: &mut State| match &writes {
+            Some(offsets) => {
+                let pointer = state.pointer;
+                for offset in offsets {
+                    state.cells.insert(pointer + offset, None);
+                }
+            }
+            None => state.forget(),
+        };
+        forget(&mut self.state);
+        self.run(open + 1, close);
+        self.record(close);
+        forget(&mut self.state);
+        self.state.set(Some(0));
+    }
+
+    // The cells a loop body writes, by offset, when it always comes back to where it started
+    fn writes(&self, open: usize, close: usize) -> Option<Vec<isize>> {
+        let mut offsets = vec![];
+        let mut offset = 0;
+        let mut starts = vec![];
+        for token in &self.tokens[open + 1..close] {
+            match token {
+                BfToken::MOV(n) => offset += n,
+                BfToken::CEL(_)
+                | BfToken::SET(_)
+                | BfToken::ACC
+                | BfToken::NUM
+                | BfToken::RND
+                | BfToken::EXT(_) => offsets.push(offset),
+                BfToken::JUM => starts.push(offset),
+                BfToken::BAC if starts.pop() != Some(offset) => return None,
+                BfToken::SCN(_) => return None,
+                _ => (),
+            }
+        }
+        (offset == 0).then_some(offsets)
+    }
+}
+
+fn add(value: u8, n: isize) -> Option<u8> {
+    u8::try_from(value as isize + n).ok()
+}
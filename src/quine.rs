@@ -0,0 +1,105 @@
+// Whether a program prints its own source, for checking quines.
+//
+// Comparisons can be loosened: `trailing` ignores whitespace at the end of either side, as
+// editors add a final newline the program may not print, `whitespace` ignores all of it, and
+// `commands` only compares the eight commands, for quines that print themselves without their
+// comments. A mismatch is shown as the one stretch that differs between the longest common
+// start and end.
+use std::str::FromStr;
+
+use crate::analysis::COMMANDS;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Normalize {
+    Exact,
+    Trailing,
+    Whitespace,
+    Commands,
+}
+
+impl FromStr for Normalize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact" => Ok(Self::Exact),
+            "trailing" => Ok(Self::Trailing),
+            "whitespace" => Ok(Self::Whitespace),
+            "commands" => Ok(Self::Commands),
+            _ => Err(format!(
+                "Invalid normalization {s:?}, expected exact, trailing, whitespace or commands"
+            )),
+        }
+    }
+}
+
+impl Normalize {
+    pub fn apply(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Exact => bytes.to_vec(),
+            Self::Trailing => {
+                let end = bytes
+                    .iter()
+                    .rposition(|byte| !byte.is_ascii_whitespace())
+                    .map_or(0, |idx| idx + 1);
+                bytes[..end].to_vec()
+            }
+            Self::Whitespace => bytes
+                .iter()
+                .copied()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect(),
+            Self::Commands => bytes
+                .iter()
+                .copied()
+                .filter(|&byte| COMMANDS.contains(&(byte as char)))
+                .collect(),
+        }
+    }
+}
+
+// Where normalized source and output part ways, as offsets into the normalized bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub at: usize,
+    pub source: Vec<u8>, // The bytes only the source has there
+    pub output: Vec<u8>, // and what the output has instead
+    pub matching: usize, // Bytes of the longer side outside the differing stretch
+    pub total: usize,    // Bytes of the longer side
+}
+
+impl Mismatch {
+    // Only a small part differs, like a quoted character or a missing newline
+    pub fn is_near(&self) -> bool {
+        self.matching * 10 >= self.total * 9
+    }
+}
+
+// `None` when the program is a quine under `normalize`
+pub fn compare(source: &[u8], output: &[u8], normalize: Normalize) -> Option<Mismatch> {
+    let (source, output) = (normalize.apply(source), normalize.apply(output));
+    if source == output {
+        return None;
+    }
+    let start = source
+        .iter()
+        .zip(&output)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let shorter = source.len().min(output.len());
+    let end = source
+        .iter()
+        .rev()
+        .zip(output.iter().rev())
+        .take(shorter - start)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let total = source.len().max(output.len());
+    Some(Mismatch {
+        at: start,
+        source: source[start..source.len() - end].to_vec(),
+        output: output[start..output.len() - end].to_vec(),
+        matching: start + end,
+        total,
+    })
+}
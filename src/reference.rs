@@ -0,0 +1,182 @@
+// A deliberately plain interpreter that runs the source one character at a time, with no
+// compilation or optimization, to check everything else against.
+use std::collections::VecDeque;
+
+use crate::error::BfError;
+use crate::rng::Rng;
+use crate::settings::{Eof, Settings};
+
+// How a reference run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: Vec<u8>,
+    pub tape: Vec<u32>, // Every cell the pointer visited, left to right
+    pub pointer: usize, // Index into `tape`
+    pub steps: u64,     // Commands executed, comments don't count
+}
+
+// One thread of a run, there is only ever one without the `fork` extension
+#[derive(Clone)]
+struct Thread {
+    tape: Vec<u32>,
+    pointer: usize,
+    pc: usize,
+    steps: u64,
+}
+
+impl Thread {
+    fn right(&mut self) {
+        self.pointer += 1;
+        if self.pointer == self.tape.len() {
+            self.tape.push(0);
+        }
+    }
+}
+
+// Runs `source` over `input`, honoring the cell size, EOF mode, step limit, extensions and
+// seed in `settings`. Forked threads take turns the same way `Interpreter` does, and the
+// outcome is the first thread's.
+pub fn run(source: &str, input: &[u8], settings: &Settings) -> Result<Outcome, BfError> {
+    let code: Vec<(usize, char)> = source.char_indices().collect();
+    let jumps = match_brackets(&code)?;
+    let mask = settings.cell_size.mask();
+    let commands = settings.extensions.commands();
+    let mut rng = settings.seed.map_or_else(Rng::from_time, Rng::new);
+
+    let mut threads = vec![Thread {
+        tape: vec![0],
+        pointer: 0,
+        pc: 0,
+        steps: 0,
+    }];
+    let mut queue = VecDeque::new();
+    let mut current = 0;
+    let mut input = input.iter();
+    let mut output = vec![];
+    loop {
+        let thread = &mut threads[current];
+        if thread.pc == code.len() {
+            match queue.pop_front() {
+                Some(next) => current = next,
+                None => break,
+            }
+            continue;
+        }
+        let command = code[thread.pc].1;
+        if !commands.contains(command) {
+            thread.pc += 1;
+            continue;
+        }
+        if settings
+            .max_steps
+            .is_some_and(|limit| thread.steps >= limit)
+        {
+            return Err(BfError::StepLimit(thread.steps));
+        }
+        thread.steps += 1;
+        let (tape, pointer) = (&mut thread.tape, &mut thread.pointer);
+        match command {
+            '+' => tape[*pointer] = tape[*pointer].wrapping_add(1) & mask,
+            '-' => tape[*pointer] = tape[*pointer].wrapping_sub(1) & mask,
+            '>' => thread.right(),
+            '<' => {
+                if *pointer == 0 {
+                    tape.insert(0, 0);
+                } else {
+                    *pointer -= 1;
+                }
+            }
+            '[' if tape[*pointer] == 0 => thread.pc = jumps[thread.pc],
+            ']' if tape[*pointer] != 0 => thread.pc = jumps[thread.pc],
+            '.' => output.push(tape[*pointer] as u8),
+            ',' => match (input.next(), settings.eof) {
+                (Some(&byte), _) => tape[*pointer] = byte as u32,
+                (None, Eof::Zero) => tape[*pointer] = 0,
+                (None, Eof::Minus1) => tape[*pointer] = mask,
+                (None, Eof::Unchanged) => (),
+            },
+            ':' => output.extend(tape[*pointer].to_string().bytes()),
+            ';' => {
+                let (mut value, mut negative, mut started) = (0u64, false, false);
+                let mut ended = false;
+                for &byte in input.by_ref() {
+                    match byte {
+                        b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' if !started => continue,
+                        b'-' | b'+' if !started => negative = byte == b'-',
+                        b'0'..=b'9' => {
+                            value = value.wrapping_mul(10).wrapping_add((byte - b'0') as u64)
+                        }
+                        _ => {
+                            ended = true;
+                            break;
+                        }
+                    }
+                    started = true;
+                }
+                match (started || ended, settings.eof) {
+                    (true, _) if negative => {
+                        tape[*pointer] = 0u64.wrapping_sub(value) as u32 & mask
+                    }
+                    (true, _) => tape[*pointer] = value as u32 & mask,
+                    (false, Eof::Zero) => tape[*pointer] = 0,
+                    (false, Eof::Minus1) => tape[*pointer] = mask,
+                    (false, Eof::Unchanged) => (),
+                }
+            }
+            '?' => tape[*pointer] = rng.byte() as u32,
+            'Y' => {
+                let mut child = thread.clone();
+                thread.tape[thread.pointer] = 0;
+                child.pc += 1;
+                child.right();
+                child.tape[child.pointer] = 1;
+                threads.push(child);
+                queue.push_back(threads.len() - 1);
+            }
+            _ => (),
+        }
+        let thread = &mut threads[current];
+        thread.pc += 1;
+        // Every `.`, `,`, `:`, `;` and `Y` ends the thread's turn
+        if matches!(command, '.' | ',' | ':' | ';' | 'Y') {
+            if let Some(next) = queue.pop_front() {
+                queue.push_back(current);
+                current = next;
+            }
+        }
+    }
+
+    let Thread {
+        tape,
+        pointer,
+        steps,
+        ..
+    } = threads.swap_remove(0);
+    Ok(Outcome {
+        output,
+        tape,
+        pointer,
+        steps,
+    })
+}
+
+// The index of each bracket's partner, with errors at the same source offsets as `Program`
+fn match_brackets(code: &[(usize, char)]) -> Result<Vec<usize>, BfError> {
+    let mut jumps = vec![0; code.len()];
+    let mut open = vec![];
+    for (idx, &(at, c)) in code.iter().enumerate() {
+        match c {
+            '[' => open.push(idx),
+            ']' => {
+                let start = open.pop().ok_or(BfError::UnopenedBracket(at))?;
+                jumps[start] = idx;
+                jumps[idx] = start;
+            }
+            _ => (),
+        }
+    }
+    match open.pop() {
+        Some(idx) => Err(BfError::UnclosedBracket(code[idx].0)),
+        None => Ok(jumps),
+    }
+}
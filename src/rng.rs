@@ -0,0 +1,55 @@
+// Small seedable xorshift64* generator, deterministic for a given seed on every platform.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero, and nearby seeds shouldn't start out looking alike
+        let mut rng = Self(seed ^ 0x9E37_79B9_7F4A_7C15);
+        if rng.0 == 0 {
+            rng.0 = 1;
+        }
+        rng.next_u64();
+        rng
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Self::new(nanos)
+    }
+
+    // Where the generator is, to pick up from later with `from_state`
+    pub fn state(&self) -> u64 {
+        self.0
+    }
+
+    // A generator carrying on from what `state` gave
+    pub fn from_state(state: u64) -> Self {
+        Self(state.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    // Uniform in `0..n`, `n` must not be zero
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    // True with probability `percent / 100`
+    pub fn chance(&mut self, percent: u32) -> bool {
+        self.below(100) < percent as usize
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::extension::Extensions;
+use crate::tape::Tape;
+
+// How many bits each cell on the tape holds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CellSize {
+    U8,
+    U16,
+    U32,
+}
+
+impl CellSize {
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::U8 => 8,
+            Self::U16 => 16,
+            Self::U32 => 32,
+        }
+    }
+
+    // The largest value a cell can hold, also used to wrap arithmetic
+    pub fn mask(self) -> u32 {
+        match self {
+            Self::U8 => u8::MAX as u32,
+            Self::U16 => u16::MAX as u32,
+            Self::U32 => u32::MAX,
+        }
+    }
+}
+
+impl FromStr for CellSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(Self::U8),
+            "16" => Ok(Self::U16),
+            "32" => Ok(Self::U32),
+            _ => Err(format!("Invalid cell size {s:?}, expected 8, 16 or 32")),
+        }
+    }
+}
+
+impl fmt::Display for CellSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bits())
+    }
+}
+
+// The integer types that name a cell size, for `InterpreterBuilder::cell::<u16>()`
+pub trait CellWidth {
+    const SIZE: CellSize;
+}
+
+impl CellWidth for u8 {
+    const SIZE: CellSize = CellSize::U8;
+}
+
+impl CellWidth for u16 {
+    const SIZE: CellSize = CellSize::U16;
+}
+
+impl CellWidth for u32 {
+    const SIZE: CellSize = CellSize::U32;
+}
+
+// What `,` stores in the current cell once the input runs out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Eof {
+    Zero,
+    Minus1,
+    Unchanged,
+}
+
+impl FromStr for Eof {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" | "zero" => Ok(Self::Zero),
+            "-1" | "minus1" => Ok(Self::Minus1),
+            "unchanged" => Ok(Self::Unchanged),
+            _ => Err(format!(
+                "Invalid EOF mode {s:?}, expected zero, minus1 or unchanged"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Eof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Zero => write!(f, "zero"),
+            Self::Minus1 => write!(f, "minus1"),
+            Self::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+// When `.` output is pushed out to the writer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Flush {
+    Always, // After every byte, for programs that draw with escape codes
+    Line,   // After every newline
+    Block,  // Only when buffers fill, before `,` and at the end, the fastest
+}
+
+impl FromStr for Flush {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "line" => Ok(Self::Line),
+            "block" => Ok(Self::Block),
+            _ => Err(format!(
+                "Invalid flush policy {s:?}, expected always, line or block"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Flush {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Always => write!(f, "always"),
+            Self::Line => write!(f, "line"),
+            Self::Block => write!(f, "block"),
+        }
+    }
+}
+
+// The default nesting limit, far beyond any program written by hand, which keeps the stack of
+// open loops to a few megabytes whatever a generated one does
+pub const MAX_DEPTH: usize = 1 << 20;
+
+// Everything that changes how a program is compiled and run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub cell_size: CellSize,
+    pub eof: Eof,
+    pub tape: Tape,
+    pub max_steps: Option<u64>,
+    pub max_depth: Option<usize>, // How deep loops may nest in the source
+    pub opt_level: u8,
+    pub flush: Flush,
+    pub extensions: Extensions,
+    pub seed: Option<u64>, // For `?`, taken from the clock when not set
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cell_size: CellSize::U8,
+            eof: Eof::Zero,
+            tape: Tape::Dynamic,
+            max_steps: None,
+            max_depth: Some(MAX_DEPTH),
+            opt_level: 2,
+            flush: Flush::Line,
+            extensions: Extensions::default(),
+            seed: None,
+        }
+    }
+}
+
+// Presets bundling the cell size, EOF mode and tape of well-known interpreter families, for
+// `spec`. Every cell wraps on overflow, the only behavior there is.
+//
+// classic  8-bit cells, `,` leaves the cell unchanged at EOF, as in the original compiler
+// modern   32-bit cells, EOF stores -1 like C's `getchar`, sparse tape for far-flung pointers
+// nesdev   8-bit cells, EOF stores 0, contiguous tape, matching 6502-era byte machines
+pub const SPECS: [&str; 3] = ["classic", "modern", "nesdev"];
+
+impl Settings {
+    // Applies one of `SPECS`, leaving settings it doesn't cover alone
+    pub fn spec(&mut self, name: &str) -> Result<(), String> {
+        let (cell_size, eof, tape) = match name {
+            "classic" => (CellSize::U8, Eof::Unchanged, Tape::Dynamic),
+            "modern" => (CellSize::U32, Eof::Minus1, Tape::Sparse),
+            "nesdev" => (CellSize::U8, Eof::Zero, Tape::Dynamic),
+            _ => {
+                return Err(format!(
+                    "Unknown spec {name:?}, expected {}",
+                    SPECS.join(", ")
+                ))
+            }
+        };
+        self.cell_size = cell_size;
+        self.eof = eof;
+        self.tape = tape;
+        Ok(())
+    }
+
+    // Changes one setting by the name it has in config files, e.g. `eof` to `minus1`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = |value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid {key} {value:?}, expected a number"))
+        };
+        match key {
+            "cell_size" => self.cell_size = value.parse()?,
+            "eof" => self.eof = value.parse()?,
+            "tape" => self.tape = value.parse()?,
+            "flush" => self.flush = value.parse()?,
+            "extensions" => self.extensions = value.parse()?,
+            "seed" => self.seed = Some(number(value)?),
+            // Zero turns the limit off
+            "max_steps" => self.max_steps = Some(number(value)?).filter(|&n| n > 0),
+            "max_depth" => {
+                let depth = number(value)?.min(usize::MAX as u64) as usize;
+                self.max_depth = Some(depth).filter(|&n| n > 0);
+            }
+            "opt_level" => self.opt_level = number(value)?.min(u8::MAX as u64) as u8,
+            "spec" => self.spec(value)?,
+            _ => return Err(format!("Unknown setting {key:?}")),
+        }
+        Ok(())
+    }
+
+    // Reads top-level `key = value` lines, which is all of TOML the settings need
+    pub fn apply_config(&mut self, text: &str) -> Result<(), String> {
+        for (idx, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |err: String| format!("line {}: {err}", idx + 1);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| err("expected `key = value`".to_string()))?;
+            let value = parse_value(value.trim()).map_err(err)?;
+            self.set(key.trim(), &value).map_err(err)?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (idx, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..idx],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Result<String, String> {
+    let quoted = |q: char| value.len() >= 2 && value.starts_with(q) && value.ends_with(q);
+    if quoted('"') || quoted('\'') {
+        return Ok(value[1..value.len() - 1].to_string());
+    }
+    if value.is_empty() || value.contains(char::is_whitespace) {
+        return Err(format!("invalid value {value:?}"));
+    }
+    Ok(value.to_string())
+}
@@ -0,0 +1,250 @@
+// Named snippets for writing Brainfuck by hand, pulled in by a line of their own:
+//
+//     @use set(72)          # the current cell becomes 72
+//     @use print_num        # writes it in decimal, "72"
+//     @use print("!\n")     # from a blank cell
+//
+// `expand` replaces each such line with its snippet's code, leaving the other lines as they
+// were, so line numbers in errors still point at the file as written. Unless a snippet says
+// otherwise it ends where it started, and the cells it uses for scratch have to be zero
+// before and are zero again after.
+use std::borrow::Cow;
+
+use crate::error::BfError;
+
+pub struct Snippet {
+    pub name: &'static str,
+    pub params: &'static str, // As shown in `--list`
+    pub doc: &'static str,
+}
+
+pub const SNIPPETS: &[Snippet] = &[
+    Snippet {
+        name: "set",
+        params: "n",
+        doc: "Sets the current cell to n",
+    },
+    Snippet {
+        name: "add",
+        params: "n",
+        doc: "Adds n to the current cell, or subtracts for a negative n",
+    },
+    Snippet {
+        name: "clear",
+        params: "",
+        doc: "Sets the current cell to 0",
+    },
+    Snippet {
+        name: "move",
+        params: "to",
+        doc: "Adds the current cell to the one `to` cells away and clears it",
+    },
+    Snippet {
+        name: "copy",
+        params: "to, temp",
+        doc: "Adds the current cell to the one `to` cells away, using `temp` as scratch",
+    },
+    Snippet {
+        name: "mul",
+        params: "a, b",
+        doc: "Adds a times b to the current cell, counting in the next one",
+    },
+    Snippet {
+        name: "equal",
+        params: "n",
+        doc: "Sets the next cell to 1 if the current one is n and 0 if not, the one after is \
+              scratch",
+    },
+    Snippet {
+        name: "print_num",
+        params: "",
+        doc: "Writes the current cell in decimal, with one scratch cell per digit and 4 more",
+    },
+    Snippet {
+        name: "print",
+        params: "\"text\"",
+        doc: "Writes the text using the current cell, which has to be 0",
+    },
+];
+
+enum Arg {
+    Number(isize),
+    Text(Vec<u8>),
+}
+
+// The source with every `@use` line replaced, borrowed when there weren't any
+pub fn expand(source: &str) -> Result<Cow<'_, str>, BfError> {
+    if !source
+        .lines()
+        .any(|line| line.trim_start().starts_with("@use"))
+    {
+        return Ok(Cow::Borrowed(source));
+    }
+    let mut out = String::with_capacity(source.len());
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let directive = line.trim_start();
+        let Some(rest) = directive.strip_prefix("@use") else {
+            out.push_str(line);
+            continue;
+        };
+        let at = start + line.len() - directive.len();
+        let code = snippet(rest, at + 4).map_err(|(pos, text)| BfError::Syntax(pos, text))?;
+        out.push_str(&code);
+        if line.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    Ok(Cow::Owned(out))
+}
+
+// The code for `name(args)`, with errors at offsets into the whole source
+fn snippet(text: &str, at: usize) -> Result<String, (usize, String)> {
+    let trimmed = text.trim();
+    let at = at + text.len() - text.trim_start().len();
+    let (name, args) = match trimmed.split_once('(') {
+        Some((name, rest)) => {
+            let inner = rest.strip_suffix(')').ok_or_else(|| {
+                (
+                    at + trimmed.len(),
+                    "expected `)` at the end of the line".to_string(),
+                )
+            })?;
+            (name.trim(), parse_args(inner, at + name.len() + 1)?)
+        }
+        None => (trimmed, vec![]),
+    };
+    let Some(known) = SNIPPETS.iter().find(|snippet| snippet.name == name) else {
+        let names: Vec<&str> = SNIPPETS.iter().map(|snippet| snippet.name).collect();
+        return Err((
+            at,
+            format!(
+                "unknown snippet {name:?}, expected one of {}",
+                names.join(", ")
+            ),
+        ));
+    };
+    let wrong = || {
+        let shape = format!("{}({})", known.name, known.params);
+        (at, format!("expected `{shape}`"))
+    };
+    let numbers: Option<Vec<isize>> = args
+        .iter()
+        .map(|arg| match arg {
+            Arg::Number(n) => Some(*n),
+            Arg::Text(_) => None,
+        })
+        .collect();
+    let code = match (name, numbers.as_deref(), &args[..]) {
+        ("set", Some(&[n]), _) => format!("[-]{}", add(n)),
+        ("add", Some(&[n]), _) => add(n),
+        ("clear", Some(&[]), _) => "[-]".to_string(),
+        ("move", Some(&[to]), _) => format!("[-{}+{}]", shift(to), shift(-to)),
+        ("copy", Some(&[to, temp]), _) => format!(
+            "[-{}+{}+{}]{}[-{}+{}]{}",
+            shift(to),
+            shift(temp - to),
+            shift(-temp),
+            shift(temp),
+            shift(-temp),
+            shift(temp),
+            shift(-temp)
+        ),
+        ("mul", Some(&[a, b]), _) if a >= 0 => format!(">{}[<{}>-]<", add(a), add(b)),
+        // Copied two cells on, where whatever is left after subtracting n clears the flag
+        ("equal", Some(&[n]), _) => format!("[->+>+<<]>[-<+>]+>{}[[-]<->]<<", add(-n)),
+        ("print_num", Some(&[]), _) => print_num(),
+        ("print", _, [Arg::Text(text)]) => print(text),
+        _ => return Err(wrong()),
+    };
+    Ok(code)
+}
+
+fn parse_args(text: &str, at: usize) -> Result<Vec<Arg>, (usize, String)> {
+    let mut args = vec![];
+    let mut chars = text.char_indices().peekable();
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(pos, c)) = chars.peek() else {
+            break;
+        };
+        if c == '"' {
+            chars.next();
+            let mut bytes = vec![];
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((pos, '\\')) => {
+                        let escaped = match chars.next() {
+                            Some((_, 'n')) => b'\n',
+                            Some((_, 't')) => b'\t',
+                            Some((_, '0')) => 0,
+                            Some((_, c @ ('\\' | '"'))) => c as u8,
+                            _ => return Err((at + pos, "unknown escape".to_string())),
+                        };
+                        bytes.push(escaped);
+                    }
+                    Some((_, c)) => bytes.extend(c.to_string().as_bytes()),
+                    None => return Err((at + pos, "unclosed string".to_string())),
+                }
+            }
+            args.push(Arg::Text(bytes));
+        } else {
+            let mut word = String::new();
+            while let Some((_, c)) = chars.next_if(|&(_, c)| c != ',' && !c.is_whitespace()) {
+                word.push(c);
+            }
+            let number = word
+                .parse()
+                .map_err(|_| (at + pos, format!("expected a number, found {word:?}")))?;
+            args.push(Arg::Number(number));
+        }
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None => break,
+            Some((_, ',')) => (),
+            Some((pos, _)) => return Err((at + pos, "expected `,` between arguments".into())),
+        }
+    }
+    Ok(args)
+}
+
+fn add(n: isize) -> String {
+    let c = if n < 0 { "-" } else { "+" };
+    c.repeat(n.unsigned_abs())
+}
+
+fn shift(n: isize) -> String {
+    let c = if n < 0 { "<" } else { ">" };
+    c.repeat(n.unsigned_abs())
+}
+
+// Divides a copy by 10 until nothing is left, stacking each remainder plus 1 to the right of
+// a zero cell, then writes them back from the last one
+fn print_num() -> String {
+    // `n 10` becomes `0 10-n%10 n%10 n/10`
+    let divmod = "[->-[>+>>]>[+[-<+>]>+>>]<<<<<]";
+    // Keeps the remainder plus 1 and moves on to the quotient
+    let keep = ">[-]>[-<<+>>]>[-<<+>>]<<<+>";
+    let ten = add(10);
+    format!(
+        "[->+>+<<]>[-<+>]>>{ten}<{divmod}{keep}[>{ten}<{divmod}{keep}]<[{}.[-]<]<",
+        add(47)
+    )
+}
+
+fn print(text: &[u8]) -> String {
+    let mut code = String::new();
+    let mut cell = 0;
+    for &byte in text {
+        code.push_str(&add(byte as isize - cell));
+        code.push('.');
+        cell = byte as isize;
+    }
+    if cell != 0 {
+        code.push_str("[-]");
+    }
+    code
+}
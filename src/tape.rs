@@ -0,0 +1,360 @@
+// Storage for the cells of the tape, grown on demand in both directions, or of a fixed number
+// of cells that the pointer can't leave.
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::BfError;
+use crate::settings::CellSize;
+
+// Cells per page of the sparse tape, 4 KiB of `u32`s
+const PAGE: usize = 1024;
+
+// How the tape is laid out in memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tape {
+    Dynamic,      // One contiguous vector, shifted over when the pointer moves left of it
+    Sparse,       // Pages in a hashmap, only the ones the pointer visits get allocated
+    Fixed(usize), // This many cells from the starting one rightwards, like the classic 30000
+}
+
+impl FromStr for Tape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dynamic" => Ok(Self::Dynamic),
+            "sparse" => Ok(Self::Sparse),
+            _ => match s.strip_prefix("fixed:").map(str::parse) {
+                // Allocated whole up front, so a length past what gets gathered is refused
+                Some(Ok(cells)) if cells > MAX_GATHERED => Err(format!(
+                    "Invalid tape {s:?}, a fixed tape can have at most {MAX_GATHERED} cells"
+                )),
+                Some(Ok(cells)) if cells > 0 => Ok(Self::Fixed(cells)),
+                _ => Err(format!(
+                    "Invalid tape {s:?}, expected dynamic, sparse or fixed:CELLS"
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Tape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dynamic => write!(f, "dynamic"),
+            Self::Sparse => write!(f, "sparse"),
+            Self::Fixed(cells) => write!(f, "fixed:{cells}"),
+        }
+    }
+}
+
+// The cells and the pointer into them.
+#[derive(Debug, Clone)]
+pub enum Memory {
+    Dynamic {
+        cells: Vec<u32>,
+        pointer: usize,
+        reallocations: u64, // Times growing moved the cells to a bigger allocation
+    },
+    Sparse {
+        pages: Vec<Box<[u32]>>,
+        index: HashMap<isize, usize>, // Page number to its position in `pages`
+        pointer: isize,
+        page: usize, // Position in `pages` of the page under the pointer
+        min: isize,
+        max: isize,
+    },
+    Fixed {
+        cells: Vec<u32>,
+        pointer: usize,
+    },
+}
+
+impl Memory {
+    pub fn new(tape: Tape) -> Self {
+        match tape {
+            Tape::Dynamic => Self::Dynamic {
+                cells: vec![0],
+                pointer: 0,
+                reallocations: 0,
+            },
+            Tape::Sparse => Self::Sparse {
+                pages: vec![vec![0; PAGE].into_boxed_slice()],
+                index: HashMap::from([(0, 0)]),
+                pointer: 0,
+                page: 0,
+                min: 0,
+                max: 0,
+            },
+            Tape::Fixed(len) => Self::Fixed {
+                cells: vec![0; len],
+                pointer: 0,
+            },
+        }
+    }
+
+    // A tape holding `cells`, with the pointer at `pointer` within them
+    pub fn from_cells(tape: Tape, mut cells: Vec<u32>, pointer: usize) -> Self {
+        match tape {
+            Tape::Dynamic => {
+                return Self::Dynamic {
+                    cells,
+                    pointer,
+                    reallocations: 0,
+                }
+            }
+            Tape::Fixed(len) => {
+                cells.resize(len, 0);
+                return Self::Fixed { cells, pointer };
+            }
+            Tape::Sparse => (),
+        }
+        // Only step onto the cells that hold something, so empty stretches stay unallocated
+        let mut memory = Self::new(tape);
+        let mut at = 0;
+        for (idx, &cell) in cells.iter().enumerate().filter(|(_, &cell)| cell != 0) {
+            memory.grow(idx as isize - at);
+            *memory.get_mut() = cell;
+            at = idx as isize;
+        }
+        let last = cells.len().max(1) as isize - 1;
+        memory.grow(last - at);
+        memory.grow(pointer as isize - last);
+        memory
+    }
+
+    pub fn get(&self) -> u32 {
+        match self {
+            Self::Dynamic { cells, pointer, .. } | Self::Fixed { cells, pointer } => {
+                cells[*pointer]
+            }
+            Self::Sparse {
+                pages,
+                pointer,
+                page,
+                ..
+            } => pages[*page][pointer.rem_euclid(PAGE as isize) as usize],
+        }
+    }
+
+    // The cell `offset` away from the pointer, 0 for one never visited or off a fixed tape
+    pub fn peek(&self, offset: isize) -> u32 {
+        match self {
+            Self::Dynamic { cells, pointer, .. } | Self::Fixed { cells, pointer } => pointer
+                .checked_add_signed(offset)
+                .and_then(|at| cells.get(at))
+                .copied()
+                .unwrap_or(0),
+            Self::Sparse {
+                pages,
+                index,
+                pointer,
+                ..
+            } => {
+                let at = pointer + offset;
+                let number = at.div_euclid(PAGE as isize);
+                let offset = at.rem_euclid(PAGE as isize) as usize;
+                index.get(&number).map_or(0, |&page| pages[page][offset])
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut u32 {
+        match self {
+            Self::Dynamic { cells, pointer, .. } | Self::Fixed { cells, pointer } => {
+                &mut cells[*pointer]
+            }
+            Self::Sparse {
+                pages,
+                pointer,
+                page,
+                ..
+            } => &mut pages[*page][pointer.rem_euclid(PAGE as isize) as usize],
+        }
+    }
+
+    // Moves the pointer by `n` cells, making room if it walks off the known tape. Only a fixed
+    // tape can fail, leaving the pointer where it was.
+    pub fn shift(&mut self, n: isize) -> Result<(), BfError> {
+        if let Self::Fixed { cells, pointer } = self {
+            let to = *pointer as isize + n;
+            if !(0..cells.len() as isize).contains(&to) {
+                return Err(BfError::TapeBounds(to));
+            }
+            *pointer = to as usize;
+            return Ok(());
+        }
+        self.grow(n);
+        Ok(())
+    }
+
+    // `shift` for the tapes that never run out
+    fn grow(&mut self, n: isize) {
+        match self {
+            Self::Dynamic {
+                cells,
+                pointer,
+                reallocations,
+            } => {
+                let capacity = cells.capacity();
+                if n > 0 {
+                    let n = n as usize;
+                    // Check if there is room on the tape to move right, if not make room
+                    if *pointer + n >= cells.len() {
+                        cells.resize(*pointer + n + 1, 0);
+                    }
+                    *pointer += n;
+                } else {
+                    // Opposite for moving left
+                    let n = n.unsigned_abs();
+                    if *pointer >= n {
+                        *pointer -= n
+                    } else {
+                        cells.splice(0..0, vec![0; n - *pointer]);
+                        *pointer = 0;
+                    }
+                }
+                if cells.capacity() != capacity {
+                    *reallocations += 1;
+                }
+            }
+            Self::Sparse {
+                pages,
+                index,
+                pointer,
+                page,
+                min,
+                max,
+            } => {
+                let before = pointer.div_euclid(PAGE as isize);
+                *pointer += n;
+                *min = (*min).min(*pointer);
+                *max = (*max).max(*pointer);
+                let number = pointer.div_euclid(PAGE as isize);
+                if number != before {
+                    *page = *index.entry(number).or_insert_with(|| {
+                        pages.push(vec![0; PAGE].into_boxed_slice());
+                        pages.len() - 1
+                    });
+                }
+            }
+            Self::Fixed { .. } => unreachable!("fixed tapes are moved by `shift`"),
+        }
+    }
+
+    // The cell `at` from the leftmost visited one, what `cells()[at]` holds without gathering
+    // them, 0 past the rightmost
+    pub fn cell(&self, at: usize) -> u32 {
+        self.peek(at as isize - self.pointer() as isize)
+    }
+
+    // The cells holding something, by their index in `cells`. A sparse tape is read page by
+    // page, so the stretches never visited cost nothing.
+    pub fn nonzero(&self) -> Vec<(usize, u32)> {
+        match self {
+            Self::Dynamic { cells, .. } | Self::Fixed { cells, .. } => cells
+                .iter()
+                .enumerate()
+                .filter(|(_, &cell)| cell != 0)
+                .map(|(at, &cell)| (at, cell))
+                .collect(),
+            Self::Sparse {
+                pages, index, min, ..
+            } => {
+                let mut numbers: Vec<_> = index.iter().collect();
+                numbers.sort_unstable();
+                numbers
+                    .into_iter()
+                    .flat_map(|(&number, &page)| {
+                        let start = number * PAGE as isize - min;
+                        pages[page]
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, &cell)| cell != 0)
+                            .map(move |(offset, &cell)| ((start + offset as isize) as usize, cell))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Every cell between the leftmost and rightmost ones the pointer has visited. On a sparse
+    // tape that means gathering them, check `cell_count` against `MAX_GATHERED` first where
+    // the pointer may have gone far.
+    pub fn cells(&self) -> Cow<'_, [u32]> {
+        match self {
+            Self::Dynamic { cells, .. } | Self::Fixed { cells, .. } => Cow::Borrowed(cells),
+            Self::Sparse {
+                pages,
+                index,
+                min,
+                max,
+                ..
+            } => Cow::Owned(
+                (*min..=*max)
+                    .map(|at| {
+                        let number = at.div_euclid(PAGE as isize);
+                        let offset = at.rem_euclid(PAGE as isize) as usize;
+                        index.get(&number).map_or(0, |&page| pages[page][offset])
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    // How many cells `cells` holds, without gathering them
+    pub fn cell_count(&self) -> usize {
+        match self {
+            Self::Dynamic { cells, .. } | Self::Fixed { cells, .. } => cells.len(),
+            Self::Sparse { min, max, .. } => (max - min) as usize + 1,
+        }
+    }
+
+    // Bytes held for cells, whether or not the pointer got to them
+    pub fn allocated(&self) -> usize {
+        let cells = match self {
+            Self::Dynamic { cells, .. } => cells.capacity(),
+            Self::Fixed { cells, .. } => cells.len(),
+            Self::Sparse { pages, .. } => pages.len() * PAGE,
+        };
+        cells * std::mem::size_of::<u32>()
+    }
+
+    // Times the tape grew into new memory: moves of a dynamic tape to a bigger allocation,
+    // or pages added to a sparse one after the first. A fixed tape never grows.
+    pub fn reallocations(&self) -> u64 {
+        match self {
+            Self::Dynamic { reallocations, .. } => *reallocations,
+            Self::Fixed { .. } => 0,
+            Self::Sparse { pages, .. } => pages.len() as u64 - 1,
+        }
+    }
+
+    // Position of the pointer within `cells`
+    pub fn pointer(&self) -> usize {
+        match self {
+            Self::Dynamic { pointer, .. } | Self::Fixed { pointer, .. } => *pointer,
+            Self::Sparse { pointer, min, .. } => (pointer - min) as usize,
+        }
+    }
+}
+
+// The most cells worth gathering from a sparse tape into one slice, 1 GiB of them
+pub const MAX_GATHERED: usize = 1 << 28;
+
+// A tape saved for other tools to read: the magic `BFTAPE01`, one byte for the cell width
+// in bytes, the pointer's index and the number of cells as little-endian `u64`s, then each
+// cell little-endian at the cell width.
+pub fn dump(cells: &[u32], pointer: usize, cell_size: CellSize) -> Vec<u8> {
+    let width = cell_size.bits() as usize / 8;
+    let mut out = b"BFTAPE01".to_vec();
+    out.push(width as u8);
+    out.extend((pointer as u64).to_le_bytes());
+    out.extend((cells.len() as u64).to_le_bytes());
+    for cell in cells {
+        out.extend(&cell.to_le_bytes()[..width]);
+    }
+    out
+}
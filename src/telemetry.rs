@@ -0,0 +1,130 @@
+// Structured events and spans from compiling and running programs, for embedders that want
+// them in their own logs.
+//
+// Like `async_io`, this mirrors the shape of another crate, here `tracing`, rather than
+// depending on it: a `Subscriber` forwarding `enter`/`exit` to `tracing::span!` and `event` to
+// `tracing::event!` is a few lines. Nothing is recorded until `set_subscriber` is called, and
+// without the `telemetry` feature the calls aren't compiled in at all.
+//
+// Spans are `compile` and `run`. Events are `pass` (name, before, after, elapsed_us) and
+// `compiled` (instructions, elapsed_us) at debug level, `forked` (threads) at trace level, and
+// `finished` (steps) or `failed` (steps, error) at info level.
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    U64(u64),
+    Str(&'static str),
+    String(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::U64(n) => write!(f, "{n}"),
+            Self::Str(text) => write!(f, "{text}"),
+            Self::String(text) => write!(f, "{text}"),
+        }
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Self::U64(value)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Self::U64(value as u64)
+    }
+}
+
+impl From<&'static str> for Value {
+    fn from(value: &'static str) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+#[derive(Debug)]
+pub struct Event<'a> {
+    pub level: Level,
+    pub name: &'static str,
+    pub fields: &'a [(&'static str, Value)],
+}
+
+pub trait Subscriber: Send + Sync {
+    // Called before every event, so a subscriber can filter cheaply
+    fn enabled(&self, level: Level, name: &'static str) -> bool {
+        let _ = (level, name);
+        true
+    }
+
+    fn event(&self, event: &Event<'_>);
+
+    fn enter(&self, span: &'static str) {
+        let _ = span;
+    }
+
+    fn exit(&self, span: &'static str, elapsed: Duration) {
+        let _ = (span, elapsed);
+    }
+}
+
+static SUBSCRIBER: OnceLock<Box<dyn Subscriber>> = OnceLock::new();
+
+// Installs the subscriber for the rest of the process, false if one already was
+pub fn set_subscriber(subscriber: impl Subscriber + 'static) -> bool {
+    SUBSCRIBER.set(Box::new(subscriber)).is_ok()
+}
+
+pub(crate) fn event(level: Level, name: &'static str, fields: &[(&'static str, Value)]) {
+    if let Some(subscriber) = SUBSCRIBER.get() {
+        if subscriber.enabled(level, name) {
+            subscriber.event(&Event {
+                level,
+                name,
+                fields,
+            });
+        }
+    }
+}
+
+// Entered when created, exited when dropped
+pub(crate) struct Span {
+    name: &'static str,
+    start: Instant,
+}
+
+pub(crate) fn span(name: &'static str) -> Span {
+    if let Some(subscriber) = SUBSCRIBER.get() {
+        subscriber.enter(name);
+    }
+    Span {
+        name,
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(subscriber) = SUBSCRIBER.get() {
+            subscriber.exit(self.name, self.start.elapsed());
+        }
+    }
+}
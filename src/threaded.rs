@@ -0,0 +1,230 @@
+// Threaded code: the program compiled once into a closure per instruction, each doing its
+// work with its operand already bound and handing back the index of the next one. Running
+// it is a loop of indirect calls, without the `match` on every instruction that `Machine`
+// makes. Cells, EOF, tapes, step limits and flushing behave as they do in the interpreter,
+// and each call counts as the one step the interpreter would count.
+use std::io::{ErrorKind, Read, Write};
+use std::ops::Range;
+
+use crate::error::BfError;
+use crate::extension::Number;
+use crate::program::Program;
+use crate::rng::Rng;
+use crate::settings::{Eof, Flush, Settings};
+use crate::tape::Memory;
+use crate::token::BfToken;
+
+// What the instructions work on, shared with `tiered`
+pub(crate) struct State<'a> {
+    pub(crate) tape: Memory,
+    pub(crate) mask: u32,
+    eof: Eof,
+    flush: Flush,
+    pub(crate) rng: Rng,
+    pub(crate) output: &'a mut dyn Write,
+    input: &'a mut dyn Read,
+}
+
+impl<'a> State<'a> {
+    pub(crate) fn new(
+        settings: &Settings,
+        input: &'a mut dyn Read,
+        output: &'a mut dyn Write,
+    ) -> Self {
+        Self {
+            tape: Memory::new(settings.tape),
+            mask: settings.cell_size.mask(),
+            eof: settings.eof,
+            flush: settings.flush,
+            rng: settings.seed.map_or_else(Rng::from_time, Rng::new),
+            input,
+            output,
+        }
+    }
+
+    pub(crate) fn read(&mut self) -> Result<(), BfError> {
+        // A prompt should be visible before blocking on input
+        self.output.flush()?;
+        let mut buf = [0u8];
+        let cell = self.tape.get_mut();
+        match self.input.read_exact(&mut buf) {
+            Ok(()) => *cell = buf[0] as u32,
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => match self.eof {
+                Eof::Zero => *cell = 0,
+                Eof::Minus1 => *cell = self.mask,
+                Eof::Unchanged => (),
+            },
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_number(&mut self) -> Result<(), BfError> {
+        self.output.flush()?;
+        let mut number = Number::default();
+        let mut buf = [0u8];
+        let value = loop {
+            let byte = match self.input.read_exact(&mut buf) {
+                Ok(()) => Some(buf[0]),
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => None,
+                Err(err) => return Err(err.into()),
+            };
+            if let Some(value) = number.push(byte) {
+                break value;
+            }
+        };
+        let cell = self.tape.get_mut();
+        match (value, self.eof) {
+            (Some(value), _) => *cell = value as u32 & self.mask,
+            (None, Eof::Zero) => *cell = 0,
+            (None, Eof::Minus1) => *cell = self.mask,
+            (None, Eof::Unchanged) => (),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write(&mut self) -> Result<(), BfError> {
+        let byte = self.tape.get() as u8;
+        self.output.write_all(&[byte])?;
+        match self.flush {
+            Flush::Always => self.output.flush()?,
+            Flush::Line if byte == b'\n' => self.output.flush()?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_number(&mut self) -> Result<(), BfError> {
+        self.output
+            .write_all(self.tape.get().to_string().as_bytes())?;
+        if self.flush == Flush::Always {
+            self.output.flush()?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) type Op = Box<dyn for<'a> Fn(&mut State<'a>) -> Result<usize, BfError>>;
+
+pub struct Threaded {
+    ops: Vec<Op>,
+}
+
+// How a threaded run ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finished {
+    pub steps: u64,
+    pub tape: Vec<u32>,
+    pub pointer: usize,
+}
+
+impl Threaded {
+    // Fails on `Y`, each thread would need closures of its own
+    pub fn compile(program: &Program) -> Result<Self, BfError> {
+        let ops = compile_range(program, 0..program.len())?;
+        Ok(Self { ops })
+    }
+
+    pub fn run(
+        &self,
+        settings: &Settings,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> Result<Finished, BfError> {
+        let mut state = State::new(settings, input, output);
+        let (mut ip, mut steps) = (0, 0);
+        let result = match settings.max_steps {
+            // Kept out of the loop without a limit, which is the loop that needs the speed
+            None => loop {
+                let Some(op) = self.ops.get(ip) else {
+                    break Ok(());
+                };
+                match op(&mut state) {
+                    Ok(to) => ip = to,
+                    Err(err) => break Err(err),
+                }
+                steps += 1;
+            },
+            Some(limit) => loop {
+                let Some(op) = self.ops.get(ip) else {
+                    break Ok(());
+                };
+                if steps >= limit {
+                    break Err(BfError::StepLimit(limit));
+                }
+                match op(&mut state) {
+                    Ok(to) => ip = to,
+                    Err(err) => break Err(err),
+                }
+                steps += 1;
+            },
+        };
+        // Output written before a failure is still shown before the error
+        state.output.flush()?;
+        result?;
+        Ok(Finished {
+            steps,
+            tape: state.tape.cells().into_owned(),
+            pointer: state.tape.pointer(),
+        })
+    }
+}
+
+// The closures for `range` of `program`, the jumps in them going to indices in the program
+pub(crate) fn compile_range(program: &Program, range: Range<usize>) -> Result<Vec<Op>, BfError> {
+    range.map(|ip| compile_op(program, ip)).collect()
+}
+
+fn compile_op(program: &Program, ip: usize) -> Result<Op, BfError> {
+    let next = ip + 1;
+    let jump = program.jumps[ip] + 1;
+    let op: Op = match program.tokens[ip] {
+        BfToken::CEL(n) => Box::new(move |state| {
+            let cell = state.tape.get_mut();
+            *cell = cell.wrapping_add(n as u32) & state.mask;
+            Ok(next)
+        }),
+        BfToken::SET(n) => Box::new(move |state| {
+            *state.tape.get_mut() = n as u32 & state.mask;
+            Ok(next)
+        }),
+        BfToken::MOV(n) => Box::new(move |state| {
+            state.tape.shift(n)?;
+            Ok(next)
+        }),
+        BfToken::SCN(n) => Box::new(move |state| {
+            while state.tape.get() != 0 {
+                state.tape.shift(n)?;
+            }
+            Ok(next)
+        }),
+        BfToken::JUM => Box::new(move |state| match state.tape.get() {
+            0 => Ok(jump),
+            _ => Ok(next),
+        }),
+        BfToken::BAC => Box::new(move |state| match state.tape.get() {
+            0 => Ok(next),
+            _ => Ok(jump),
+        }),
+        BfToken::ACC => Box::new(move |state| state.read().map(|()| next)),
+        BfToken::OUT => Box::new(move |state| state.write().map(|()| next)),
+        BfToken::NUM => Box::new(move |state| state.read_number().map(|()| next)),
+        BfToken::DEC => Box::new(move |state| state.write_number().map(|()| next)),
+        BfToken::RND => Box::new(move |state| {
+            *state.tape.get_mut() = state.rng.byte() as u32;
+            Ok(next)
+        }),
+        BfToken::FRK => {
+            return Err(BfError::Unsupported(
+                "the threaded backend can't run forking programs".to_string(),
+            ))
+        }
+        BfToken::EXT(_) => {
+            return Err(BfError::Unsupported(
+                "host opcodes need the interpreter".to_string(),
+            ))
+        }
+        BfToken::NAN => Box::new(move |_| Ok(next)),
+    };
+    Ok(op)
+}
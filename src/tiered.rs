@@ -0,0 +1,144 @@
+// Tiered execution: a program starts out interpreted, with a count kept of how often each
+// loop goes around. A loop that goes around `HOT` times is turned into threaded code there
+// and then, the closures `threaded` builds rather than machine code, and from then on runs
+// as that, loops nested in it included. Short programs never pay for building closures and
+// long ones spend their time in them. Both tiers work on the same tape and count steps as
+// the interpreter does.
+use std::io::{Read, Write};
+
+use crate::error::BfError;
+use crate::program::Program;
+use crate::settings::Settings;
+use crate::threaded::{compile_range, Finished, Op, State};
+use crate::token::BfToken;
+
+// Turns after which a loop is compiled
+pub const HOT: u64 = 1000;
+
+pub struct Tiered {
+    pub finished: Finished,
+    pub promoted: Vec<usize>, // The loops compiled, by the index of their `[`, in order
+}
+
+// Fails on `Y` and host opcodes, as the threaded backend does
+pub fn run(
+    program: &Program,
+    settings: &Settings,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    hot: u64,
+) -> Result<Tiered, BfError> {
+    if let Some(ip) = program
+        .tokens
+        .iter()
+        .position(|token| matches!(token, BfToken::FRK | BfToken::EXT(_)))
+    {
+        // Compiling it fails, better now than once a loop gets hot
+        compile_range(program, ip..ip + 1)?;
+    }
+    let mut state = State::new(settings, input, output);
+    let limit = settings.max_steps.unwrap_or(u64::MAX);
+    let mut turns = vec![0; program.len()];
+    // Indexed by the `[` of each loop
+    let mut compiled: Vec<Option<Vec<Op>>> = (0..program.len()).map(|_| None).collect();
+    let mut promoted = vec![];
+    let (mut ip, mut steps) = (0, 0);
+    let result = loop {
+        let Some(&token) = program.tokens.get(ip) else {
+            break Ok(());
+        };
+        if let Some(ops) = &compiled[ip] {
+            match run_compiled(ops, ip, ip, &mut state, &mut steps, limit) {
+                Ok(to) => ip = to,
+                Err(err) => break Err(err),
+            }
+            continue;
+        }
+        if steps >= limit {
+            break Err(BfError::StepLimit(limit));
+        }
+        steps += 1;
+        let next = match token {
+            BfToken::BAC if state.tape.get() != 0 => {
+                let start = program.jumps[ip];
+                turns[start] += 1;
+                if turns[start] == hot {
+                    compiled[start] = Some(compile_range(program, start..ip + 1)?);
+                    promoted.push(start);
+                }
+                match &compiled[start] {
+                    // Straight back into the body, as the jump would have gone
+                    Some(ops) => run_compiled(ops, start, start + 1, &mut state, &mut steps, limit),
+                    None => Ok(start + 1),
+                }
+            }
+            token => interpret(program, ip, token, &mut state),
+        };
+        match next {
+            Ok(to) => ip = to,
+            Err(err) => break Err(err),
+        }
+    };
+    // Output written before a failure is still shown before the error
+    state.output.flush()?;
+    result?;
+    Ok(Tiered {
+        finished: Finished {
+            steps,
+            tape: state.tape.cells().into_owned(),
+            pointer: state.tape.pointer(),
+        },
+        promoted,
+    })
+}
+
+// Runs the closures for the loop whose `[` is at `base` from `ip` until it leaves the loop,
+// handing back where it went
+fn run_compiled(
+    ops: &[Op],
+    base: usize,
+    mut ip: usize,
+    state: &mut State,
+    steps: &mut u64,
+    limit: u64,
+) -> Result<usize, BfError> {
+    while let Some(op) = ops.get(ip.wrapping_sub(base)) {
+        if *steps >= limit {
+            return Err(BfError::StepLimit(limit));
+        }
+        ip = op(state)?;
+        *steps += 1;
+    }
+    Ok(ip)
+}
+
+// The instruction at `ip` done once, as its closure would do it
+fn interpret(
+    program: &Program,
+    ip: usize,
+    token: BfToken,
+    state: &mut State,
+) -> Result<usize, BfError> {
+    let next = ip + 1;
+    match token {
+        BfToken::CEL(n) => {
+            let cell = state.tape.get_mut();
+            *cell = cell.wrapping_add(n as u32) & state.mask;
+        }
+        BfToken::SET(n) => *state.tape.get_mut() = n as u32 & state.mask,
+        BfToken::MOV(n) => state.tape.shift(n)?,
+        BfToken::SCN(n) => {
+            while state.tape.get() != 0 {
+                state.tape.shift(n)?;
+            }
+        }
+        BfToken::JUM if state.tape.get() == 0 => return Ok(program.jumps[ip] + 1),
+        BfToken::ACC => state.read()?,
+        BfToken::OUT => state.write()?,
+        BfToken::NUM => state.read_number()?,
+        BfToken::DEC => state.write_number()?,
+        BfToken::RND => *state.tape.get_mut() = state.rng.byte() as u32,
+        _ => {}
+    }
+    Ok(next)
+}
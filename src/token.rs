@@ -0,0 +1,96 @@
+use std::ops::AddAssign;
+
+// Represents the possible operations in Brainf*** language.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BfToken {
+    CEL(isize), // Increment the current cell by N
+    MOV(isize), // Move the pointer by N
+    SET(isize), // Set the current cell to N
+    SCN(isize), // Move the pointer by N until the current cell is zero, a loop like `[>]`
+    JUM,        // Jump if the value of the current cell is zero
+    BAC,        // Jump to the matching opening bracket
+    ACC,        // Accept one byte of input, storing its value in the current cell
+    OUT,        // Output the value of the current cell as a character
+    RND,        // Store a pseudo-random byte in the current cell, the `random` extension
+    FRK,        // Fork into two threads, the `fork` extension
+    DEC,        // Output the value of the current cell in decimal, the `numbers` extension
+    NUM,        // Read a decimal number into the current cell, the `numbers` extension
+    EXT(u8),    // Call the host opcode registered for this ASCII character, see `opcode::Opcodes`
+    NAN,        // Not a valid operation
+}
+
+impl BfToken {
+    // Whether two tokens can be folded into one by adding their values
+    pub fn same_kind(&self, other: &Self) -> bool {
+        match (self, other) {
+            // Match against the same variant for each operation
+            (Self::CEL(_), Self::CEL(_)) => true,
+            (Self::MOV(_), Self::MOV(_)) => true,
+            (_, _) => false,
+        }
+    }
+}
+
+// Implements the From trait to convert a character to a BfToken.
+impl From<char> for BfToken {
+    fn from(value: char) -> Self {
+        match value {
+            '>' => Self::MOV(1),
+            '<' => Self::MOV(-1),
+            '+' => Self::CEL(1),
+            '-' => Self::CEL(-1),
+            '.' => Self::OUT,
+            ',' => Self::ACC,
+            '[' => Self::JUM,
+            ']' => Self::BAC,
+            _ => Self::NAN,
+        }
+    }
+}
+
+impl From<BfToken> for String {
+    fn from(value: BfToken) -> Self {
+        match value {
+            BfToken::CEL(n) => {
+                if n > 0 {
+                    "+".repeat(n as usize)
+                } else if n < 0 {
+                    "-".repeat(n.unsigned_abs())
+                } else {
+                    "".to_string()
+                }
+            }
+            BfToken::MOV(n) => {
+                if n > 0 {
+                    ">".repeat(n as usize)
+                } else if n < 0 {
+                    "<".repeat(n.unsigned_abs())
+                } else {
+                    "".to_string()
+                }
+            }
+            BfToken::SET(n) => format!("[-]{}", String::from(BfToken::CEL(n))),
+            BfToken::SCN(n) => format!("[{}]", String::from(BfToken::MOV(n))),
+            BfToken::JUM => "[".to_string(),
+            BfToken::BAC => "]".to_string(),
+            BfToken::ACC => ",".to_string(),
+            BfToken::OUT => ".".to_string(),
+            BfToken::RND => "?".to_string(),
+            BfToken::FRK => "Y".to_string(),
+            BfToken::DEC => ":".to_string(),
+            BfToken::NUM => ";".to_string(),
+            BfToken::EXT(c) => (c as char).to_string(),
+            BfToken::NAN => "".to_string(),
+        }
+    }
+}
+
+impl AddAssign for BfToken {
+    fn add_assign(&mut self, rhs: Self) {
+        match (self, &rhs) {
+            (Self::CEL(n), Self::CEL(a)) => *n += a,
+            (Self::MOV(n), Self::MOV(a)) => *n += a,
+            (_, _) => (),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+// A record of every instruction a run executed, for tools that analyze or replay it.
+//
+// Each event is the index of the instruction that ran, the pointer afterwards as a distance
+// from where it started (so it doesn't depend on how the tape grew) and the value written to
+// the current cell, for the instructions that write one (`+-` runs, SET, `,` and `?`).
+//
+// `json` is JSON Lines: a header `{"format":"bf-rust-trace","version":1}` and then one object
+// per event, e.g. `{"ip":4,"pointer":-1,"write":65}` with `write` left out when nothing was
+// written.
+//
+// When a program forks with `Y` only the first thread is traced, and the `Y` counts as writing
+// the 0 it leaves in the cell.
+//
+// `binary` starts with the magic `BFTRACE1`, then each event is the instruction index as an
+// unsigned LEB128, the change in pointer since the previous event as a signed LEB128, a flag
+// byte and, if its bit 0 is set, the value written as an unsigned LEB128. Bit 1 means a
+// keyframe follows, the tape after that event so replays can start from it instead of the
+// beginning: the position of its first cell as a signed LEB128 from the starting one, then
+// the number of cells and each value as unsigned LEB128s, with the zeros at either end left
+// out. Binary traces get a keyframe every `KEYFRAME_EVERY` events unless `set_keyframes`
+// says otherwise. `read_binary` turns a trace back into events, `read_recording` into events
+// and keyframes.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::machine::Machine;
+use crate::tape::MAX_GATHERED;
+use crate::token::BfToken;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TraceFormat {
+    Json,
+    Binary,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!(
+                "Invalid trace format {s:?}, expected json or binary"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TraceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+// One executed instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub ip: usize,
+    pub pointer: isize,
+    pub write: Option<u32>,
+}
+
+pub const KEYFRAME_EVERY: u64 = 100_000;
+
+// The tape after `event` events, as cells from `start` on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyframe {
+    pub event: usize,
+    pub start: isize,
+    pub cells: Vec<u32>,
+}
+
+// Writes events in a `TraceFormat` as the machine runs.
+pub struct Tracer {
+    out: Box<dyn Write + Send>,
+    format: TraceFormat,
+    pointer: isize, // Where the machine's pointer is now
+    last: isize,    // Where it was at the previous event
+    events: u64,
+    keyframes: Option<u64>, // Events between keyframes
+}
+
+impl Tracer {
+    pub fn new(mut out: Box<dyn Write + Send>, format: TraceFormat) -> io::Result<Self> {
+        match format {
+            TraceFormat::Json => writeln!(out, "{{\"format\":\"bf-rust-trace\",\"version\":1}}")?,
+            TraceFormat::Binary => out.write_all(b"BFTRACE1")?,
+        }
+        Ok(Self {
+            out,
+            format,
+            pointer: 0,
+            last: 0,
+            events: 0,
+            keyframes: match format {
+                TraceFormat::Json => None,
+                TraceFormat::Binary => Some(KEYFRAME_EVERY),
+            },
+        })
+    }
+
+    // How many events apart keyframes are, `None` for none, only binary traces have them
+    pub fn set_keyframes(&mut self, every: Option<u64>) {
+        if self.format == TraceFormat::Binary {
+            self.keyframes = every.filter(|&every| every > 0);
+        }
+    }
+
+    // Records the instruction at `ip`, which `machine` has just executed
+    pub fn record(&mut self, machine: &Machine, ip: usize) -> io::Result<()> {
+        let token = machine.program().tokens[ip];
+        let moved = match token {
+            BfToken::MOV(n) => n,
+            BfToken::SCN(_) => machine.scanned(),
+            _ => 0,
+        };
+        let write = match token {
+            BfToken::CEL(_)
+            | BfToken::SET(_)
+            | BfToken::ACC
+            | BfToken::NUM
+            | BfToken::RND
+            | BfToken::EXT(_)
+            | BfToken::FRK => Some(machine.cell()),
+            _ => None,
+        };
+        self.pointer += moved;
+        let keyframe = match self.keyframes {
+            Some(every) if (self.events + 1).is_multiple_of(every) => {
+                let memory = machine.memory();
+                let nonzero = memory.nonzero();
+                let first = nonzero.first().map_or(memory.cell_count(), |&(at, _)| at);
+                let end = nonzero.last().map_or(first, |&(last, _)| last + 1);
+                // A sparse tape written too far apart gets none, replays start from an
+                // earlier keyframe instead
+                (end - first <= MAX_GATHERED).then(|| {
+                    let mut cells = vec![0; end - first];
+                    for (at, cell) in nonzero {
+                        cells[at - first] = cell;
+                    }
+                    Keyframe {
+                        event: self.events as usize + 1,
+                        start: self.pointer - machine.pointer() as isize + first as isize,
+                        cells,
+                    }
+                })
+            }
+            _ => None,
+        };
+        let event = Event {
+            ip,
+            pointer: self.pointer,
+            write,
+        };
+        self.write_event(event, keyframe.as_ref())
+    }
+
+    fn write_event(&mut self, event: Event, keyframe: Option<&Keyframe>) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Json => {
+                write!(
+                    self.out,
+                    "{{\"ip\":{},\"pointer\":{}",
+                    event.ip, event.pointer
+                )?;
+                if let Some(value) = event.write {
+                    write!(self.out, ",\"write\":{value}")?;
+                }
+                writeln!(self.out, "}}")?;
+            }
+            TraceFormat::Binary => {
+                let mut bytes = vec![];
+                uleb128(&mut bytes, event.ip as u64);
+                sleb128(&mut bytes, (event.pointer - self.last) as i64);
+                let flag = event.write.is_some() as u8 | (keyframe.is_some() as u8) << 1;
+                bytes.push(flag);
+                if let Some(value) = event.write {
+                    uleb128(&mut bytes, value as u64);
+                }
+                if let Some(keyframe) = keyframe {
+                    sleb128(&mut bytes, keyframe.start as i64);
+                    uleb128(&mut bytes, keyframe.cells.len() as u64);
+                    for &cell in &keyframe.cells {
+                        uleb128(&mut bytes, cell as u64);
+                    }
+                }
+                self.out.write_all(&bytes)?;
+            }
+        }
+        self.last = event.pointer;
+        self.events += 1;
+        Ok(())
+    }
+
+    pub fn events(&self) -> u64 {
+        self.events
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// The events of a trace written in the binary format
+pub fn read_binary(bytes: &[u8]) -> io::Result<Vec<Event>> {
+    Ok(read_recording(bytes)?.events)
+}
+
+// A binary trace with its keyframes, for stepping through a run at random
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub events: Vec<Event>,
+    pub keyframes: Vec<Keyframe>, // In order of `event`
+}
+
+impl Recording {
+    // Where the pointer was after the first `step` events, from the starting cell
+    pub fn pointer_at(&self, step: usize) -> isize {
+        match step {
+            0 => 0,
+            _ => self.events[step - 1].pointer,
+        }
+    }
+
+    // The cells that aren't zero after the first `step` events, replayed from the last
+    // keyframe before them
+    pub fn tape_at(&self, step: usize) -> BTreeMap<isize, u32> {
+        let after = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.event <= step);
+        let mut tape = BTreeMap::new();
+        let mut from = 0;
+        if let Some(keyframe) = after.checked_sub(1).map(|idx| &self.keyframes[idx]) {
+            from = keyframe.event;
+            for (at, &cell) in keyframe.cells.iter().enumerate() {
+                if cell != 0 {
+                    tape.insert(keyframe.start + at as isize, cell);
+                }
+            }
+        }
+        for event in &self.events[from..step] {
+            match event.write {
+                Some(0) => tape.remove(&event.pointer),
+                Some(value) => tape.insert(event.pointer, value),
+                None => None,
+            };
+        }
+        tape
+    }
+}
+
+pub fn read_recording(bytes: &[u8]) -> io::Result<Recording> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut rest = bytes
+        .strip_prefix(b"BFTRACE1")
+        .ok_or_else(|| invalid("not a binary trace"))?;
+    let mut recording = Recording::default();
+    let mut pointer = 0;
+    while !rest.is_empty() {
+        let truncated = || invalid("trace ends in the middle of an event");
+        let ip = read_uleb128(&mut rest).ok_or_else(truncated)? as usize;
+        pointer += read_sleb128(&mut rest).ok_or_else(truncated)? as isize;
+        let (&flag, after) = rest.split_first().ok_or_else(truncated)?;
+        rest = after;
+        if flag > 3 {
+            return Err(invalid("bad flag in trace"));
+        }
+        let write = match flag & 1 {
+            0 => None,
+            _ => Some(read_uleb128(&mut rest).ok_or_else(truncated)? as u32),
+        };
+        recording.events.push(Event { ip, pointer, write });
+        if flag & 2 != 0 {
+            let start = read_sleb128(&mut rest).ok_or_else(truncated)? as isize;
+            let len = read_uleb128(&mut rest).ok_or_else(truncated)? as usize;
+            // Not trusting the length for the allocation, every cell takes at least a byte
+            let mut cells = Vec::with_capacity(len.min(rest.len()));
+            for _ in 0..len {
+                cells.push(read_uleb128(&mut rest).ok_or_else(truncated)? as u32);
+            }
+            recording.keyframes.push(Keyframe {
+                event: recording.events.len(),
+                start,
+                cells,
+            });
+        }
+    }
+    Ok(recording)
+}
+
+fn read_uleb128(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn read_sleb128(bytes: &mut &[u8]) -> Option<i64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as i64) << shift;
+        if byte & 0x80 == 0 {
+            // Sign-extend from the last byte's top bit
+            if shift + 7 < 64 && byte & 0x40 != 0 {
+                value |= -1 << (shift + 7);
+            }
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn uleb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb128(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
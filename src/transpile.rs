@@ -0,0 +1,167 @@
+// Translates a program to Rust source.
+use std::fmt::Write;
+
+use crate::program::Program;
+use crate::settings::{CellSize, Eof, Settings};
+use crate::token::BfToken;
+
+// The Rust type of a cell
+pub fn cell_type(cell_size: CellSize) -> &'static str {
+    match cell_size {
+        CellSize::U8 => "u8",
+        CellSize::U16 => "u16",
+        CellSize::U32 => "u32",
+    }
+}
+
+// Statements that run the program, expecting `input: &mut dyn Read` and `output: &mut dyn Write`
+// in scope and evaluating to `std::io::Result<()>`
+pub fn to_rust_body(program: &Program, settings: &Settings) -> String {
+    let cell = cell_type(settings.cell_size);
+    let mask = settings.cell_size.mask() as u64;
+    let wrap = |n: isize| (n as i64 as u64) & mask;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "let mut tape: ::std::vec::Vec<{cell}> = ::std::vec![0];"
+    );
+    let _ = writeln!(out, "let mut p: usize = 0;");
+    if program.tokens.contains(&BfToken::RND) {
+        // The same generator as `Rng`, so a seed gives the same bytes as the interpreter
+        let seed = match settings.seed {
+            Some(seed) => format!("{seed}u64"),
+            None => "::std::time::SystemTime::now().duration_since(::std::time::UNIX_EPOCH)\
+                     .map_or(0, |t| t.as_nanos() as u64)"
+                .to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "let mut rng: u64 = {seed} ^ 0x9E37_79B9_7F4A_7C15; if rng == 0 {{ rng = 1; }}"
+        );
+        let _ = writeln!(
+            out,
+            "let mut random = move || {{ rng ^= rng >> 12; rng ^= rng << 25; rng ^= rng >> 27; \
+             (rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8 }};"
+        );
+        let _ = writeln!(out, "random();");
+    }
+    if program.tokens.contains(&BfToken::NUM) {
+        // Reads a number the way `Number` does, `None` being EOF
+        let _ = writeln!(
+            out,
+            "fn read_number(input: &mut dyn ::std::io::Read) -> ::std::io::Result<Option<u64>> {{ \
+             let (mut value, mut negative, mut started) = (0u64, false, false); \
+             let sign = |value: u64, negative: bool| if negative {{ value.wrapping_neg() }} else {{ value }}; \
+             let mut b = [0u8]; loop {{ match ::std::io::Read::read_exact(input, &mut b) {{ \
+             Ok(()) => (), \
+             Err(e) if e.kind() == ::std::io::ErrorKind::UnexpectedEof => break, \
+             Err(e) => return Err(e), }} \
+             match b[0] {{ c if c.is_ascii_whitespace() && !started => (), \
+             c @ (b'-' | b'+') if !started => {{ negative = c == b'-'; started = true; }} \
+             c @ b'0'..=b'9' => {{ value = value.wrapping_mul(10).wrapping_add((c - b'0') as u64); started = true; }} \
+             _ => return Ok(Some(sign(value, negative))), }} }} \
+             Ok(started.then(|| sign(value, negative))) }}"
+        );
+    }
+    let mut depth = 0;
+    for &token in &program.tokens {
+        let pad = "    ".repeat(depth);
+        let eof = match settings.eof {
+            Eof::Zero => "tape[p] = 0;".to_string(),
+            Eof::Minus1 => format!("tape[p] = {cell}::MAX;"),
+            Eof::Unchanged => String::new(),
+        };
+        let line = match token {
+            // Runs that cancel out, like `+-`, do nothing
+            BfToken::CEL(0) | BfToken::MOV(0) => continue,
+            BfToken::CEL(n) => format!("tape[p] = tape[p].wrapping_add({});", wrap(n)),
+            BfToken::SET(n) => format!("tape[p] = {};", wrap(n)),
+            BfToken::MOV(n) => shift(n),
+            BfToken::SCN(n) => format!("while tape[p] != 0 {{ {} }}", shift(n)),
+            BfToken::JUM => {
+                depth += 1;
+                "while tape[p] != 0 {".to_string()
+            }
+            BfToken::BAC => {
+                depth -= 1;
+                let _ = writeln!(out, "{}}}", "    ".repeat(depth));
+                continue;
+            }
+            BfToken::ACC => {
+                format!(
+                    "{{ output.flush()?; let mut b = [0u8]; \
+                     match input.read_exact(&mut b) {{ \
+                     Ok(()) => tape[p] = b[0] as {cell}, \
+                     Err(e) if e.kind() == ::std::io::ErrorKind::UnexpectedEof => {{ {eof} }} \
+                     Err(e) => return Err(e), }} }}"
+                )
+            }
+            BfToken::OUT => "output.write_all(&[tape[p] as u8])?;".to_string(),
+            BfToken::DEC => "output.write_all(tape[p].to_string().as_bytes())?;".to_string(),
+            BfToken::NUM => format!(
+                "{{ output.flush()?; match read_number(&mut *input)? {{ \
+                 Some(n) => tape[p] = n as {cell}, None => {{ {eof} }} }} }}"
+            ),
+            BfToken::RND => format!("tape[p] = random() as {cell};"),
+            // Threads don't map onto straight-line Rust
+            BfToken::FRK => {
+                "::std::compile_error!(\"forking with `Y` can't be translated to Rust\");"
+                    .to_string()
+            }
+            BfToken::EXT(c) => format!(
+                "::std::compile_error!(\"the host opcode `{}` can't be translated to Rust\");",
+                (c as char).escape_default()
+            ),
+            BfToken::NAN => continue,
+        };
+        let _ = writeln!(out, "{pad}{line}");
+    }
+    let _ = writeln!(out, "output.flush()?;");
+    let _ = writeln!(out, "Ok(())");
+    out
+}
+
+// Moves `p` by `n`, growing the tape at either end as needed
+fn shift(n: isize) -> String {
+    match n > 0 {
+        true => format!("p += {n}; if p >= tape.len() {{ tape.resize(p + 1, 0); }}"),
+        false => {
+            let n = n.unsigned_abs();
+            format!(
+                "if p >= {n} {{ p -= {n}; }} else {{ \
+                 tape.splice(0..0, ::std::iter::repeat(0).take({n} - p)); p = 0; }}"
+            )
+        }
+    }
+}
+
+// A complete program reading stdin and writing stdout
+pub fn to_rust(program: &Program, settings: &Settings) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "#![allow(unused_mut, unused_variables, unused_imports)]"
+    );
+    let _ = writeln!(out, "use std::io::{{Read, Write}};\n");
+    let _ = writeln!(
+        out,
+        "fn run(input: &mut dyn Read, output: &mut dyn Write) -> std::io::Result<()> {{"
+    );
+    for line in to_rust_body(program, settings).lines() {
+        let _ = writeln!(out, "    {line}");
+    }
+    let _ = writeln!(out, "}}\n");
+    let _ = writeln!(out, "fn main() {{");
+    let _ = writeln!(out, "    let mut input = std::io::stdin().lock();");
+    let _ = writeln!(
+        out,
+        "    let mut output = std::io::BufWriter::new(std::io::stdout().lock());"
+    );
+    let _ = writeln!(out, "    if let Err(err) = run(&mut input, &mut output) {{");
+    let _ = writeln!(out, "        eprintln!(\"error: {{err}}\");");
+    let _ = writeln!(out, "        std::process::exit(1);");
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+    out
+}
@@ -0,0 +1,494 @@
+// Compiles a program to a WebAssembly module, as text (WAT) or binary.
+//
+// For `Target::Env` the module imports `env.putchar(i32)` and `env.getchar() -> i32`
+// (returning -1 at end of input), exports its `memory` as the tape and a `run` function that
+// executes the program. Programs using `?` also import `env.random() -> i32` for a byte to
+// store. The tape starts at address 0 and is `PAGES` pages long, moving off either end traps.
+//
+// `Target::Wasi` makes a WASI command instead, that runtimes like wasmtime and wasmer run
+// directly: `_start` runs the program, with `.` writing to stdout through `fd_write`, `,`
+// reading stdin through `fd_read` and `?` asking `random_get`. Those calls need a few bytes of
+// memory of their own, an extra page after the tape, so moving off its right end runs into
+// that page before trapping.
+use std::fmt::Write;
+
+use crate::program::Program;
+use crate::settings::{CellSize, Eof, Settings};
+use crate::token::BfToken;
+
+pub const PAGES: u32 = 16;
+
+// Where a module's I/O comes from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Target {
+    Env,  // Imports from the embedder, who needs a few lines of glue
+    Wasi, // WASI's stdin and stdout
+}
+
+// What `,`, `.` and `?` call: imports for `Target::Env`, and the same functions built over
+// WASI's for `Target::Wasi`
+const PUTCHAR: u32 = 0;
+const GETCHAR: u32 = 1;
+const RANDOM: u32 = 2;
+const HELPERS: [&str; 3] = ["putchar", "getchar", "random"];
+
+// The WASI imports, in import order
+const FD_WRITE: u32 = 0;
+const FD_READ: u32 = 1;
+const RANDOM_GET: u32 = 2;
+const WASI: [&str; 3] = ["fd_write", "fd_read", "random_get"];
+
+// Scratch space for WASI calls in the page after the tape: an iovec pointing at `BYTE`, the
+// count of bytes transferred and the byte itself
+const IOVEC: i32 = (PAGES * 65536) as i32;
+const COUNT: i32 = IOVEC + 8;
+const BYTE: i32 = IOVEC + 12;
+
+// Locals of `run`
+const P: u32 = 0;
+const C: u32 = 1;
+
+// The handful of instructions the backend needs
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Op {
+    LocalGet(u32),
+    LocalSet(u32),
+    Const(i32),
+    Add,
+    Ne,
+    Eqz,
+    Load,
+    Store,
+    Block,
+    Loop,
+    If,
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Call(u32),
+    Unreachable,
+    // Only used by the WASI helpers
+    Drop,
+    LoadByte,
+    StoreByte,
+    LoadWord,
+    StoreWord,
+    IfValue, // An `if` leaving an i32
+    CallWasi(u32),
+}
+
+fn lower(program: &Program, settings: &Settings) -> Vec<Op> {
+    let width = (settings.cell_size.bits() / 8) as i32;
+    let mut ops = vec![];
+    for &token in &program.tokens {
+        match token {
+            BfToken::CEL(n) => ops.extend([
+                Op::LocalGet(P),
+                Op::LocalGet(P),
+                Op::Load,
+                Op::Const(n as i32),
+                Op::Add,
+                Op::Store,
+            ]),
+            BfToken::MOV(n) => ops.extend([
+                Op::LocalGet(P),
+                Op::Const(n as i32 * width),
+                Op::Add,
+                Op::LocalSet(P),
+            ]),
+            BfToken::SET(n) => ops.extend([Op::LocalGet(P), Op::Const(n as i32), Op::Store]),
+            BfToken::SCN(n) => ops.extend([
+                Op::Block,
+                Op::Loop,
+                Op::LocalGet(P),
+                Op::Load,
+                Op::Eqz,
+                Op::BrIf(1),
+                Op::LocalGet(P),
+                Op::Const(n as i32 * width),
+                Op::Add,
+                Op::LocalSet(P),
+                Op::Br(0),
+                Op::End,
+                Op::End,
+            ]),
+            BfToken::JUM => ops.extend([
+                Op::Block,
+                Op::Loop,
+                Op::LocalGet(P),
+                Op::Load,
+                Op::Eqz,
+                Op::BrIf(1),
+            ]),
+            BfToken::BAC => ops.extend([Op::Br(0), Op::End, Op::End]),
+            BfToken::ACC => {
+                ops.extend([
+                    Op::Call(GETCHAR),
+                    Op::LocalSet(C),
+                    Op::LocalGet(C),
+                    Op::Const(-1),
+                    Op::Ne,
+                    Op::If,
+                    Op::LocalGet(P),
+                    Op::LocalGet(C),
+                    Op::Store,
+                ]);
+                match settings.eof {
+                    Eof::Zero => ops.extend([Op::Else, Op::LocalGet(P), Op::Const(0), Op::Store]),
+                    Eof::Minus1 => {
+                        ops.extend([Op::Else, Op::LocalGet(P), Op::Const(-1), Op::Store])
+                    }
+                    Eof::Unchanged => (),
+                }
+                ops.push(Op::End);
+            }
+            BfToken::OUT => ops.extend([Op::LocalGet(P), Op::Load, Op::Call(PUTCHAR)]),
+            BfToken::RND => ops.extend([Op::LocalGet(P), Op::Call(RANDOM), Op::Store]),
+            // There are no threads to fork into, so a `Y` traps, and nothing formats or
+            // parses numbers, so `:` and `;` do too, as do host opcodes with no host
+            BfToken::FRK | BfToken::DEC | BfToken::NUM | BfToken::EXT(_) => {
+                ops.push(Op::Unreachable)
+            }
+            BfToken::NAN => (),
+        }
+    }
+    ops
+}
+
+// The functions a WASI module defines in front of `run`, with the names `HELPERS` gives them
+fn wasi_helpers(random: bool) -> Vec<Vec<Op>> {
+    let iovec = [
+        Op::Const(IOVEC),
+        Op::Const(BYTE),
+        Op::StoreWord,
+        Op::Const(IOVEC + 4),
+        Op::Const(1),
+        Op::StoreWord,
+    ];
+    // fd, iovs, iovs_len and where to put the count
+    let transfer = |fd| {
+        [
+            Op::Const(fd),
+            Op::Const(IOVEC),
+            Op::Const(1),
+            Op::Const(COUNT),
+        ]
+    };
+    let mut putchar = vec![Op::Const(BYTE), Op::LocalGet(0), Op::StoreByte];
+    putchar.extend(iovec);
+    putchar.extend(transfer(1));
+    putchar.extend([Op::CallWasi(FD_WRITE), Op::Drop]);
+    // An error or reading nothing is the end of input
+    let mut getchar = iovec.to_vec();
+    getchar.extend(transfer(0));
+    getchar.extend([
+        Op::CallWasi(FD_READ),
+        Op::IfValue,
+        Op::Const(-1),
+        Op::Else,
+        Op::Const(COUNT),
+        Op::LoadWord,
+        Op::Eqz,
+        Op::IfValue,
+        Op::Const(-1),
+        Op::Else,
+        Op::Const(BYTE),
+        Op::LoadByte,
+        Op::End,
+        Op::End,
+    ]);
+    let mut helpers = vec![putchar, getchar];
+    if random {
+        helpers.push(vec![
+            Op::Const(BYTE),
+            Op::Const(1),
+            Op::CallWasi(RANDOM_GET),
+            Op::Drop,
+            Op::Const(BYTE),
+            Op::LoadByte,
+        ]);
+    }
+    helpers
+}
+
+pub fn emit_wat(program: &Program, settings: &Settings) -> String {
+    emit_wat_for(program, settings, Target::Env)
+}
+
+pub fn emit_wat_for(program: &Program, settings: &Settings, target: Target) -> String {
+    let cell = match settings.cell_size {
+        CellSize::U8 => ("i32.load8_u", "i32.store8"),
+        CellSize::U16 => ("i32.load16_u", "i32.store16"),
+        CellSize::U32 => ("i32.load", "i32.store"),
+    };
+    let random = program.tokens.contains(&BfToken::RND);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "(module");
+    let pages = match target {
+        Target::Env => {
+            let _ = writeln!(
+                out,
+                "  (import \"env\" \"putchar\" (func $putchar (param i32)))"
+            );
+            let _ = writeln!(
+                out,
+                "  (import \"env\" \"getchar\" (func $getchar (result i32)))"
+            );
+            if random {
+                let _ = writeln!(
+                    out,
+                    "  (import \"env\" \"random\" (func $random (result i32)))"
+                );
+            }
+            PAGES
+        }
+        Target::Wasi => {
+            let io = "(param i32 i32 i32 i32) (result i32)";
+            for name in ["fd_write", "fd_read"] {
+                let _ = writeln!(
+                    out,
+                    "  (import \"wasi_snapshot_preview1\" \"{name}\" (func ${name} {io}))"
+                );
+            }
+            if random {
+                let _ = writeln!(
+                    out,
+                    "  (import \"wasi_snapshot_preview1\" \"random_get\" (func $random_get (param i32 i32) (result i32)))"
+                );
+            }
+            PAGES + 1
+        }
+    };
+    let _ = writeln!(out, "  (memory (export \"memory\") {pages})");
+    let run = match target {
+        Target::Env => "run",
+        Target::Wasi => {
+            for (idx, body) in wasi_helpers(random).iter().enumerate() {
+                let signature = match idx as u32 {
+                    PUTCHAR => "(param $c i32)",
+                    _ => "(result i32)",
+                };
+                let _ = writeln!(out, "  (func ${} {signature}", HELPERS[idx]);
+                wat_body(&mut out, body, cell, &["$c"]);
+                let _ = writeln!(out, "  )");
+            }
+            "_start"
+        }
+    };
+    let _ = writeln!(
+        out,
+        "  (func (export \"{run}\") (local $p i32) (local $c i32)"
+    );
+    wat_body(&mut out, &lower(program, settings), cell, &["$p", "$c"]);
+    let _ = writeln!(out, "  )\n)");
+    out
+}
+
+// Writes `ops` inside a function, `cell` being the load and store for tape cells and
+// `locals` the names of the function's locals
+fn wat_body(out: &mut String, ops: &[Op], cell: (&str, &str), locals: &[&str]) {
+    let mut depth = 2;
+    for &op in ops {
+        if matches!(op, Op::End | Op::Else) {
+            depth -= 1;
+        }
+        let text = match op {
+            Op::LocalGet(idx) => format!("local.get {}", locals[idx as usize]),
+            Op::LocalSet(idx) => format!("local.set {}", locals[idx as usize]),
+            Op::Const(n) => format!("i32.const {n}"),
+            Op::Add => "i32.add".to_string(),
+            Op::Ne => "i32.ne".to_string(),
+            Op::Eqz => "i32.eqz".to_string(),
+            Op::Load => cell.0.to_string(),
+            Op::Store => cell.1.to_string(),
+            Op::Block => "block".to_string(),
+            Op::Loop => "loop".to_string(),
+            Op::If => "if".to_string(),
+            Op::Else => "else".to_string(),
+            Op::End => "end".to_string(),
+            Op::Br(depth) => format!("br {depth}"),
+            Op::BrIf(depth) => format!("br_if {depth}"),
+            Op::Call(func) => format!("call ${}", HELPERS[func as usize]),
+            Op::Unreachable => "unreachable".to_string(),
+            Op::Drop => "drop".to_string(),
+            Op::LoadByte => "i32.load8_u".to_string(),
+            Op::StoreByte => "i32.store8".to_string(),
+            Op::LoadWord => "i32.load".to_string(),
+            Op::StoreWord => "i32.store".to_string(),
+            Op::IfValue => "if (result i32)".to_string(),
+            Op::CallWasi(func) => format!("call ${}", WASI[func as usize]),
+        };
+        let _ = writeln!(out, "{}{text}", "  ".repeat(depth));
+        if matches!(op, Op::Block | Op::Loop | Op::If | Op::IfValue | Op::Else) {
+            depth += 1;
+        }
+    }
+}
+
+pub fn emit_wasm(program: &Program, settings: &Settings) -> Vec<u8> {
+    emit_wasm_for(program, settings, Target::Env)
+}
+
+pub fn emit_wasm_for(program: &Program, settings: &Settings, target: Target) -> Vec<u8> {
+    let cell = match settings.cell_size {
+        CellSize::U8 => (0x2d, 0x3a, 0),
+        CellSize::U16 => (0x2f, 0x3b, 1),
+        CellSize::U32 => (0x28, 0x36, 2),
+    };
+    let random = program.tokens.contains(&BfToken::RND);
+    let calls = 2 + random as u32;
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    // Types: (i32) -> (), () -> (i32), () -> () and for WASI
+    // (i32, i32, i32, i32) -> (i32), (i32, i32) -> (i32)
+    let mut types = vec![0x60, 1, 0x7f, 0, 0x60, 0, 1, 0x7f, 0x60, 0, 0];
+    let (module_name, imports, helpers, start) = match target {
+        Target::Env => (
+            "env",
+            [(HELPERS[0], 0), (HELPERS[1], 1), (HELPERS[2], 1)],
+            vec![],
+            "run",
+        ),
+        Target::Wasi => {
+            types.extend([0x60, 4, 0x7f, 0x7f, 0x7f, 0x7f, 1, 0x7f]);
+            types.extend([0x60, 2, 0x7f, 0x7f, 1, 0x7f]);
+            let imports = [(WASI[0], 3), (WASI[1], 3), (WASI[2], 4)];
+            (
+                "wasi_snapshot_preview1",
+                imports,
+                wasi_helpers(random),
+                "_start",
+            )
+        }
+    };
+    let mut section_types = vec![if target == Target::Env { 3 } else { 5 }];
+    section_types.extend(types);
+    section(&mut module, 1, &section_types);
+
+    let mut import_section = vec![calls as u8];
+    for (name, ty) in &imports[..calls as usize] {
+        name_bytes(&mut import_section, module_name);
+        name_bytes(&mut import_section, name);
+        import_section.extend([0, *ty]);
+    }
+    section(&mut module, 2, &import_section);
+
+    // The helpers' types follow `HELPERS`, and `run` comes last
+    let mut functions = vec![helpers.len() as u8 + 1];
+    functions.extend([0, 1, 1].iter().take(helpers.len()));
+    functions.push(2);
+    section(&mut module, 3, &functions);
+
+    let pages = match target {
+        Target::Env => PAGES,
+        Target::Wasi => PAGES + 1,
+    };
+    let mut memory = vec![1, 0];
+    uleb128(&mut memory, pages);
+    section(&mut module, 5, &memory);
+
+    // Imports are numbered first, then `,` `.` and `?` call the helpers when there are any
+    let run = (calls + helpers.len() as u32) as u8;
+    let offset = match target {
+        Target::Env => 0,
+        Target::Wasi => calls,
+    };
+    let mut exports = vec![2];
+    name_bytes(&mut exports, "memory");
+    exports.extend([2, 0]);
+    name_bytes(&mut exports, start);
+    exports.extend([0, run]);
+    section(&mut module, 7, &exports);
+
+    let mut bodies = vec![helpers.len() as u8 + 1];
+    for helper in &helpers {
+        // No locals
+        let mut code = vec![0];
+        encode(&mut code, helper, cell, offset);
+        uleb128(&mut bodies, code.len() as u32);
+        bodies.extend(code);
+    }
+    // One group of two i32 locals
+    let mut code = vec![1, 2, 0x7f];
+    encode(&mut code, &lower(program, settings), cell, offset);
+    uleb128(&mut bodies, code.len() as u32);
+    bodies.extend(code);
+    section(&mut module, 10, &bodies);
+    module
+}
+
+// Appends the function body `ops` and its closing `end`, `cell` being the load and store
+// opcodes for tape cells with their alignment and `offset` the index of the first helper
+fn encode(code: &mut Vec<u8>, ops: &[Op], cell: (u8, u8, u8), offset: u32) {
+    let (load, store, align) = cell;
+    for &op in ops {
+        match op {
+            Op::LocalGet(idx) => code.extend([0x20, idx as u8]),
+            Op::LocalSet(idx) => code.extend([0x21, idx as u8]),
+            Op::Const(n) => {
+                code.push(0x41);
+                sleb128(code, n);
+            }
+            Op::Add => code.push(0x6a),
+            Op::Ne => code.push(0x47),
+            Op::Eqz => code.push(0x45),
+            Op::Load => code.extend([load, align, 0]),
+            Op::Store => code.extend([store, align, 0]),
+            Op::Block => code.extend([0x02, 0x40]),
+            Op::Loop => code.extend([0x03, 0x40]),
+            Op::If => code.extend([0x04, 0x40]),
+            Op::Else => code.push(0x05),
+            Op::End => code.push(0x0b),
+            Op::Br(depth) => code.extend([0x0c, depth as u8]),
+            Op::BrIf(depth) => code.extend([0x0d, depth as u8]),
+            Op::Call(func) => code.extend([0x10, (offset + func) as u8]),
+            Op::Unreachable => code.push(0x00),
+            Op::Drop => code.push(0x1a),
+            Op::LoadByte => code.extend([0x2d, 0, 0]),
+            Op::StoreByte => code.extend([0x3a, 0, 0]),
+            Op::LoadWord => code.extend([0x28, 2, 0]),
+            Op::StoreWord => code.extend([0x36, 2, 0]),
+            Op::IfValue => code.extend([0x04, 0x7f]),
+            Op::CallWasi(func) => code.extend([0x10, func as u8]),
+        }
+    }
+    code.push(0x0b);
+}
+
+fn section(module: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    module.push(id);
+    uleb128(module, contents.len() as u32);
+    module.extend_from_slice(contents);
+}
+
+fn name_bytes(out: &mut Vec<u8>, name: &str) {
+    uleb128(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn uleb128(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb128(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
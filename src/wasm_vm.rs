@@ -0,0 +1,697 @@
+// A small WebAssembly interpreter for running the modules `wasm` emits in-process, behind the
+// `wasm` feature, without a runtime to depend on. It is there to check the emitter, not to run
+// programs fast: an instruction costs more here than in the Brainfuck interpreter, and
+// wasmtime or wasmer are the way to run `--emit-wasm` output at speed.
+//
+// It decodes the binary module, not the program it came from, so a run checks the emitter as
+// much as the program. Only what the MVP needs for those modules is understood: functions over
+// i32, one memory, imported host functions, blocks, loops, ifs, branches, calls and the i32
+// loads, stores and arithmetic `wasm::encode` writes. Anything else is refused when decoding.
+// Branch targets are worked out once there, so running is a loop over a flat instruction list.
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+
+use crate::error::BfError;
+use crate::program::Program;
+use crate::rng::Rng;
+use crate::settings::{Flush, Settings};
+use crate::tape::Tape;
+use crate::token::BfToken;
+use crate::wasm::emit_wasm;
+
+const PAGE: usize = 65536;
+
+// Why a module can't be run, or stopped running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trap {
+    Invalid(String),  // A module this interpreter can't decode
+    OutOfBounds(i64), // A load or store at this address, off the end of memory
+    Unreachable,      // An `unreachable` instruction ran
+    Host(String),     // An imported function failed
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(reason) => write!(f, "invalid module: {reason}"),
+            Self::OutOfBounds(at) => write!(f, "memory access out of bounds at {at}"),
+            Self::Unreachable => write!(f, "unreachable executed"),
+            Self::Host(reason) => write!(f, "host function failed: {reason}"),
+        }
+    }
+}
+
+// The imported functions, called by their index among the imports with their arguments
+pub trait Host {
+    fn call(&mut self, import: usize, args: &[i32]) -> Result<Option<i32>, Trap>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Width {
+    Byte,
+    Half,
+    Word,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Instr {
+    Unreachable,
+    // Blocks hold the index of their `end`, and an `if` its `else` too if it has one
+    Block {
+        results: usize,
+        end: usize,
+    },
+    Loop,
+    If {
+        results: usize,
+        other: Option<usize>,
+        end: usize,
+    },
+    Else {
+        end: usize,
+    },
+    End,
+    Br(u32),
+    BrIf(u32),
+    Call(u32),
+    Drop,
+    LocalGet(u32),
+    LocalSet(u32),
+    Const(i32),
+    Eqz,
+    Ne,
+    Add,
+    Load(Width, u32),
+    Store(Width, u32),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Type {
+    params: usize,
+    results: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Func {
+    ty: Type,
+    locals: usize, // Besides the parameters
+    code: Vec<Instr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    imports: Vec<(String, String, Type)>,
+    funcs: Vec<Func>,
+    pages: usize,
+    exports: Vec<(String, u8, u32)>,
+}
+
+// A control frame: where its values start and where a branch to it goes
+struct Frame {
+    height: usize,
+    arity: usize,
+    target: usize,
+    looping: bool,
+}
+
+// Bytes are read from here, failing on a module that ends early
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, Trap> {
+        let byte = *self
+            .bytes
+            .get(self.at)
+            .ok_or_else(|| invalid("unexpected end"))?;
+        self.at += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Trap> {
+        let end = self
+            .at
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len());
+        let bytes = &self.bytes[self.at..end.ok_or_else(|| invalid("unexpected end"))?];
+        self.at += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Trap> {
+        let mut value = 0u64;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return u32::try_from(value).map_err(|_| invalid("integer too large"));
+            }
+        }
+        Err(invalid("integer too long"))
+    }
+
+    fn i32(&mut self) -> Result<i32, Trap> {
+        let mut value = 0i64;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as i64) << shift;
+            if byte & 0x80 == 0 {
+                if byte & 0x40 != 0 {
+                    value |= -1 << (shift + 7);
+                }
+                return i32::try_from(value).map_err(|_| invalid("integer too large"));
+            }
+        }
+        Err(invalid("integer too long"))
+    }
+
+    fn name(&mut self) -> Result<String, Trap> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("name isn't UTF-8"))
+    }
+
+    fn done(&self) -> bool {
+        self.at == self.bytes.len()
+    }
+}
+
+fn invalid(reason: &str) -> Trap {
+    Trap::Invalid(reason.to_string())
+}
+
+impl Module {
+    pub fn decode(bytes: &[u8]) -> Result<Self, Trap> {
+        let mut reader = Reader { bytes, at: 0 };
+        if reader.take(8)? != b"\0asm\x01\0\0\0" {
+            return Err(invalid("not a version 1 module"));
+        }
+        let mut types = vec![];
+        let mut module = Module {
+            imports: vec![],
+            funcs: vec![],
+            pages: 0,
+            exports: vec![],
+        };
+        let mut signatures = vec![];
+        while !reader.done() {
+            let id = reader.byte()?;
+            let len = reader.u32()? as usize;
+            let mut section = Reader {
+                bytes: reader.take(len)?,
+                at: 0,
+            };
+            match id {
+                1 => {
+                    for _ in 0..section.u32()? {
+                        if section.byte()? != 0x60 {
+                            return Err(invalid("expected a function type"));
+                        }
+                        let params = i32s(&mut section)?;
+                        let results = i32s(&mut section)?;
+                        types.push(Type { params, results });
+                    }
+                }
+                2 => {
+                    for _ in 0..section.u32()? {
+                        let (module_name, name) = (section.name()?, section.name()?);
+                        if section.byte()? != 0 {
+                            return Err(invalid("only functions can be imported"));
+                        }
+                        let ty = lookup(&types, section.u32()?)?;
+                        module.imports.push((module_name, name, ty));
+                    }
+                }
+                3 => {
+                    for _ in 0..section.u32()? {
+                        signatures.push(lookup(&types, section.u32()?)?);
+                    }
+                }
+                5 => {
+                    if section.u32()? != 1 {
+                        return Err(invalid("expected one memory"));
+                    }
+                    let flags = section.byte()?;
+                    module.pages = section.u32()? as usize;
+                    if flags & 1 != 0 {
+                        section.u32()?;
+                    }
+                }
+                7 => {
+                    for _ in 0..section.u32()? {
+                        let name = section.name()?;
+                        let kind = section.byte()?;
+                        module.exports.push((name, kind, section.u32()?));
+                    }
+                }
+                10 => {
+                    let count = section.u32()? as usize;
+                    if count != signatures.len() {
+                        return Err(invalid("function and code counts differ"));
+                    }
+                    for &ty in &signatures {
+                        let len = section.u32()? as usize;
+                        let mut body = Reader {
+                            bytes: section.take(len)?,
+                            at: 0,
+                        };
+                        let mut locals = 0;
+                        for _ in 0..body.u32()? {
+                            locals += body.u32()? as usize;
+                            if body.byte()? != 0x7f {
+                                return Err(invalid("only i32 locals are supported"));
+                            }
+                        }
+                        let code = decode_code(&mut body)?;
+                        module.funcs.push(Func { ty, locals, code });
+                    }
+                }
+                // Custom sections are names and the like
+                0 => (),
+                _ => return Err(invalid(&format!("unsupported section {id}"))),
+            }
+        }
+        if module.funcs.len() != signatures.len() {
+            return Err(invalid("functions without code"));
+        }
+        Ok(module)
+    }
+
+    // The functions a host has to provide, as module and name
+    pub fn imports(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.imports
+            .iter()
+            .map(|(module, name, _)| (module.as_str(), name.as_str()))
+    }
+
+    // Calls the exported function `name`, which takes nothing, with a fresh memory
+    pub fn run(&self, name: &str, host: &mut dyn Host) -> Result<Vec<u8>, Trap> {
+        let &(_, _, func) = self
+            .exports
+            .iter()
+            .find(|(export, kind, _)| export == name && *kind == 0)
+            .ok_or_else(|| invalid(&format!("no function exported as {name:?}")))?;
+        let mut instance = Instance {
+            module: self,
+            memory: vec![0; self.pages * PAGE],
+            host,
+        };
+        instance.call(func, &[])?;
+        Ok(instance.memory)
+    }
+}
+
+fn i32s(reader: &mut Reader) -> Result<usize, Trap> {
+    let count = reader.u32()? as usize;
+    for _ in 0..count {
+        if reader.byte()? != 0x7f {
+            return Err(invalid("only i32 values are supported"));
+        }
+    }
+    Ok(count)
+}
+
+fn lookup(types: &[Type], idx: u32) -> Result<Type, Trap> {
+    types
+        .get(idx as usize)
+        .copied()
+        .ok_or_else(|| invalid("unknown type"))
+}
+
+// A function body up to and including its final `end`, with every block's targets filled in
+fn decode_code(reader: &mut Reader) -> Result<Vec<Instr>, Trap> {
+    let mut code = vec![];
+    let mut open = vec![];
+    loop {
+        let op = reader.byte()?;
+        let block_results = |reader: &mut Reader| match reader.byte()? {
+            0x40 => Ok(0),
+            0x7f => Ok(1),
+            _ => Err(invalid("unsupported block type")),
+        };
+        let memarg = |reader: &mut Reader| -> Result<u32, Trap> {
+            reader.u32()?;
+            reader.u32()
+        };
+        let instr = match op {
+            0x00 => Instr::Unreachable,
+            0x02 => Instr::Block {
+                results: block_results(reader)?,
+                end: 0,
+            },
+            0x03 => {
+                block_results(reader)?;
+                Instr::Loop
+            }
+            0x04 => Instr::If {
+                results: block_results(reader)?,
+                other: None,
+                end: 0,
+            },
+            0x05 => Instr::Else { end: 0 },
+            0x0b => Instr::End,
+            0x0c => Instr::Br(reader.u32()?),
+            0x0d => Instr::BrIf(reader.u32()?),
+            0x10 => Instr::Call(reader.u32()?),
+            0x1a => Instr::Drop,
+            0x20 => Instr::LocalGet(reader.u32()?),
+            0x21 => Instr::LocalSet(reader.u32()?),
+            0x28 => Instr::Load(Width::Word, memarg(reader)?),
+            0x2d => Instr::Load(Width::Byte, memarg(reader)?),
+            0x2f => Instr::Load(Width::Half, memarg(reader)?),
+            0x36 => Instr::Store(Width::Word, memarg(reader)?),
+            0x3a => Instr::Store(Width::Byte, memarg(reader)?),
+            0x3b => Instr::Store(Width::Half, memarg(reader)?),
+            0x41 => Instr::Const(reader.i32()?),
+            0x45 => Instr::Eqz,
+            0x47 => Instr::Ne,
+            0x6a => Instr::Add,
+            _ => return Err(invalid(&format!("unsupported instruction 0x{op:02x}"))),
+        };
+        let idx = code.len();
+        code.push(instr);
+        match instr {
+            Instr::Block { .. } | Instr::Loop | Instr::If { .. } => open.push(idx),
+            Instr::Else { .. } => match open.last().map(|&start| &mut code[start]) {
+                Some(Instr::If { other, .. }) if other.is_none() => *other = Some(idx),
+                _ => return Err(invalid("`else` outside an `if`")),
+            },
+            Instr::End => {
+                let Some(start) = open.pop() else {
+                    if !reader.done() {
+                        return Err(invalid("code after the function's end"));
+                    }
+                    return Ok(code);
+                };
+                match &mut code[start] {
+                    Instr::Block { end, .. } => *end = idx,
+                    Instr::If { other, end, .. } => {
+                        *end = idx;
+                        if let Some(other) = *other {
+                            code[other] = Instr::Else { end: idx };
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+struct Instance<'a> {
+    module: &'a Module,
+    memory: Vec<u8>,
+    host: &'a mut dyn Host,
+}
+
+impl Instance<'_> {
+    fn call(&mut self, func: u32, args: &[i32]) -> Result<Option<i32>, Trap> {
+        let imports = self.module.imports.len();
+        let func = func as usize;
+        if func < imports {
+            return self.host.call(func, args);
+        }
+        let module = self.module;
+        let func = module
+            .funcs
+            .get(func - imports)
+            .ok_or_else(|| invalid("call to an unknown function"))?;
+        let mut locals = args.to_vec();
+        locals.resize(func.ty.params + func.locals, 0);
+        let mut values: Vec<i32> = vec![];
+        // The body is a block of its own, which a branch out of returns
+        let mut frames = vec![Frame {
+            height: 0,
+            arity: func.ty.results,
+            target: func.code.len(),
+            looping: false,
+        }];
+        let code = &func.code;
+        let mut pc = 0;
+        while pc < code.len() {
+            let mut next = pc + 1;
+            match code[pc] {
+                Instr::Unreachable => return Err(Trap::Unreachable),
+                Instr::Block { results, end } => frames.push(Frame {
+                    height: values.len(),
+                    arity: results,
+                    target: end + 1,
+                    looping: false,
+                }),
+                Instr::Loop => frames.push(Frame {
+                    height: values.len(),
+                    arity: 0,
+                    target: pc + 1,
+                    looping: true,
+                }),
+                Instr::If {
+                    results,
+                    other,
+                    end,
+                } => {
+                    let taken = pop(&mut values)? != 0;
+                    let frame = Frame {
+                        height: values.len(),
+                        arity: results,
+                        target: end + 1,
+                        looping: false,
+                    };
+                    if taken {
+                        frames.push(frame);
+                    } else if let Some(other) = other {
+                        frames.push(frame);
+                        next = other + 1;
+                    } else {
+                        next = end + 1;
+                    }
+                }
+                // The end of the branch taken, the `end` after it closes the frame
+                Instr::Else { end } => next = end,
+                Instr::End => {
+                    frames.pop();
+                }
+                Instr::Br(depth) => next = branch(&mut frames, &mut values, depth)?,
+                Instr::BrIf(depth) => {
+                    if pop(&mut values)? != 0 {
+                        next = branch(&mut frames, &mut values, depth)?;
+                    }
+                }
+                Instr::Call(callee) => {
+                    let params = self.signature(callee)?.params;
+                    let at = values
+                        .len()
+                        .checked_sub(params)
+                        .ok_or_else(|| invalid("stack underflow"))?;
+                    let args = values.split_off(at);
+                    values.extend(self.call(callee, &args)?);
+                }
+                Instr::Drop => {
+                    pop(&mut values)?;
+                }
+                Instr::LocalGet(idx) => values.push(*local(&mut locals, idx)?),
+                Instr::LocalSet(idx) => {
+                    let value = pop(&mut values)?;
+                    *local(&mut locals, idx)? = value;
+                }
+                Instr::Const(n) => values.push(n),
+                Instr::Eqz => {
+                    let value = pop(&mut values)?;
+                    values.push((value == 0) as i32);
+                }
+                Instr::Ne => {
+                    let (b, a) = (pop(&mut values)?, pop(&mut values)?);
+                    values.push((a != b) as i32);
+                }
+                Instr::Add => {
+                    let (b, a) = (pop(&mut values)?, pop(&mut values)?);
+                    values.push(a.wrapping_add(b));
+                }
+                Instr::Load(width, offset) => {
+                    let base = pop(&mut values)?;
+                    let bytes = self.access(base, offset, width)?;
+                    let mut word = [0; 4];
+                    word[..bytes.len()].copy_from_slice(bytes);
+                    values.push(i32::from_le_bytes(word));
+                }
+                Instr::Store(width, offset) => {
+                    let value = pop(&mut values)?;
+                    let base = pop(&mut values)?;
+                    let bytes = self.access(base, offset, width)?;
+                    let len = bytes.len();
+                    bytes.copy_from_slice(&value.to_le_bytes()[..len]);
+                }
+            }
+            pc = next;
+        }
+        match func.ty.results {
+            0 => Ok(None),
+            _ => pop(&mut values).map(Some),
+        }
+    }
+
+    fn signature(&self, func: u32) -> Result<Type, Trap> {
+        let imports = &self.module.imports;
+        match imports.get(func as usize) {
+            Some(&(_, _, ty)) => Ok(ty),
+            None => self
+                .module
+                .funcs
+                .get(func as usize - imports.len())
+                .map(|func| func.ty)
+                .ok_or_else(|| invalid("call to an unknown function")),
+        }
+    }
+
+    // The memory a load or store of `width` touches, addresses being unsigned
+    fn access(&mut self, base: i32, offset: u32, width: Width) -> Result<&mut [u8], Trap> {
+        let len = match width {
+            Width::Byte => 1,
+            Width::Half => 2,
+            Width::Word => 4,
+        };
+        let at = base as u32 as u64 + offset as u64;
+        if at + len as u64 > self.memory.len() as u64 {
+            // Reported as the signed address the program moved to
+            return Err(Trap::OutOfBounds(base as i64 + offset as i64));
+        }
+        let at = at as usize;
+        Ok(&mut self.memory[at..at + len])
+    }
+}
+
+fn pop(values: &mut Vec<i32>) -> Result<i32, Trap> {
+    values.pop().ok_or_else(|| invalid("stack underflow"))
+}
+
+fn local(locals: &mut [i32], idx: u32) -> Result<&mut i32, Trap> {
+    locals
+        .get_mut(idx as usize)
+        .ok_or_else(|| invalid("unknown local"))
+}
+
+// Unwinds to the frame `depth` out, keeping the values it ends with, and says where to go
+fn branch(frames: &mut Vec<Frame>, values: &mut Vec<i32>, depth: u32) -> Result<usize, Trap> {
+    let idx = frames
+        .len()
+        .checked_sub(depth as usize + 1)
+        .ok_or_else(|| invalid("branch out of the function"))?;
+    let frame = &frames[idx];
+    let kept = values
+        .len()
+        .checked_sub(frame.arity)
+        .filter(|&kept| kept >= frame.height)
+        .ok_or_else(|| invalid("stack underflow"))?;
+    values.drain(frame.height..kept);
+    let target = frame.target;
+    // A loop is branched back into, so it stays open
+    frames.truncate(idx + frame.looping as usize);
+    Ok(target)
+}
+
+// The imports of an `env` module, over a reader and writer. An I/O error stops the run, and
+// is kept to be returned in place of the trap that does it
+struct Env<'a> {
+    names: Vec<String>,
+    input: &'a mut dyn Read,
+    output: &'a mut dyn Write,
+    flush: Flush,
+    rng: Rng,
+    error: Option<BfError>,
+}
+
+impl Env<'_> {
+    fn io<T>(&mut self, result: std::io::Result<T>) -> Result<T, Trap> {
+        result.map_err(|err| {
+            let reason = err.to_string();
+            self.error = Some(err.into());
+            Trap::Host(reason)
+        })
+    }
+}
+
+impl Host for Env<'_> {
+    fn call(&mut self, import: usize, args: &[i32]) -> Result<Option<i32>, Trap> {
+        match self.names[import].as_str() {
+            "putchar" => {
+                let byte = args[0] as u8;
+                let written = self.output.write_all(&[byte]);
+                self.io(written)?;
+                let flushed = match self.flush {
+                    Flush::Always => self.output.flush(),
+                    Flush::Line if byte == b'\n' => self.output.flush(),
+                    _ => Ok(()),
+                };
+                self.io(flushed)?;
+                Ok(None)
+            }
+            "getchar" => {
+                // A prompt should be visible before blocking on input
+                let flushed = self.output.flush();
+                self.io(flushed)?;
+                let mut buf = [0u8];
+                match self.input.read_exact(&mut buf) {
+                    Ok(()) => Ok(Some(buf[0] as i32)),
+                    Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(Some(-1)),
+                    Err(err) => self.io(Err(err)),
+                }
+            }
+            "random" => Ok(Some(self.rng.byte() as i32)),
+            name => Err(Trap::Host(format!("no import named {name:?}"))),
+        }
+    }
+}
+
+// Compiles `program` to a module for `execute`, refusing what modules can't do. They have a
+// fixed memory of `wasm::PAGES` pages, starting on its first cell, and count no steps
+pub fn compile(program: &Program, settings: &Settings) -> Result<Module, BfError> {
+    if settings.max_steps.is_some() {
+        let reason = "the wasm backend can't enforce a step limit";
+        return Err(BfError::Unsupported(reason.to_string()));
+    }
+    if let Tape::Fixed(_) = settings.tape {
+        let reason = "the wasm backend's tape is its memory, it can't be resized";
+        return Err(BfError::Unsupported(reason.to_string()));
+    }
+    let unsupported = |token: &BfToken| {
+        matches!(
+            token,
+            BfToken::FRK | BfToken::DEC | BfToken::NUM | BfToken::EXT(_)
+        )
+    };
+    if program.tokens.iter().any(unsupported) {
+        let reason = "the wasm backend can't fork, read or write numbers or call host opcodes";
+        return Err(BfError::Unsupported(reason.to_string()));
+    }
+    Module::decode(&emit_wasm(program, settings))
+        .map_err(|trap| BfError::Unsupported(format!("wasm: {trap}")))
+}
+
+// Runs a module from `compile` with the settings it was compiled with
+pub fn execute(
+    module: &Module,
+    settings: &Settings,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<(), BfError> {
+    let mut env = Env {
+        names: module.imports().map(|(_, name)| name.to_string()).collect(),
+        input,
+        output,
+        flush: settings.flush,
+        rng: settings.seed.map_or_else(Rng::from_time, Rng::new),
+        error: None,
+    };
+    let result = module.run("run", &mut env);
+    if let Some(err) = env.error {
+        return Err(err);
+    }
+    let width = (settings.cell_size.bits() / 8) as i64;
+    result.map_err(|trap| match trap {
+        Trap::OutOfBounds(at) => BfError::TapeBounds(at.div_euclid(width) as isize),
+        trap => BfError::Unsupported(format!("wasm: {trap}")),
+    })?;
+    env.output.flush()?;
+    Ok(())
+}
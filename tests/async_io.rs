@@ -0,0 +1,51 @@
+// Checks that the async interpreter runs programs on in-memory I/O and yields during long
+// stretches without any.
+#![cfg(feature = "async")]
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use bf::async_io::AsyncInterpreter;
+use bf::{BfError, Program};
+
+// Polls `future` to completion, counting the times it wasn't ready
+fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut pending = 0;
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return (output, pending),
+            Poll::Pending => pending += 1,
+        }
+    }
+}
+
+fn run(code: &str, extensions: &str, input: &[u8]) -> (Result<Vec<u8>, BfError>, usize) {
+    // Left unoptimized so the steps are the commands written
+    let (program, _) = Program::with_passes(code, extensions.parse().unwrap(), &[]).unwrap();
+    let mut interpreter = AsyncInterpreter::new(program, input, vec![]);
+    let (result, pending) = block_on(interpreter.run());
+    let (_, _, output) = interpreter.into_parts();
+    (result.map(|()| output), pending)
+}
+
+#[test]
+fn async_runs_complete_and_yield() {
+    // Echoes its input raised by one, and stops at EOF
+    let (output, pending) = run(",[+.,]", "none", b"HAL");
+    assert_eq!(output.unwrap(), b"IBM");
+    assert_eq!(pending, 0);
+
+    // Numbers in decimal both ways
+    let (output, _) = run(";+:", "numbers", b"41\n");
+    assert_eq!(output.unwrap(), b"42");
+
+    let (output, _) = run("+Y.", "fork", b"");
+    assert!(matches!(output, Err(BfError::Unsupported(_))));
+
+    // 255 passes clearing 255 each, past 1 << 16 steps twice and less than three times
+    let (output, pending) = run("-[>-[-]<-]>.", "none", b"");
+    assert_eq!(output.unwrap(), [0]);
+    assert_eq!(pending, 2);
+}
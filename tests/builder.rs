@@ -0,0 +1,128 @@
+// Checks that the builder's settings reach the machine it builds, and what it sets up.
+use bf::{BfError, Eof, InterpreterBuilder, Tape};
+
+#[test]
+fn builder_applies_its_settings() {
+    let mut output = vec![];
+    // 256 only wraps to 0 in 8-bit cells, and at EOF the cell becomes all ones
+    let code = "++++++++++++++++[>++++++++++++++++<-]>[>+<[-]]>.,[>+++++<[+]]>.";
+    let mut interpreter = InterpreterBuilder::new()
+        .cell::<u16>()
+        .eof(Eof::Minus1)
+        .source(code)
+        .input(&b""[..])
+        .output(&mut output)
+        .build()
+        .unwrap();
+    interpreter.run().unwrap();
+    assert_eq!(output, [1, 5]);
+
+    let mut interpreter = InterpreterBuilder::new()
+        .max_steps(10)
+        .source("+[]")
+        .output(vec![])
+        .build()
+        .unwrap();
+    assert!(matches!(interpreter.run(), Err(BfError::StepLimit(10))));
+    assert!(matches!(
+        InterpreterBuilder::new().build(),
+        Err(BfError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn fixed_tapes_end() {
+    let run = |code: &str| {
+        InterpreterBuilder::new()
+            .tape(Tape::Fixed(3))
+            .source(code)
+            .output(vec![])
+            .build()
+            .unwrap()
+            .run()
+    };
+    assert!(run(">>").is_ok());
+    assert!(matches!(run(">>>"), Err(BfError::TapeBounds(3))));
+    assert!(matches!(run("<"), Err(BfError::TapeBounds(-1))));
+    // Scans stop at the end too
+    assert!(matches!(run("+[>+]"), Err(BfError::TapeBounds(3))));
+    assert_eq!("fixed:30000".parse(), Ok(Tape::Fixed(30_000)));
+    assert!("fixed:0".parse::<Tape>().is_err());
+    // Would abort the process allocating 400 GB
+    let err = "fixed:100000000000".parse::<Tape>().unwrap_err();
+    assert!(err.contains("at most"), "{err}");
+}
+
+#[test]
+fn profiles_count_every_instruction() {
+    let mut interpreter = InterpreterBuilder::new()
+        .opt_level(1)
+        .source("+++[>++<-]>.")
+        .output(vec![])
+        .profile()
+        .build()
+        .unwrap();
+    interpreter.run().unwrap();
+    let program = interpreter.machine().program().clone();
+    let profile = interpreter.take_profiler().unwrap().report(&program);
+    let counts: Vec<u64> = profile.instructions.iter().map(|s| s.count).collect();
+    assert_eq!(counts, [1, 1, 3, 3, 3, 3, 3, 1, 1]);
+    let total: std::time::Duration = profile.instructions.iter().map(|s| s.time).sum();
+    assert!(total <= profile.elapsed + std::time::Duration::from_micros(1));
+    assert_eq!(profile.loops.len(), 1);
+    assert!(profile.loops[0].time >= profile.instructions[2].time);
+    assert!(profile
+        .render(&program, "+++[>++<-]>.", 5)
+        .contains("loops"));
+}
+
+#[test]
+fn history_keeps_the_last_instructions() {
+    let mut interpreter = InterpreterBuilder::new()
+        .opt_level(0)
+        .max_steps(9)
+        .history(4)
+        .source("+[>+<]")
+        .output(vec![])
+        .build()
+        .unwrap();
+    assert!(matches!(interpreter.run(), Err(BfError::StepLimit(9))));
+    // `+[` then around the loop, stopping before the `]` of the second time through
+    assert_eq!(interpreter.machine().history().unwrap(), [5, 2, 3, 4]);
+
+    // A `,` that waited for its byte is kept too
+    let mut interpreter = InterpreterBuilder::new()
+        .opt_level(0)
+        .tape(Tape::Fixed(1))
+        .history(4)
+        .source("+,<")
+        .input(&b"a"[..])
+        .output(vec![])
+        .build()
+        .unwrap();
+    assert!(matches!(interpreter.run(), Err(BfError::TapeBounds(-1))));
+    assert_eq!(interpreter.machine().history().unwrap(), [0, 1]);
+}
+
+#[test]
+fn speed_paces_the_run() {
+    // 40 instructions at 400 a second take a tenth of a second, the first running at once
+    let start = std::time::Instant::now();
+    let mut output = vec![];
+    InterpreterBuilder::new()
+        .speed(400)
+        .opt_level(0)
+        .source(format!("{}.", "+".repeat(39)))
+        .output(&mut output)
+        .build()
+        .unwrap()
+        .run()
+        .unwrap();
+    let elapsed = start.elapsed();
+    assert_eq!(output, [39]);
+    assert!(
+        elapsed >= std::time::Duration::from_millis(95),
+        "{elapsed:?}"
+    );
+    assert!(elapsed < std::time::Duration::from_secs(1), "{elapsed:?}");
+}
@@ -0,0 +1,39 @@
+// Checks that a cached program comes back the way it was compiled, and only for its own key.
+use bf::cache::{from_bytes, key, to_bytes};
+use bf::{passes, Extensions, Machine, Program, Settings};
+
+#[test]
+fn artifacts_round_trip() {
+    // Ending on the extensions' instructions, a random cell overwritten by EOF
+    let code = &format!("{}?;", include_str!("../corpus/hello.b"));
+    let extensions: Extensions = "random,numbers".parse().unwrap();
+    let passes = passes::for_level(3);
+    let (program, _) = Program::with_passes(code, extensions, &passes).unwrap();
+    let key = key(code, extensions, &passes, None);
+    let bytes = to_bytes(&program, key);
+    let loaded = from_bytes(&bytes, key).unwrap();
+    assert_eq!(loaded.tokens, program.tokens);
+    assert_eq!(loaded.jumps, program.jumps);
+    assert_eq!(loaded.spans, program.spans);
+
+    let mut machine = Machine::with_settings(loaded, &Settings::default());
+    machine.close_input();
+    machine.run_for(u64::MAX);
+    assert_eq!(machine.take_output(), b"Hello World!\n");
+
+    // Anything that changes the program changes the key, and a stale or damaged artifact
+    // is never loaded
+    assert_ne!(key, bf::cache::key(code, extensions, &passes[..2], None));
+    assert_ne!(
+        key,
+        bf::cache::key(code, Extensions::default(), &passes, None)
+    );
+    assert_ne!(key, bf::cache::key(code, extensions, &passes, Some(10)));
+    assert_ne!(key, bf::cache::key(&code[1..], extensions, &passes, None));
+    assert!(from_bytes(&bytes, key ^ 1).is_none());
+    assert!(from_bytes(&bytes[..bytes.len() - 1], key).is_none());
+    let mut unbalanced = bytes.clone();
+    let close = unbalanced.len() - 25 * 4;
+    unbalanced[close] = 4;
+    assert!(from_bytes(&unbalanced, key).is_none());
+}
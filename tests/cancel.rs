@@ -0,0 +1,40 @@
+// Checks that a run can be stopped from another thread and picked up again.
+use std::thread;
+use std::time::Duration;
+
+use bf::{BfError, CancelToken, InterpreterBuilder};
+
+#[test]
+fn cancelling_stops_a_run_elsewhere() {
+    let token = CancelToken::new();
+    let runner = {
+        let token = token.clone();
+        thread::spawn(move || {
+            let mut interpreter = InterpreterBuilder::new()
+                .source("+[>+<]")
+                .input(&b""[..])
+                .output(vec![])
+                .cancel(token.clone())
+                .build()
+                .unwrap();
+            let first = interpreter.run();
+            let steps = interpreter.machine().steps();
+            // Resumes where it stopped once the token is reset
+            token.reset();
+            let watcher = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                token.cancel();
+            });
+            let second = interpreter.run();
+            watcher.join().unwrap();
+            (first, second, steps, interpreter.machine().steps())
+        })
+    };
+    thread::sleep(Duration::from_millis(20));
+    assert!(!token.is_cancelled());
+    token.cancel();
+    let (first, second, before, after) = runner.join().unwrap();
+    assert!(matches!(first, Err(BfError::Interrupted)));
+    assert!(matches!(second, Err(BfError::Interrupted)));
+    assert!(before > 0 && after > before);
+}
@@ -0,0 +1,104 @@
+// Checks that a run saved partway and resumed carries on exactly as if it never stopped.
+use bf::checkpoint::Checkpoint;
+use bf::input::{Endian, Word};
+use bf::{CellSize, Machine, Program, RunState, Settings};
+
+// Saves `machine` to bytes and reads it back into a new one
+fn resume(machine: &Machine, source: &str, settings: &Settings) -> Machine {
+    let saved = Checkpoint::capture(machine, "test", source, false, settings).unwrap();
+    let loaded = Checkpoint::from_bytes(&saved.to_bytes()).unwrap();
+    // The seed is spent, it's the generator's state that carries on
+    let unseeded = Settings {
+        seed: None,
+        ..saved.settings
+    };
+    assert_eq!(loaded.settings, unseeded);
+    assert_eq!((loaded.rng, loaded.number), (saved.rng, saved.number));
+    loaded.restore(loaded.program().unwrap()).unwrap()
+}
+
+#[test]
+fn resumed_runs_match_uninterrupted_ones() {
+    // Random bytes forever, from a fixed seed
+    let source = "+[>?.<]";
+    let settings = Settings {
+        extensions: "random".parse().unwrap(),
+        seed: Some(7),
+        max_depth: Some(3),
+        ..Settings::default()
+    };
+    let program = Program::with_extensions(source, 2, settings.extensions).unwrap();
+    let mut whole = Machine::with_settings(program.clone(), &settings);
+    assert!(matches!(whole.run_for(2000), RunState::Paused));
+
+    let mut first = Machine::with_settings(program, &settings);
+    first.run_for(1000);
+    let mut output = first.take_output();
+    let mut resumed = resume(&first, source, &settings);
+    resumed.run_for(1000);
+    output.extend(resumed.take_output());
+    assert_eq!(output, whole.take_output());
+    assert_eq!(resumed.steps(), 2000);
+
+    // Stopped partway through a number, the rest of its digits still count
+    let source = ";:";
+    let settings = Settings {
+        extensions: "numbers".parse().unwrap(),
+        max_depth: None,
+        ..Settings::default()
+    };
+    let program = Program::with_extensions(source, 2, settings.extensions).unwrap();
+    let mut machine = Machine::with_settings(program, &settings);
+    machine.feed(b"-1");
+    assert!(matches!(machine.run_for(100), RunState::NeedsInput));
+    let mut resumed = resume(&machine, source, &settings);
+    resumed.feed(b"2\n");
+    resumed.close_input();
+    assert!(matches!(resumed.run_for(100), RunState::Finished));
+    // -12 in an 8-bit cell
+    assert_eq!(resumed.take_output(), b"244");
+
+    // or partway through a word of input, the bytes read so far still count
+    let settings = Settings {
+        cell_size: CellSize::U16,
+        ..Settings::default()
+    };
+    let word = Word {
+        bytes: 2,
+        endian: Endian::Big,
+    };
+    let program = Program::with_settings(",.", &settings).unwrap();
+    let mut machine = Machine::with_settings(program, &settings);
+    machine.set_input_word(word);
+    machine.feed(&[0x01]);
+    assert!(matches!(machine.run_for(100), RunState::NeedsInput));
+    let mut resumed = resume(&machine, ",.", &settings);
+    resumed.set_input_word(word);
+    resumed.feed(b"A");
+    assert!(matches!(resumed.run_for(100), RunState::Finished));
+    assert_eq!(resumed.take_output(), b"A");
+    assert_eq!(resumed.tape()[0], 0x0141);
+
+    // Checkpoints from before words were saved still load, with none partly read
+    let saved = Checkpoint::capture(&resumed, "test", ",.", false, &settings).unwrap();
+    let mut old = saved.to_bytes();
+    old.truncate(old.len() - 8);
+    old[..8].copy_from_slice(b"BFCKPT02");
+    assert_eq!(Checkpoint::from_bytes(&old).unwrap(), saved);
+}
+
+#[test]
+fn preloaded_tapes_refuse_checkpoints() {
+    // Resuming would recompile the program for a blank tape, comment loop left out and all
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_bf-rust"))
+        .args(["-e", "[ comment ]+[]", "--tape-init-hex", "00"])
+        .args(["--checkpoint", "unused.ckpt"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("can't be used with a backend or a checkpoint"),
+        "{stderr}"
+    );
+}
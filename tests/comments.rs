@@ -0,0 +1,76 @@
+// Checks which loops are taken for comments and left out, and that leaving them out changes
+// nothing but the statistics.
+use bf::program::comment_loops;
+use bf::reference;
+use bf::{Interpreter, InterpreterBuilder, Program, Settings, Span};
+
+#[test]
+fn comment_loops_are_left_out() {
+    let code = "[ Adds 2 and 3, then prints. ][ More. ]++>+++[<+>-]<[ not a comment.[-]]\
+                ++++++++[<++++++>-]<.[-][ Done! ][.]";
+    let spans = comment_loops(code, Default::default()).unwrap();
+    let texts: Vec<&str> = spans
+        .iter()
+        .map(|span| &code[span.start..span.end])
+        .collect();
+    // Either a loop runs or it holds nothing but commands
+    assert_eq!(
+        texts,
+        ["[ Adds 2 and 3, then prints. ]", "[ More. ]", "[ Done! ]"]
+    );
+
+    for opt_level in 0..=3 {
+        let program = Program::compile(code, opt_level).unwrap();
+        assert!(!program.spans.iter().any(|span| spans
+            .iter()
+            .any(|comment| comment.start <= span.start && span.end <= comment.end)));
+        let mut output = vec![];
+        Interpreter::new(program, &b""[..], &mut output)
+            .run()
+            .unwrap();
+        let expected = reference::run(code, &[], &Settings::default()).unwrap();
+        assert_eq!(output, expected.output, "-O{opt_level}");
+    }
+
+    let inspection = bf::inspect(code).unwrap();
+    assert_eq!(inspection.comments, spans);
+    // The `.` ending "not a comment.", the one printing and the one in `[.]`
+    assert_eq!(inspection.counts[6], 3);
+    let notes = bf::analysis::comments(code).unwrap();
+    assert_eq!(notes.len(), 3);
+    assert_eq!(
+        notes[0].labels[0].span,
+        Span {
+            start: 0,
+            end: texts[0].len()
+        }
+    );
+    // Brackets are still checked as written
+    assert!(comment_loops("[ unclosed", Default::default()).is_err());
+    assert!(Program::from_source("[ unclosed").is_err());
+}
+
+#[test]
+fn opening_loops_run_on_preloaded_tapes() {
+    // A comment on a blank tape, but the cell it starts on might not be zero
+    let code = "[print each byte .>][ never runs ]";
+    let (program, _) =
+        Program::with_tape_start(code, Default::default(), &[], None, false).unwrap();
+    assert_eq!(program.len(), 4);
+    assert!(Program::compile(code, 0).unwrap().is_empty());
+
+    let run = |preload: Option<&[u8]>| {
+        let mut output = vec![];
+        let mut builder = InterpreterBuilder::new()
+            .source(code)
+            .input(&b""[..])
+            .output(&mut output);
+        if let Some(data) = preload {
+            builder = builder.preload(data);
+        }
+        builder.build().unwrap().run().unwrap();
+        output
+    };
+    assert_eq!(run(Some(b"ABC")), b"ABC");
+    assert_eq!(run(None), b"");
+}
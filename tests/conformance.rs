@@ -0,0 +1,63 @@
+use bf::conformance::run;
+use bf::{CellSize, Eof, Settings};
+
+fn behavior(settings: &Settings, probe: &str) -> String {
+    let findings = run(settings);
+    let finding = findings.iter().find(|f| f.probe == probe).unwrap();
+    finding.behavior.clone()
+}
+
+#[test]
+fn defaults_pass_the_classic_tests() {
+    for finding in run(&Settings::default()) {
+        assert!(
+            !finding.behavior.starts_with("failed") && !finding.behavior.starts_with("unexpected"),
+            "{}: {}",
+            finding.probe,
+            finding.behavior
+        );
+    }
+}
+
+#[test]
+fn probes_tell_settings_apart() {
+    for (eof, expected) in [
+        (Eof::Zero, "end of input stores 0"),
+        (Eof::Minus1, "end of input stores -1"),
+        (Eof::Unchanged, "end of input leaves the cell unchanged"),
+    ] {
+        let settings = Settings {
+            eof,
+            ..Settings::default()
+        };
+        assert_eq!(behavior(&settings, "eof"), expected);
+    }
+    let settings = Settings {
+        cell_size: CellSize::U32,
+        ..Settings::default()
+    };
+    assert_eq!(behavior(&settings, "cell size"), "32 bit cells");
+}
+
+#[test]
+fn specs_bundle_their_settings() {
+    let mut settings = Settings::default();
+    settings.set("spec", "classic").unwrap();
+    assert_eq!(
+        behavior(&settings, "eof"),
+        "end of input leaves the cell unchanged"
+    );
+    assert_eq!(
+        behavior(&settings, "cell size"),
+        "8 bit cells that wrap around"
+    );
+    settings.set("spec", "modern").unwrap();
+    assert_eq!(behavior(&settings, "eof"), "end of input stores -1");
+    assert_eq!(behavior(&settings, "cell size"), "32 bit cells");
+    // Only the settings a spec covers change
+    settings.set("max_steps", "100000000").unwrap();
+    settings.set("spec", "nesdev").unwrap();
+    assert_eq!(settings.cell_size, CellSize::U8);
+    assert_eq!(settings.max_steps, Some(100_000_000));
+    assert!(settings.set("spec", "bogus").is_err());
+}
@@ -0,0 +1,59 @@
+// Runs every program in corpus/ and checks it against its recorded output.
+use std::path::Path;
+
+use bf::corpus::{load, Case};
+use bf::{Settings, Tape};
+
+fn cases() -> Vec<Case> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    load(&dir, &Settings::default()).unwrap()
+}
+
+#[test]
+fn corpus_matches_recorded_output() {
+    let cases = cases();
+    assert!(cases.len() >= 5);
+    for case in cases {
+        let expected = case.expected.as_ref().expect("missing .out file");
+        assert_eq!(case.run().unwrap(), *expected, "{}", case.name);
+    }
+}
+
+#[test]
+fn corpus_is_independent_of_optimization_and_tape() {
+    for mut case in cases() {
+        let expected = case.expected.clone().unwrap();
+        for opt_level in 0..=2 {
+            for tape in [Tape::Dynamic, Tape::Sparse] {
+                case.settings.opt_level = opt_level;
+                case.settings.tape = tape;
+                assert_eq!(
+                    case.run().unwrap(),
+                    expected,
+                    "{} -O{opt_level} {tape}",
+                    case.name
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn quine_prints_itself() {
+    let quine = cases()
+        .into_iter()
+        .find(|case| case.name == "quine")
+        .unwrap();
+    assert_eq!(quine.run().unwrap(), quine.source.as_bytes());
+}
+
+#[test]
+fn factor_is_compiled_from_its_dsl_source() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    let source = std::fs::read_to_string(dir.join("factor.dsl")).unwrap();
+    let compiled = std::fs::read_to_string(dir.join("factor.b")).unwrap();
+    assert_eq!(
+        bf::dsl::compile(&source).unwrap().trim_end(),
+        compiled.trim_end()
+    );
+}
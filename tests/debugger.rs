@@ -0,0 +1,47 @@
+// Checks that the debugger's loop-level commands stop where they say they do.
+use bf::{Debugger, Machine, Program, RunState};
+
+fn debugger(source: &str) -> Debugger {
+    Debugger::new(Machine::new(Program::compile(source, 0).unwrap()))
+}
+
+#[test]
+fn loops_enclose_their_bodies() {
+    // 0 1 2 3 4 5 6 7 8 9 ...
+    // + + [ > + + [ > + < - ] < - ] > > .
+    let program = Program::compile("++[>++[>+<-]<-]>>.", 0).unwrap();
+    let outer = [3, 4, 5, 6, 12, 13, 14];
+    for ip in 0..program.len() {
+        let expected = match ip {
+            7..=11 => Some(6),
+            _ if outer.contains(&ip) => Some(2),
+            _ => None,
+        };
+        assert_eq!(program.enclosing_loop(ip), expected, "instruction {ip}");
+    }
+    assert_eq!(program.depth(8), 2);
+    assert_eq!(program.depth(6), 1);
+    assert_eq!(program.depth(16), 0);
+}
+
+#[test]
+fn loop_commands_stop_at_the_loop_edges() {
+    let mut debugger = debugger("++[>++[>+<-]<-]>>.");
+    assert!(debugger.next_loop().is_none());
+    assert!(matches!(debugger.step(8), RunState::Paused));
+    assert_eq!(debugger.machine().ip(), 8);
+    assert!(matches!(debugger.finish(), Some(RunState::Paused)));
+    assert_eq!(debugger.machine().ip(), 11);
+    // Already at the `]`, which jumps back and comes round again
+    assert!(matches!(debugger.finish(), Some(RunState::Paused)));
+    assert_eq!(debugger.machine().ip(), 11);
+    assert!(matches!(debugger.next_loop(), Some(RunState::Paused)));
+    assert_eq!(debugger.machine().ip(), 12);
+    assert!(matches!(debugger.next_loop(), Some(RunState::Paused)));
+    assert_eq!(debugger.machine().ip(), 15);
+    assert_eq!(debugger.machine().tape()[2], 4);
+
+    assert!(matches!(debugger.until(16), RunState::Paused));
+    assert!(matches!(debugger.until(3), RunState::Finished));
+    assert_eq!(debugger.machine_mut().take_output(), [4]);
+}
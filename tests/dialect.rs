@@ -0,0 +1,57 @@
+// Checks that programs survive conversion between dialects and errors point at their source.
+use bf::dialect::{Dialect, Substitution};
+use bf::{BfError, Extensions, Interpreter, Program};
+
+fn output(program: Program) -> Vec<u8> {
+    let mut output = vec![];
+    Interpreter::new(program, &b""[..], &mut output)
+        .run()
+        .unwrap();
+    output
+}
+
+#[test]
+fn conversions_round_trip() {
+    let code = "Hi! ++++++++[>+++++++++<-]>.<++++[>++++++++<-]>+.";
+    let words = "+ = up\n- = down\n# comments are fine\n> = right\n< = left\n\
+                 [ = loop\n] = end\n. = say\n, = hear\n";
+    let map = Dialect::Map(Substitution::parse(words).unwrap());
+    let none = Extensions::default();
+    let program = Program::compile(code, 0).unwrap();
+    for dialect in [Dialect::Ook, map] {
+        let text = dialect.write(&program).unwrap();
+        let back = dialect.compile(&text, 0, none).unwrap();
+        assert_eq!(back.tokens, program.tokens);
+        assert_eq!(Dialect::Brainfuck.write(&back).unwrap(), &code[4..]);
+        assert_eq!(output(back), b"Hi");
+    }
+    // Optimized instructions are written out as the commands they stand for
+    let optimized = Program::compile(",[-]>,[>]<", 3).unwrap();
+    let text = Dialect::Ook.write(&optimized).unwrap();
+    let back = Dialect::Ook.compile(&text, 0, none).unwrap();
+    assert_eq!(Dialect::Brainfuck.write(&back).unwrap(), ",[-]>,[>]<");
+}
+
+#[test]
+fn dialect_errors_point_into_the_source() {
+    let none = Extensions::default();
+    assert!(matches!(
+        Dialect::Ook.compile("Hello Ook. Ook. Ook! Ook?", 0, none),
+        Err(BfError::UnclosedBracket(16))
+    ));
+    assert!(matches!(
+        Dialect::Ook.compile("Ook. Ook. Ook?", 0, none),
+        Err(BfError::Syntax(10, _))
+    ));
+    assert!(matches!(
+        Dialect::Ook.compile("Ook? Ook?", 0, none),
+        Err(BfError::Syntax(0, _))
+    ));
+    let random = Program::with_extensions("?", 0, "random".parse().unwrap()).unwrap();
+    assert!(matches!(
+        Dialect::Ook.write(&random),
+        Err(BfError::Unsupported(_))
+    ));
+    assert!(Substitution::parse("+ = a\n- = a").is_err());
+    assert!(Substitution::parse("+ = a\n- = b").is_err());
+}
@@ -0,0 +1,61 @@
+// Checks that lockstep runs find the first place two programs disagree.
+use bf::diff::{lockstep, Verdict};
+use bf::{Machine, Program};
+
+fn diff(left: &str, right: &str, tapes: bool) -> bf::diff::Report {
+    let machine = |source| {
+        let mut machine = Machine::new(Program::compile(source, 0).unwrap());
+        machine.feed(b"ab");
+        machine.close_input();
+        machine
+    };
+    lockstep(machine(left), machine(right), tapes)
+}
+
+#[test]
+fn equivalent_programs_agree() {
+    let report = diff(",.,.", ",>,<.>.", false);
+    assert!(matches!(report.verdict, Verdict::Same));
+    assert_eq!(report.outputs, [b"ab".to_vec(), b"ab".to_vec()]);
+    // Same output, but the left one reads `b` over the `a`, the right one next to it
+    let report = diff(",.,.", ",>,<.>.", true);
+    assert!(matches!(
+        report.verdict,
+        Verdict::Tape {
+            cell: 0,
+            left: 98,
+            right: 97
+        }
+    ));
+    assert_eq!(report.step, 3);
+}
+
+#[test]
+fn first_differing_byte_is_reported() {
+    let report = diff(",.,.", ",.,+.", false);
+    assert!(matches!(
+        report.verdict,
+        Verdict::Output {
+            at: 1,
+            left: Some(b'b'),
+            right: Some(b'c')
+        }
+    ));
+    // A side that stops early disagrees once the other writes more
+    let report = diff(",.", ",.,.", false);
+    assert!(matches!(
+        report.verdict,
+        Verdict::Output {
+            at: 1,
+            left: None,
+            right: Some(b'b')
+        }
+    ));
+    // A side that never finishes might still write, so only its step limit ends the run
+    let mut endless = Machine::new(Program::compile("+[]", 0).unwrap());
+    endless.set_max_steps(Some(100));
+    let quick = Machine::new(Program::compile(".", 0).unwrap());
+    let report = lockstep(endless, quick, false);
+    assert!(matches!(report.verdict, Verdict::Failed { left: true, .. }));
+    assert_eq!(report.outputs[1], [0]);
+}
@@ -0,0 +1,403 @@
+// Compares every way of running a program against the unoptimized reference interpreter.
+use std::path::Path;
+
+use bf::backend::{backends, Io};
+use bf::reference::{self, Outcome};
+use bf::rng::Rng;
+use bf::threaded::Threaded;
+use bf::tiered;
+use bf::{
+    BfError, CellSize, Eof, Interpreter, InterpreterBuilder, Machine, Pipeline, Program, RunState,
+    Settings, Tape,
+};
+
+// The non-zero cells by their distance from the pointer, which doesn't depend on how far
+// the tape happened to grow
+fn contents(tape: &[u32], pointer: usize) -> Vec<(isize, u32)> {
+    let at = |idx: usize| idx as isize - pointer as isize;
+    tape.iter()
+        .enumerate()
+        .filter(|(_, &cell)| cell != 0)
+        .map(|(idx, &cell)| (at(idx), cell))
+        .collect()
+}
+
+// Runs `source` every in-process way, checking output and final tape against `expected`
+fn check_interpreters(name: &str, source: &str, input: &[u8], base: &Settings, expected: &Outcome) {
+    let want = contents(&expected.tape, expected.pointer);
+    for opt_level in 0..=3 {
+        for tape in [Tape::Dynamic, Tape::Sparse] {
+            let settings = Settings {
+                opt_level,
+                tape,
+                ..*base
+            };
+            let what = format!("{name} -O{opt_level} --tape {tape}");
+            let program = Program::with_extensions(source, opt_level, base.extensions).unwrap();
+
+            let mut output = vec![];
+            let mut interpreter =
+                Interpreter::with_settings(program.clone(), &settings, input, &mut output);
+            interpreter.run().unwrap();
+            let machine = interpreter.machine();
+            assert_eq!(contents(&machine.tape(), machine.pointer()), want, "{what}");
+            assert!(machine.steps() <= expected.steps, "{what}");
+            let steps = machine.steps();
+            assert_eq!(output, expected.output, "{what}");
+
+            // Threaded code takes exactly the interpreter's steps
+            let mut output = vec![];
+            let finished = Threaded::compile(&program)
+                .unwrap()
+                .run(&settings, &mut &input[..], &mut output)
+                .unwrap();
+            assert_eq!(contents(&finished.tape, finished.pointer), want, "{what}");
+            assert_eq!(finished.steps, steps, "{what} threaded");
+            assert_eq!(output, expected.output, "{what} threaded");
+
+            // So are tiered runs, whichever loops get compiled along the way
+            for hot in [1, 3, tiered::HOT] {
+                let mut output = vec![];
+                let finished = tiered::run(&program, &settings, &mut &input[..], &mut output, hot)
+                    .unwrap()
+                    .finished;
+                assert_eq!(contents(&finished.tape, finished.pointer), want, "{what}");
+                assert_eq!(finished.steps, steps, "{what} tiered {hot}");
+                assert_eq!(output, expected.output, "{what} tiered {hot}");
+            }
+
+            let mut machine = Machine::with_settings(program.clone(), &settings);
+            machine.feed(input);
+            machine.close_input();
+            let mut output = vec![];
+            loop {
+                let state = machine.run_for(7);
+                output.extend(machine.take_output());
+                match state {
+                    RunState::Paused => (),
+                    RunState::Finished => break,
+                    state => panic!("{what}: run_for stopped with {state:?}"),
+                }
+            }
+            assert_eq!(output, expected.output, "{what} run_for");
+
+            let mut output = vec![];
+            Pipeline::with_settings(vec![program], settings)
+                .run(input, &mut output)
+                .unwrap();
+            assert_eq!(output, expected.output, "{what} pipeline");
+        }
+    }
+}
+
+#[test]
+fn corpus_agrees_with_reference() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    for case in bf::corpus::load(&dir, &Settings::default()).unwrap() {
+        let expected = reference::run(&case.source, &case.input, &case.settings).unwrap();
+        assert_eq!(
+            Some(&expected.output),
+            case.expected.as_ref(),
+            "{}",
+            case.name
+        );
+        check_interpreters(
+            &case.name,
+            &case.source,
+            &case.input,
+            &case.settings,
+            &expected,
+        );
+
+        // Other backends only promise the same output
+        let program = Program::compile(&case.source, case.settings.opt_level).unwrap();
+        for backend in backends() {
+            let mut output = vec![];
+            let io = Io {
+                input: &mut &case.input[..],
+                output: &mut output,
+            };
+            backend.execute(&program, io, &case.settings).unwrap();
+            assert_eq!(
+                output,
+                expected.output,
+                "{} on {}",
+                case.name,
+                backend.name()
+            );
+        }
+    }
+}
+
+// A random program with balanced brackets, weighted towards the usual idioms
+fn random_program(rng: &mut Rng) -> String {
+    const PIECES: &[&str] = &[
+        "+", "-", ">", "<", ".", ",", "+++", "---", ">>", "<<", "[-]", "[+]", "[->+<]", "[-<+>]",
+        "[>]", "[<<]",
+    ];
+    let mut out = String::new();
+    let mut depth = 0;
+    for _ in 0..rng.below(40) + 1 {
+        match rng.below(10) {
+            0 if depth < 3 => {
+                out.push('[');
+                depth += 1;
+            }
+            1 if depth > 0 => {
+                out.push(']');
+                depth -= 1;
+            }
+            2 => out.push_str(" comment "),
+            _ => out.push_str(PIECES[rng.below(PIECES.len())]),
+        }
+    }
+    out.extend(std::iter::repeat_n(']', depth));
+    out
+}
+
+#[test]
+fn random_programs_agree_with_reference() {
+    let mut rng = Rng::new(0x5eed);
+    let mut finished = 0;
+    for idx in 0..2000 {
+        let source = random_program(&mut rng);
+        let input: Vec<u8> = (0..rng.below(6)).map(|_| rng.byte()).collect();
+        let settings = Settings {
+            cell_size: [CellSize::U8, CellSize::U16, CellSize::U32][rng.below(3)],
+            eof: [Eof::Zero, Eof::Minus1, Eof::Unchanged][rng.below(3)],
+            max_steps: Some(20_000),
+            ..Settings::default()
+        };
+        // Programs that don't finish within the limit say nothing about correctness
+        let Ok(expected) = reference::run(&source, &input, &settings) else {
+            continue;
+        };
+        finished += 1;
+        let name = format!("program {idx} {source:?} with {settings:?}");
+        check_interpreters(&name, &source, &input, &settings, &expected);
+    }
+    assert!(finished > 1000, "only {finished} programs finished");
+}
+
+#[test]
+fn random_extension_is_reproducible() {
+    let settings = Settings {
+        extensions: "random".parse().unwrap(),
+        seed: Some(42),
+        ..Settings::default()
+    };
+    for source in ["?.?.?.", "?[.?]", "?>?>?[<]>[.>]", "+++[?.>?.<-]"] {
+        let expected = reference::run(source, &[], &settings).unwrap();
+        assert_ne!(expected.output, [0; 3], "{source:?}");
+        check_interpreters(source, source, &[], &settings, &expected);
+    }
+    // Without the extension `?` is a comment
+    let plain = reference::run("?.", &[], &Settings::default()).unwrap();
+    assert_eq!(plain.output, [0]);
+}
+
+#[test]
+fn numbers_agree_with_reference() {
+    let cases: [(&str, &[u8]); 6] = [
+        (";:", b"  42\n"),
+        (";>;[<+>-]<:", b"300 -5"),
+        (";:;:", b"-1"),
+        (";:", b"\n\t "),
+        (";:>;:", b"x12"),
+        ("+[;:[-]+]", b"1 22 333 18446744073709551617"),
+    ];
+    for (source, input) in cases {
+        for cell_size in [CellSize::U8, CellSize::U16, CellSize::U32] {
+            for eof in [Eof::Zero, Eof::Minus1, Eof::Unchanged] {
+                let settings = Settings {
+                    extensions: "numbers".parse().unwrap(),
+                    cell_size,
+                    eof,
+                    max_steps: Some(10_000),
+                    ..Settings::default()
+                };
+                let name = format!("{source:?} with {settings:?}");
+                let Ok(expected) = reference::run(source, input, &settings) else {
+                    continue;
+                };
+                check_interpreters(&name, source, input, &settings, &expected);
+            }
+        }
+    }
+    let settings = Settings {
+        extensions: "numbers".parse().unwrap(),
+        ..Settings::default()
+    };
+    let expected = reference::run(";>;[<+>-]<:", b"300 -5", &settings).unwrap();
+    assert_eq!(expected.output, b"39");
+
+    // A number split across feeds waits for the byte that ends it
+    let program = Program::with_extensions(";:", 1, settings.extensions).unwrap();
+    let mut machine = Machine::with_settings(program, &settings);
+    for byte in b"12" {
+        machine.feed(&[*byte]);
+        assert!(matches!(machine.run_for(10), RunState::NeedsInput));
+    }
+    machine.close_input();
+    assert!(matches!(machine.run_for(10), RunState::Finished));
+    assert_eq!(machine.take_output(), b"12");
+    assert_eq!(machine.steps(), 2);
+}
+
+#[test]
+fn forked_threads_agree_with_reference() {
+    let settings = Settings {
+        extensions: "fork".parse().unwrap(),
+        max_steps: Some(100_000),
+        ..Settings::default()
+    };
+    let sources = [
+        "+++++[>++++++++++<-]>Y>[<+.>]<.",
+        "YY>>.<.<.",
+        ",Y.,.>.",
+        "Y[>+<-]>[+++.]",
+        "+[Y>[-]<.[-]]++++++++[>++++++<-]>.",
+        // The child's loop runs three times, the parent's not at all
+        ">>+++<<Y>[>+<-]>.",
+    ];
+    for source in sources {
+        let expected = reference::run(source, b"ab", &settings).unwrap();
+        for opt_level in 0..=3 {
+            for tape in [Tape::Dynamic, Tape::Sparse] {
+                let settings = Settings {
+                    opt_level,
+                    tape,
+                    ..settings
+                };
+                let what = format!("{source:?} -O{opt_level} --tape {tape}");
+                let program = Program::with_extensions(source, opt_level, settings.extensions);
+                let mut output = vec![];
+                let mut interpreter = Interpreter::with_settings(
+                    program.unwrap(),
+                    &settings,
+                    &b"ab"[..],
+                    &mut output,
+                );
+                interpreter.run().unwrap();
+                let machine = interpreter.machine();
+                let want = contents(&expected.tape, expected.pointer);
+                assert_eq!(contents(&machine.tape(), machine.pointer()), want, "{what}");
+                assert_eq!(output, expected.output, "{what}");
+            }
+        }
+    }
+    // Unrolling on its own, without the passes that come before it at -O3
+    let expected = reference::run(sources[5], &[], &settings).unwrap();
+    let passes = bf::passes::parse("fold,unroll").unwrap();
+    let (program, _) = Program::with_passes(sources[5], settings.extensions, &passes).unwrap();
+    let mut output = vec![];
+    Interpreter::with_settings(program, &settings, &[][..], &mut output)
+        .run()
+        .unwrap();
+    assert_eq!(output, expected.output);
+    assert_eq!(output, [3, 3]);
+    // The child writes first, then the parent
+    let expected = reference::run(sources[0], &[], &settings).unwrap();
+    assert_eq!(expected.output, [1, 0]);
+}
+
+#[test]
+fn const_eval_agrees_with_reference() {
+    const HELLO: &str = include_str!("../corpus/hello.b");
+    const OUTPUT: [u8; 13] = bf::bf_eval(HELLO);
+    let expected = reference::run(HELLO, &[], &Settings::default()).unwrap();
+    assert_eq!(OUTPUT[..], expected.output);
+    const WRAPS: [u8; 2] = bf::bf_eval("-.[+]+.");
+    assert_eq!(WRAPS, [255, 1]);
+}
+
+#[test]
+fn reference_reports_brackets_like_the_compiler() {
+    for source in ["[", "]", "[[]", "[]]", "+[>[<]", "a]b["] {
+        let reference = reference::run(source, &[], &Settings::default()).unwrap_err();
+        let compiled = Program::from_source(source).unwrap_err();
+        assert_eq!(reference.to_string(), compiled.to_string(), "{source:?}");
+    }
+}
+
+#[test]
+fn hang_detection_only_flags_programs_that_never_finish() {
+    let mut rng = Rng::new(0x4a26);
+    for _ in 0..2000 {
+        let source = random_program(&mut rng);
+        let settings = Settings {
+            max_steps: Some(20_000),
+            ..Settings::default()
+        };
+        let mut machine = Machine::with_settings(Program::from_source(&source).unwrap(), &settings);
+        machine.detect_hangs();
+        machine.close_input();
+        let finished = reference::run(&source, &[], &settings).is_ok();
+        if let RunState::Error(BfError::NonTerminating(_)) = machine.run_for(u64::MAX) {
+            assert!(!finished, "{source:?}");
+        }
+    }
+}
+
+#[test]
+fn tiered_runs_compile_hot_loops() {
+    // The inner loop turns 255 times each time round the outer one, at -O0 so nothing
+    // folds them away
+    let (program, _) = Program::with_passes("-[>-[-]<-]>+.", Default::default(), &[]).unwrap();
+    let settings = Settings::default();
+    let mut output = vec![];
+    let tiered = tiered::run(&program, &settings, &mut &b""[..], &mut output, 100).unwrap();
+    assert_eq!(output, [1]);
+    // The inner loop warms up first, on its first pass, then the outer one with it inside
+    assert_eq!(tiered.promoted, [4, 1]);
+
+    // Steps stop where the interpreter stops them, compiled or not
+    let settings = Settings {
+        max_steps: Some(5000),
+        ..settings
+    };
+    let err = tiered::run(&program, &settings, &mut &b""[..], &mut vec![], 100);
+    assert!(matches!(err, Err(BfError::StepLimit(5000))));
+
+    let program = Program::with_extensions("+Y", 0, "fork".parse().unwrap()).unwrap();
+    let err = tiered::run(&program, &settings, &mut &b""[..], &mut vec![], 100);
+    assert!(matches!(err, Err(BfError::Unsupported(_))));
+}
+
+#[test]
+fn preloaded_tapes_agree_with_reference() {
+    let data = b"ABC";
+    // The same cells set by code, for the reference to start from
+    let mut setup: String = data
+        .iter()
+        .map(|&byte| "+".repeat(byte as usize) + ">")
+        .collect();
+    setup += &"<".repeat(data.len());
+    // Constants propagated or loops unrolled from a blank tape would print other bytes, and
+    // an opening loop left out as a comment would print none
+    for source in [
+        "+.",
+        "+[.>]",
+        "++++[>+<-]>.<.",
+        "[-]+++[>++<-]>.",
+        "[print each byte .>]",
+    ] {
+        let expected = reference::run(&(setup.clone() + source), b"", &Settings::default())
+            .unwrap()
+            .output;
+        for opt_level in 0..=3 {
+            let mut output = vec![];
+            InterpreterBuilder::new()
+                .opt_level(opt_level)
+                .source(source)
+                .preload(&data[..])
+                .input(&b""[..])
+                .output(&mut output)
+                .build()
+                .unwrap()
+                .run()
+                .unwrap();
+            assert_eq!(output, expected, "{source} -O{opt_level}");
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Checks that the mini language compiles the corpus program it was written for, and reports
+// what it can't compile with where it is.
+use bf::{BfError, Interpreter, Program};
+
+fn run(source: &str, input: &[u8]) -> Vec<u8> {
+    let code = bf::dsl::compile(source).unwrap();
+    let mut output = vec![];
+    Interpreter::new(Program::from_source(&code).unwrap(), input, &mut output)
+        .run()
+        .unwrap();
+    output
+}
+
+#[test]
+fn dsl_compiles_the_corpus() {
+    let compiled = bf::dsl::compile(include_str!("../corpus/factor.dsl")).unwrap();
+    assert_eq!(
+        compiled.trim_end(),
+        include_str!("../corpus/factor.b").trim_end()
+    );
+
+    let source = "read c; n = 3; while n { print c + n - 1; n = n - 1; } \
+                  if n { print \"set\"; } else { print \"\\n\"; }";
+    assert_eq!(run(source, b"a"), b"cba\n");
+}
+
+#[test]
+fn dsl_reports_errors_where_they_are() {
+    let error = |source: &str| match bf::dsl::compile(source) {
+        Err(BfError::Syntax(at, message)) => (at, message),
+        other => panic!("expected a syntax error, got {other:?}"),
+    };
+    assert_eq!(
+        error("x = y + 1;"),
+        (4, "undeclared variable `y`".to_string())
+    );
+    // Assigning a name declares it only once its value is worked out
+    assert_eq!(error("read a;\nx = a + x;").0, 16);
+    assert_eq!(error("while 1 { print 2 }").1, "expected `;`");
+    assert_eq!(
+        error("print 'ab';").1,
+        "character literals hold a single byte"
+    );
+}
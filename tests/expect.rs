@@ -0,0 +1,54 @@
+// Checks that interaction scripts wait for prompts and feed input where they say.
+use bf::expect::{Action, Failure, Script};
+use bf::{BfError, Machine, Program};
+
+// Prints `?`, then echoes its input until EOF
+const PROMPT: &str = "+++++++[>+++++++++<-]>.[-],[.,]";
+
+fn run(script: &str) -> Result<(), Failure> {
+    let mut machine = Machine::new(Program::compile(PROMPT, 2).unwrap());
+    Script::parse(script).unwrap().run(&mut machine)
+}
+
+#[test]
+fn scripts_parse_into_actions() {
+    let script = Script::parse("expect \"Name?\"; send \"Bob\\n\" # greet\n\neof\n").unwrap();
+    assert_eq!(
+        script.actions,
+        [
+            Action::Expect(b"Name?".to_vec()),
+            Action::Send(b"Bob\n".to_vec()),
+            Action::Eof
+        ]
+    );
+    assert!(matches!(
+        Script::parse("send \"a\" send \"b\""),
+        Err(BfError::Syntax(9, _))
+    ));
+    assert!(matches!(
+        Script::parse("expect \"\\q\""),
+        Err(BfError::Syntax(_, _))
+    ));
+}
+
+#[test]
+fn expects_wait_for_their_text() {
+    run("expect \"?\"; send \"Bob\\n\"; expect \"Bob\"; send \"!\"; expect \"\\n!\"").unwrap();
+    match run("send \"Al\"; expect \"Bob\"") {
+        Err(Failure::Mismatch {
+            step,
+            output,
+            finished,
+            ..
+        }) => {
+            assert_eq!(step, 1);
+            assert_eq!(output, b"?Al");
+            assert!(!finished);
+        }
+        other => panic!("expected a mismatch, got {other:?}"),
+    }
+    assert!(matches!(
+        run("eof; expect \"x\""),
+        Err(Failure::Mismatch { finished: true, .. })
+    ));
+}
@@ -0,0 +1,65 @@
+// Checks that fuzzing finds the inputs a program mishandles, from random bytes and grammars.
+use bf::fuzz::{fuzz, Grammar, Inputs, Kind, Options};
+use bf::judge::Limits;
+use bf::rng::Rng;
+use bf::{Program, Settings};
+
+// Spins forever when its first byte is `q`, otherwise reads the rest of its input
+fn hangs_on_q() -> Program {
+    let code = format!("+>,{}[<->[-]]<[]>,[,]", "-".repeat(113));
+    Program::compile(&code, 2).unwrap()
+}
+
+#[test]
+fn finds_inputs_that_hit_the_step_limit() {
+    let options = Options {
+        runs: 200,
+        limits: Limits {
+            max_steps: 10_000,
+            ..Limits::default()
+        },
+        ..Options::default()
+    };
+    let grammar = Grammar::parse(
+        "# lines of commands\nstart = cmd | cmd \"\\n\" start\ncmd = \"go\" | \"quit\" | \"\\x41\"",
+    )
+    .unwrap();
+    for inputs in [Inputs::Random, Inputs::Grammar(grammar)] {
+        let report = fuzz(&hangs_on_q(), &Settings::default(), &inputs, &options);
+        assert!(!report.findings.is_empty(), "{inputs:?}");
+        for finding in &report.findings {
+            assert_eq!(finding.kind, Kind::StepLimit);
+            assert_eq!(finding.input.first(), Some(&b'q'));
+        }
+        assert_eq!(report.finished + report.findings.len(), report.runs);
+        // The same seed finds the same inputs
+        assert_eq!(
+            fuzz(&hangs_on_q(), &Settings::default(), &inputs, &options),
+            report
+        );
+    }
+}
+
+#[test]
+fn grammars() {
+    let grammar = Grammar::parse("list = item | item \",\" list\nitem = \"x\" | \"yy\"").unwrap();
+    let mut rng = Rng::new(1);
+    for _ in 0..100 {
+        let input = grammar.generate(&mut rng, 1000);
+        let text = String::from_utf8(input).unwrap();
+        assert!(
+            text.split(',').all(|item| item == "x" || item == "yy"),
+            "{text}"
+        );
+    }
+    // Recursion with no way out still stops at the length limit
+    let endless = Grammar::parse("a = \"a\" a").unwrap();
+    assert_eq!(endless.generate(&mut rng, 10), b"aaaaaaaaaa");
+
+    assert!(Grammar::parse("a = b")
+        .unwrap_err()
+        .contains("no rule named `b`"));
+    assert!(Grammar::parse("a = \"x")
+        .unwrap_err()
+        .contains("unterminated"));
+}
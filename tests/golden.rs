@@ -0,0 +1,44 @@
+// Checks that golden runs line up across optimization levels and catch a tape that differs
+// where the output doesn't.
+use bf::golden::Golden;
+use bf::InterpreterBuilder;
+
+fn record(code: &str, opt_level: u8, every: u64) -> (Golden, Vec<u8>) {
+    let mut output = vec![];
+    let mut interpreter = InterpreterBuilder::new()
+        .opt_level(opt_level)
+        .source(code)
+        .input(&b"ab"[..])
+        .output(&mut output)
+        .golden(Golden::new(every))
+        .build()
+        .unwrap();
+    interpreter.run().unwrap();
+    let golden = interpreter.take_golden().unwrap();
+    (golden, output)
+}
+
+#[test]
+fn golden_runs_catch_a_different_tape() {
+    // Reads, clears, moves and scans, which the levels below -O3 all run differently
+    let code = "++++[->+++<]>,.>,.<<[-]>>[<]>>+++.";
+    let (golden, _) = record(code, 0, 1);
+    // A state after each of the five reads and writes and one at the end
+    assert_eq!(golden.links().len(), 6);
+    for opt_level in 1..=2 {
+        assert_eq!(record(code, opt_level, 1).0.compare(&golden), None);
+    }
+    let text = golden.to_text();
+    assert_eq!(Golden::from_text(&text).unwrap(), golden);
+    assert!(Golden::from_text(&text.replace("every", "each")).is_err());
+
+    // Same output, but a cell left behind
+    let (changed, output) = record("++++[->+++<]>,.>,.<<[-]>>[<]>>+++.<<<+", 0, 1);
+    assert_eq!(output, record(code, 0, 1).1);
+    let divergence = changed.compare(&golden).unwrap();
+    assert_eq!(divergence.state, 5);
+    assert_eq!(divergence.found.unwrap().io, 5);
+
+    // Taking every other state still takes the last
+    assert_eq!(record(code, 0, 2).0.links().len(), 3);
+}
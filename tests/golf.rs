@@ -0,0 +1,50 @@
+// Checks that golfed programs are no longer than their sources and still produce the corpus
+// outputs.
+use std::path::Path;
+
+use bf::corpus::load;
+use bf::golf::golf;
+use bf::{BfError, Settings, Tape};
+
+#[test]
+fn golfing_preserves_output() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    for case in load(&dir, &Settings::default()).unwrap() {
+        let golfed = golf(&case.source).unwrap();
+        assert!(golfed.code.len() <= golfed.minified, "{}", case.name);
+        let mut run = case.clone();
+        run.source = golfed.code;
+        assert_eq!(run.run().unwrap(), case.expected.unwrap(), "{}", case.name);
+    }
+
+    // Temp cells stay on a tape that ends left of the start, wherever the original did
+    let fixed = Settings {
+        tape: Tape::Fixed(30_000),
+        ..Settings::default()
+    };
+    for case in load(&dir, &fixed).unwrap() {
+        let Ok(expected) = case.run() else {
+            continue;
+        };
+        let mut run = case.clone();
+        run.source = golf(&case.source).unwrap().code;
+        assert_eq!(run.run().unwrap(), expected, "{}", case.name);
+    }
+    let run = |source: &str| {
+        let program = bf::Program::with_settings(source, &fixed)?;
+        let mut output = vec![];
+        bf::Interpreter::with_settings(program, &fixed, &[][..], &mut output).run()?;
+        Ok::<_, BfError>(output)
+    };
+    // Only the cell left of the start is free, then the first cell is too
+    for (source, rewrites) in [(">+<", 0), (">>+<", 1)] {
+        let golfed = golf(&format!("{source}{}.", "+".repeat(72))).unwrap();
+        assert_eq!(golfed.rewrites, rewrites, "{}", golfed.code);
+        assert_eq!(run(&golfed.code).unwrap(), b"H", "{}", golfed.code);
+    }
+
+    // A long constant run becomes a multiplication loop
+    let golfed = golf(&format!("{}.", "+".repeat(72))).unwrap();
+    assert_eq!(golfed.rewrites, 1);
+    assert_eq!(golfed.code, ">++++++++[<+++++++++>-]<.");
+}
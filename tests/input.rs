@@ -0,0 +1,56 @@
+// Checks reading input as words of several bytes and input that repeats.
+use std::io::Read;
+
+use bf::input::{Endian, Repeat, Word};
+use bf::{CellSize, InterpreterBuilder, Machine, Program, Settings};
+
+#[test]
+fn words_are_read_in_either_byte_order() {
+    let data = [0x01, 0x02, 0x03, 0x04, 0x0a];
+    let settings = Settings {
+        cell_size: CellSize::U32,
+        extensions: "numbers".parse().unwrap(),
+        ..Settings::default()
+    };
+    // The last word is cut short and padded, the one after it reads EOF
+    for (endian, expected) in [
+        (Endian::Little, "513 1027 10 0"),
+        (Endian::Big, "258 772 2560 0"),
+    ] {
+        let mut output = vec![];
+        InterpreterBuilder::new()
+            .settings(settings)
+            .source(">>++++++++[<++++>-]<<,:>.<,:>.<,:>.<,:")
+            .input(&data[..])
+            .output(&mut output)
+            .input_word(Word { bytes: 2, endian })
+            .build()
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected, "{endian}");
+    }
+
+    // A machine fed a byte at a time waits for the rest of the word
+    let program = Program::from_source(",").unwrap();
+    let mut machine = Machine::with_settings(program, &settings);
+    machine.set_input_word(Word::with_bits("32", Endian::Big).unwrap());
+    for byte in [0xde, 0xad, 0xbe] {
+        machine.feed(&[byte]);
+        assert!(matches!(machine.run_for(10), bf::RunState::NeedsInput));
+    }
+    machine.feed(&[0xef]);
+    assert!(matches!(machine.run_for(10), bf::RunState::Finished));
+    assert_eq!(machine.cell(), 0xdeadbeef);
+    assert_eq!(machine.usage().read, 4);
+}
+
+#[test]
+fn repeated_input_starts_over() {
+    let mut repeat = Repeat::new(&b"abc"[..]);
+    let mut buf = [0; 8];
+    repeat.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"abcabcab");
+    // Nothing to repeat is still the end of input
+    assert_eq!(Repeat::new(&b""[..]).read(&mut buf).unwrap(), 0);
+}
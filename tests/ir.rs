@@ -0,0 +1,43 @@
+// Checks that the IR printed by --emit-ir reads back, with --ir, as the program it came from.
+use std::path::Path;
+
+use bf::corpus::load;
+use bf::{Interpreter, Program, Settings};
+
+#[test]
+fn ir_round_trips_on_the_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    for case in load(&dir, &Settings::default()).unwrap() {
+        let expected = case.expected.clone().unwrap();
+        for opt_level in 0..=3 {
+            let (program, _) = Program::with_passes(
+                &case.source,
+                case.settings.extensions,
+                &bf::passes::for_level(opt_level),
+            )
+            .unwrap();
+            let text = bf::ir::disassemble(&program);
+            let assembled = bf::ir::assemble(&text).unwrap();
+            assert_eq!(
+                assembled.tokens, program.tokens,
+                "{} -O{opt_level}",
+                case.name
+            );
+            assert_eq!(
+                assembled.jumps, program.jumps,
+                "{} -O{opt_level}",
+                case.name
+            );
+
+            let mut output = vec![];
+            Interpreter::with_settings(assembled, &case.settings, &case.input[..], &mut output)
+                .run()
+                .unwrap();
+            assert_eq!(output, expected, "{} -O{opt_level}", case.name);
+        }
+    }
+
+    // Unknown mnemonics and unbalanced loops are refused
+    assert!(bf::ir::assemble("ADD 1\nFLY 2\n").is_err());
+    assert!(bf::ir::assemble("JZ\nADD 1\n").is_err());
+}
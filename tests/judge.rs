@@ -0,0 +1,47 @@
+// Checks that the judge tells each way a submission can fail apart and ranks them.
+use bf::judge::{judge, rank, Limits, Test, Verdict};
+use bf::quine::Normalize;
+use bf::Settings;
+
+fn tests() -> Vec<Test> {
+    let test = |name: &str, input: &[u8], expected: &[u8]| Test {
+        name: name.to_string(),
+        input: input.to_vec(),
+        expected: expected.to_vec(),
+    };
+    // Echo the input back with one added to every byte
+    vec![test("empty", b"", b""), test("word", b"HAL", b"IBM")]
+}
+
+#[test]
+fn verdicts_and_ranking() {
+    let limits = Limits {
+        max_steps: 10_000,
+        ..Limits::default()
+    };
+    let settings = Settings::default();
+    let submit = |name: &str, source: &str| {
+        judge(name, source, &settings, &tests(), limits, Normalize::Exact)
+    };
+    let mut submissions = vec![
+        submit("forever", "+[]"),
+        submit("slow", ",[+.[-]>>>>>>>>>><<<<<<<<<<,]"),
+        submit("fast", ",[+.,]"),
+        submit("unchanged", ",[.,]"),
+        submit("broken", ",[+.,"),
+    ];
+    let verdicts = |idx: usize| -> Vec<Verdict> {
+        let cases = &submissions[idx].cases;
+        cases.iter().map(|case| case.verdict.clone()).collect()
+    };
+    assert_eq!(verdicts(0), [Verdict::StepLimit, Verdict::StepLimit]);
+    assert_eq!(verdicts(3), [Verdict::Accepted, Verdict::WrongAnswer(0)]);
+    assert!(matches!(verdicts(4)[0], Verdict::Error(_)));
+
+    rank(&mut submissions);
+    let order: Vec<&str> = submissions.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(order, ["fast", "slow", "unchanged", "broken", "forever"]);
+    assert_eq!(submissions[0].passed(), 2);
+    // Snippets are expanded as they are for `run`
+    assert_eq!(submit("snippet", ",[\n@use add(1)\n.,]").passed(), 2);
+}
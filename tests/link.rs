@@ -0,0 +1,55 @@
+// Checks that linked units run like their sources joined together, hand the tape off at
+// their origin when asked, and refuse a unit that needs a blank tape anywhere but first.
+use bf::link::{link, Handoff, Unit};
+use bf::{Extensions, InterpreterBuilder, Program};
+
+fn unit(code: &str, opt_level: u8) -> Unit {
+    let passes = bf::passes::for_level(opt_level);
+    let unit = Unit::compile(code, Extensions::default(), &passes, None).unwrap();
+    Unit::from_bytes(&unit.to_bytes()).unwrap()
+}
+
+fn run(program: Program) -> Vec<u8> {
+    let mut output = vec![];
+    InterpreterBuilder::new()
+        .program(program)
+        .output(&mut output)
+        .build()
+        .unwrap()
+        .run()
+        .unwrap();
+    output
+}
+
+#[test]
+fn linked_units_share_the_tape() {
+    let (first, second) = ("++++++++[>++++++++<-]>+", "[>+>+<<-]>.>++.");
+    let joined = run(Program::from_source(&format!("{first}{second}")).unwrap());
+    for opt_level in 0..=2 {
+        let units = [unit(first, opt_level), unit(second, opt_level)];
+        let linked = link(&units, Handoff::Continue).unwrap();
+        assert_eq!(linked.source_len, first.len() + second.len());
+        assert_eq!(run(linked.program), joined, "-O{opt_level}");
+    }
+
+    // Back on the first cell, which the loop of `first` counted down to nothing
+    let units = [unit(first, 1), unit("+++++[>>+++++++++++++<<-]>>.", 1)];
+    let linked = link(&units, Handoff::Origin).unwrap();
+    assert_eq!(run(linked.program), b"A");
+    let units = [unit(",[>]", 1), unit(".", 1)];
+    assert_eq!(link(&units, Handoff::Origin).unwrap_err().0, 0);
+
+    // Worked out from a blank tape, or with an opening comment left out, it must come first
+    let blank = unit("+++.", 3);
+    assert!(blank.blank_start);
+    assert!(link(&[blank.clone(), unit(first, 1)], Handoff::Continue).is_ok());
+    assert_eq!(
+        link(&[unit(first, 1), blank], Handoff::Continue)
+            .unwrap_err()
+            .0,
+        1
+    );
+    assert!(unit("[comment]+.", 1).blank_start);
+    assert!(!unit("+[comment]", 1).blank_start);
+    assert!(Unit::from_bytes(b"BFUNIT01").is_err());
+}
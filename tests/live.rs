@@ -0,0 +1,57 @@
+// Checks that live snapshots taken mid-run are states the program really passed through.
+use std::thread;
+
+use bf::live::LiveTape;
+use bf::{BfError, CancelToken, CellSize, InterpreterBuilder, Machine, Program, Settings};
+
+#[test]
+fn snapshots_match_a_replay() {
+    let code = "+[>+>+++<<]";
+    let settings = Settings {
+        cell_size: CellSize::U32,
+        opt_level: 0,
+        ..Settings::default()
+    };
+    let live = LiveTape::new(1000);
+    let cancel = CancelToken::new();
+    let runner = {
+        let (live, cancel) = (live.clone(), cancel.clone());
+        thread::spawn(move || {
+            InterpreterBuilder::new()
+                .settings(settings)
+                .source(code)
+                .input(&b""[..])
+                .output(vec![])
+                .live_tape(live)
+                .cancel(cancel)
+                .build()
+                .unwrap()
+                .run()
+        })
+    };
+    let mut seen = vec![];
+    while seen.len() < 3 {
+        let snapshot = live.snapshot();
+        if seen
+            .last()
+            .is_none_or(|last: &bf::live::TapeSnapshot| last.steps < snapshot.steps)
+        {
+            seen.push(snapshot);
+        }
+    }
+    cancel.cancel();
+    assert!(matches!(runner.join().unwrap(), Err(BfError::Interrupted)));
+    // The last copy is made when the run stops
+    assert!(live.snapshot().steps >= seen[2].steps);
+
+    for snapshot in &seen[1..] {
+        assert_eq!(snapshot.steps % 1000, 0);
+        let mut machine = Machine::with_settings(Program::compile(code, 0).unwrap(), &settings);
+        machine.run_for(snapshot.steps);
+        assert_eq!(machine.tape()[..], snapshot.cells[..]);
+        assert_eq!(
+            (machine.pointer(), machine.ip()),
+            (snapshot.pointer, snapshot.ip)
+        );
+    }
+}
@@ -0,0 +1,63 @@
+// Checks that generated programs nested hundreds of thousands deep compile and run without
+// overflowing the stack, and that the nesting limit rejects them with the `[` at fault.
+use bf::{BfError, InterpreterBuilder, Machine, Program, RunState, Settings};
+
+const DEPTH: usize = 300_000;
+
+// Enters every loop once on the way in and leaves each on the way out
+fn deep(depth: usize) -> String {
+    format!("+{}-{}.", "[".repeat(depth), "]".repeat(depth))
+}
+
+#[test]
+fn deep_programs_compile_and_run() {
+    let code = deep(DEPTH);
+    for opt_level in 0..=3 {
+        let program = Program::compile(&code, opt_level).unwrap();
+        let mut machine = Machine::new(program);
+        let state = machine.run_for(u64::MAX);
+        assert!(matches!(state, RunState::Finished), "-O{opt_level}");
+        assert_eq!(machine.take_output(), [0]);
+    }
+    let info = bf::check(&code).unwrap();
+    assert_eq!(info.max_depth, DEPTH);
+    bf::inspect(&code).unwrap();
+    bf::warnings(&code).unwrap();
+    assert!(bf::equivalence::equivalent(&code, &code).unwrap());
+}
+
+#[test]
+fn nesting_limit() {
+    let build = |depth: usize| {
+        InterpreterBuilder::new()
+            .settings(Settings {
+                max_depth: Some(10),
+                ..Settings::default()
+            })
+            .source(deep(depth))
+            .input(&b""[..])
+            .output(vec![])
+            .build()
+    };
+    assert!(build(10).is_ok());
+    // `+` then ten `[` before the one over the limit
+    assert!(matches!(build(11), Err(BfError::NestingLimit(11, 10))));
+    assert!(matches!(
+        Program::compile(&deep(bf::settings::MAX_DEPTH + 1), 0),
+        Err(BfError::NestingLimit(_, bf::settings::MAX_DEPTH))
+    ));
+    // Compiling by the settings keeps to their limit, or to none
+    let settings = Settings {
+        max_depth: Some(10),
+        ..Settings::default()
+    };
+    assert!(matches!(
+        Program::with_settings(&deep(11), &settings),
+        Err(BfError::NestingLimit(11, 10))
+    ));
+    let settings = Settings {
+        max_depth: None,
+        ..settings
+    };
+    assert!(Program::with_settings(&deep(bf::settings::MAX_DEPTH + 1), &settings).is_ok());
+}
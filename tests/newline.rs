@@ -0,0 +1,50 @@
+// Checks that line endings are translated both ways, including across reads and writes.
+use std::io::{Read, Write};
+
+use bf::newline::{InputNewlines, Newline, OutputNewlines};
+
+#[test]
+fn input_line_endings_become_the_expected_one() {
+    // One byte per read, so a CRLF is split between reads
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some((&first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+    let source = b"a\nb\r\nc\rd";
+    for (newline, expected) in [
+        (Newline::Lf, &b"a\nb\r\nc\rd"[..]),
+        (Newline::Crlf, b"a\r\nb\r\nc\r\nd"),
+        (Newline::Cr, b"a\rb\rc\rd"),
+    ] {
+        let mut read = vec![];
+        InputNewlines::new(Trickle(source), newline)
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, expected, "{newline:?}");
+    }
+}
+
+#[test]
+fn output_line_endings_become_lf() {
+    let mut out = vec![];
+    {
+        let mut writer = OutputNewlines::new(&mut out, Newline::Crlf);
+        for part in [&b"a\r"[..], b"\nb\rc\r\n\r"] {
+            writer.write_all(part).unwrap();
+        }
+    }
+    assert_eq!(out, b"a\nb\rc\n\r");
+
+    let mut out = vec![];
+    OutputNewlines::new(&mut out, Newline::Cr)
+        .write_all(b"a\rb\n")
+        .unwrap();
+    assert_eq!(out, b"a\nb\n");
+}
@@ -0,0 +1,27 @@
+// Checks that obfuscated programs still produce the corpus outputs, whatever the seed.
+use std::path::Path;
+
+use bf::corpus::load;
+use bf::rng::Rng;
+use bf::{Obfuscator, Settings};
+
+#[test]
+fn obfuscating_preserves_output() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    for case in load(&dir, &Settings::default()).unwrap() {
+        let expected = case.expected.clone().unwrap();
+        for seed in 0..3 {
+            let mut obfuscated = case.clone();
+            obfuscated.source = Obfuscator::new(Rng::new(seed), 60)
+                .obfuscate(&case.source)
+                .unwrap();
+            assert_ne!(obfuscated.source, case.source);
+            assert_eq!(
+                obfuscated.run().unwrap(),
+                expected,
+                "{} seed {seed}",
+                case.name
+            );
+        }
+    }
+}
@@ -0,0 +1,65 @@
+// Checks host opcodes: registered characters call back into Rust at every optimization level,
+// and everything that can't run them says so.
+use std::sync::{Arc, Mutex};
+
+use bf::opcode::Opcodes;
+use bf::threaded::Threaded;
+use bf::{BfError, InterpreterBuilder, Program};
+
+#[test]
+fn opcodes_call_back_into_the_host() {
+    let plotted = Arc::new(Mutex::new(vec![]));
+    let mut opcodes = Opcodes::new();
+    let log = plotted.clone();
+    // Plots the cell two to the left at the one to the left, like a tiny screen
+    opcodes
+        .register('#', move |state| {
+            log.lock().unwrap().push((state.peek(-2), state.peek(-1)));
+            Ok(())
+        })
+        .unwrap();
+    // A sensor reading 300 more than the cell, wrapped to its size
+    opcodes
+        .register('@', |state| {
+            state.set_cell(state.cell() + 300);
+            Ok(())
+        })
+        .unwrap();
+    // The cell set before `@` changes under it, which constant propagation must see
+    let code = "+++>++>+++++#@.";
+    for opt_level in 0..=3 {
+        let mut output = vec![];
+        InterpreterBuilder::new()
+            .opt_level(opt_level)
+            .opcodes(opcodes.clone())
+            .source(code)
+            .output(&mut output)
+            .build()
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(plotted.lock().unwrap().pop(), Some((3, 2)), "-O{opt_level}");
+        assert_eq!(output, [(5 + 300) as u8], "-O{opt_level}");
+    }
+
+    // Commands and characters outside ASCII can't be taken over
+    assert!(opcodes.register('+', |_| Ok(())).is_err());
+    assert!(opcodes.register(';', |_| Ok(())).is_err());
+    assert!(opcodes.register('é', |_| Ok(())).is_err());
+
+    // Without the opcodes `#` is a comment, and a program using them needs the handlers
+    let program = Program::from_source(code).unwrap();
+    assert_eq!(program.len(), 6);
+    let (program, _) = Program::with_passes(code, opcodes.extend(Default::default()), &[]).unwrap();
+    assert!(matches!(
+        Threaded::compile(&program),
+        Err(BfError::Unsupported(_))
+    ));
+    let result = InterpreterBuilder::new()
+        .program(program)
+        .output(vec![])
+        .build()
+        .unwrap()
+        .run();
+    assert!(matches!(result, Err(BfError::Unsupported(_))));
+}
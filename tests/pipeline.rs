@@ -0,0 +1,40 @@
+// Checks that a pipeline gives the same output as running its stages by hand, each on the
+// output of the one before.
+use bf::{Interpreter, Pipeline, Program};
+
+fn run(code: &str, input: &[u8]) -> Vec<u8> {
+    let mut output = vec![];
+    Interpreter::new(Program::from_source(code).unwrap(), input, &mut output)
+        .run()
+        .unwrap();
+    output
+}
+
+#[test]
+fn pipelines_match_their_stages() {
+    // Raises every byte by two, reverses its input, then lowers every byte by one
+    let stages = [",[++.,]", ">,[>,]<[.<]", ",[-.,]"];
+    let input = b"Hello, pipes\n";
+    let mut expected = input.to_vec();
+    for stage in stages {
+        expected = run(stage, &expected);
+    }
+    assert_eq!(expected, b"\x0btfqjq!-pmmfI");
+
+    let programs = stages
+        .iter()
+        .map(|code| Program::from_source(code).unwrap())
+        .collect();
+    let pipeline = Pipeline::new(programs);
+    let mut output = vec![];
+    pipeline.run(&input[..], &mut output).unwrap();
+    assert_eq!(output, expected);
+    let mut output = vec![];
+    pipeline.run_concurrent(&input[..], &mut output).unwrap();
+    assert_eq!(output, expected);
+
+    // With no stages the input goes straight through
+    let mut output = vec![];
+    Pipeline::new(vec![]).run(&input[..], &mut output).unwrap();
+    assert_eq!(output, input);
+}
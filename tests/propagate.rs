@@ -0,0 +1,55 @@
+// Checks what constant propagation infers and that the pass keeps programs behaving the same.
+use bf::passes::parse;
+use bf::propagate::{analyze, Fact};
+use bf::{Interpreter, Program};
+
+#[test]
+fn facts_follow_known_loops() {
+    // A loop that can't run, one that runs 8 times, then input the rest depends on. Without
+    // any text the first loop isn't a comment loop left out of the program.
+    let code = "[]++++++++[>++++++++<-]>+.,[-]>[.]";
+    let program = Program::compile(code, 0).unwrap();
+    let facts = analyze(&program.tokens);
+    assert_eq!(facts.cells[0], Fact::Const(0));
+    assert_eq!(facts.cells[2], Fact::Const(0));
+    assert_eq!(facts.cells[1], Fact::Unreached);
+    let prefix = &facts.prefix;
+    assert_eq!(prefix.output, b"A");
+    assert_eq!(prefix.cells, [(0, 0), (1, 65)]);
+    assert_eq!(prefix.pointer, 1);
+    // The `[-]` after input can run any number of times but only writes its own cell, so the
+    // next one is still zero and the loop on it never runs
+    let last = program.tokens.len() - 3;
+    assert_eq!(facts.cells[last], Fact::Const(0));
+    assert_eq!(facts.cells[last + 1], Fact::Unreached);
+    assert_eq!(facts.cells[2 + 8 + 1], Fact::Varies);
+}
+
+#[test]
+fn propagation_keeps_behavior() {
+    let code = "[x]++++++++[>++++[>++<-]>+<<-]>>.,[>+<-]>.<<[>]+++.";
+    let run = |program: Program| {
+        let mut output = vec![];
+        Interpreter::new(program, &b"a"[..], &mut output)
+            .run()
+            .unwrap();
+        output
+    };
+    let plain = Program::compile(code, 0).unwrap();
+    let passes = parse("fold,propagate").unwrap();
+    let (optimized, _) = Program::with_passes(code, Default::default(), &passes).unwrap();
+    assert!(optimized.len() < 20);
+    assert_eq!(run(optimized), run(plain));
+}
+
+#[test]
+fn endless_loops_still_compile() {
+    // Loops that never change their cell, empty or holding only other loops
+    for code in ["+[]", "+[[]]"] {
+        for opt_level in 0..=3 {
+            let program = Program::compile(code, opt_level).unwrap();
+            assert!(!program.is_empty(), "{code} -O{opt_level}");
+        }
+        analyze(&Program::compile(code, 0).unwrap().tokens);
+    }
+}
@@ -0,0 +1,36 @@
+// Checks how quine checks compare a source with what the program printed.
+use bf::quine::{compare, Normalize};
+
+#[test]
+fn normalizations_loosen_the_comparison() {
+    let source = b"+[prints itself]\n";
+    assert!(compare(source, source, Normalize::Exact).is_none());
+    assert!(compare(source, b"+[prints itself]", Normalize::Exact).is_some());
+    assert!(compare(source, b"+[prints itself]  \r\n", Normalize::Trailing).is_none());
+    assert!(compare(source, b"+ [prints\titself]", Normalize::Whitespace).is_none());
+    assert!(compare(source, b"+[]", Normalize::Commands).is_none());
+    // Nothing but comments prints nothing, which is all of it that counts
+    assert!(compare(b"no commands\n", b"", Normalize::Commands).is_none());
+    assert!(compare(b"+", b"-", Normalize::Commands).is_some());
+    "lines".parse::<Normalize>().unwrap_err();
+}
+
+#[test]
+fn mismatches_point_at_the_differing_stretch() {
+    let source = b"++++++++++[>+++++++<-]>--.";
+    let output = b"++++++++++[>++++++<-]>--.";
+    let mismatch = compare(source, output, Normalize::Exact).unwrap();
+    assert_eq!(mismatch.at, 18);
+    assert_eq!(mismatch.source, b"+");
+    assert_eq!(mismatch.output, b"");
+    assert!(mismatch.is_near());
+
+    // Repeated bytes can't be counted on both sides of the stretch
+    let mismatch = compare(b"aaa", b"aa", Normalize::Exact).unwrap();
+    assert_eq!((mismatch.at, &mismatch.source[..]), (2, &b"a"[..]));
+    assert!(mismatch.output.is_empty());
+
+    let mismatch = compare(b"+[-]>.", b"hello", Normalize::Exact).unwrap();
+    assert_eq!(mismatch.at, 0);
+    assert!(!mismatch.is_near());
+}
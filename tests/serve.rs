@@ -0,0 +1,74 @@
+// Checks that the HTTP server turns away requests that would take it down or tie up a
+// worker, and keeps answering after them.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+
+// The server on a free port, killed when dropped
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_bf-rust"))
+            .args(["serve", "--port", "0", "--max-steps", "1000000000000"])
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stderr.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let addr = line
+            .trim()
+            .strip_prefix("serving on http://")
+            .unwrap()
+            .to_string();
+        Self { child, addr }
+    }
+
+    // The status line and body of the answer to `POST /run` with `body`
+    fn post(&self, body: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        write!(
+            stream,
+            "POST /run HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn serve_survives_hostile_requests() {
+    let server = Server::start();
+    // Would have the process try to allocate 400 GB
+    let (status, body) = server.post(r#"{"program":"+.","tape":"fixed:100000000000"}"#);
+    assert_eq!(status, "HTTP/1.1 400 Bad Request", "{body}");
+    assert!(body.contains("fixed tape"), "{body}");
+
+    // Endless, and at -O3 once hung compiling, out of the deadline's reach
+    for opt_level in [0, 3, 9] {
+        let request = format!(r#"{{"program":"+[]","opt_level":{opt_level},"timeout_ms":200}}"#);
+        let (status, body) = server.post(&request);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains(r#""status":"timeout""#), "{body}");
+    }
+
+    // Still up
+    let (_, body) = server.post(r#"{"program":"++++++++[>++++++++<-]>+.","tape":"fixed:100"}"#);
+    assert!(body.contains(r#""output":"A""#), "{body}");
+}
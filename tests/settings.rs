@@ -0,0 +1,73 @@
+// Checks that every setting reads from config files by its name, and that bad lines and
+// values are refused.
+use bf::{CellSize, Eof, Flush, Settings, Tape};
+
+#[test]
+fn config_files_set_every_key() {
+    let mut settings = Settings::default();
+    let config = "\
+# Comments and blank lines are skipped
+
+spec = modern
+cell_size = 16          # after the spec, so it wins
+eof = 'unchanged'
+tape = \"fixed:300\"
+max_steps = 1000
+max_depth = 0
+opt_level = 3
+flush = always
+extensions = \"random, numbers\"
+seed = 7
+";
+    settings.apply_config(config).unwrap();
+    assert_eq!(settings.cell_size, CellSize::U16);
+    assert_eq!(settings.eof, Eof::Unchanged);
+    assert_eq!(settings.tape, Tape::Fixed(300));
+    assert_eq!(settings.max_steps, Some(1000));
+    // Zero turns a limit off
+    assert_eq!(settings.max_depth, None);
+    assert_eq!(settings.opt_level, 3);
+    assert_eq!(settings.flush, Flush::Always);
+    assert_eq!(settings.extensions, "random,numbers".parse().unwrap());
+    assert_eq!(settings.seed, Some(7));
+
+    // A spec only changes the settings it bundles
+    let mut settings = Settings::default();
+    settings.apply_config("spec = \"classic\"").unwrap();
+    assert_eq!(settings.eof, Eof::Unchanged);
+    assert_eq!(settings.tape, Tape::Dynamic);
+    assert_eq!(settings.opt_level, Settings::default().opt_level);
+}
+
+#[test]
+fn bad_settings_are_refused() {
+    let bad = [
+        ("cell_size", "12"),
+        ("eof", "maybe"),
+        ("tape", "fixed:0"),
+        ("tape", "endless"),
+        ("max_steps", "-1"),
+        ("max_depth", "deep"),
+        ("opt_level", "fast"),
+        ("flush", "never"),
+        ("extensions", "random,goto"),
+        ("seed", "0x10"),
+        ("spec", "ancient"),
+        ("colour", "blue"),
+    ];
+    for (key, value) in bad {
+        let mut settings = Settings::default();
+        assert!(settings.set(key, value).is_err(), "{key} = {value}");
+        assert_eq!(settings, Settings::default(), "{key} = {value}");
+    }
+
+    // The line number comes with the reason
+    let mut settings = Settings::default();
+    let err = settings
+        .apply_config("eof = zero\n\ncell_size")
+        .unwrap_err();
+    assert!(err.starts_with("line 3:"), "{err}");
+    let err = settings.apply_config("opt_level = 1 2").unwrap_err();
+    assert!(err.starts_with("line 1: invalid value"), "{err}");
+    assert!(settings.apply_config("opt_level = ").is_err());
+}
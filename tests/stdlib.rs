@@ -0,0 +1,83 @@
+// Checks that the snippets do what they say, under every cell size they're meant for.
+use std::process::Command;
+
+use bf::stdlib::expand;
+use bf::{BfError, CellSize, Interpreter, Program, Settings};
+
+fn run(source: &str, cell_size: CellSize) -> (Vec<u8>, Vec<u32>) {
+    let code = expand(source).unwrap();
+    let settings = Settings {
+        cell_size,
+        ..Settings::default()
+    };
+    let mut output = vec![];
+    let mut interpreter = Interpreter::with_settings(
+        Program::compile(&code, 2).unwrap(),
+        &settings,
+        &b""[..],
+        &mut output,
+    );
+    interpreter.run().unwrap();
+    let tape = interpreter.machine().tape().into_owned();
+    (output, tape)
+}
+
+#[test]
+fn snippets_compute_what_they_document() {
+    for (value, printed) in [(0, "0"), (7, "7"), (10, "10"), (255, "255")] {
+        let source = format!("@use set({value})\n@use print_num\n");
+        let (output, tape) = run(&source, CellSize::U8);
+        assert_eq!(output, printed.as_bytes());
+        assert_eq!(tape[0], value);
+        assert!(tape[1..].iter().all(|&cell| cell == 0));
+    }
+    let (output, _) = run("@use mul(250, 280)\n@use print_num", CellSize::U32);
+    assert_eq!(output, b"70000");
+
+    let (_, tape) = run("@use set(9)\n@use copy(3, 1)\n@use move(2)", CellSize::U8);
+    assert_eq!(tape, [0, 0, 9, 9]);
+    for (value, flag) in [(4, 1), (5, 0)] {
+        let (_, tape) = run(&format!("@use add({value})\n@use equal(4)"), CellSize::U8);
+        assert_eq!(&tape[..3], [value, flag, 0]);
+    }
+    let (output, tape) = run("@use print(\"Hi\\n\")", CellSize::U8);
+    assert_eq!(output, b"Hi\n");
+    assert_eq!(tape, [0]);
+}
+
+#[test]
+fn directives_keep_lines_and_report_mistakes() {
+    assert_eq!(expand("+.\n  @use add(-2)\n.").unwrap(), "+.\n--\n.");
+    assert!(matches!(expand("++"), Ok(std::borrow::Cow::Borrowed("++"))));
+    assert!(matches!(
+        expand("\n@use mul(1)"),
+        Err(BfError::Syntax(6, _))
+    ));
+    assert!(matches!(
+        expand("@use print(\"a)"),
+        Err(BfError::Syntax(_, _))
+    ));
+}
+
+#[test]
+fn commands_reading_source_expand_directives() {
+    let path = std::env::temp_dir().join(format!("bf-stdlib-{}.b", std::process::id()));
+    std::fs::write(&path, "@use mul(3,5)\n.").unwrap();
+    let command = |name: &str| {
+        let out = Command::new(env!("CARGO_BIN_EXE_bf-rust"))
+            .arg(name)
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{name}: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+    let (check, golf) = (command("check"), command("golf"));
+    std::fs::remove_file(&path).unwrap();
+    assert!(check.contains(": ok, 16 instructions, 1 loop"), "{check}");
+    assert_eq!(golf.trim(), ">+++[<+++++>-]<.");
+}
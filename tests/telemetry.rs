@@ -0,0 +1,62 @@
+// Checks that a subscriber sees compilation and the run, in order.
+#![cfg(feature = "telemetry")]
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bf::telemetry::{self, Event, Level, Subscriber, Value};
+use bf::{Interpreter, Program};
+
+static SEEN: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+struct Recorder;
+
+impl Subscriber for Recorder {
+    fn enabled(&self, level: Level, _: &'static str) -> bool {
+        level >= Level::Debug
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        let fields: Vec<String> = event
+            .fields
+            .iter()
+            .filter(|(key, _)| *key != "elapsed_us")
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        SEEN.lock()
+            .unwrap()
+            .push(format!("{} {}", event.name, fields.join(" ")));
+    }
+
+    fn enter(&self, span: &'static str) {
+        SEEN.lock().unwrap().push(format!("> {span}"));
+    }
+
+    fn exit(&self, span: &'static str, _: Duration) {
+        SEEN.lock().unwrap().push(format!("< {span}"));
+    }
+}
+
+#[test]
+fn subscribers_see_compilation_and_runs() {
+    assert!(telemetry::set_subscriber(Recorder));
+    assert!(!telemetry::set_subscriber(Recorder));
+    let program = Program::compile("++[-]>+.", 2).unwrap();
+    let mut output = vec![];
+    Interpreter::new(program, &[][..], &mut output)
+        .run()
+        .unwrap();
+    assert_eq!(
+        *SEEN.lock().unwrap(),
+        [
+            "> compile",
+            "pass name=fold before=8 after=7",
+            "pass name=clear-loops before=7 after=5",
+            "compiled instructions=5",
+            "< compile",
+            "> run",
+            "finished steps=5",
+            "< run",
+        ]
+    );
+    assert_eq!(Value::from(3usize).to_string(), "3");
+}
@@ -0,0 +1,52 @@
+// Checks that binary traces replay to the same tape from their keyframes as from the start.
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use bf::trace::{read_recording, TraceFormat, Tracer};
+use bf::{Interpreter, Program};
+
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn keyframes_replay_like_the_whole_trace() {
+    // Goes left of the start, clears cells and reads input, so kept, zeroed and negative
+    // positions all show up in keyframes
+    let code = "<+++[->++>+<<]>>[-<<+>>]<,[->+<]>.<<<[-]";
+    let buffer = Shared::default();
+    let mut tracer = Tracer::new(Box::new(buffer.clone()), TraceFormat::Binary).unwrap();
+    tracer.set_keyframes(Some(7));
+    let mut output = vec![];
+    let mut interpreter = Interpreter::new(
+        Program::compile(code, 0).unwrap(),
+        &b"\x05"[..],
+        &mut output,
+    );
+    interpreter.set_trace(tracer);
+    interpreter.run().unwrap();
+    interpreter.take_trace().unwrap().finish().unwrap();
+
+    let recording = read_recording(&buffer.0.lock().unwrap()).unwrap();
+    let steps = recording.events.len();
+    assert_eq!(recording.keyframes.len(), steps / 7);
+    let mut plain = recording.clone();
+    plain.keyframes.clear();
+    for step in 0..=steps {
+        assert_eq!(recording.tape_at(step), plain.tape_at(step), "step {step}");
+    }
+    let tape: Vec<(isize, u32)> = recording.tape_at(steps).into_iter().collect();
+    assert_eq!(tape, [(-1, 3), (1, 5)]);
+    assert_eq!(recording.pointer_at(steps), -2);
+    assert_eq!(output, [5]);
+}
@@ -0,0 +1,125 @@
+// Checks what a run reports using on each kind of tape.
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use bf::backend::{backend, Io};
+use bf::golden::Golden;
+use bf::live::LiveTape;
+use bf::trace::{read_recording, TraceFormat, Tracer};
+use bf::{Interpreter, Machine, Program, Settings, Tape};
+
+// Collects a trace the tracer owns the writer for
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn usage_by_tape() {
+    // Echoes two bytes, then walks 2000 cells right, past the first page of a sparse tape
+    let code = format!(",.,.{}", ">".repeat(2000));
+    let usage = |tape: Tape| {
+        let settings = Settings {
+            tape,
+            ..Settings::default()
+        };
+        let mut machine = Machine::with_settings(Program::compile(&code, 0).unwrap(), &settings);
+        machine.feed(b"hi");
+        machine.close_input();
+        machine.run_for(u64::MAX);
+        assert_eq!(machine.take_output(), b"hi");
+        machine.usage()
+    };
+
+    let dynamic = usage(Tape::Dynamic);
+    assert_eq!((dynamic.cells, dynamic.read, dynamic.written), (2001, 2, 2));
+    assert!(dynamic.reallocations > 1 && dynamic.allocated >= 2001 * 4);
+
+    let sparse = usage(Tape::Sparse);
+    assert_eq!((sparse.cells, sparse.reallocations), (2001, 1));
+    assert_eq!(sparse.allocated, 2 * 1024 * 4);
+
+    let fixed = usage(Tape::Fixed(30_000));
+    assert_eq!((fixed.cells, fixed.reallocations), (30_000, 0));
+    assert_eq!(fixed.allocated, 120_000);
+}
+
+#[test]
+fn far_sparse_tapes_are_read_by_page() {
+    // A cell a billion to either side, nothing allocated between them
+    let program =
+        bf::ir::assemble("ADD 1\nMOV 1000000000\nADD 2\nMOV -2000000000\nADD 3\n").unwrap();
+    let settings = Settings {
+        tape: Tape::Sparse,
+        ..Settings::default()
+    };
+    let mut machine = Machine::with_settings(program, &settings);
+    machine.run_for(u64::MAX);
+    let usage = machine.usage();
+    assert_eq!(usage.cells, 2_000_000_001);
+    assert_eq!(usage.allocated, 3 * 1024 * 4);
+
+    let tape = machine.memory();
+    assert_eq!(
+        tape.nonzero(),
+        [(0, 3), (1_000_000_000, 1), (2_000_000_000, 2)]
+    );
+    assert_eq!(
+        (tape.cell(0), tape.cell(1_000_000_000), tape.cell(7)),
+        (3, 1, 0)
+    );
+    // Saving it whole is refused rather than gathering gigabytes
+    let err = bf::checkpoint::Checkpoint::capture(&machine, "far", "", true, &settings);
+    assert!(matches!(err, Err(bf::BfError::Checkpoint(_))));
+}
+
+#[test]
+fn far_sparse_tapes_are_never_gathered() {
+    // Hashing, tracing, watching or reporting the tape doesn't gather the two billion cells
+    let source = "ADD 1\nMOV 1000000000\nADD 2\nMOV -2000000000\nADD 3\n";
+    let settings = Settings {
+        tape: Tape::Sparse,
+        ..Settings::default()
+    };
+    let program = bf::ir::assemble(source).unwrap();
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let mut tracer = Tracer::new(Box::new(Shared(buffer.clone())), TraceFormat::Binary).unwrap();
+    tracer.set_keyframes(Some(1));
+    let live = LiveTape::new(1);
+    let mut output = vec![];
+    let mut interpreter =
+        Interpreter::with_settings(program.clone(), &settings, &[][..], &mut output);
+    interpreter.set_trace(tracer);
+    interpreter.set_golden(Golden::new(1));
+    interpreter.set_live_tape(live.clone());
+    interpreter.run().unwrap();
+    interpreter.take_trace().unwrap().finish().unwrap();
+    assert_eq!(interpreter.take_golden().unwrap().links().len(), 1);
+
+    // Only the events before the second cell was written get a keyframe
+    let recording = read_recording(&buffer.lock().unwrap()).unwrap();
+    assert_eq!(recording.events.len(), 5);
+    assert_eq!(recording.keyframes.len(), 2);
+    // and readers keep the last copy short enough to take
+    assert_eq!(live.snapshot().cells.len(), 1);
+
+    let (mut input, mut output) = (&[][..], vec![]);
+    let io = Io {
+        input: &mut input,
+        output: &mut output,
+    };
+    let report = backend("interp")
+        .unwrap()
+        .execute(&program, io, &settings)
+        .unwrap();
+    assert_eq!(report.steps, Some(5));
+    assert!(report.tape.is_none());
+}
@@ -0,0 +1,218 @@
+// Checks that the WebAssembly the wasm backend emits runs in-process like the interpreter, and
+// that the interpreter it runs on gets modules written by hand, not by the emitter, right.
+#![cfg(feature = "wasm")]
+use std::path::Path;
+
+use bf::backend::{backend, Io};
+use bf::corpus::load;
+use bf::wasm_vm::{Host, Module, Trap};
+use bf::{BfError, CellSize, Eof, Program, Settings};
+
+// A module of `(id, contents)` sections, every length under 128 so one byte each
+fn module(sections: &[(u8, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+    for (id, contents) in sections {
+        bytes.extend([*id, contents.len() as u8]);
+        bytes.extend(contents);
+    }
+    bytes
+}
+
+// A code section entry: its length, then the body
+fn body(code: &[u8]) -> Vec<u8> {
+    let mut entry = vec![code.len() as u8];
+    entry.extend(code);
+    entry
+}
+
+// Answers `get` with 41 and keeps what `put` is given
+struct Recorder(Vec<i32>);
+
+impl Host for Recorder {
+    fn call(&mut self, import: usize, args: &[i32]) -> Result<Option<i32>, Trap> {
+        match import {
+            0 => Ok(Some(41)),
+            _ if args[0] < 0 => Err(Trap::Host("negative".to_string())),
+            _ => {
+                self.0.push(args[0]);
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn run(code: &str, settings: &Settings, input: &[u8]) -> Result<Vec<u8>, BfError> {
+    let program = Program::with_extensions(code, settings.opt_level, settings.extensions)?;
+    let mut output = vec![];
+    let io = Io {
+        input: &mut &input[..],
+        output: &mut output,
+    };
+    backend("wasm").unwrap().execute(&program, io, settings)?;
+    Ok(output)
+}
+
+#[test]
+fn wasm_runs_the_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+    for case in load(&dir, &Settings::default()).unwrap() {
+        let output = run(&case.source, &case.settings, &case.input).unwrap();
+        assert_eq!(output, case.expected.unwrap(), "{}", case.name);
+    }
+}
+
+#[test]
+fn wasm_follows_the_settings() {
+    // 256 only wraps to 0 in 8-bit cells, and EOF leaves what each mode leaves
+    let code = "++++++++++++++++[>++++++++++++++++<-]>[>+<[-]]>.,+.";
+    for (cell_size, eof, expected) in [
+        (CellSize::U8, Eof::Zero, [0, 1]),
+        (CellSize::U16, Eof::Unchanged, [1, 2]),
+        (CellSize::U32, Eof::Minus1, [1, 0]),
+    ] {
+        let settings = Settings {
+            cell_size,
+            eof,
+            ..Settings::default()
+        };
+        assert_eq!(run(code, &settings, b"").unwrap(), expected, "{cell_size}");
+    }
+    let settings = Settings {
+        extensions: "random".parse().unwrap(),
+        seed: Some(7),
+        ..Settings::default()
+    };
+    // The same bytes from the same seed as the interpreter
+    let program = Program::with_extensions("?.>?.", 2, settings.extensions).unwrap();
+    let mut expected = vec![];
+    let io = Io {
+        input: &mut &b""[..],
+        output: &mut expected,
+    };
+    backend("interp")
+        .unwrap()
+        .execute(&program, io, &settings)
+        .unwrap();
+    assert_eq!(run("?.>?.", &settings, b"").unwrap(), expected);
+
+    // The tape starts on the first cell of the module's memory
+    let settings = Settings::default();
+    assert!(matches!(
+        run("<+", &settings, b""),
+        Err(BfError::TapeBounds(-1))
+    ));
+    let settings = Settings {
+        extensions: "numbers".parse().unwrap(),
+        ..Settings::default()
+    };
+    assert!(matches!(
+        run("+:", &settings, b""),
+        Err(BfError::Unsupported(_))
+    ));
+    let settings = Settings {
+        max_steps: Some(10),
+        ..Settings::default()
+    };
+    assert!(matches!(
+        run("+", &settings, b""),
+        Err(BfError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn vm_runs_hand_written_modules() {
+    // `run` sums 10 down to 1 into memory[0] by a loop, stores `double(21)` at 4 and at 5
+    // 1 or 2 depending on whether the sum is 55
+    let run = [
+        &[1, 1, 0x7f][..],               // One i32 local, the counter
+        &[0x41, 10, 0x21, 0],            // counter = 10
+        &[0x02, 0x40, 0x03, 0x40],       // block, loop
+        &[0x20, 0, 0x45, 0x0d, 1],       // break out once it reaches 0
+        &[0x41, 0, 0x41, 0, 0x28, 2, 0], // memory[0] + ...
+        &[0x20, 0, 0x6a, 0x36, 2, 0],    // counter, stored back
+        &[0x20, 0, 0x41, 0x7f, 0x6a],    // counter - 1
+        &[0x21, 0, 0x0c, 0, 0x0b, 0x0b], // round again, end loop and block
+        &[0x41, 4, 0x41, 21, 0x10, 1],   // double(21)
+        &[0x3a, 0, 0],                   // stored as a byte
+        &[0x41, 5, 0x41, 0, 0x28, 2, 0], // memory[0]
+        &[0x41, 55, 0x47, 0x04, 0x7f],   // != 55, if with a result
+        &[0x41, 1, 0x05, 0x41, 2, 0x0b], // 1 else 2
+        &[0x3a, 0, 0, 0x0b],             // stored as a byte, end
+    ]
+    .concat();
+    let double = [0, 0x20, 0, 0x20, 0, 0x6a, 0x0b];
+    let bytes = module(&[
+        (1, vec![2, 0x60, 0, 0, 0x60, 1, 0x7f, 1, 0x7f]),
+        (3, vec![2, 0, 1]),
+        (5, vec![1, 0, 1]),
+        (7, vec![1, 3, b'r', b'u', b'n', 0, 0]),
+        (10, [vec![2], body(&run), body(&double)].concat()),
+    ]);
+    let memory = Module::decode(&bytes)
+        .unwrap()
+        .run("run", &mut Recorder(vec![]))
+        .unwrap();
+    assert_eq!(memory.len(), 65536);
+    assert_eq!(memory[..6], [55, 0, 0, 0, 42, 2]);
+
+    // Imports come first in the function indices: put(get() + 1)
+    let types = vec![2, 0x60, 0, 1, 0x7f, 0x60, 1, 0x7f, 0];
+    let imports = [
+        &[2, 3, b'e', b'n', b'v', 3, b'g', b'e', b't', 0, 0][..],
+        &[3, b'e', b'n', b'v', 3, b'p', b'u', b't', 0, 1],
+    ]
+    .concat();
+    let call = |code: &[u8]| {
+        module(&[
+            (1, types.clone()),
+            (2, imports.clone()),
+            (3, vec![1, 1]),
+            (5, vec![1, 0, 1]),
+            (7, vec![1, 3, b'r', b'u', b'n', 0, 2]),
+            (10, [vec![1], body(&[&[0][..], code].concat())].concat()),
+        ])
+    };
+    let module = Module::decode(&call(&[0x10, 0, 0x41, 1, 0x6a, 0x10, 1, 0x0b])).unwrap();
+    assert_eq!(
+        module.imports().collect::<Vec<_>>(),
+        [("env", "get"), ("env", "put")]
+    );
+    let mut host = Recorder(vec![]);
+    module.run("run", &mut host).unwrap();
+    assert_eq!(host.0, [42]);
+    let module = Module::decode(&call(&[0x41, 0x7f, 0x10, 1, 0x0b])).unwrap();
+    let trap = module.run("run", &mut host).unwrap_err();
+    assert_eq!(trap, Trap::Host("negative".to_string()));
+}
+
+#[test]
+fn vm_traps_and_refuses_what_it_cant_run() {
+    let function = |code: &[u8]| {
+        module(&[
+            (1, vec![1, 0x60, 0, 0]),
+            (3, vec![1, 0]),
+            (5, vec![1, 0, 1]),
+            (7, vec![1, 3, b'r', b'u', b'n', 0, 0]),
+            (10, [vec![1], body(&[&[0][..], code].concat())].concat()),
+        ])
+    };
+    let run = |bytes: &[u8]| Module::decode(bytes)?.run("run", &mut Recorder(vec![]));
+    assert_eq!(run(&function(&[0x00, 0x0b])), Err(Trap::Unreachable));
+    // One past the end of the one page
+    let load = [0x41, 0x80, 0x80, 0x04, 0x2d, 0, 0, 0x1a, 0x0b];
+    assert_eq!(run(&function(&load)), Err(Trap::OutOfBounds(65536)));
+
+    let invalid = |bytes: &[u8]| matches!(run(bytes), Err(Trap::Invalid(_)));
+    assert!(invalid(b"\0asm\x02\0\0\0"));
+    // i64.const, outside the i32 subset
+    assert!(invalid(&function(&[0x42, 0, 0x1a, 0x0b])));
+    // A table section
+    assert!(invalid(&module(&[(4, vec![0])])));
+    assert!(invalid(&function(&[0x0b])[..20]));
+    let bytes = function(&[0x0b]);
+    let module = Module::decode(&bytes).unwrap();
+    assert!(matches!(
+        module.run("main", &mut Recorder(vec![])),
+        Err(Trap::Invalid(_))
+    ));
+}